@@ -0,0 +1,100 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use exitfailure::ExitFailure;
+use oysterpack_trust::metrics::catalog::MetricCatalog;
+use std::{
+    fs,
+    io::{self, Read},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+#[cfg_attr(tarpaulin, skip)]
+fn main() -> Result<(), ExitFailure> {
+    Command::from_args().execute()?;
+    Ok(())
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "oysterpack-metrics",
+    raw(setting = "structopt::clap::AppSettings::ColoredHelp")
+)]
+enum Command {
+    #[structopt(name = "catalog")]
+    /// print the metric descriptor catalog for a Prometheus text exposition, i.e., a metrics scrape
+    /// - the text exposition is read from the specified file, or from stdin if no file is specified
+    Catalog {
+        #[structopt(short = "f", long = "format", default_value = "json")]
+        /// output format: json | markdown | rules
+        format: CatalogFormat,
+        #[structopt(short = "g", long = "group", default_value = "oysterpack")]
+        /// recording rules group name
+        group: String,
+        #[structopt(parse(from_os_str))]
+        /// Prometheus text exposition file
+        file: Option<PathBuf>,
+    },
+}
+
+impl Command {
+    fn execute(self) -> Result<(), failure::Error> {
+        match self {
+            Command::Catalog {
+                format,
+                group,
+                file,
+            } => {
+                let text = match file {
+                    Some(file) => fs::read_to_string(file)?,
+                    None => {
+                        let mut text = String::new();
+                        io::stdin().read_to_string(&mut text)?;
+                        text
+                    }
+                };
+                let catalog = MetricCatalog::from_text_exposition(&text);
+                match format {
+                    CatalogFormat::Json => println!("{}", serde_json::to_string_pretty(&catalog)?),
+                    CatalogFormat::Markdown => print!("{}", catalog.to_markdown()),
+                    CatalogFormat::Rules => print!("{}", catalog.to_recording_rules(&group)),
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum CatalogFormat {
+    Json,
+    Markdown,
+    Rules,
+}
+
+impl std::str::FromStr for CatalogFormat {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<CatalogFormat, failure::Error> {
+        match s {
+            "json" => Ok(CatalogFormat::Json),
+            "markdown" | "md" => Ok(CatalogFormat::Markdown),
+            "rules" => Ok(CatalogFormat::Rules),
+            _ => Err(failure::format_err!("invalid catalog format: {}", s)),
+        }
+    }
+}
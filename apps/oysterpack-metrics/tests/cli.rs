@@ -0,0 +1,80 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::{env, process::Command};
+
+const TEXT_EXPOSITION: &str = r#"# HELP M01D4ZHRS7RV42RXN1R83Q8QDPA Executor spawned task count
+# TYPE M01D4ZHRS7RV42RXN1R83Q8QDPA counter
+M01D4ZHRS7RV42RXN1R83Q8QDPA{L01D4ZHRS7RV42RXN1R83Q8QDPB="01D4ZHRS7RV42RXN1R83Q8QDPC"} 10
+"#;
+
+#[test]
+fn run_cmd_with_no_args() {
+    let mut cmd = Command::main_binary().unwrap();
+    cmd.assert().failure().stderr(
+        predicate::str::contains(format!(
+            "oysterpack-metrics {}",
+            env::var("CARGO_PKG_VERSION").unwrap()
+        ))
+        .and(predicate::str::contains("USAGE:"))
+        .and(predicate::str::contains("SUBCOMMANDS:"))
+        .and(predicate::str::contains("catalog")),
+    );
+}
+
+#[test]
+fn catalog_json() {
+    let mut cmd = Command::main_binary().unwrap();
+    cmd.arg("catalog")
+        .with_stdin()
+        .buffer(TEXT_EXPOSITION)
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains(r#""name": "M01D4ZHRS7RV42RXN1R83Q8QDPA""#)
+                .and(predicate::str::contains(r#""metric_type": "counter""#)),
+        );
+}
+
+#[test]
+fn catalog_markdown() {
+    let mut cmd = Command::main_binary().unwrap();
+    cmd.args(&["catalog", "--format", "markdown"])
+        .with_stdin()
+        .buffer(TEXT_EXPOSITION)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "| `M01D4ZHRS7RV42RXN1R83Q8QDPA` | counter | Executor spawned task count |",
+        ));
+}
+
+#[test]
+fn catalog_recording_rules() {
+    let mut cmd = Command::main_binary().unwrap();
+    cmd.args(&["catalog", "--format", "rules", "--group", "foo"])
+        .with_stdin()
+        .buffer(TEXT_EXPOSITION)
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("  - name: foo\n").and(predicate::str::contains(
+                "      - record: executor_spawned_task_count\n        expr: M01D4ZHRS7RV42RXN1R83Q8QDPA\n",
+            )),
+        );
+}
@@ -41,7 +41,8 @@ use oysterpack_trust::concurrent::execution::{
 };
 use oysterpack_uid::ULID;
 
-use grpcio::{ChannelBuilder, EnvBuilder, Environment, ServerBuilder, WriteFlags};
+use grpcio::{ChannelBuilder, EnvBuilder, WriteFlags};
+use oysterpack_trust_grpc::server::{GrpcServerBuilder, GrpcServerHandle, ServerTlsConfig};
 use hashbrown::HashMap;
 use parking_lot::Mutex;
 use std::time::Duration;
@@ -186,45 +187,26 @@ fn grpc_secure_bench(c: &mut Criterion) {
     stop_server(server);
 }
 
-fn start_server() -> grpcio::Server {
-    let env = Arc::new(Environment::new(num_cpus::get()));
-    let service = foo_grpc::create_foo(FooServer::default());
-    let mut server = ServerBuilder::new(env)
-        .register_service(service)
-        .bind("127.0.0.1", 0)
-        .build()
-        .unwrap();
-    server.start();
-    server
+fn start_server() -> GrpcServerHandle {
+    GrpcServerBuilder::new(global_executor())
+        .register_service(foo_grpc::create_foo(FooServer::default()))
+        .start()
+        .unwrap()
 }
 
-fn start_secure_server() -> (grpcio::Server, String) {
-    let subject_alt_names: &[_] = &["127.0.0.1".to_string()];
-
-    let cert = rcgen::generate_simple_self_signed(subject_alt_names);
-    let cert_pem = cert.serialize_pem();
-    let cert_private_key_pem = cert.serialize_private_key_pem();
-    let server_credentials = grpcio::ServerCredentialsBuilder::new()
-        .add_cert(
-            cert_pem.as_bytes().to_vec(),
-            cert_private_key_pem.as_bytes().to_vec(),
-        )
-        .build();
-
-    let env = Arc::new(Environment::new(1));
-    let service = foo_grpc::create_foo(FooServer::default());
-    let mut server = ServerBuilder::new(env)
-        .register_service(service)
-        .bind_secure("127.0.0.1", 0, server_credentials)
-        .build()
+fn start_secure_server() -> (GrpcServerHandle, String) {
+    let server = GrpcServerBuilder::new(global_executor())
+        .set_completion_queue_count(1)
+        .set_tls(ServerTlsConfig::self_signed(&["127.0.0.1".to_string()]))
+        .register_service(foo_grpc::create_foo(FooServer::default()))
+        .start()
         .unwrap();
-    server.start();
+    let cert_pem = server.tls().unwrap().cert_pem().to_string();
     (server, cert_pem)
 }
 
-fn stop_server(mut server: grpcio::Server) {
-    use futures::Future;
-    if let Err(err) = server.shutdown().wait() {
+fn stop_server(server: GrpcServerHandle) {
+    if let Err(err) = server.shutdown_blocking() {
         println!("Error occurred while shutting down server: {:?}", err);
     }
 }
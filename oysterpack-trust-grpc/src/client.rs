@@ -0,0 +1,871 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Provides an instrumented gRPC client.
+//!
+//! - [GrpcClient](struct.GrpcClient.html) wraps a `grpcio::Client` and is used to make unary and
+//!   streaming calls
+//!   - methods are specified via `grpcio::Method` descriptors - see [unary_method()](../reqrep/fn.unary_method.html)
+//!     and [protobuf_method()](../compat/fn.protobuf_method.html)
+//!   - streaming replies are exposed as futures 0.3 streams, and requests can be sent via
+//!     [compat::send_all()](../compat/fn.send_all.html)
+//!   - generated clients can apply the interceptors via [GrpcClient::call_option()](struct.GrpcClient.html#method.call_option),
+//!     but their calls are not instrumented
+//! - [ClientInterceptor](trait.ClientInterceptor.html)(s) are applied to each call before it is sent,
+//!   and are used to inject metadata and set deadlines
+//!   - [CorrelationIdInterceptor](struct.CorrelationIdInterceptor.html) - sets a new correlation ULID
+//!     per call, which servers can retrieve via [correlation_id()](fn.correlation_id.html)
+//!   - [BearerTokenInterceptor](struct.BearerTokenInterceptor.html) - sets the `authorization` header
+//!   - any `Fn(&mut CallContext) -> Result<(), grpcio::Error>` closure is a ClientInterceptor
+//! - a default deadline can be configured for all calls via [GrpcClient::set_timeout()](struct.GrpcClient.html#method.set_timeout)
+//!
+//! ## Metrics
+//! Per method metrics are registered in the global metrics registry, using the same labels as the
+//! [server metrics](../server/index.html#metrics):
+//! - number of calls - [CLIENT_REQUEST_COUNT_METRIC_ID](constant.CLIENT_REQUEST_COUNT_METRIC_ID.html)
+//! - call latency - [CLIENT_REQUEST_TIMER_METRIC_ID](constant.CLIENT_REQUEST_TIMER_METRIC_ID.html)
+//! - response status codes - [CLIENT_RESPONSE_COUNT_METRIC_ID](constant.CLIENT_RESPONSE_COUNT_METRIC_ID.html)
+//!   - errors that are not gRPC status failures are reported as `Unknown`
+//!   - calls whose reply future or stream is dropped before completing are reported as `Cancelled`
+//!   - streaming calls are recorded when the reply stream is done, or when it yields an error
+
+use crate::server::{latency_timer_buckets, METHOD_LABEL_ID, STATUS_CODE_LABEL_ID};
+use lazy_static::lazy_static;
+use oysterpack_trust::{
+    concurrent::execution::futures::{
+        compat::{Compat01As03, Future01CompatExt},
+        stream::StreamExt,
+        task::{Poll, Waker},
+        Future, Stream,
+    },
+    metrics,
+};
+use oysterpack_uid::ULID;
+use std::{
+    fmt,
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+lazy_static! {
+    static ref CLIENT_REQUEST_COUNT: prometheus::IntCounterVec = metrics::registry().register_int_counter_vec(
+        CLIENT_REQUEST_COUNT_METRIC_ID,
+        "Number of gRPC client calls per method",
+        &[METHOD_LABEL_ID],
+        None
+    ).unwrap();
+
+    static ref CLIENT_REQUEST_TIMER: prometheus::HistogramVec = metrics::registry().register_histogram_vec(
+        CLIENT_REQUEST_TIMER_METRIC_ID,
+        "gRPC client call latency per method",
+        &[METHOD_LABEL_ID],
+        latency_timer_buckets(),
+        None
+    ).unwrap();
+
+    static ref CLIENT_RESPONSE_COUNT: prometheus::IntCounterVec = metrics::registry().register_int_counter_vec(
+        CLIENT_RESPONSE_COUNT_METRIC_ID,
+        "Number of gRPC client call responses per method and status code",
+        &[METHOD_LABEL_ID, STATUS_CODE_LABEL_ID],
+        None
+    ).unwrap();
+}
+
+/// IntCounterVec MetricId for the number of gRPC client calls by method: `M01M57H0ZZHSRBZ8C0CRFDPSMN8`
+pub const CLIENT_REQUEST_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166791407615232450494222279700697768);
+/// HistogramVec MetricId for the gRPC client call latency by method: `M01M57H0ZZKZ46M478QTFS1WGXJ`
+pub const CLIENT_REQUEST_TIMER_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166791407617853166593186596118217650);
+/// IntCounterVec MetricId for the number of gRPC client call responses by method and status code: `M01M57H0ZZNAXWVHYWNVRY15KD5`
+pub const CLIENT_RESPONSE_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166791407619507995675973056535383461);
+
+/// Binary metadata key used to propagate the correlation ULID
+pub const CORRELATION_ID_KEY: &str = "correlation-id-bin";
+
+/// Metadata key used for bearer tokens
+pub const AUTHORIZATION_KEY: &str = "authorization";
+
+/// Returns the correlation ULID from the server call's request metadata, if present
+/// - see [CorrelationIdInterceptor](struct.CorrelationIdInterceptor.html)
+pub fn correlation_id(ctx: &grpcio::RpcContext) -> Option<ULID> {
+    ctx.request_headers().iter().find_map(|(key, value)| {
+        if key == CORRELATION_ID_KEY {
+            ULID::try_from_bytes(value).ok()
+        } else {
+            None
+        }
+    })
+}
+
+/// Call context, which is passed to each [ClientInterceptor](trait.ClientInterceptor.html) before
+/// the call is sent
+pub struct CallContext<'a> {
+    method: &'a str,
+    metadata: grpcio::MetadataBuilder,
+    timeout: Option<Duration>,
+}
+
+impl<'a> CallContext<'a> {
+    /// the fully qualified method name, i.e., `/{package}.{service}/{method}`
+    pub fn method(&self) -> &str {
+        self.method
+    }
+
+    /// Request metadata, i.e., headers
+    /// - binary metadata keys must use the `-bin` suffix
+    pub fn metadata(&mut self) -> &mut grpcio::MetadataBuilder {
+        &mut self.metadata
+    }
+
+    /// the call deadline, relative to when the call is sent
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Sets the call deadline, relative to when the call is sent
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = Some(timeout);
+    }
+
+    fn call_option(self) -> grpcio::CallOption {
+        let call_option = grpcio::CallOption::default().headers(self.metadata.build());
+        match self.timeout {
+            Some(timeout) => call_option.timeout(timeout),
+            None => call_option,
+        }
+    }
+}
+
+impl<'a> fmt::Debug for CallContext<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CallContext")
+            .field("method", &self.method)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+/// Client interceptors are applied in order to each call before it is sent.
+/// - if an interceptor fails, then the call is not sent and the error is returned
+/// - any `Fn(&mut CallContext) -> Result<(), grpcio::Error>` closure is a ClientInterceptor
+pub trait ClientInterceptor: Send + Sync + 'static {
+    /// used to inject metadata and set the deadline
+    fn intercept(&self, call: &mut CallContext) -> Result<(), grpcio::Error>;
+}
+
+impl<F> ClientInterceptor for F
+where
+    F: Fn(&mut CallContext) -> Result<(), grpcio::Error> + Send + Sync + 'static,
+{
+    fn intercept(&self, call: &mut CallContext) -> Result<(), grpcio::Error> {
+        self(call)
+    }
+}
+
+/// Sets a new correlation ULID on each call using the [CORRELATION_ID_KEY](constant.CORRELATION_ID_KEY.html)
+/// binary metadata key
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CorrelationIdInterceptor;
+
+impl ClientInterceptor for CorrelationIdInterceptor {
+    fn intercept(&self, call: &mut CallContext) -> Result<(), grpcio::Error> {
+        call.metadata()
+            .add_bytes(CORRELATION_ID_KEY, &ULID::generate().to_bytes())?;
+        Ok(())
+    }
+}
+
+/// Sets the `authorization: Bearer {token}` metadata on each call
+/// - the token is obtained from the token provider per call, which enables tokens to be refreshed
+#[derive(Clone)]
+pub struct BearerTokenInterceptor {
+    token_provider: Arc<dyn Fn() -> String + Send + Sync>,
+}
+
+impl BearerTokenInterceptor {
+    /// uses the same token for all calls
+    pub fn new(token: String) -> BearerTokenInterceptor {
+        BearerTokenInterceptor::with_provider(move || token.clone())
+    }
+
+    /// the token is obtained from the provider per call
+    pub fn with_provider<F>(token_provider: F) -> BearerTokenInterceptor
+    where
+        F: Fn() -> String + Send + Sync + 'static,
+    {
+        BearerTokenInterceptor {
+            token_provider: Arc::new(token_provider),
+        }
+    }
+}
+
+impl ClientInterceptor for BearerTokenInterceptor {
+    fn intercept(&self, call: &mut CallContext) -> Result<(), grpcio::Error> {
+        let token = (self.token_provider)();
+        call.metadata()
+            .add_str(AUTHORIZATION_KEY, &format!("Bearer {}", token))?;
+        Ok(())
+    }
+}
+
+impl fmt::Debug for BearerTokenInterceptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // the token is never logged
+        f.write_str("BearerTokenInterceptor")
+    }
+}
+
+/// Instrumented gRPC client
+/// - cloning is cheap, i.e., the underlying channel and interceptors are shared
+#[derive(Clone)]
+pub struct GrpcClient {
+    client: Arc<grpcio::Client>,
+    interceptors: Vec<Arc<dyn ClientInterceptor>>,
+    timeout: Option<Duration>,
+}
+
+impl GrpcClient {
+    /// constructor
+    pub fn new(channel: grpcio::Channel) -> GrpcClient {
+        GrpcClient {
+            client: Arc::new(grpcio::Client::new(channel)),
+            interceptors: Vec::new(),
+            timeout: None,
+        }
+    }
+
+    /// Adds an interceptor - interceptors are applied in the order they are added
+    pub fn add_interceptor<I: ClientInterceptor>(mut self, interceptor: I) -> Self {
+        self.interceptors.push(Arc::new(interceptor));
+        self
+    }
+
+    /// Sets the default call deadline, relative to when the call is sent
+    /// - interceptors may override the deadline per call
+    pub fn set_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// the default call deadline
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Returns the underlying grpcio client
+    pub fn client(&self) -> &grpcio::Client {
+        &self.client
+    }
+
+    /// Makes a unary call and blocks until the response is received
+    pub fn unary_call<Req, Rep>(
+        &self,
+        method: &grpcio::Method<Req, Rep>,
+        req: &Req,
+    ) -> Result<Rep, grpcio::Error> {
+        let timer = CallTimer::start(method.name);
+        let result = self
+            .call_option(method.name)
+            .and_then(|call_option| self.client.unary_call(method, req, call_option));
+        timer.observe(&result);
+        result
+    }
+
+    /// Makes a unary call asynchronously
+    pub fn unary_call_async<Req, Rep>(
+        &self,
+        method: &grpcio::Method<Req, Rep>,
+        req: &Req,
+    ) -> impl Future<Output = Result<Rep, grpcio::Error>> {
+        let timer = CallTimer::start(method.name);
+        let receiver = self
+            .call_option(method.name)
+            .and_then(|call_option| self.client.unary_call_async(method, req, call_option));
+        async move {
+            let result = match receiver {
+                Ok(receiver) => await!(receiver.compat()),
+                Err(err) => Err(err),
+            };
+            timer.observe(&result);
+            result
+        }
+    }
+
+    /// Starts a client streaming call
+    /// - requests are sent via the returned sender, e.g., via [compat::send_all()](../compat/fn.send_all.html)
+    /// - the call metrics are recorded when the reply is received
+    pub fn client_streaming<Req, Rep>(
+        &self,
+        method: &grpcio::Method<Req, Rep>,
+    ) -> Result<
+        (
+            grpcio::ClientCStreamSender<Req>,
+            impl Future<Output = Result<Rep, grpcio::Error>>,
+        ),
+        grpcio::Error,
+    > {
+        let timer = CallTimer::start(method.name);
+        let call = self
+            .call_option(method.name)
+            .and_then(|call_option| self.client.client_streaming(method, call_option));
+        match call {
+            Ok((sender, receiver)) => {
+                let reply = async move {
+                    let result = await!(receiver.compat());
+                    timer.observe(&result);
+                    result
+                };
+                Ok((sender, reply))
+            }
+            Err(err) => {
+                timer.observe_error(&err);
+                Err(err)
+            }
+        }
+    }
+
+    /// Starts a server streaming call, which returns the replies as a futures 0.3 stream
+    /// - the call metrics are recorded when the reply stream is done, or when it yields an error
+    pub fn server_streaming<Req, Rep>(
+        &self,
+        method: &grpcio::Method<Req, Rep>,
+        req: &Req,
+    ) -> Result<ReplyStream<grpcio::ClientSStreamReceiver<Rep>>, grpcio::Error> {
+        let timer = CallTimer::start(method.name);
+        let call = self
+            .call_option(method.name)
+            .and_then(|call_option| self.client.server_streaming(method, req, call_option));
+        match call {
+            Ok(receiver) => Ok(ReplyStream::new(receiver, timer)),
+            Err(err) => {
+                timer.observe_error(&err);
+                Err(err)
+            }
+        }
+    }
+
+    /// Starts a duplex streaming call
+    /// - requests are sent via the returned sender, e.g., via [compat::send_all()](../compat/fn.send_all.html)
+    /// - the call metrics are recorded when the reply stream is done, or when it yields an error
+    pub fn duplex_streaming<Req, Rep>(
+        &self,
+        method: &grpcio::Method<Req, Rep>,
+    ) -> Result<
+        (
+            grpcio::ClientDuplexSender<Req>,
+            ReplyStream<grpcio::ClientDuplexReceiver<Rep>>,
+        ),
+        grpcio::Error,
+    > {
+        let timer = CallTimer::start(method.name);
+        let call = self
+            .call_option(method.name)
+            .and_then(|call_option| self.client.duplex_streaming(method, call_option));
+        match call {
+            Ok((sender, receiver)) => Ok((sender, ReplyStream::new(receiver, timer))),
+            Err(err) => {
+                timer.observe_error(&err);
+                Err(err)
+            }
+        }
+    }
+
+    /// Applies the interceptors, and returns the call option for the specified method, e.g., for
+    /// calls that are made via generated clients
+    /// - calls that are made via generated clients are not instrumented
+    pub fn call_option(&self, method: &str) -> Result<grpcio::CallOption, grpcio::Error> {
+        let mut call = CallContext {
+            method,
+            metadata: grpcio::MetadataBuilder::new(),
+            timeout: self.timeout,
+        };
+        for interceptor in self.interceptors.iter() {
+            interceptor.intercept(&mut call)?;
+        }
+        Ok(call.call_option())
+    }
+}
+
+impl fmt::Debug for GrpcClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GrpcClient")
+            .field("interceptor_count", &self.interceptors.len())
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+/// Streaming call reply stream, which is exposed as a futures 0.3 stream
+/// - the call metrics are recorded when the stream is done, or when it yields an error
+pub struct ReplyStream<S> {
+    replies: Compat01As03<S>,
+    timer: Option<CallTimer>,
+}
+
+impl<S> ReplyStream<S> {
+    fn new(replies: S, timer: CallTimer) -> ReplyStream<S> {
+        ReplyStream {
+            replies: Compat01As03::new(replies),
+            timer: Some(timer),
+        }
+    }
+}
+
+impl<S, T> Stream for ReplyStream<S>
+where
+    S: futures::Stream<Item = T, Error = grpcio::Error>,
+{
+    type Item = Result<T, grpcio::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, waker: &Waker) -> Poll<Option<Self::Item>> {
+        let reply = self.replies.poll_next_unpin(waker);
+        match &reply {
+            Poll::Ready(None) => {
+                if let Some(timer) = self.timer.take() {
+                    timer.observe_status(grpcio::RpcStatusCode::Ok);
+                }
+            }
+            Poll::Ready(Some(Err(err))) => {
+                if let Some(timer) = self.timer.take() {
+                    timer.observe_error(err);
+                }
+            }
+            _ => (),
+        }
+        reply
+    }
+}
+
+impl<S> fmt::Debug for ReplyStream<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ReplyStream")
+            .field("timer", &self.timer)
+            .finish()
+    }
+}
+
+/// Records the per method client call metrics
+/// - if the timer is dropped without being observed, then the call is recorded as `Cancelled`
+#[derive(Debug)]
+struct CallTimer {
+    method: &'static str,
+    start: Instant,
+    observed: bool,
+}
+
+impl CallTimer {
+    fn start(method: &'static str) -> CallTimer {
+        CLIENT_REQUEST_COUNT.with_label_values(&[method]).inc();
+        CallTimer {
+            method,
+            start: Instant::now(),
+            observed: false,
+        }
+    }
+
+    fn observe<T>(self, result: &Result<T, grpcio::Error>) {
+        match result {
+            Ok(_) => self.observe_status(grpcio::RpcStatusCode::Ok),
+            Err(err) => self.observe_error(err),
+        }
+    }
+
+    /// errors that are not gRPC status failures are reported as `Unknown`
+    fn observe_error(self, err: &grpcio::Error) {
+        match err {
+            grpcio::Error::RpcFailure(status) => self.observe_status(status.status),
+            _ => self.observe_status(grpcio::RpcStatusCode::Unknown),
+        }
+    }
+
+    fn observe_status(mut self, status: grpcio::RpcStatusCode) {
+        self.record(status);
+    }
+
+    fn record(&mut self, status: grpcio::RpcStatusCode) {
+        CLIENT_REQUEST_TIMER
+            .with_label_values(&[self.method])
+            .observe(metrics::duration_as_secs_f64(self.start.elapsed()));
+        CLIENT_RESPONSE_COUNT
+            .with_label_values(&[self.method, &format!("{:?}", status)])
+            .inc();
+        self.observed = true;
+    }
+}
+
+impl Drop for CallTimer {
+    fn drop(&mut self) {
+        if !self.observed {
+            self.record(grpcio::RpcStatusCode::Cancelled);
+        }
+    }
+}
+
+#[allow(warnings)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compat;
+    use crate::{configure_logging, method};
+    use crate::protos::message::MessageHeader;
+    use crate::server::{GrpcServerBuilder, GrpcServerHandle};
+    use oysterpack_trust::concurrent::{
+        execution::{
+            futures::{channel::oneshot, future::FutureExt, stream},
+            global_executor,
+        },
+        messaging::reqrep::{FutureReply, Processor, ReqRepConfig, ReqRepId},
+    };
+    use std::thread;
+
+    /// completes after the specified duration without blocking the executor thread
+    async fn delay(duration: Duration) {
+        let (tx, rx) = oneshot::channel();
+        thread::spawn(move || {
+            thread::sleep(duration);
+            let _ = tx.send(());
+        });
+        let _ = await!(rx);
+    }
+
+    /// replies after a delay of the number of millis specified by the request ID
+    struct SleepService;
+
+    impl Processor<MessageHeader, MessageHeader> for SleepService {
+        fn process(&mut self, req: MessageHeader) -> FutureReply<MessageHeader> {
+            async move {
+                if let Ok(millis) = req.get_id().parse() {
+                    await!(delay(Duration::from_millis(millis)));
+                }
+                req
+            }
+                .boxed()
+        }
+    }
+
+    fn message(id: &str) -> MessageHeader {
+        let mut msg = MessageHeader::new();
+        msg.set_id(id.to_string());
+        msg
+    }
+
+    /// replies with the request metadata value for the key specified by the request ID
+    /// - the correlation ID is retrieved via correlation_id(), and is returned as a ULID string
+    fn echo_header(
+        ctx: &grpcio::RpcContext,
+        req: &MessageHeader,
+    ) -> Result<MessageHeader, grpcio::RpcStatus> {
+        let key = req.get_id();
+        let value = if key == CORRELATION_ID_KEY {
+            correlation_id(ctx).map(|id| id.to_string())
+        } else {
+            ctx.request_headers()
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, value)| String::from_utf8_lossy(value).to_string())
+        };
+        value.map(|value| message(&value)).ok_or_else(|| {
+            grpcio::RpcStatus::new(grpcio::RpcStatusCode::NotFound, Some(key.to_string()))
+        })
+    }
+
+    fn internal_error(err: grpcio::Error) -> grpcio::RpcStatus {
+        grpcio::RpcStatus::new(grpcio::RpcStatusCode::Internal, Some(err.to_string()))
+    }
+
+    const SLEEP: &str = "/oysterpack.Client/sleep";
+    const ECHO_HEADER: &str = "/oysterpack.Client/echo_header";
+    const COUNT: &str = "/oysterpack.Client/count";
+    const CONCAT: &str = "/oysterpack.Client/concat";
+    const ECHO: &str = "/oysterpack.Client/echo";
+
+    /// starts a server that hosts the test methods
+    /// - sleep: unary ReqRep method - see [SleepService](struct.SleepService.html)
+    /// - echo_header: unary method - see [echo_header()](fn.echo_header.html)
+    /// - count: server streaming method, which streams back the numbers `1..=n`, where n is the
+    ///   request ID
+    /// - concat: client streaming method, which replies with the concatenated request IDs
+    /// - echo: duplex streaming method, which echoes back each request
+    fn start_server() -> (GrpcServerHandle, grpcio::Channel) {
+        let reqrep = ReqRepConfig::new(
+            ReqRepId::generate(),
+            metrics::timer_buckets(vec![Duration::from_millis(1), Duration::from_millis(10)])
+                .unwrap(),
+        )
+        .start_service(SleepService, global_executor())
+        .unwrap();
+        let builder = compat::add_unary_handler(
+            grpcio::ServiceBuilder::new(),
+            &method(grpcio::MethodType::Unary, ECHO_HEADER),
+            global_executor(),
+            |ctx, req: MessageHeader| {
+                let reply = echo_header(ctx, &req);
+                async move { reply }
+            },
+        );
+        let builder = compat::add_server_streaming_handler(
+            builder,
+            &method(grpcio::MethodType::ServerStreaming, COUNT),
+            global_executor(),
+            |_ctx, req: MessageHeader| {
+                let n: usize = req.get_id().parse().unwrap_or(0);
+                stream::iter((1..=n).map(|i| Ok(message(&i.to_string()))))
+            },
+        );
+        let builder = compat::add_client_streaming_handler(
+            builder,
+            &method(grpcio::MethodType::ClientStreaming, CONCAT),
+            global_executor(),
+            |_ctx, requests: compat::RequestStream03<MessageHeader>| {
+                async move {
+                    let requests: Vec<_> = await!(requests.collect());
+                    let mut ids = String::new();
+                    for req in requests {
+                        match req {
+                            Ok(req) => ids.push_str(req.get_id()),
+                            Err(err) => return Err(internal_error(err)),
+                        }
+                    }
+                    Ok(message(&ids))
+                }
+            },
+        );
+        let builder = compat::add_duplex_streaming_handler(
+            builder,
+            &method(grpcio::MethodType::Duplex, ECHO),
+            global_executor(),
+            |_ctx, requests: compat::RequestStream03<MessageHeader>| {
+                requests.map(|req| req.map_err(internal_error))
+            },
+        );
+        let server = GrpcServerBuilder::new(global_executor())
+            .register_unary_reqrep(&method(grpcio::MethodType::Unary, SLEEP), reqrep)
+            .register_service(builder.build())
+            .start()
+            .unwrap();
+        let (host, port) = server.bind_addrs()[0].clone();
+        let env = Arc::new(grpcio::EnvBuilder::new().build());
+        let channel = grpcio::ChannelBuilder::new(env).connect(&format!("{}:{}", host, port));
+        (server, channel)
+    }
+
+    #[test]
+    fn grpc_client_metrics_and_timeout() {
+        configure_logging();
+
+        // GIVEN: a server
+        let (server, channel) = start_server();
+        let method = method(grpcio::MethodType::Unary, SLEEP);
+        // AND: a client with a deadline
+        let client = GrpcClient::new(channel).set_timeout(Duration::from_millis(500));
+
+        // WHEN: a call completes before the deadline
+        let req = message("0");
+        let rep = client.unary_call(&method, &req).unwrap();
+        assert_eq!(rep, req);
+        // AND: an async call completes before the deadline
+        let rep = global_executor()
+            .run(client.unary_call_async(&method, &req))
+            .unwrap();
+        assert_eq!(rep, req);
+
+        // WHEN: a call exceeds the deadline
+        match client.unary_call(&method, &message("1000")) {
+            // THEN: the call fails with DeadlineExceeded
+            Err(grpcio::Error::RpcFailure(status)) => {
+                assert_eq!(status.status, grpcio::RpcStatusCode::DeadlineExceeded)
+            }
+            other => panic!("expected DeadlineExceeded: {:?}", other),
+        }
+
+        // THEN: the per method client metrics are recorded
+        assert_eq!(
+            CLIENT_REQUEST_COUNT.with_label_values(&[method.name]).get(),
+            3
+        );
+        assert_eq!(
+            CLIENT_RESPONSE_COUNT
+                .with_label_values(&[method.name, "Ok"])
+                .get(),
+            2
+        );
+        assert_eq!(
+            CLIENT_RESPONSE_COUNT
+                .with_label_values(&[method.name, "DeadlineExceeded"])
+                .get(),
+            1
+        );
+        assert_eq!(
+            CLIENT_REQUEST_TIMER
+                .with_label_values(&[method.name])
+                .get_sample_count(),
+            3
+        );
+        server.shutdown_blocking().unwrap();
+    }
+
+    #[test]
+    fn grpc_client_interceptors_inject_metadata() {
+        configure_logging();
+
+        // GIVEN: a server that echoes back request metadata
+        let (server, channel) = start_server();
+        let method = method(grpcio::MethodType::Unary, ECHO_HEADER);
+        // AND: a client with interceptors
+        let client = GrpcClient::new(channel.clone())
+            .add_interceptor(CorrelationIdInterceptor)
+            .add_interceptor(BearerTokenInterceptor::new("secret".to_string()))
+            .add_interceptor(|call: &mut CallContext| {
+                let method = call.method().to_string();
+                call.metadata().add_str("x-method", &method)?;
+                Ok(())
+            });
+
+        // WHEN: calls are made
+        // THEN: the metadata injected by the interceptors is received by the server
+        let header = |key: &str| {
+            let rep = client.unary_call(&method, &message(key)).unwrap();
+            rep.get_id().to_string()
+        };
+        assert_eq!(header(AUTHORIZATION_KEY), "Bearer secret");
+        assert_eq!(header("x-method"), ECHO_HEADER);
+        // AND: the server retrieves the correlation ID via correlation_id()
+        let correlation_id_1: ULID = header(CORRELATION_ID_KEY).parse().unwrap();
+        let correlation_id_2: ULID = header(CORRELATION_ID_KEY).parse().unwrap();
+        // AND: a new correlation ID is set per call
+        assert_ne!(correlation_id_1, correlation_id_2);
+
+        // WHEN: the correlation ID metadata is not present
+        let client = GrpcClient::new(channel);
+        match client.unary_call(&method, &message(CORRELATION_ID_KEY)) {
+            // THEN: correlation_id() returns None
+            Err(grpcio::Error::RpcFailure(status)) => {
+                assert_eq!(status.status, grpcio::RpcStatusCode::NotFound)
+            }
+            other => panic!("expected NotFound: {:?}", other),
+        }
+        server.shutdown_blocking().unwrap();
+    }
+
+    #[test]
+    fn grpc_client_streaming_calls() {
+        configure_logging();
+
+        // GIVEN: a server with streaming methods
+        let (server, channel) = start_server();
+        let client = GrpcClient::new(channel).add_interceptor(CorrelationIdInterceptor);
+        let mut executor = global_executor();
+
+        // WHEN: a server streaming call is made
+        let count = method(grpcio::MethodType::ServerStreaming, COUNT);
+        let replies = client.server_streaming(&count, &message("3")).unwrap();
+        let replies: Vec<_> = executor.run(replies.collect());
+        // THEN: all replies are received
+        let ids: Vec<_> = replies
+            .into_iter()
+            .map(|rep| rep.unwrap().get_id().to_string())
+            .collect();
+        assert_eq!(ids, vec!["1", "2", "3"]);
+
+        // WHEN: a client streaming call is made
+        let concat = method(grpcio::MethodType::ClientStreaming, CONCAT);
+        let (sender, reply) = client.client_streaming(&concat).unwrap();
+        let requests = stream::iter(vec![message("a"), message("b"), message("c")]);
+        let rep = executor.run(
+            async move {
+                await!(compat::send_all(sender, requests)).unwrap();
+                await!(reply)
+            },
+        );
+        // THEN: the reply is received
+        assert_eq!(rep.unwrap().get_id(), "abc");
+
+        // WHEN: a duplex streaming call is made
+        let echo = method(grpcio::MethodType::Duplex, ECHO);
+        let (sender, replies) = client.duplex_streaming(&echo).unwrap();
+        let requests = stream::iter(vec![message("x"), message("y")]);
+        let replies: Vec<_> = executor.run(
+            async move {
+                await!(compat::send_all(sender, requests)).unwrap();
+                await!(replies.collect())
+            },
+        );
+        // THEN: the replies are received
+        let ids: Vec<_> = replies
+            .into_iter()
+            .map(|rep| rep.unwrap().get_id().to_string())
+            .collect();
+        assert_eq!(ids, vec!["x", "y"]);
+
+        // AND: the per method client metrics are recorded when the calls complete
+        for &method in [COUNT, CONCAT, ECHO].iter() {
+            assert_eq!(CLIENT_REQUEST_COUNT.with_label_values(&[method]).get(), 1);
+            assert_eq!(
+                CLIENT_RESPONSE_COUNT
+                    .with_label_values(&[method, "Ok"])
+                    .get(),
+                1
+            );
+            assert_eq!(
+                CLIENT_REQUEST_TIMER
+                    .with_label_values(&[method])
+                    .get_sample_count(),
+                1
+            );
+        }
+
+        // WHEN: a server streaming reply stream is dropped before it is done
+        drop(client.server_streaming(&count, &message("3")).unwrap());
+        // THEN: the call is recorded as Cancelled
+        assert_eq!(
+            CLIENT_RESPONSE_COUNT
+                .with_label_values(&[COUNT, "Cancelled"])
+                .get(),
+            1
+        );
+        server.shutdown_blocking().unwrap();
+    }
+
+    #[test]
+    fn grpc_client_interceptor_failure() {
+        configure_logging();
+
+        // GIVEN: a client with an interceptor that fails
+        let env = Arc::new(grpcio::EnvBuilder::new().build());
+        let channel = grpcio::ChannelBuilder::new(env).connect("127.0.0.1:1");
+        let client = GrpcClient::new(channel).add_interceptor(|call: &mut CallContext| {
+            // binary metadata must be added via add_bytes()
+            call.metadata().add_str("invalid-bin", "value")?;
+            Ok(())
+        });
+
+        // WHEN: a call is made
+        let method = method(grpcio::MethodType::Unary, "/oysterpack.Invalid/invalid");
+        let result = client.unary_call(&method, &MessageHeader::new());
+
+        // THEN: the call is not sent
+        assert!(result.is_err());
+        assert_eq!(
+            CLIENT_RESPONSE_COUNT
+                .with_label_values(&[method.name, "Unknown"])
+                .get(),
+            1
+        );
+    }
+}
@@ -0,0 +1,681 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Bridges grpcio, which is built on futures 0.1, with oysterpack_trust
+//! [executors](https://docs.rs/oysterpack_trust/latest/oysterpack_trust/concurrent/execution/struct.Executor.html),
+//! which run futures-preview 0.3 futures.
+//!
+//! ## Server
+//! gRPC handlers are registered as functions that return 0.3 futures and streams. The handler is
+//! invoked on the gRPC thread, i.e., the RpcContext can be inspected synchronously, and the returned
+//! future or stream is driven on the executor:
+//! - [add_unary_handler()](fn.add_unary_handler.html)
+//! - [add_client_streaming_handler()](fn.add_client_streaming_handler.html)
+//! - [add_server_streaming_handler()](fn.add_server_streaming_handler.html)
+//! - [add_duplex_streaming_handler()](fn.add_duplex_streaming_handler.html)
+//!
+//! Request streams are exposed as 0.3 streams, i.e., [RequestStream03](type.RequestStream03.html).
+//! Replies are returned as `Result<Rep, RpcStatus>`, where the status is sent back to the client
+//! when the call fails. Per method request metrics are recorded via a
+//! [RequestTimer](../server/struct.RequestTimer.html) - see [server](../server/index.html#metrics).
+//! For generated service traits, the same building blocks are available directly:
+//! - [spawn_handler()](fn.spawn_handler.html)
+//! - [ReplySink](trait.ReplySink.html) and [send_stream()](fn.send_stream.html)
+//!
+//! ## Client
+//! - unary calls: [into_future03()](fn.into_future03.html)
+//! - client streaming calls: [client_streaming()](fn.client_streaming.html)
+//! - server streaming calls: [into_stream03()](fn.into_stream03.html)
+//! - duplex streaming calls: [duplex_streaming()](fn.duplex_streaming.html)
+//!
+//! ## Example
+//! ```no_run
+//! # #![feature(await_macro, async_await, futures_api)]
+//! # use oysterpack_trust_grpc::{protos::message::MessageHeader, compat::*};
+//! # use oysterpack_trust::concurrent::execution::global_executor;
+//! # fn example(method: grpcio::Method<MessageHeader, MessageHeader>) {
+//! let service = add_unary_handler(
+//!     grpcio::ServiceBuilder::new(),
+//!     &method,
+//!     global_executor(),
+//!     |_ctx, req: MessageHeader| async move { Ok::<_, grpcio::RpcStatus>(req) },
+//! )
+//! .build();
+//! # }
+//! ```
+
+use crate::server::RequestTimer;
+use oysterpack_log::*;
+use oysterpack_trust::concurrent::execution::{
+    futures::{
+        channel::oneshot,
+        compat::{Compat, Compat01As03, Future01CompatExt},
+        future::FutureExt,
+        stream::{self, StreamExt},
+        task::{Poll, SpawnError, SpawnExt, Waker},
+        Future, Stream,
+    },
+    Executor,
+};
+use std::{fmt, pin::Pin};
+
+/// gRPC request stream, which is exposed as a futures 0.3 stream
+pub type RequestStream03<T> = Compat01As03<grpcio::RequestStream<T>>;
+
+/// Converts a futures 0.1 Future into a futures 0.3 Future, e.g., the receiver for an async unary call
+pub fn into_future03<F: futures::Future>(future01: F) -> Compat01As03<F> {
+    Compat01As03::new(future01)
+}
+
+/// Converts a futures 0.1 Stream into a futures 0.3 Stream, e.g., the receiver for a server streaming call
+pub fn into_stream03<S: futures::Stream>(stream01: S) -> Compat01As03<S> {
+    Compat01As03::new(stream01)
+}
+
+/// Constructs a gRPC method descriptor, which uses protobuf marshalling
+/// - the name is the fully qualified method name, i.e., `/{package}.{service}/{method}`
+/// - for unary methods, see [unary_method()](../reqrep/fn.unary_method.html)
+pub fn protobuf_method<Req, Rep>(
+    ty: grpcio::MethodType,
+    name: &'static str,
+) -> grpcio::Method<Req, Rep>
+where
+    Req: protobuf::Message,
+    Rep: protobuf::Message,
+{
+    grpcio::Method {
+        ty,
+        name,
+        req_mar: grpcio::Marshaller {
+            ser: grpcio::pb_ser,
+            de: grpcio::pb_de,
+        },
+        resp_mar: grpcio::Marshaller {
+            ser: grpcio::pb_ser,
+            de: grpcio::pb_de,
+        },
+    }
+}
+
+/// Returns the gRPC method name, which is used for logging
+fn method_name(ctx: &grpcio::RpcContext) -> String {
+    String::from_utf8_lossy(ctx.method()).to_string()
+}
+
+/// Spawns the gRPC handler task on the executor
+/// - if the task fails to spawn, then the error is logged and the task is dropped, which drops the
+///   call's sink and cancels the call
+///   - if the task owns a [RequestTimer](../server/struct.RequestTimer.html), then the call is
+///     recorded as `Cancelled`
+pub fn spawn_handler<F>(executor: &mut Executor, ctx: &grpcio::RpcContext, handler: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    if let Err(err) = executor.spawn(handler) {
+        error!(
+            "{}: failed to spawn gRPC handler task: is_shutdown = {}",
+            method_name(ctx),
+            err.is_shutdown()
+        );
+    }
+}
+
+/// Sinks that complete a call with a single reply, i.e., `grpcio::UnarySink` and
+/// `grpcio::ClientStreamingSink`
+pub trait ReplySink<T> {
+    /// futures 0.1 sink result
+    type Result: futures::Future<Item = (), Error = grpcio::Error>;
+
+    /// Sends the reply, or fails the call with the status
+    fn send_reply(self, reply: Result<T, grpcio::RpcStatus>) -> Compat01As03<Self::Result>;
+}
+
+impl<T> ReplySink<T> for grpcio::UnarySink<T> {
+    type Result = grpcio::UnarySinkResult;
+
+    fn send_reply(self, reply: Result<T, grpcio::RpcStatus>) -> Compat01As03<Self::Result> {
+        match reply {
+            Ok(reply) => self.success(reply).compat(),
+            Err(status) => self.fail(status).compat(),
+        }
+    }
+}
+
+impl<T> ReplySink<T> for grpcio::ClientStreamingSink<T> {
+    type Result = grpcio::ClientStreamingSinkResult;
+
+    fn send_reply(self, reply: Result<T, grpcio::RpcStatus>) -> Compat01As03<Self::Result> {
+        match reply {
+            Ok(reply) => self.success(reply).compat(),
+            Err(status) => self.fail(status).compat(),
+        }
+    }
+}
+
+/// Server side sinks that stream replies, i.e., `grpcio::ServerStreamingSink` and `grpcio::DuplexSink`
+pub trait StreamingSink<T>:
+    futures::Sink<SinkItem = (T, grpcio::WriteFlags), SinkError = grpcio::Error> + Sized
+{
+    /// futures 0.1 sink failure result
+    type Failure: futures::Future<Item = (), Error = grpcio::Error>;
+
+    /// Fails the call with the status
+    fn fail_call(self, status: grpcio::RpcStatus) -> Self::Failure;
+}
+
+impl<T> StreamingSink<T> for grpcio::ServerStreamingSink<T> {
+    type Failure = grpcio::ServerStreamingSinkFailure;
+
+    fn fail_call(self, status: grpcio::RpcStatus) -> Self::Failure {
+        self.fail(status)
+    }
+}
+
+impl<T> StreamingSink<T> for grpcio::DuplexSink<T> {
+    type Failure = grpcio::DuplexSinkFailure;
+
+    fn fail_call(self, status: grpcio::RpcStatus) -> Self::Failure {
+        self.fail(status)
+    }
+}
+
+/// Streams the replies back to the client
+/// - once the stream is done, the sink is closed, which completes the call with an `OK` status
+/// - if the stream yields an error status, then the call is failed with the status, and the rest of
+///   the stream is dropped
+pub fn send_stream<Si, T, S>(
+    sink: Si,
+    replies: S,
+) -> impl Future<Output = Result<(), grpcio::Error>>
+where
+    Si: StreamingSink<T>,
+    S: Stream<Item = Result<T, grpcio::RpcStatus>>,
+{
+    send_stream_with_status(sink, replies).map(|(_, result)| result)
+}
+
+/// Streams the replies back to the client - see [send_stream()](fn.send_stream.html)
+/// - the returned status code is the status the call was completed with, which is used to record
+///   the request metrics
+fn send_stream_with_status<Si, T, S>(
+    sink: Si,
+    replies: S,
+) -> impl Future<Output = (grpcio::RpcStatusCode, Result<(), grpcio::Error>)>
+where
+    Si: StreamingSink<T>,
+    S: Stream<Item = Result<T, grpcio::RpcStatus>>,
+{
+    async move {
+        let mut sink = sink;
+        let mut replies = Box::pin(replies);
+        while let Some(reply) = await!(replies.next()) {
+            match reply {
+                Ok(reply) => {
+                    match await!(sink.send((reply, grpcio::WriteFlags::default())).compat()) {
+                        Ok(s) => sink = s,
+                        Err(err) => return (grpcio::RpcStatusCode::Unknown, Err(err)),
+                    }
+                }
+                Err(status) => {
+                    let code = status.status;
+                    return (code, await!(sink.fail_call(status).compat()));
+                }
+            }
+        }
+        let result = await!(futures::future::poll_fn(move || sink.close()).compat());
+        (grpcio::RpcStatusCode::Ok, result)
+    }
+}
+
+/// Returns the status code that the call is completed with
+fn status_code<T>(reply: &Result<T, grpcio::RpcStatus>) -> grpcio::RpcStatusCode {
+    match reply {
+        Ok(_) => grpcio::RpcStatusCode::Ok,
+        Err(status) => status.status,
+    }
+}
+
+/// Sends all items on the stream via the futures 0.1 gRPC sink, e.g., the request sender for client
+/// streaming and duplex streaming calls
+/// - once the stream is done, the sink is closed
+pub fn send_all<Si, T, S>(sink: Si, items: S) -> impl Future<Output = Result<(), grpcio::Error>>
+where
+    Si: futures::Sink<SinkItem = (T, grpcio::WriteFlags), SinkError = grpcio::Error>,
+    S: Stream<Item = T>,
+{
+    let items = items.map(|item| Ok::<_, grpcio::Error>((item, grpcio::WriteFlags::default())));
+    sink.send_all(Compat::new(Box::pin(items)))
+        .compat()
+        .map(|result| result.map(|_| ()))
+}
+
+/// Registers a unary handler, which returns a futures 0.3 future that is run on the executor
+pub fn add_unary_handler<Req, Rep, F, Fut>(
+    builder: grpcio::ServiceBuilder,
+    method: &grpcio::Method<Req, Rep>,
+    executor: Executor,
+    handler: F,
+) -> grpcio::ServiceBuilder
+where
+    Req: 'static,
+    Rep: Send + 'static,
+    F: FnMut(&grpcio::RpcContext, Req) -> Fut + Send + Clone + 'static,
+    Fut: Future<Output = Result<Rep, grpcio::RpcStatus>> + Send + 'static,
+{
+    let mut executor = executor;
+    let mut handler = handler;
+    builder.add_unary_handler(method, move |ctx, req, sink: grpcio::UnarySink<Rep>| {
+        let timer = RequestTimer::from_context(&ctx);
+        let reply = handler(&ctx, req);
+        spawn_handler(
+            &mut executor,
+            &ctx,
+            async move {
+                let reply = await!(reply);
+                let status = status_code(&reply);
+                if let Err(err) = await!(sink.send_reply(reply)) {
+                    warn!("{}: failed to send reply: {}", timer.method(), err);
+                }
+                timer.observe(status);
+            },
+        );
+    })
+}
+
+/// Registers a client streaming handler, which receives the request stream as a futures 0.3 stream
+/// and returns a futures 0.3 future that is run on the executor
+pub fn add_client_streaming_handler<Req, Rep, F, Fut>(
+    builder: grpcio::ServiceBuilder,
+    method: &grpcio::Method<Req, Rep>,
+    executor: Executor,
+    handler: F,
+) -> grpcio::ServiceBuilder
+where
+    Req: 'static,
+    Rep: Send + 'static,
+    F: FnMut(&grpcio::RpcContext, RequestStream03<Req>) -> Fut + Send + Clone + 'static,
+    Fut: Future<Output = Result<Rep, grpcio::RpcStatus>> + Send + 'static,
+{
+    let mut executor = executor;
+    let mut handler = handler;
+    builder.add_client_streaming_handler(
+        method,
+        move |ctx, requests, sink: grpcio::ClientStreamingSink<Rep>| {
+            let timer = RequestTimer::from_context(&ctx);
+            let reply = handler(&ctx, into_stream03(requests));
+            spawn_handler(
+                &mut executor,
+                &ctx,
+                async move {
+                    let reply = await!(reply);
+                    let status = status_code(&reply);
+                    if let Err(err) = await!(sink.send_reply(reply)) {
+                        warn!("{}: failed to send reply: {}", timer.method(), err);
+                    }
+                    timer.observe(status);
+                },
+            );
+        },
+    )
+}
+
+/// Registers a server streaming handler, which returns a futures 0.3 stream of replies that is sent
+/// back to the client on the executor - see [send_stream()](fn.send_stream.html)
+pub fn add_server_streaming_handler<Req, Rep, F, S>(
+    builder: grpcio::ServiceBuilder,
+    method: &grpcio::Method<Req, Rep>,
+    executor: Executor,
+    handler: F,
+) -> grpcio::ServiceBuilder
+where
+    Req: 'static,
+    Rep: Send + 'static,
+    F: FnMut(&grpcio::RpcContext, Req) -> S + Send + Clone + 'static,
+    S: Stream<Item = Result<Rep, grpcio::RpcStatus>> + Send + 'static,
+{
+    let mut executor = executor;
+    let mut handler = handler;
+    builder.add_server_streaming_handler(
+        method,
+        move |ctx, req, sink: grpcio::ServerStreamingSink<Rep>| {
+            let timer = RequestTimer::from_context(&ctx);
+            let replies = handler(&ctx, req);
+            spawn_handler(
+                &mut executor,
+                &ctx,
+                async move {
+                    let (status, result) = await!(send_stream_with_status(sink, replies));
+                    if let Err(err) = result {
+                        warn!("{}: failed to send replies: {}", timer.method(), err);
+                    }
+                    timer.observe(status);
+                },
+            );
+        },
+    )
+}
+
+/// Registers a duplex streaming handler, which receives the request stream as a futures 0.3 stream
+/// and returns a futures 0.3 stream of replies that is sent back to the client on the executor -
+/// see [send_stream()](fn.send_stream.html)
+pub fn add_duplex_streaming_handler<Req, Rep, F, S>(
+    builder: grpcio::ServiceBuilder,
+    method: &grpcio::Method<Req, Rep>,
+    executor: Executor,
+    handler: F,
+) -> grpcio::ServiceBuilder
+where
+    Req: 'static,
+    Rep: Send + 'static,
+    F: FnMut(&grpcio::RpcContext, RequestStream03<Req>) -> S + Send + Clone + 'static,
+    S: Stream<Item = Result<Rep, grpcio::RpcStatus>> + Send + 'static,
+{
+    let mut executor = executor;
+    let mut handler = handler;
+    builder.add_duplex_streaming_handler(
+        method,
+        move |ctx, requests, sink: grpcio::DuplexSink<Rep>| {
+            let timer = RequestTimer::from_context(&ctx);
+            let replies = handler(&ctx, into_stream03(requests));
+            spawn_handler(
+                &mut executor,
+                &ctx,
+                async move {
+                    let (status, result) = await!(send_stream_with_status(sink, replies));
+                    if let Err(err) = result {
+                        warn!("{}: failed to send replies: {}", timer.method(), err);
+                    }
+                    timer.observe(status);
+                },
+            );
+        },
+    )
+}
+
+/// Completes a client streaming call, i.e., all requests are sent, and then the reply is received
+/// - if the server fails the call, then its status is returned, even if sending the requests failed
+pub fn client_streaming<Req, Rep, S>(
+    sender: grpcio::ClientCStreamSender<Req>,
+    receiver: grpcio::ClientCStreamReceiver<Rep>,
+    requests: S,
+) -> impl Future<Output = Result<Rep, grpcio::Error>>
+where
+    S: Stream<Item = Req>,
+{
+    async move {
+        let sent = await!(send_all(sender, requests));
+        match await!(receiver.compat()) {
+            Ok(reply) => sent.map(|_| reply),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Starts a duplex streaming call: the requests are sent on the executor, and the replies are
+/// returned as a futures 0.3 stream
+/// - if sending the requests fails, then the error is returned on the reply stream once the server
+///   completes the call
+///   - if the server fails the call, then its status is returned instead, i.e., the same as for
+///     [client_streaming()](fn.client_streaming.html)
+pub fn duplex_streaming<Req, Rep, S>(
+    executor: &mut Executor,
+    sender: grpcio::ClientDuplexSender<Req>,
+    receiver: grpcio::ClientDuplexReceiver<Rep>,
+    requests: S,
+) -> Result<DuplexReplies<Rep>, SpawnError>
+where
+    Req: Send + 'static,
+    S: Stream<Item = Req> + Send + 'static,
+{
+    let send = send_all(sender, requests);
+    let (send_error_tx, send_error_rx) = oneshot::channel();
+    executor.spawn(
+        async move {
+            if let Err(err) = await!(send) {
+                debug!("failed to send duplex streaming requests: {}", err);
+                // the reply stream may have been dropped
+                let _ = send_error_tx.send(err);
+            }
+        },
+    )?;
+    Ok(DuplexReplies {
+        replies: into_stream03(receiver).fuse(),
+        send_error: Some(send_error_rx),
+    })
+}
+
+/// Duplex streaming call reply stream, which is exposed as a futures 0.3 stream - see
+/// [duplex_streaming()](fn.duplex_streaming.html)
+pub struct DuplexReplies<Rep> {
+    replies: stream::Fuse<Compat01As03<grpcio::ClientDuplexReceiver<Rep>>>,
+    send_error: Option<oneshot::Receiver<grpcio::Error>>,
+}
+
+impl<Rep> Stream for DuplexReplies<Rep> {
+    type Item = Result<Rep, grpcio::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, waker: &Waker) -> Poll<Option<Self::Item>> {
+        match self.replies.poll_next_unpin(waker) {
+            // once the server completes the call, wait for the requests to be sent
+            Poll::Ready(None) => {
+                let send_error = self.send_error.as_mut().map(|rx| rx.poll_unpin(waker));
+                match send_error {
+                    Some(Poll::Pending) => Poll::Pending,
+                    Some(Poll::Ready(Ok(err))) => {
+                        self.send_error = None;
+                        Poll::Ready(Some(Err(err)))
+                    }
+                    // the sender is dropped once all requests are sent
+                    _ => {
+                        self.send_error = None;
+                        Poll::Ready(None)
+                    }
+                }
+            }
+            // the server's status takes precedence over send errors
+            Poll::Ready(Some(Err(err))) => {
+                self.send_error = None;
+                Poll::Ready(Some(Err(err)))
+            }
+            reply => reply,
+        }
+    }
+}
+
+impl<Rep> fmt::Debug for DuplexReplies<Rep> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("DuplexReplies")
+    }
+}
+
+#[allow(warnings)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protos::message::MessageHeader;
+    use crate::{configure_logging, method, start_server};
+    use oysterpack_trust::concurrent::execution::{futures::stream, global_executor};
+
+    fn message(id: usize) -> MessageHeader {
+        let mut msg = MessageHeader::new();
+        msg.set_id(id.to_string());
+        msg
+    }
+
+    fn invalid_argument() -> grpcio::RpcStatus {
+        grpcio::RpcStatus::new(
+            grpcio::RpcStatusCode::InvalidArgument,
+            Some("id must be a number".to_string()),
+        )
+    }
+
+    fn internal_error(err: grpcio::Error) -> grpcio::RpcStatus {
+        grpcio::RpcStatus::new(grpcio::RpcStatusCode::Internal, Some(err.to_string()))
+    }
+
+    /// parses the message ID, which must be a number
+    fn parse_id(msg: &MessageHeader) -> Result<usize, grpcio::RpcStatus> {
+        msg.get_id().parse().map_err(|_| invalid_argument())
+    }
+
+    #[test]
+    fn compat_unary_and_client_streaming() {
+        configure_logging();
+
+        // GIVEN: a unary handler that echoes the request
+        let unary = method(grpcio::MethodType::Unary, "/oysterpack.Compat/unary");
+        // AND: a client streaming handler that replies with the sum of the request IDs
+        let sum = method(grpcio::MethodType::ClientStreaming, "/oysterpack.Compat/sum");
+        let builder = add_unary_handler(
+            grpcio::ServiceBuilder::new(),
+            &unary,
+            global_executor(),
+            |_ctx, req: MessageHeader| async move { parse_id(&req).map(|_| req) },
+        );
+        let builder = add_client_streaming_handler(
+            builder,
+            &sum,
+            global_executor(),
+            |_ctx, mut requests: RequestStream03<MessageHeader>| {
+                async move {
+                    let mut sum = 0;
+                    while let Some(req) = await!(requests.next()) {
+                        match req.map_err(internal_error).and_then(|req| parse_id(&req)) {
+                            Ok(id) => sum += id,
+                            Err(status) => return Err(status),
+                        }
+                    }
+                    Ok(message(sum))
+                }
+            },
+        );
+        let (_server, client) = start_server(builder.build());
+
+        // WHEN: a unary call is made asynchronously
+        let receiver = client
+            .unary_call_async(&unary, &message(1), grpcio::CallOption::default())
+            .unwrap();
+        // THEN: the reply is received via the 0.3 future
+        let rep = global_executor().run(into_future03(receiver)).unwrap();
+        assert_eq!(rep, message(1));
+
+        // WHEN: the unary handler fails
+        let receiver = client
+            .unary_call_async(&unary, &MessageHeader::new(), grpcio::CallOption::default())
+            .unwrap();
+        match global_executor().run(into_future03(receiver)) {
+            // THEN: the status is returned to the client
+            Err(grpcio::Error::RpcFailure(status)) => {
+                assert_eq!(status.status, grpcio::RpcStatusCode::InvalidArgument)
+            }
+            other => panic!("expected RpcFailure: {:?}", other),
+        }
+
+        // WHEN: requests are streamed from a 0.3 stream
+        let (sender, receiver) = client
+            .client_streaming(&sum, grpcio::CallOption::default())
+            .unwrap();
+        let requests = stream::iter((1..=10).map(message));
+        let rep = global_executor()
+            .run(client_streaming(sender, receiver, requests))
+            .unwrap();
+        // THEN: the server received all requests
+        assert_eq!(rep, message(55));
+    }
+
+    #[test]
+    fn compat_server_and_duplex_streaming() {
+        configure_logging();
+
+        // GIVEN: a server streaming handler that streams back `id` replies
+        let count = method(grpcio::MethodType::ServerStreaming, "/oysterpack.Compat/count");
+        // AND: a duplex streaming handler that echoes each request
+        let echo = method(grpcio::MethodType::Duplex, "/oysterpack.Compat/echo");
+        let builder = add_server_streaming_handler(
+            grpcio::ServiceBuilder::new(),
+            &count,
+            global_executor(),
+            |_ctx, req: MessageHeader| {
+                let replies: Vec<_> = match parse_id(&req) {
+                    Ok(n) => (0..n).map(|i| Ok(message(i))).collect(),
+                    Err(status) => vec![Err(status)],
+                };
+                stream::iter(replies)
+            },
+        );
+        let builder = add_duplex_streaming_handler(
+            builder,
+            &echo,
+            global_executor(),
+            |_ctx, requests: RequestStream03<MessageHeader>| {
+                requests.map(|req| {
+                    req.map_err(internal_error)
+                        .and_then(|req| parse_id(&req).map(|_| req))
+                })
+            },
+        );
+        let (_server, client) = start_server(builder.build());
+
+        // WHEN: a server streaming call is made
+        let receiver = client
+            .server_streaming(&count, &message(5), grpcio::CallOption::default())
+            .unwrap();
+        // THEN: all replies are received via the 0.3 stream
+        let replies: Vec<_> = global_executor().run(into_stream03(receiver).collect());
+        let replies: Vec<_> = replies.into_iter().map(Result::unwrap).collect();
+        assert_eq!(replies, (0..5).map(message).collect::<Vec<_>>());
+
+        // WHEN: the server streaming handler fails
+        let receiver = client
+            .server_streaming(&count, &MessageHeader::new(), grpcio::CallOption::default())
+            .unwrap();
+        let (reply, _) = global_executor().run(into_stream03(receiver).into_future());
+        // THEN: the status is returned to the client
+        match reply {
+            Some(Err(grpcio::Error::RpcFailure(status))) => {
+                assert_eq!(status.status, grpcio::RpcStatusCode::InvalidArgument)
+            }
+            other => panic!("expected RpcFailure: {:?}", other),
+        }
+
+        // WHEN: a duplex streaming call is made
+        let (sender, receiver) = client
+            .duplex_streaming(&echo, grpcio::CallOption::default())
+            .unwrap();
+        let requests = stream::iter((0..10).map(message));
+        let replies = duplex_streaming(&mut global_executor(), sender, receiver, requests).unwrap();
+        // THEN: each request is echoed back
+        let replies: Vec<_> = global_executor().run(replies.collect());
+        let replies: Vec<_> = replies.into_iter().map(Result::unwrap).collect();
+        assert_eq!(replies, (0..10).map(message).collect::<Vec<_>>());
+
+        // WHEN: the duplex streaming handler fails the call while requests are being sent
+        let (sender, receiver) = client
+            .duplex_streaming(&echo, grpcio::CallOption::default())
+            .unwrap();
+        let requests =
+            stream::iter(vec![MessageHeader::new()]).chain(stream::iter((0..10).map(message)));
+        let replies = duplex_streaming(&mut global_executor(), sender, receiver, requests).unwrap();
+        let replies: Vec<_> = global_executor().run(replies.collect());
+        // THEN: the server's status is returned on the reply stream, even if sending requests failed
+        match replies.last() {
+            Some(Err(grpcio::Error::RpcFailure(status))) => {
+                assert_eq!(status.status, grpcio::RpcStatusCode::InvalidArgument)
+            }
+            other => panic!("expected RpcFailure: {:?}", other),
+        }
+    }
+}
@@ -14,11 +14,13 @@
  *    limitations under the License.
  */
 
-//! **oysterpack-trust-nng** provides support for building robust scalable distributed applications
-//! leveraging proven [nng](https://nanomsg.github.io/nng/) messaging technology.
+//! **oysterpack-trust-grpc** provides support for exposing oysterpack-trust services via
+//! [gRPC](https://grpc.io/).
 //!
-//! nng provides the advanced messaging protocol-specific processing. Rust provides the power of
-//! fearless concurrency and futures for message processing.
+//! - [reqrep](reqrep/index.html) exposes ReqRep services as unary gRPC methods
+//! - [client](client/index.html) provides an instrumented gRPC client with interceptors
+//! - [compat](compat/index.html) bridges grpcio futures 0.1 handlers and calls with futures 0.3 executors
+//! - [server](server/index.html) provides a gRPC server builder with TLS, metrics and graceful shutdown
 
 #![feature(await_macro, async_await, futures_api, arbitrary_self_types)]
 #![deny(clippy::all)]
@@ -32,8 +34,12 @@
 #[macro_use]
 extern crate pretty_assertions;
 
+pub mod client;
+pub mod compat;
 #[allow(missing_debug_implementations)]
 pub mod protos;
+pub mod reqrep;
+pub mod server;
 
 #[allow(warnings)]
 #[cfg(test)]
@@ -53,3 +59,42 @@ fn log_config() -> oysterpack_log::LogConfig {
 fn configure_logging() {
     oysterpack_log::init(log_config(), oysterpack_log::StderrLogger);
 }
+
+/// Constructs a gRPC method descriptor for MessageHeader requests and replies
+#[cfg(test)]
+fn method(
+    ty: grpcio::MethodType,
+    name: &'static str,
+) -> grpcio::Method<protos::message::MessageHeader, protos::message::MessageHeader> {
+    compat::protobuf_method(ty, name)
+}
+
+/// Starts a server on an OS assigned port for the specified service, and returns a client that is
+/// connected to it
+#[cfg(test)]
+fn start_server(service: grpcio::Service) -> (grpcio::Server, grpcio::Client) {
+    let env = std::sync::Arc::new(grpcio::Environment::new(1));
+    let mut server = grpcio::ServerBuilder::new(env.clone())
+        .register_service(service)
+        .bind("127.0.0.1", 0)
+        .build()
+        .unwrap();
+    server.start();
+    let (host, port) = server.bind_addrs()[0].clone();
+    let channel = grpcio::ChannelBuilder::new(env).connect(&format!("{}:{}", host, port));
+    (server, grpcio::Client::new(channel))
+}
+
+/// Polls the condition until it holds, for up to 1 sec
+/// - server metrics are recorded after the reply is sent, i.e., the client may receive the reply
+///   before the metrics are recorded
+#[cfg(test)]
+fn eventually<F: Fn() -> bool>(condition: F) -> bool {
+    (0..100).any(|_| {
+        if condition() {
+            return true;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        false
+    })
+}
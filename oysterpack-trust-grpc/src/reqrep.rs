@@ -0,0 +1,439 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Exposes [ReqRep](https://docs.rs/oysterpack_trust/latest/oysterpack_trust/concurrent/messaging/reqrep/struct.ReqRep.html)
+//! services as unary gRPC methods, i.e., the same ReqRep service backend can be reached by gRPC
+//! clients without duplicating handler code.
+//!
+//! - [UnaryReqRepHandler](struct.UnaryReqRepHandler.html) forwards gRPC requests to the ReqRep service
+//!   - it can be [registered](struct.UnaryReqRepHandler.html#method.register) directly with a
+//!     `grpcio::ServiceBuilder` for a [unary method](fn.unary_method.html)
+//!   - or it can be invoked from a generated gRPC service trait implementation via
+//!     [handle()](struct.UnaryReqRepHandler.html#method.handle)
+//! - requests and replies are converted through protobuf messages via
+//!   [FromProtobuf](trait.FromProtobuf.html) and [IntoProtobuf](trait.IntoProtobuf.html)
+//!   - protobuf messages convert into themselves, i.e., ReqRep services whose request and reply types
+//!     are protobuf messages can be exposed as is
+//!   - request conversion failures are returned to the client as `INVALID_ARGUMENT`
+//! - per method request metrics are recorded - see [server](../server/index.html#metrics)
+//! - ReqRep [ChannelError](https://docs.rs/oysterpack_trust/latest/oysterpack_trust/concurrent/messaging/errors/enum.ChannelError.html)(s)
+//!   are mapped to gRPC status codes - see [channel_error_status()](fn.channel_error_status.html)
+//!
+//! ## Example
+//! ```no_run
+//! # #![feature(await_macro, async_await, futures_api)]
+//! # use oysterpack_trust_grpc::{protos::message::MessageHeader, reqrep::*};
+//! # use oysterpack_trust::concurrent::{execution::global_executor, messaging::reqrep::ReqRep};
+//! # fn example(reqrep: ReqRep<MessageHeader, MessageHeader>) {
+//! let method = unary_method::<MessageHeader, MessageHeader>("/oysterpack.Echo/echo");
+//! let service = UnaryReqRepHandler::new(reqrep, global_executor())
+//!     .register(grpcio::ServiceBuilder::new(), &method)
+//!     .build();
+//! # }
+//! ```
+
+use crate::{compat::protobuf_method, server::RequestTimer};
+use futures::{sync::oneshot, Future};
+use oysterpack_log::*;
+use oysterpack_trust::concurrent::{
+    execution::{futures::task::SpawnExt, Executor},
+    messaging::{
+        errors::ChannelError,
+        reqrep::{ReqRep, ReqRepId},
+    },
+};
+use std::{
+    fmt::{self, Debug},
+    marker::PhantomData,
+};
+
+/// Converts a protobuf request message into the ReqRep request type
+pub trait FromProtobuf<P>: Sized {
+    /// If the conversion fails, then the returned status is sent back to the client
+    /// - the status code should normally be `InvalidArgument`
+    fn from_protobuf(msg: P) -> Result<Self, grpcio::RpcStatus>;
+}
+
+impl<P: protobuf::Message> FromProtobuf<P> for P {
+    fn from_protobuf(msg: P) -> Result<Self, grpcio::RpcStatus> {
+        Ok(msg)
+    }
+}
+
+/// Converts the ReqRep reply into a protobuf reply message
+pub trait IntoProtobuf<P> {
+    /// performs the conversion
+    fn into_protobuf(self) -> P;
+}
+
+impl<P: protobuf::Message> IntoProtobuf<P> for P {
+    fn into_protobuf(self) -> P {
+        self
+    }
+}
+
+/// Constructs a unary gRPC method descriptor, which uses protobuf marshalling
+/// - the name is the fully qualified method name, i.e., `/{package}.{service}/{method}`
+pub fn unary_method<PReq, PRep>(name: &'static str) -> grpcio::Method<PReq, PRep>
+where
+    PReq: protobuf::Message,
+    PRep: protobuf::Message,
+{
+    protobuf_method(grpcio::MethodType::Unary, name)
+}
+
+/// Maps a ReqRep ChannelError to a gRPC status
+/// - [SenderDisconnected](https://docs.rs/oysterpack_trust/latest/oysterpack_trust/concurrent/messaging/errors/enum.ChannelError.html#variant.SenderDisconnected)
+///   -> `UNAVAILABLE`, i.e., the ReqRep service is not running
+/// - [ReceiverDisconnected](https://docs.rs/oysterpack_trust/latest/oysterpack_trust/concurrent/messaging/errors/enum.ChannelError.html#variant.ReceiverDisconnected)
+///   -> `INTERNAL`, i.e., the ReqRep service dropped the request without replying, e.g., the processor panicked
+pub fn channel_error_status(err: &ChannelError) -> grpcio::RpcStatus {
+    let code = match err {
+        ChannelError::SenderDisconnected => grpcio::RpcStatusCode::Unavailable,
+        ChannelError::ReceiverDisconnected => grpcio::RpcStatusCode::Internal,
+    };
+    grpcio::RpcStatus::new(code, Some(err.to_string()))
+}
+
+/// Unary gRPC method handler, which forwards requests to a ReqRep service.
+/// - Req and Rep are the ReqRep service request and reply types
+/// - PReq and PRep are the protobuf request and reply message types
+/// - ReqRep replies are awaited asynchronously on the specified executor, i.e., gRPC threads are
+///   never blocked waiting on the ReqRep service
+pub struct UnaryReqRepHandler<Req, Rep, PReq, PRep>
+where
+    Req: FromProtobuf<PReq> + Debug + Send + 'static,
+    Rep: IntoProtobuf<PRep> + Debug + Send + 'static,
+    PReq: protobuf::Message,
+    PRep: protobuf::Message,
+{
+    reqrep: ReqRep<Req, Rep>,
+    executor: Executor,
+    _phantom: PhantomData<fn(PReq) -> PRep>,
+}
+
+impl<Req, Rep, PReq, PRep> UnaryReqRepHandler<Req, Rep, PReq, PRep>
+where
+    Req: FromProtobuf<PReq> + Debug + Send + 'static,
+    Rep: IntoProtobuf<PRep> + Debug + Send + 'static,
+    PReq: protobuf::Message,
+    PRep: protobuf::Message,
+{
+    /// constructor
+    /// - the executor is used to await the ReqRep reply and send it back to the client
+    pub fn new(reqrep: ReqRep<Req, Rep>, executor: Executor) -> Self {
+        UnaryReqRepHandler {
+            reqrep,
+            executor,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Returns the ReqRepId of the backend ReqRep service
+    pub fn reqrep_id(&self) -> ReqRepId {
+        self.reqrep.id()
+    }
+
+    /// Registers the handler for the specified unary method
+    pub fn register(
+        self,
+        builder: grpcio::ServiceBuilder,
+        method: &grpcio::Method<PReq, PRep>,
+    ) -> grpcio::ServiceBuilder {
+        let mut handler = self;
+        builder.add_unary_handler(method, move |ctx, req, sink| handler.handle(&ctx, req, sink))
+    }
+
+    /// Forwards the request to the ReqRep service, and sends the reply back to the client.
+    /// - if the request fails to convert, then the conversion status is sent back to the client
+    /// - if the ReqRep service request fails, then the ChannelError is mapped to a gRPC status via
+    ///   [channel_error_status()](fn.channel_error_status.html)
+    /// - if the handler task fails to spawn on the executor, e.g., the executor is shutdown, then
+    ///   `UNAVAILABLE` is sent back to the client
+    pub fn handle(&mut self, ctx: &grpcio::RpcContext, req: PReq, sink: grpcio::UnarySink<PRep>) {
+        let timer = RequestTimer::from_context(ctx);
+        let method = timer.method().to_string();
+        let reqrep_id = self.reqrep.id();
+        let req = Req::from_protobuf(req);
+        let mut reqrep = self.reqrep.clone();
+        // the reply is handed back to the gRPC thread, which owns the sink
+        // - if the handler task is dropped without replying, then the reply channel is cancelled
+        let (reply_tx, reply_rx) = oneshot::channel::<Result<PRep, grpcio::RpcStatus>>();
+        let result = self.executor.spawn(
+            async move {
+                let reply = match req {
+                    Ok(req) => match await!(reqrep.send_recv(req)) {
+                        Ok(rep) => Ok(rep.into_protobuf()),
+                        Err(err) => {
+                            warn!("{}: ReqRep({}) request failed: {}", method, reqrep_id, err);
+                            Err(channel_error_status(&err))
+                        }
+                    },
+                    Err(status) => {
+                        debug!("{}: invalid request: {:?}", method, status);
+                        Err(status)
+                    }
+                };
+                // the receiver is only dropped if the call was cancelled
+                let _ = reply_tx.send(reply);
+            },
+        );
+        if let Err(err) = result {
+            error!(
+                "Failed to spawn ReqRep({}) gRPC handler task: is_shutdown = {}",
+                reqrep_id,
+                err.is_shutdown()
+            );
+        }
+
+        ctx.spawn(reply_rx.then(move |reply| {
+            let reply = reply.unwrap_or_else(|_| {
+                Err(grpcio::RpcStatus::new(
+                    grpcio::RpcStatusCode::Unavailable,
+                    Some("ReqRep gRPC handler task is not available".to_string()),
+                ))
+            });
+            let status = match &reply {
+                Ok(_) => grpcio::RpcStatusCode::Ok,
+                Err(status) => status.status,
+            };
+            let send_reply = match reply {
+                Ok(rep) => sink.success(rep),
+                Err(status) => sink.fail(status),
+            };
+            send_reply.then(move |result| {
+                if let Err(err) = result {
+                    warn!("{}: failed to send reply: {}", timer.method(), err);
+                }
+                timer.observe(status);
+                Ok(())
+            })
+        }));
+    }
+}
+
+impl<Req, Rep, PReq, PRep> Clone for UnaryReqRepHandler<Req, Rep, PReq, PRep>
+where
+    Req: FromProtobuf<PReq> + Debug + Send + 'static,
+    Rep: IntoProtobuf<PRep> + Debug + Send + 'static,
+    PReq: protobuf::Message,
+    PRep: protobuf::Message,
+{
+    fn clone(&self) -> Self {
+        UnaryReqRepHandler {
+            reqrep: self.reqrep.clone(),
+            executor: self.executor.clone(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<Req, Rep, PReq, PRep> fmt::Debug for UnaryReqRepHandler<Req, Rep, PReq, PRep>
+where
+    Req: FromProtobuf<PReq> + Debug + Send + 'static,
+    Rep: IntoProtobuf<PRep> + Debug + Send + 'static,
+    PReq: protobuf::Message,
+    PRep: protobuf::Message,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UnaryReqRepHandler")
+            .field("reqrep", &self.reqrep)
+            .finish()
+    }
+}
+
+#[allow(warnings)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protos::message::MessageHeader;
+    use crate::{configure_logging, start_server};
+    use oysterpack_trust::{
+        concurrent::{
+            execution::{futures::future::FutureExt, global_executor},
+            messaging::reqrep::{FutureReply, Processor, ReqRepConfig},
+        },
+        metrics,
+    };
+    use std::time::Duration;
+
+    /// echoes back the request
+    struct EchoService;
+
+    impl Processor<MessageHeader, MessageHeader> for EchoService {
+        fn process(&mut self, req: MessageHeader) -> FutureReply<MessageHeader> {
+            async move { req }.boxed()
+        }
+    }
+
+    /// panics on every request - the panic is cascaded, which terminates the ReqRep service
+    struct PanicService;
+
+    impl Processor<MessageHeader, MessageHeader> for PanicService {
+        fn process(&mut self, req: MessageHeader) -> FutureReply<MessageHeader> {
+            async move { panic!("BOOM!") }.boxed()
+        }
+    }
+
+    /// Request ID, which must not be blank
+    #[derive(Debug)]
+    struct RequestId(String);
+
+    impl FromProtobuf<MessageHeader> for RequestId {
+        fn from_protobuf(msg: MessageHeader) -> Result<Self, grpcio::RpcStatus> {
+            if msg.get_id().trim().is_empty() {
+                Err(grpcio::RpcStatus::new(
+                    grpcio::RpcStatusCode::InvalidArgument,
+                    Some("id is required".to_string()),
+                ))
+            } else {
+                Ok(RequestId(msg.get_id().to_string()))
+            }
+        }
+    }
+
+    /// reply with the length of the request ID
+    #[derive(Debug)]
+    struct IdLen(usize);
+
+    impl IntoProtobuf<MessageHeader> for IdLen {
+        fn into_protobuf(self) -> MessageHeader {
+            let mut msg = MessageHeader::new();
+            msg.set_id(self.0.to_string());
+            msg
+        }
+    }
+
+    struct IdLenService;
+
+    impl Processor<RequestId, IdLen> for IdLenService {
+        fn process(&mut self, req: RequestId) -> FutureReply<IdLen> {
+            async move { IdLen(req.0.len()) }.boxed()
+        }
+    }
+
+    fn timer_buckets() -> Vec<f64> {
+        metrics::timer_buckets(vec![Duration::from_millis(1), Duration::from_millis(10)]).unwrap()
+    }
+
+    #[test]
+    fn unary_reqrep_protobuf() {
+        configure_logging();
+
+        // GIVEN: a ReqRep service whose request and reply types are protobuf messages
+        let reqrep = ReqRepConfig::new(ReqRepId::generate(), timer_buckets())
+            .start_service(EchoService, global_executor())
+            .unwrap();
+        // AND: the ReqRep service is exposed as a unary gRPC method
+        let method = unary_method::<MessageHeader, MessageHeader>("/oysterpack.Echo/echo");
+        let service = UnaryReqRepHandler::new(reqrep, global_executor())
+            .register(grpcio::ServiceBuilder::new(), &method)
+            .build();
+        let (_server, client) = start_server(service);
+
+        // WHEN: a gRPC client sends a request
+        let mut req = MessageHeader::new();
+        req.set_id("01D4FGEP1J4AB6Q4FHS0XRNHKX".to_string());
+        let rep = client
+            .unary_call(&method, &req, grpcio::CallOption::default())
+            .unwrap();
+
+        // THEN: the ReqRep service replies
+        assert_eq!(rep, req);
+    }
+
+    #[test]
+    fn unary_reqrep_conversions() {
+        configure_logging();
+
+        // GIVEN: a ReqRep service whose request and reply types are converted via protobuf messages
+        let reqrep = ReqRepConfig::new(ReqRepId::generate(), timer_buckets())
+            .start_service(IdLenService, global_executor())
+            .unwrap();
+        let method = unary_method::<MessageHeader, MessageHeader>("/oysterpack.IdLen/id_len");
+        let service = UnaryReqRepHandler::new(reqrep, global_executor())
+            .register(grpcio::ServiceBuilder::new(), &method)
+            .build();
+        let (_server, client) = start_server(service);
+
+        // WHEN: a valid request is sent
+        let mut req = MessageHeader::new();
+        req.set_id("abc".to_string());
+        let rep = client
+            .unary_call(&method, &req, grpcio::CallOption::default())
+            .unwrap();
+        // THEN: the reply is converted into the protobuf reply message
+        assert_eq!(rep.get_id(), "3");
+
+        // WHEN: a request that fails to convert is sent
+        match client.unary_call(&method, &MessageHeader::new(), grpcio::CallOption::default()) {
+            // THEN: the conversion status is returned
+            Err(grpcio::Error::RpcFailure(status)) => {
+                assert_eq!(status.status, grpcio::RpcStatusCode::InvalidArgument)
+            }
+            other => panic!("expected RpcFailure: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unary_reqrep_service_unavailable() {
+        configure_logging();
+
+        // GIVEN: a ReqRep service that terminates when it processes a request
+        let reqrep = ReqRepConfig::new(ReqRepId::generate(), timer_buckets())
+            .start_service(PanicService, global_executor())
+            .unwrap();
+        let method = unary_method::<MessageHeader, MessageHeader>("/oysterpack.Panic/panic");
+        let service = UnaryReqRepHandler::new(reqrep, global_executor())
+            .register(grpcio::ServiceBuilder::new(), &method)
+            .build();
+        let (_server, client) = start_server(service);
+        let status = |client: &grpcio::Client| {
+            match client.unary_call(&method, &MessageHeader::new(), grpcio::CallOption::default()) {
+                Err(grpcio::Error::RpcFailure(status)) => status.status,
+                other => panic!("expected RpcFailure: {:?}", other),
+            }
+        };
+
+        // WHEN: the request is dropped because the ReqRep service panicked
+        // THEN: INTERNAL is returned
+        assert_eq!(status(&client), grpcio::RpcStatusCode::Internal);
+
+        // WHEN: requests are sent after the ReqRep service has stopped
+        // THEN: UNAVAILABLE is returned
+        // - requests that were buffered before the service stopped are dropped, i.e., INTERNAL
+        let unavailable = (0..10).any(|_| {
+            if status(&client) == grpcio::RpcStatusCode::Unavailable {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+            false
+        });
+        assert!(unavailable, "UNAVAILABLE was not returned after the ReqRep service stopped");
+    }
+
+    #[test]
+    fn channel_error_status_mapping() {
+        assert_eq!(
+            channel_error_status(&ChannelError::SenderDisconnected).status,
+            grpcio::RpcStatusCode::Unavailable
+        );
+        assert_eq!(
+            channel_error_status(&ChannelError::ReceiverDisconnected).status,
+            grpcio::RpcStatusCode::Internal
+        );
+    }
+}
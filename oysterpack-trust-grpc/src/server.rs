@@ -0,0 +1,627 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Provides support for building and running gRPC servers.
+//!
+//! - [GrpcServerBuilder](struct.GrpcServerBuilder.html) is used to register services and start the server
+//!   - ReqRep services can be registered directly as unary methods - see
+//!     [register_unary_reqrep()](struct.GrpcServerBuilder.html#method.register_unary_reqrep)
+//!   - TLS is configured via [ServerTlsConfig](struct.ServerTlsConfig.html), which supports
+//!     auto-generated self-signed certificates
+//! - [GrpcServerHandle](struct.GrpcServerHandle.html) is used to gracefully shutdown the server
+//!
+//! ## Metrics
+//! Per method metrics are registered in the global metrics registry:
+//! - number of requests - [SERVER_REQUEST_COUNT_METRIC_ID](constant.SERVER_REQUEST_COUNT_METRIC_ID.html)
+//! - request latency - [SERVER_REQUEST_TIMER_METRIC_ID](constant.SERVER_REQUEST_TIMER_METRIC_ID.html)
+//! - response status codes - [SERVER_RESPONSE_COUNT_METRIC_ID](constant.SERVER_RESPONSE_COUNT_METRIC_ID.html)
+//!
+//! Metrics are recorded for ReqRep services registered via
+//! [register_unary_reqrep()](struct.GrpcServerBuilder.html#method.register_unary_reqrep), and for
+//! handlers that are registered via the [compat](../compat/index.html) helpers. grpcio does
+//! not support server interceptors, thus other services that are registered via
+//! [register_service()](struct.GrpcServerBuilder.html#method.register_service) need to record
+//! metrics using a [RequestTimer](struct.RequestTimer.html).
+
+use crate::reqrep::{FromProtobuf, IntoProtobuf, UnaryReqRepHandler};
+use failure::Fail;
+use lazy_static::lazy_static;
+use oysterpack_log::*;
+use oysterpack_trust::{
+    concurrent::{
+        execution::{
+            futures::{compat::Future01CompatExt, Future},
+            Executor,
+        },
+        messaging::reqrep::ReqRep,
+    },
+    metrics,
+};
+use std::{
+    fmt::{self, Debug},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+lazy_static! {
+    static ref SERVER_REQUEST_COUNT: prometheus::IntCounterVec = metrics::registry().register_int_counter_vec(
+        SERVER_REQUEST_COUNT_METRIC_ID,
+        "Number of gRPC requests received per method",
+        &[METHOD_LABEL_ID],
+        None
+    ).unwrap();
+
+    static ref SERVER_REQUEST_TIMER: prometheus::HistogramVec = metrics::registry().register_histogram_vec(
+        SERVER_REQUEST_TIMER_METRIC_ID,
+        "gRPC request latency per method",
+        &[METHOD_LABEL_ID],
+        latency_timer_buckets(),
+        None
+    ).unwrap();
+
+    static ref SERVER_RESPONSE_COUNT: prometheus::IntCounterVec = metrics::registry().register_int_counter_vec(
+        SERVER_RESPONSE_COUNT_METRIC_ID,
+        "Number of gRPC responses per method and status code",
+        &[METHOD_LABEL_ID, STATUS_CODE_LABEL_ID],
+        None
+    ).unwrap();
+}
+
+/// IntCounterVec MetricId for the number of gRPC requests by method: `M01M57GXSZRVVMDQMMP4S49QVQB`
+pub const SERVER_REQUEST_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166791281353890335600284114943209195);
+/// HistogramVec MetricId for the gRPC request latency by method: `M01M57GXSZVJDSSKZ42G02Q3149`
+pub const SERVER_REQUEST_TIMER_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166791281357160772559855292765013129);
+/// IntCounterVec MetricId for the number of gRPC responses by method and status code: `M01M57GXSZXV9NKC4M32N9KQPD4`
+pub const SERVER_RESPONSE_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166791281359913757445849393198258596);
+/// The fully qualified gRPC method name, i.e., `/{package}.{service}/{method}`: `L01M57GXSZZJ5TWRGRKXBR7PPJB`
+pub const METHOD_LABEL_ID: metrics::LabelId =
+    metrics::LabelId(2166791281361987071621579056254507595);
+/// The gRPC status code, e.g., `Ok`, `InvalidArgument`: `L01M57GXT01HRARRYRA85RKB3PH`
+pub const STATUS_CODE_LABEL_ID: metrics::LabelId =
+    metrics::LabelId(2166791281364388980677994586230329041);
+
+/// Latency histogram buckets, which are shared by the server and [client](../client/index.html)
+/// request timers
+pub(crate) fn latency_timer_buckets() -> Vec<f64> {
+    metrics::timer_buckets(vec![
+        Duration::from_millis(1),
+        Duration::from_millis(5),
+        Duration::from_millis(10),
+        Duration::from_millis(25),
+        Duration::from_millis(50),
+        Duration::from_millis(100),
+        Duration::from_millis(250),
+        Duration::from_millis(500),
+        Duration::from_secs(1),
+        Duration::from_secs(5),
+    ])
+    .unwrap()
+}
+
+/// Records the per method request metrics
+/// - the request count is incremented when the timer is started
+/// - the latency and response status code are recorded when the timer is
+///   [observed](struct.RequestTimer.html#method.observe)
+/// - if the timer is dropped without being observed, e.g., the handler task was dropped, then the
+///   response status code is recorded as `Cancelled`
+#[derive(Debug)]
+pub struct RequestTimer {
+    method: String,
+    start: Instant,
+    observed: bool,
+}
+
+impl RequestTimer {
+    /// starts the request timer for the specified method
+    pub fn start<Method: AsRef<str>>(method: Method) -> RequestTimer {
+        let method = method.as_ref().to_string();
+        SERVER_REQUEST_COUNT.with_label_values(&[&method]).inc();
+        RequestTimer {
+            method,
+            start: Instant::now(),
+            observed: false,
+        }
+    }
+
+    /// starts the request timer for the RPC context's method
+    pub fn from_context(ctx: &grpcio::RpcContext) -> RequestTimer {
+        RequestTimer::start(String::from_utf8_lossy(ctx.method()))
+    }
+
+    /// the method name
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// records the request latency and the response status code
+    pub fn observe(mut self, status: grpcio::RpcStatusCode) {
+        self.record(status);
+    }
+
+    fn record(&mut self, status: grpcio::RpcStatusCode) {
+        SERVER_REQUEST_TIMER
+            .with_label_values(&[&self.method])
+            .observe(metrics::duration_as_secs_f64(self.start.elapsed()));
+        SERVER_RESPONSE_COUNT
+            .with_label_values(&[&self.method, &format!("{:?}", status)])
+            .inc();
+        self.observed = true;
+    }
+}
+
+impl Drop for RequestTimer {
+    fn drop(&mut self) {
+        if !self.observed {
+            self.record(grpcio::RpcStatusCode::Cancelled);
+        }
+    }
+}
+
+/// Server TLS config
+#[derive(Clone)]
+pub struct ServerTlsConfig {
+    cert_pem: String,
+    private_key_pem: String,
+    client_root_cert_pem: Option<String>,
+}
+
+impl ServerTlsConfig {
+    /// constructor
+    /// - cert_pem is the PEM encoded certificate chain
+    /// - private_key_pem is the PEM encoded private key
+    pub fn new(cert_pem: String, private_key_pem: String) -> ServerTlsConfig {
+        ServerTlsConfig {
+            cert_pem,
+            private_key_pem,
+            client_root_cert_pem: None,
+        }
+    }
+
+    /// Generates a self-signed certificate for the specified subject alt names, e.g., `localhost`,
+    /// `127.0.0.1`
+    /// - clients need to be configured to trust the [certificate](#method.cert_pem)
+    pub fn self_signed(subject_alt_names: &[String]) -> ServerTlsConfig {
+        let cert = rcgen::generate_simple_self_signed(subject_alt_names);
+        ServerTlsConfig::new(cert.serialize_pem(), cert.serialize_private_key_pem())
+    }
+
+    /// Clients are required to present a certificate that is signed by the specified PEM encoded
+    /// root certificates, i.e., mutual TLS
+    pub fn set_client_root_cert(self, client_root_cert_pem: String) -> ServerTlsConfig {
+        let mut this = self;
+        this.client_root_cert_pem = Some(client_root_cert_pem);
+        this
+    }
+
+    /// PEM encoded certificate chain
+    pub fn cert_pem(&self) -> &str {
+        &self.cert_pem
+    }
+
+    /// PEM encoded root certificates, which are used to verify client certificates
+    pub fn client_root_cert_pem(&self) -> Option<&str> {
+        self.client_root_cert_pem.as_ref().map(String::as_str)
+    }
+
+    fn credentials(&self) -> grpcio::ServerCredentials {
+        let builder = grpcio::ServerCredentialsBuilder::new().add_cert(
+            self.cert_pem.as_bytes().to_vec(),
+            self.private_key_pem.as_bytes().to_vec(),
+        );
+        match self.client_root_cert_pem.as_ref() {
+            Some(client_root_cert_pem) => builder
+                .root_cert(client_root_cert_pem.as_bytes().to_vec(), true)
+                .build(),
+            None => builder.build(),
+        }
+    }
+}
+
+impl fmt::Debug for ServerTlsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // the private key is never logged
+        f.debug_struct("ServerTlsConfig")
+            .field("cert_pem", &self.cert_pem)
+            .field("client_root_cert_pem", &self.client_root_cert_pem)
+            .finish()
+    }
+}
+
+/// gRPC server builder
+pub struct GrpcServerBuilder {
+    host: String,
+    port: u16,
+    completion_queue_count: usize,
+    tls: Option<ServerTlsConfig>,
+    services: Vec<grpcio::Service>,
+    reqrep_service: Option<grpcio::ServiceBuilder>,
+    reqrep_methods: Vec<&'static str>,
+    executor: Executor,
+}
+
+impl GrpcServerBuilder {
+    /// constructor
+    /// - the executor is used to run the registered ReqRep service handlers
+    ///
+    /// ## Default settings
+    /// - host = `127.0.0.1`
+    /// - port = 0, i.e., the port is assigned by the OS - see [GrpcServerHandle::bind_addrs()](struct.GrpcServerHandle.html#method.bind_addrs)
+    /// - completion queue count = number of available CPUs
+    pub fn new(executor: Executor) -> GrpcServerBuilder {
+        GrpcServerBuilder {
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            completion_queue_count: num_cpus::get(),
+            tls: None,
+            services: Vec::new(),
+            reqrep_service: None,
+            reqrep_methods: Vec::new(),
+            executor,
+        }
+    }
+
+    /// Sets the host address to bind to
+    pub fn set_host<Host: AsRef<str>>(mut self, host: Host) -> Self {
+        self.host = host.as_ref().to_string();
+        self
+    }
+
+    /// Sets the port to bind to
+    pub fn set_port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Sets the number of gRPC completion queues, i.e., the number of gRPC threads
+    pub fn set_completion_queue_count(mut self, count: usize) -> Self {
+        self.completion_queue_count = count;
+        self
+    }
+
+    /// Sets the TLS config
+    pub fn set_tls(mut self, tls: ServerTlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Registers a gRPC service, e.g., a service that was generated from a protobuf service definition
+    /// - metrics need to be recorded by the service via [RequestTimer](struct.RequestTimer.html)
+    pub fn register_service(mut self, service: grpcio::Service) -> Self {
+        self.services.push(service);
+        self
+    }
+
+    /// Registers the ReqRep service as a unary gRPC method
+    /// - per method metrics are recorded
+    pub fn register_unary_reqrep<Req, Rep, PReq, PRep>(
+        mut self,
+        method: &grpcio::Method<PReq, PRep>,
+        reqrep: ReqRep<Req, Rep>,
+    ) -> Self
+    where
+        Req: FromProtobuf<PReq> + Debug + Send + 'static,
+        Rep: IntoProtobuf<PRep> + Debug + Send + 'static,
+        PReq: protobuf::Message,
+        PRep: protobuf::Message,
+    {
+        let builder = self
+            .reqrep_service
+            .take()
+            .unwrap_or_else(grpcio::ServiceBuilder::new);
+        let handler = UnaryReqRepHandler::new(reqrep, self.executor.clone());
+        self.reqrep_service = Some(handler.register(builder, method));
+        self.reqrep_methods.push(method.name);
+        self
+    }
+
+    /// Builds and starts the server
+    pub fn start(self) -> Result<GrpcServerHandle, GrpcServerError> {
+        let mut services = self.services;
+        if let Some(reqrep_service) = self.reqrep_service {
+            services.push(reqrep_service.build());
+        }
+        if services.is_empty() {
+            return Err(GrpcServerError::NoServices);
+        }
+
+        let env = Arc::new(grpcio::Environment::new(self.completion_queue_count));
+        let builder = services
+            .into_iter()
+            .fold(grpcio::ServerBuilder::new(env), |builder, service| {
+                builder.register_service(service)
+            });
+        let builder = match self.tls.as_ref() {
+            Some(tls) => builder.bind_secure(self.host.as_str(), self.port, tls.credentials()),
+            None => builder.bind(self.host.as_str(), self.port),
+        };
+        let mut server = builder.build().map_err(GrpcServerError::ServerBuildFailed)?;
+        server.start();
+        for (host, port) in server.bind_addrs() {
+            info!(
+                "gRPC server is listening on {}:{} : tls = {}, reqrep methods = {:?}",
+                host,
+                port,
+                self.tls.is_some(),
+                self.reqrep_methods
+            );
+        }
+        Ok(GrpcServerHandle {
+            server,
+            tls: self.tls,
+            executor: self.executor,
+        })
+    }
+}
+
+impl fmt::Debug for GrpcServerBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GrpcServerBuilder")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("completion_queue_count", &self.completion_queue_count)
+            .field("tls", &self.tls)
+            .field("service_count", &self.services.len())
+            .field("reqrep_methods", &self.reqrep_methods)
+            .finish()
+    }
+}
+
+/// gRPC server handle
+/// - if the handle is dropped, then the server is shutdown and all in-flight calls are cancelled
+pub struct GrpcServerHandle {
+    server: grpcio::Server,
+    tls: Option<ServerTlsConfig>,
+    executor: Executor,
+}
+
+impl GrpcServerHandle {
+    /// Returns the addresses that the server is bound to
+    pub fn bind_addrs(&self) -> &[(String, u16)] {
+        self.server.bind_addrs()
+    }
+
+    /// Returns the server's TLS config, e.g., to obtain the auto-generated self-signed certificate
+    pub fn tls(&self) -> Option<&ServerTlsConfig> {
+        self.tls.as_ref()
+    }
+
+    /// Gracefully shuts down the server, i.e., no new calls are accepted, and the returned future
+    /// completes once all in-flight calls complete
+    pub fn shutdown(self) -> impl Future<Output = Result<(), grpcio::Error>> {
+        let mut server = self.server;
+        let shutdown = server.shutdown().compat();
+        async move {
+            let result = await!(shutdown);
+            // the server must outlive the shutdown, i.e., dropping the server cancels all in-flight calls
+            drop(server);
+            result
+        }
+    }
+
+    /// Gracefully shuts down the server, and blocks until all in-flight calls complete
+    pub fn shutdown_blocking(self) -> Result<(), grpcio::Error> {
+        let mut executor = self.executor.clone();
+        executor.run(self.shutdown())
+    }
+
+    /// Cancels all in-flight calls
+    pub fn cancel_all_calls(&mut self) {
+        self.server.cancel_all_calls()
+    }
+}
+
+impl fmt::Debug for GrpcServerHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GrpcServerHandle")
+            .field("bind_addrs", &self.server.bind_addrs())
+            .field("tls", &self.tls.is_some())
+            .finish()
+    }
+}
+
+/// gRPC server related errors
+#[derive(Debug, Fail)]
+pub enum GrpcServerError {
+    /// At least 1 service must be registered
+    #[fail(display = "At least 1 service must be registered")]
+    NoServices,
+    /// Failed to build the server
+    #[fail(display = "Failed to build the gRPC server: {}", _0)]
+    ServerBuildFailed(#[cause] grpcio::Error),
+}
+
+#[allow(warnings)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compat;
+    use crate::protos::message::MessageHeader;
+    use crate::reqrep::unary_method;
+    use crate::{configure_logging, eventually};
+    use oysterpack_trust::concurrent::{
+        execution::{futures::future::FutureExt, global_executor},
+        messaging::reqrep::{FutureReply, Processor, ReqRepConfig, ReqRepId},
+    };
+
+    struct EchoService;
+
+    impl Processor<MessageHeader, MessageHeader> for EchoService {
+        fn process(&mut self, req: MessageHeader) -> FutureReply<MessageHeader> {
+            async move { req }.boxed()
+        }
+    }
+
+    fn start_echo_service() -> ReqRep<MessageHeader, MessageHeader> {
+        let timer_buckets =
+            metrics::timer_buckets(vec![Duration::from_millis(1), Duration::from_millis(10)])
+                .unwrap();
+        ReqRepConfig::new(ReqRepId::generate(), timer_buckets)
+            .start_service(EchoService, global_executor())
+            .unwrap()
+    }
+
+    #[test]
+    fn grpc_server_with_self_signed_tls() {
+        configure_logging();
+
+        // GIVEN: a TLS server with an auto-generated self-signed certificate
+        let method = unary_method::<MessageHeader, MessageHeader>("/oysterpack.Echo/echo_tls");
+        let server = GrpcServerBuilder::new(global_executor())
+            .set_tls(ServerTlsConfig::self_signed(&["127.0.0.1".to_string()]))
+            .register_unary_reqrep(&method, start_echo_service())
+            .start()
+            .unwrap();
+
+        // WHEN: a client that trusts the self-signed certificate sends a request
+        let (host, port) = server.bind_addrs()[0].clone();
+        let credentials = grpcio::ChannelCredentialsBuilder::new()
+            .root_cert(server.tls().unwrap().cert_pem().as_bytes().to_vec())
+            .build();
+        let env = Arc::new(grpcio::EnvBuilder::new().build());
+        let channel = grpcio::ChannelBuilder::new(env)
+            .secure_connect(&format!("{}:{}", host, port), credentials);
+        let client = grpcio::Client::new(channel);
+        let mut req = MessageHeader::new();
+        req.set_id("tls".to_string());
+        let rep = client
+            .unary_call(&method, &req, grpcio::CallOption::default())
+            .unwrap();
+
+        // THEN: the reply is received
+        assert_eq!(rep, req);
+        // AND: the server is gracefully shutdown
+        server.shutdown_blocking().unwrap();
+    }
+
+    #[test]
+    fn grpc_server_metrics() {
+        configure_logging();
+
+        // GIVEN: a server with a ReqRep service registered
+        let method = unary_method::<MessageHeader, MessageHeader>("/oysterpack.Echo/echo_metrics");
+        let server = GrpcServerBuilder::new(global_executor())
+            .register_unary_reqrep(&method, start_echo_service())
+            .start()
+            .unwrap();
+        let (host, port) = server.bind_addrs()[0].clone();
+        let env = Arc::new(grpcio::EnvBuilder::new().build());
+        let channel = grpcio::ChannelBuilder::new(env).connect(&format!("{}:{}", host, port));
+        let client = grpcio::Client::new(channel);
+
+        // WHEN: requests are sent
+        for _ in 0..3 {
+            client
+                .unary_call(&method, &MessageHeader::new(), grpcio::CallOption::default())
+                .unwrap();
+        }
+
+        // THEN: the per method metrics are recorded
+        assert_eq!(
+            SERVER_REQUEST_COUNT.with_label_values(&[method.name]).get(),
+            3
+        );
+        // AND: the latency and status code are recorded, which happens after the reply is sent
+        assert!(eventually(|| {
+            response_count(method.name, "Ok") == 3 && latency_sample_count(method.name) == 3
+        }));
+        server.shutdown_blocking().unwrap();
+    }
+
+    #[test]
+    fn grpc_server_compat_handler_metrics() {
+        configure_logging();
+
+        // GIVEN: a service that is registered via the compat handler helpers
+        let method = unary_method::<MessageHeader, MessageHeader>("/oysterpack.Echo/echo_compat");
+        let service = compat::add_unary_handler(
+            grpcio::ServiceBuilder::new(),
+            &method,
+            global_executor(),
+            |_ctx, req: MessageHeader| {
+                async move {
+                    if req.get_id().is_empty() {
+                        Err(grpcio::RpcStatus::new(grpcio::RpcStatusCode::InvalidArgument, None))
+                    } else {
+                        Ok(req)
+                    }
+                }
+            },
+        );
+        let server = GrpcServerBuilder::new(global_executor())
+            .register_service(service.build())
+            .start()
+            .unwrap();
+        let (host, port) = server.bind_addrs()[0].clone();
+        let env = Arc::new(grpcio::EnvBuilder::new().build());
+        let channel = grpcio::ChannelBuilder::new(env).connect(&format!("{}:{}", host, port));
+        let client = grpcio::Client::new(channel);
+
+        // WHEN: a valid request and an invalid request are sent
+        let mut req = MessageHeader::new();
+        req.set_id("compat".to_string());
+        client
+            .unary_call(&method, &req, grpcio::CallOption::default())
+            .unwrap();
+        assert!(client
+            .unary_call(&method, &MessageHeader::new(), grpcio::CallOption::default())
+            .is_err());
+
+        // THEN: the per method metrics are recorded
+        assert_eq!(
+            SERVER_REQUEST_COUNT.with_label_values(&[method.name]).get(),
+            2
+        );
+        assert!(eventually(|| {
+            response_count(method.name, "Ok") == 1
+                && response_count(method.name, "InvalidArgument") == 1
+                && latency_sample_count(method.name) == 2
+        }));
+        server.shutdown_blocking().unwrap();
+    }
+
+    #[test]
+    fn request_timer_dropped() {
+        // WHEN: a request timer is dropped without being observed
+        let method = "/oysterpack.Timer/dropped";
+        drop(RequestTimer::start(method));
+        // THEN: the response status code is recorded as Cancelled
+        assert_eq!(response_count(method, "Cancelled"), 1);
+        assert_eq!(latency_sample_count(method), 1);
+    }
+
+    fn response_count(method: &str, status: &str) -> i64 {
+        SERVER_RESPONSE_COUNT
+            .with_label_values(&[method, status])
+            .get()
+    }
+
+    fn latency_sample_count(method: &str) -> u64 {
+        SERVER_REQUEST_TIMER
+            .with_label_values(&[method])
+            .get_sample_count()
+    }
+
+    #[test]
+    fn grpc_server_requires_services() {
+        match GrpcServerBuilder::new(global_executor()).start() {
+            Err(GrpcServerError::NoServices) => (),
+            other => panic!("expected NoServices error: {:?}", other),
+        }
+    }
+}
@@ -25,45 +25,20 @@ use protos::{
 
 use oysterpack_trust::concurrent::execution::{
     self,
-    futures::{
-        compat::{Compat, Compat01As03, Future01CompatExt, Stream01CompatExt},
-        sink::SinkExt,
-        stream::StreamExt,
-        task::SpawnExt,
-    },
+    futures::{stream::StreamExt, task::SpawnExt},
     global_executor,
 };
 use oysterpack_uid::ULID;
 
-use grpcio::{ChannelBuilder, EnvBuilder, Environment, ServerBuilder, WriteFlags};
+use grpcio::{ChannelBuilder, EnvBuilder};
+use oysterpack_trust_grpc::{
+    compat::{self, ReplySink},
+    server::{GrpcServerBuilder, GrpcServerHandle, ServerTlsConfig},
+};
 use hashbrown::HashMap;
 use std::time::Duration;
 use std::{sync::Arc, thread};
 
-/// converts a futures 0.1 Stream into a futures 0.3 Stream
-pub fn into_stream03<S>(
-    stream01: S,
-) -> impl execution::futures::Stream<
-    Item = Result<<S as futures::Stream>::Item, <S as futures::Stream>::Error>,
->
-where
-    S: futures::Stream,
-{
-    Compat01As03::new(stream01)
-}
-
-/// converts a futures 0.1 Future into a futures 0.3 Future
-pub fn into_future03<F>(
-    future01: F,
-) -> impl execution::futures::Future<
-    Output = Result<<F as futures::Future>::Item, <F as futures::Future>::Error>,
->
-where
-    F: futures::Future,
-{
-    Compat01As03::new(future01)
-}
-
 fn format_rpc_context(ctx: &grpcio::RpcContext) -> String {
     let request_headers =
         ctx.request_headers()
@@ -104,27 +79,27 @@ impl Foo for FooServer {
         response.set_id(req.id + 1);
         let sleep_duration = Duration::from_millis(req.sleep);
 
-        global_executor()
-            .spawn(
-                async move {
-                    println!("unary(): sleeping for {:?} ...", sleep_duration);
-                    thread::sleep(sleep_duration);
-                    if let Err(err) = await!(sink.success(response.clone()).compat()) {
-                        println!(
-                            "[{:?}]: unary(): failed to send response: {:?}",
-                            thread::current().id(),
-                            err
-                        );
-                    } else {
-                        println!(
-                            "[{:?}]: unary(): sent response: {:?}",
-                            thread::current().id(),
-                            response
-                        );
-                    }
-                },
-            )
-            .unwrap();
+        compat::spawn_handler(
+            &mut global_executor(),
+            &ctx,
+            async move {
+                println!("unary(): sleeping for {:?} ...", sleep_duration);
+                thread::sleep(sleep_duration);
+                if let Err(err) = await!(sink.send_reply(Ok(response.clone()))) {
+                    println!(
+                        "[{:?}]: unary(): failed to send response: {:?}",
+                        thread::current().id(),
+                        err
+                    );
+                } else {
+                    println!(
+                        "[{:?}]: unary(): sent response: {:?}",
+                        thread::current().id(),
+                        response
+                    );
+                }
+            },
+        );
     }
 
     fn client_streaming(
@@ -134,30 +109,31 @@ impl Foo for FooServer {
         sink: ::grpcio::ClientStreamingSink<Response>,
     ) {
         println!("client_streaming(): {}", format_rpc_context(&ctx));
-        global_executor()
-            .spawn(
-                async move {
-                    let mut id = 0;
-                    // receive all client request messages
-                    let mut stream = stream.compat();
-                    while let Some(request) = await!(stream.next()) {
-                        println!("client_streaming(): request = {:?}", request);
-                        id = request.unwrap().id;
-                    }
-                    // once all messages have been received, then send the response
-                    let mut response = Response::new();
-                    response.set_id(id);
-
-                    if let Err(err) = await!(sink.success(response).compat()) {
-                        println!(
-                            "[{:?}]: unary(): failed to send response: {:?}",
-                            thread::current().id(),
-                            err
-                        );
-                    }
-                },
-            )
-            .unwrap();
+        // the request messages are received via a futures 0.3 stream
+        let mut stream = compat::into_stream03(stream);
+        compat::spawn_handler(
+            &mut global_executor(),
+            &ctx,
+            async move {
+                let mut id = 0;
+                // receive all client request messages
+                while let Some(request) = await!(stream.next()) {
+                    println!("client_streaming(): request = {:?}", request);
+                    id = request.unwrap().id;
+                }
+                // once all messages have been received, then send the response
+                let mut response = Response::new();
+                response.set_id(id);
+
+                if let Err(err) = await!(sink.send_reply(Ok(response))) {
+                    println!(
+                        "[{:?}]: client_streaming(): failed to send response: {:?}",
+                        thread::current().id(),
+                        err
+                    );
+                }
+            },
+        );
     }
 
     fn server_streaming(
@@ -168,50 +144,25 @@ impl Foo for FooServer {
     ) {
         println!("server_streaming(): {}", format_rpc_context(&ctx));
         println!("server_streaming() request: {:?}", req);
-        // the design is to stream messages using channels
-        // ServerStreamingSink will send all messages received on the mpsc:Receiver stream
-        let (mut tx, rx) = execution::futures::channel::mpsc::channel(0);
-        {
-            use futures::prelude::Sink;
-            let send_all = sink.send_all(Compat::new(rx));
-            global_executor()
-                .spawn(
-                    async move {
-                        if let Err(err) = await!(send_all.compat()) {
-                            println!(
-                                "[{:?}]: unary(): failed to send response: {:?}",
-                                thread::current().id(),
-                                err
-                            );
-                        }
-                    },
-                )
-                .unwrap();
-        }
-
-        // deliver messages via mpsc::Sender
-        global_executor()
-            .spawn(
-                async move {
-                    let write_flags = WriteFlags::default();
-                    for i in 0..10 {
-                        let mut response = Response::new();
-                        response.id = i as u64;
-                        let msg = Result::<(Response, WriteFlags), grpcio::Error>::Ok((
-                            response,
-                            write_flags,
-                        ));
-                        if let Err(err) = await!(tx.send(msg)) {
-                            println!(
-                                "[{:?}]: unary(): failed to put response on channel: {:?}",
-                                thread::current().id(),
-                                err
-                            );
-                        }
-                    }
-                },
-            )
-            .unwrap();
+        // the responses are streamed back via a futures 0.3 stream
+        let responses = execution::futures::stream::iter((0..10).map(|i| {
+            let mut response = Response::new();
+            response.id = i as u64;
+            Ok::<_, grpcio::RpcStatus>(response)
+        }));
+        compat::spawn_handler(
+            &mut global_executor(),
+            &ctx,
+            async move {
+                if let Err(err) = await!(compat::send_stream(sink, responses)) {
+                    println!(
+                        "[{:?}]: server_streaming(): failed to send responses: {:?}",
+                        thread::current().id(),
+                        err
+                    );
+                }
+            },
+        );
     }
 
     fn bidi_streaming(
@@ -221,111 +172,68 @@ impl Foo for FooServer {
         sink: ::grpcio::DuplexSink<Response>,
     ) {
         println!("bidi_streaming(): {}", format_rpc_context(&ctx));
-        // used to stream response messages back to the client
-        let (mut tx, rx) = execution::futures::channel::mpsc::channel(0);
-        let write_flags = WriteFlags::default();
-
-        let mut tx2 = tx.clone();
-        // receive all client request messages that are streamed
-        global_executor()
-            .spawn(
-                async move {
-                    let mut stream = stream.compat();
-                    while let Some(request) = await!(stream.next()) {
-                        println!("bidi_streaming(): server request = {:?}", request);
-                        match request {
-                            Ok(request) => {
-                                let mut response = Response::new();
-                                response.id = request.id + 100;
-                                let msg: Result<(Response, WriteFlags), grpcio::Error> =
-                                    Ok((response, write_flags));
-                                let _ = await!(tx2.send(msg));
-                            }
-                            Err(_) => return,
-                        }
-                    }
-                },
-            )
-            .unwrap();
-
-        {
-            use futures::prelude::Sink;
-            let send_all = sink.send_all(Compat::new(rx)).compat();
-            global_executor()
-                .spawn(
-                    async move {
-                        let _ = await!(send_all);
-                    },
-                )
-                .unwrap();
-        }
-        global_executor()
-            .spawn(
-                async move {
-                    for i in 0..10 {
-                        let mut response = Response::new();
-                        response.id = i as u64;
-                        let msg: Result<(Response, WriteFlags), grpcio::Error> =
-                            Ok((response, write_flags));
-                        let _ = await!(tx.send(msg));
-                    }
-                },
-            )
-            .unwrap();
+        // 10 responses are streamed back first, followed by a response per client request message
+        let responses = execution::futures::stream::iter((0..10).map(|i| {
+            let mut response = Response::new();
+            response.id = i as u64;
+            Ok::<_, grpcio::RpcStatus>(response)
+        }));
+        let request_responses = compat::into_stream03(stream).map(|request| {
+            println!("bidi_streaming(): server request = {:?}", request);
+            match request {
+                Ok(request) => {
+                    let mut response = Response::new();
+                    response.id = request.id + 100;
+                    Ok(response)
+                }
+                Err(err) => Err(grpcio::RpcStatus::new(
+                    grpcio::RpcStatusCode::Internal,
+                    Some(err.to_string()),
+                )),
+            }
+        });
+        compat::spawn_handler(
+            &mut global_executor(),
+            &ctx,
+            async move {
+                let responses = responses.chain(request_responses);
+                if let Err(err) = await!(compat::send_stream(sink, responses)) {
+                    println!(
+                        "[{:?}]: bidi_streaming(): failed to send responses: {:?}",
+                        thread::current().id(),
+                        err
+                    );
+                }
+            },
+        );
     }
 }
 
-fn start_server() -> grpcio::Server {
-    let env = Arc::new(Environment::new(num_cpus::get()));
-    let service = foo_grpc::create_foo(FooServer);
-    let mut server = ServerBuilder::new(env)
-        .register_service(service)
-        .bind("127.0.0.1", 0)
-        .build()
-        .unwrap();
-    server.start();
-    server
+fn start_server() -> GrpcServerHandle {
+    start_server_with_port(0)
 }
 
-fn start_server_with_port(port: u16) -> grpcio::Server {
-    let env = Arc::new(Environment::new(num_cpus::get()));
-    let service = foo_grpc::create_foo(FooServer);
-    let mut server = ServerBuilder::new(env)
-        .register_service(service)
-        .bind("127.0.0.1", port)
-        .build()
-        .unwrap();
-    server.start();
-    server
+fn start_server_with_port(port: u16) -> GrpcServerHandle {
+    GrpcServerBuilder::new(global_executor())
+        .set_port(port)
+        .register_service(foo_grpc::create_foo(FooServer))
+        .start()
+        .unwrap()
 }
 
-fn start_secure_server() -> (grpcio::Server, String) {
-    let subject_alt_names: &[_] = &["127.0.0.1".to_string()];
-
-    let cert = rcgen::generate_simple_self_signed(subject_alt_names);
-    let cert_pem = cert.serialize_pem();
-    let cert_private_key_pem = cert.serialize_private_key_pem();
-    let server_credentials = grpcio::ServerCredentialsBuilder::new()
-        .add_cert(
-            cert_pem.as_bytes().to_vec(),
-            cert_private_key_pem.as_bytes().to_vec(),
-        )
-        .build();
-
-    let env = Arc::new(Environment::new(1));
-    let service = foo_grpc::create_foo(FooServer);
-    let mut server = ServerBuilder::new(env)
-        .register_service(service)
-        .bind_secure("127.0.0.1", 0, server_credentials)
-        .build()
+fn start_secure_server() -> (GrpcServerHandle, String) {
+    let server = GrpcServerBuilder::new(global_executor())
+        .set_completion_queue_count(1)
+        .set_tls(ServerTlsConfig::self_signed(&["127.0.0.1".to_string()]))
+        .register_service(foo_grpc::create_foo(FooServer))
+        .start()
         .unwrap();
-    server.start();
+    let cert_pem = server.tls().unwrap().cert_pem().to_string();
     (server, cert_pem)
 }
 
-fn stop_server(mut server: grpcio::Server) {
-    use futures::Future;
-    if let Err(err) = server.shutdown().wait() {
+fn stop_server(server: GrpcServerHandle) {
+    if let Err(err) = server.shutdown_blocking() {
         println!("Error occurred while shutting down server: {:?}", err);
     }
 }
@@ -394,7 +302,7 @@ fn grpc_unary_async() {
         };
 
         let reply_receiver = client.unary_async_opt(&request, call_opt).unwrap();
-        let response = global_executor().run(reply_receiver.compat()).unwrap();
+        let response = global_executor().run(compat::into_future03(reply_receiver)).unwrap();
         println!("grpc_unary_async(): response = {:?}", response);
     }
 
@@ -425,7 +333,7 @@ fn grpc_unary_async_start_server_after_client() {
     // WHEN: the server is started
     let server = start_server_with_port(PORT);
     // THEN: the client is able to send the request and receive the reply
-    let response = global_executor().run(reply_receiver.compat()).unwrap();
+    let response = global_executor().run(compat::into_future03(reply_receiver)).unwrap();
     println!("grpc_unary_async(): response = {:?}", response);
 
     stop_server(server);
@@ -457,7 +365,7 @@ fn grpc_unary_async_restart_server_in_middle_of_request() {
     stop_server(server);
     let server = start_server_with_port(PORT);
     // THEN: the request fails because the socket is disconnected
-    let result = global_executor().run(reply_receiver.compat());
+    let result = global_executor().run(compat::into_future03(reply_receiver));
     println!("grpc_unary_async(): response = {:?}", result);
     assert!(result.is_err());
 
@@ -498,7 +406,7 @@ fn grpc_unary_async_send_next_req_before_receiving_reply() {
             );
 
             // Then: the async response can be retrieved after receiving the sync response
-            let response = global_executor().run(reply_receiver.compat()).unwrap();
+            let response = global_executor().run(compat::into_future03(reply_receiver)).unwrap();
             println!(
                 "grpc_unary_async_send_next_req_before_receiving_reply(): async response = {:?}",
                 response
@@ -540,7 +448,7 @@ fn grpc_unary_async_timeout() {
         global_executor()
             .spawn(
                 async move {
-                    let response = await!(reply_receiver.compat());
+                    let response = await!(compat::into_future03(reply_receiver));
                     let _ = tx.send(response);
                 },
             )
@@ -583,7 +491,7 @@ fn grpc_unary_async_no_timeout() {
         global_executor()
             .spawn(
                 async move {
-                    let response = await!(reply_receiver.compat());
+                    let response = await!(compat::into_future03(reply_receiver));
                     let _ = tx.send(response);
                 },
             )
@@ -609,34 +517,14 @@ fn client_streaming() {
         let ch = ChannelBuilder::new(env).connect(format!("{}:{}", host, port).as_str());
         let client = foo_grpc::FooClient::new(ch);
         let (sender, receiver) = client.client_streaming().unwrap();
-        let (mut tx, rx) = execution::futures::channel::mpsc::channel(0);
-        {
-            use futures::prelude::Sink;
-            let send_all = sender.send_all(Compat::new(rx)).compat();
-            global_executor()
-                .spawn(
-                    async move {
-                        let _ = await!(send_all);
-                    },
-                )
-                .unwrap();
-        }
-        global_executor()
-            .spawn(
-                async move {
-                    let write_flags = WriteFlags::default();
-                    for i in 0..10 {
-                        let mut request = Request::new();
-                        request.id = i;
-                        let msg: Result<(Request, WriteFlags), grpcio::Error> =
-                            Ok((request, write_flags));
-                        let _ = await!(tx.send(msg));
-                    }
-                },
-            )
+        let requests = execution::futures::stream::iter((0..10).map(|i| {
+            let mut request = Request::new();
+            request.id = i;
+            request
+        }));
+        let response = global_executor()
+            .run(compat::client_streaming(sender, receiver, requests))
             .unwrap();
-        let receiver = receiver.compat();
-        let response = global_executor().run(receiver).unwrap();
         println!("client_streaming(): response = {:?}", response);
     }
 
@@ -655,7 +543,7 @@ fn server_streaming() {
         let client = foo_grpc::FooClient::new(ch);
         let request = Request::new();
         let receiver = client.server_streaming(&request).unwrap();
-        let mut receiver = receiver.compat();
+        let mut receiver = compat::into_stream03(receiver);
         global_executor().run(
             async move {
                 while let Some(response) = await!(receiver.next()) {
@@ -680,37 +568,13 @@ fn bidi_streaming() {
         let ch = ChannelBuilder::new(env).connect(format!("{}:{}", host, port).as_str());
         let client = foo_grpc::FooClient::new(ch);
         let (sender, receiver) = client.bidi_streaming().unwrap();
-
-        let (mut tx, rx) = execution::futures::channel::mpsc::channel(0);
-        {
-            use futures::prelude::Sink;
-            let send_all = sender.send_all(Compat::new(rx)).compat();
-            global_executor()
-                .spawn(
-                    async move {
-                        let _ = await!(send_all);
-                        println!("bidi_streaming(): client sent all requests");
-                    },
-                )
-                .unwrap();
-        }
-        global_executor()
-            .spawn(
-                async move {
-                    let write_flags = WriteFlags::default();
-                    for i in 0..10 {
-                        let mut request = Request::new();
-                        request.id = i;
-                        let msg: Result<(Request, WriteFlags), grpcio::Error> =
-                            Ok((request.clone(), write_flags));
-                        let _ = await!(tx.send(msg));
-                        println!("bidi_streaming(): client sent request: {:?}", request);
-                    }
-                },
-            )
-            .unwrap();
-
-        let mut receiver = receiver.compat();
+        let requests = execution::futures::stream::iter((0..10).map(|i| {
+            let mut request = Request::new();
+            request.id = i;
+            request
+        }));
+        let mut receiver =
+            compat::duplex_streaming(&mut global_executor(), sender, receiver, requests).unwrap();
         global_executor().run(
             async move {
                 while let Some(response) = await!(receiver.next()) {
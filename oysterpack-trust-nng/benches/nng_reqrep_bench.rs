@@ -31,9 +31,12 @@ use oysterpack_trust::{
     },
     metrics,
 };
-use oysterpack_trust_nng::reqrep::{
-    client::*,
-    server::{self, ServerHandle},
+use oysterpack_trust_nng::{
+    message::{MessagePool, MessagePoolConfig},
+    reqrep::{
+        client::*,
+        server::{self, ServerHandle},
+    },
 };
 use oysterpack_uid::*;
 
@@ -55,7 +58,13 @@ use std::{
     time::{Duration, Instant},
 };
 
-criterion_group!(benches, nng_reqrep_inproc_bench, nng_reqrep_tcp_bench);
+criterion_group!(
+    benches,
+    nng_reqrep_inproc_bench,
+    nng_reqrep_tcp_bench,
+    message_alloc_bench,
+    message_pool_bench
+);
 
 criterion_main!(benches);
 
@@ -134,6 +143,31 @@ fn nng_reqrep_tcp_bench(c: &mut Criterion) {
     });
 }
 
+/// baseline for [message_pool_bench](fn.message_pool_bench.html): a new message is allocated for
+/// each request
+fn message_alloc_bench(c: &mut Criterion) {
+    let body = [1_u8; 512];
+    c.bench_function("message_alloc_bench", move |b| {
+        b.iter(|| {
+            let mut msg = nng::Message::with_capacity(body.len()).unwrap();
+            msg.push_back(&body[..]).unwrap();
+            msg
+        })
+    });
+}
+
+/// measures message acquire/release round trips through the message pool
+fn message_pool_bench(c: &mut Criterion) {
+    let body = [1_u8; 512];
+    let pool = MessagePool::new(MessagePoolConfig::default());
+    c.bench_function("message_pool_bench", move |b| {
+        b.iter(|| {
+            let msg = pool.acquire_with_body(&body[..]).unwrap();
+            pool.release(msg);
+        })
+    });
+}
+
 struct EchoService;
 impl Processor<nng::Message, nng::Message> for EchoService {
     fn process(&mut self, req: nng::Message) -> reqrep::FutureReply<nng::Message> {
@@ -0,0 +1,83 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Exposes an echo ReqRep service to web clients via the WebSocket transport.
+//!
+//! ```text
+//! cargo run --example ws_reqrep_server -- ws://127.0.0.1:8080/api/echo
+//! ```
+//!
+//! Then open `examples/ws_reqrep_client.html` in a browser. The ReqRep service is unchanged - the
+//! only difference from a TCP based server is the listener URL.
+
+#![feature(await_macro, async_await, futures_api, arbitrary_self_types)]
+
+use futures::future::FutureExt;
+use oysterpack_trust::{
+    concurrent::{
+        execution::{global_executor, ExecutorBuilder, ExecutorId},
+        messaging::reqrep::{FutureReply, Processor, ReqRepConfig, ReqRepId},
+    },
+    metrics,
+};
+use oysterpack_trust_nng::{
+    reqrep::server::{self, ListenerConfig},
+    ws::WsConfig,
+};
+use std::{env, time::Duration};
+
+/// echoes back the request
+struct EchoService;
+
+impl Processor<nng::Message, nng::Message> for EchoService {
+    fn process(&mut self, req: nng::Message) -> FutureReply<nng::Message> {
+        async move { req }.boxed()
+    }
+}
+
+fn main() {
+    let url = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "ws://127.0.0.1:8080/api/echo".to_string());
+    let url = url::Url::parse(&url).expect("invalid URL");
+
+    let timer_buckets = metrics::timer_buckets(vec![
+        Duration::from_micros(100),
+        Duration::from_millis(1),
+        Duration::from_millis(10),
+    ])
+    .unwrap();
+    let service = ReqRepConfig::new(ReqRepId::generate(), timer_buckets)
+        .start_service(EchoService, global_executor())
+        .unwrap();
+
+    // NOTE: the listener does not check the `Origin` header, i.e., the browser client can be loaded
+    // from any origin, e.g., a local file - see the ws module docs
+    let listener_config = ListenerConfig::new(url.clone())
+        .set_ws(WsConfig::new().add_header("X-Service-Name", "echo"));
+
+    let server_handle = server::spawn(
+        None,
+        listener_config,
+        service,
+        ExecutorBuilder::new(ExecutorId::generate())
+            .register()
+            .unwrap(),
+    )
+    .unwrap();
+    println!("echo service is listening on: {}", url);
+    server_handle.await_shutdown();
+}
@@ -0,0 +1,884 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Provides support for declaratively configuring nng ReqRep servers and clients.
+//!
+//! A [BootstrapConfig](struct.BootstrapConfig.html) document lists the servers and clients to start.
+//! The document can be TOML or JSON, e.g.,
+//!
+//! ```toml
+//! [[servers]]
+//! reqrep_id = "01D5J2ZKNJMBRV1R2HM4ZKZKD1"
+//! executor_id = "01D5J30C1FAHNXT2TY0JQ8ETRH"
+//!
+//! [servers.listener]
+//! url = "tcp://0.0.0.0:5000"
+//! non_blocking = true
+//! parallelism = 8
+//!
+//! [[clients]]
+//! reqrep_id = "01D5J2ZKNJMBRV1R2HM4ZKZKD1"
+//! timer_buckets = [{secs = 0, nanos = 1000000}, {secs = 0, nanos = 10000000}]
+//!
+//! [clients.dialer]
+//! url = "tcp://localhost:5000"
+//! parallelism = 4
+//! ```
+//!
+//! ## Notes
+//! - ReqRepId(s) and ExecutorId(s) are specified as ULID strings
+//! - if an executor_id is not specified, then the global executor is used
+//! - referenced executors must already be registered
+//! - each entry is parsed and validated independently, and all errors are reported together, i.e.,
+//!   document errors, e.g., unknown fields, along with the entry errors - see
+//!   [ConfigErrors](struct.ConfigErrors.html)
+//! - server backend services are not configurable, thus they are provided when bootstrapping by ReqRepId
+//! - bootstrapping is all or nothing, i.e., if any server or client fails to start, then the servers
+//!   and clients that were started are stopped
+
+use crate::{
+    config,
+    reqrep::{
+        client::{self, Client, ClientRegistrationError, DialerConfig},
+        server::{self, ListenerConfig, ServerHandle},
+    },
+};
+use failure::Fail;
+use oysterpack_log::*;
+use oysterpack_trust::{
+    concurrent::{
+        execution::{self, Executor, ExecutorId},
+        messaging::reqrep::{ReqRep, ReqRepConfig, ReqRepId},
+    },
+    metrics,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{collections::HashSet, fmt, fs, io, path::Path, path::PathBuf, time::Duration};
+
+/// Declares the nng ReqRep servers and clients to start
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BootstrapConfig {
+    #[serde(default)]
+    servers: Vec<ServerEntry>,
+    #[serde(default)]
+    clients: Vec<ClientEntry>,
+}
+
+impl BootstrapConfig {
+    /// Parses a TOML config document
+    pub fn from_toml_str(doc: &str) -> Result<BootstrapConfig, BootstrapError> {
+        let doc: toml::Value = toml::from_str(doc).map_err(BootstrapError::InvalidToml)?;
+        let doc = serde_json::to_value(doc)
+            .map_err(|err| BootstrapError::InvalidDocument(err.to_string()))?;
+        BootstrapConfig::from_value(doc)
+    }
+
+    /// Parses a JSON config document
+    pub fn from_json_str(doc: &str) -> Result<BootstrapConfig, BootstrapError> {
+        let doc: serde_json::Value =
+            serde_json::from_str(doc).map_err(BootstrapError::InvalidJson)?;
+        BootstrapConfig::from_value(doc)
+    }
+
+    /// Loads the config document from the specified file. The document format is determined by the
+    /// file extension: `toml` or `json`
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<BootstrapConfig, BootstrapError> {
+        let path = path.as_ref();
+        let parse = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => BootstrapConfig::from_toml_str,
+            Some("json") => BootstrapConfig::from_json_str,
+            _ => return Err(BootstrapError::UnsupportedFileType(path.to_path_buf())),
+        };
+        let doc = fs::read_to_string(path).map_err(|err| BootstrapError::Io {
+            path: path.to_path_buf(),
+            err,
+        })?;
+        parse(&doc)
+    }
+
+    /// Each entry is deserialized separately in order to report precise per entry errors
+    fn from_value(doc: serde_json::Value) -> Result<BootstrapConfig, BootstrapError> {
+        let mut doc = match doc {
+            serde_json::Value::Object(doc) => doc,
+            _ => {
+                return Err(BootstrapError::InvalidDocument(
+                    "expected a table with `servers` and/or `clients` arrays".to_string(),
+                ))
+            }
+        };
+        let mut errors = ConfigErrors::default();
+        let servers = parse_entries(Section::Servers, doc.remove("servers"), &mut errors);
+        let clients = parse_entries(Section::Clients, doc.remove("clients"), &mut errors);
+        for key in doc.keys() {
+            errors.document_errors.push(format!("unknown field: `{}`", key));
+        }
+        errors.into_result(BootstrapConfig { servers, clients })
+    }
+
+    /// Server entries
+    pub fn servers(&self) -> &[ServerEntry] {
+        &self.servers
+    }
+
+    /// Client entries
+    pub fn clients(&self) -> &[ClientEntry] {
+        &self.clients
+    }
+
+    /// Adds a server entry
+    pub fn add_server(mut self, server: ServerEntry) -> BootstrapConfig {
+        self.servers.push(server);
+        self
+    }
+
+    /// Adds a client entry
+    pub fn add_client(mut self, client: ClientEntry) -> BootstrapConfig {
+        self.clients.push(client);
+        self
+    }
+
+    /// Validates the config against the current runtime environment, i.e., the executor registry
+    /// and the client registry. Nothing is started.
+    pub fn validate(&self) -> Result<(), BootstrapError> {
+        let mut errors = ConfigErrors::default();
+        self.collect_validation_errors(&mut errors);
+        errors.into_result(())
+    }
+
+    fn collect_validation_errors(&self, errors: &mut ConfigErrors) {
+        for (index, server) in self.servers.iter().enumerate() {
+            if let Err(kind) = server.validate() {
+                errors.add_entry_error(Section::Servers, index, kind);
+            }
+        }
+        let mut client_ids = HashSet::new();
+        for (index, entry) in self.clients.iter().enumerate() {
+            let result = entry.validate().and_then(|_| {
+                if !client_ids.insert(entry.reqrep_id) {
+                    return Err(EntryErrorKind::DuplicateClient(entry.reqrep_id));
+                }
+                if client::client(entry.reqrep_id).is_some() {
+                    return Err(EntryErrorKind::ClientAlreadyRegistered(entry.reqrep_id));
+                }
+                Ok(())
+            });
+            if let Err(kind) = result {
+                errors.add_entry_error(Section::Clients, index, kind);
+            }
+        }
+    }
+
+    /// Validates the config, and then spawns the servers and registers the clients.
+    /// - `services` provides the backend service for each server by ReqRepId
+    ///
+    /// The validation errors and the missing services are reported together. If any entry fails to
+    /// start, then the servers and clients that were started are stopped.
+    pub fn bootstrap<F>(self, mut services: F) -> Result<Bootstrap, BootstrapError>
+    where
+        F: FnMut(ReqRepId) -> Option<ReqRep<nng::Message, nng::Message>>,
+    {
+        let mut errors = ConfigErrors::default();
+        self.collect_validation_errors(&mut errors);
+        let mut server_services = Vec::with_capacity(self.servers.len());
+        for (index, server) in self.servers.iter().enumerate() {
+            match services(server.reqrep_id) {
+                Some(service) => server_services.push(service),
+                None => errors.add_entry_error(
+                    Section::Servers,
+                    index,
+                    EntryErrorKind::ServiceNotFound(server.reqrep_id),
+                ),
+            }
+        }
+        errors.into_result(())?;
+
+        let mut bootstrap = Bootstrap::default();
+        let servers = self.servers.into_iter().zip(server_services.into_iter());
+        for (index, (server, service)) in servers.enumerate() {
+            let reqrep_id = server.reqrep_id;
+            match server.spawn(service) {
+                Ok(server_handle) => bootstrap.servers.push(server_handle),
+                Err(err) => {
+                    bootstrap.stop();
+                    return Err(ConfigErrors::entry_error(Section::Servers, index, err));
+                }
+            }
+            debug!("bootstrap: servers[{}] started: {}", index, reqrep_id);
+        }
+        for (index, entry) in self.clients.into_iter().enumerate() {
+            let reqrep_id = entry.reqrep_id;
+            match entry.register() {
+                Ok(client) => bootstrap.clients.push(client),
+                Err(err) => {
+                    bootstrap.stop();
+                    return Err(ConfigErrors::entry_error(Section::Clients, index, err));
+                }
+            }
+            debug!("bootstrap: clients[{}] registered: {}", index, reqrep_id);
+        }
+        Ok(bootstrap)
+    }
+}
+
+fn parse_entries<T: DeserializeOwned>(
+    section: Section,
+    entries: Option<serde_json::Value>,
+    errors: &mut ConfigErrors,
+) -> Vec<T> {
+    match entries {
+        None => Vec::new(),
+        Some(serde_json::Value::Array(entries)) => entries
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, entry)| match serde_json::from_value(entry) {
+                Ok(entry) => Some(entry),
+                Err(err) => {
+                    errors.add_entry_error(
+                        section,
+                        index,
+                        EntryErrorKind::InvalidEntry(err.to_string()),
+                    );
+                    None
+                }
+            })
+            .collect(),
+        Some(_) => {
+            errors.document_errors.push(format!("`{}` must be an array", section));
+            Vec::new()
+        }
+    }
+}
+
+fn lookup_executor(executor_id: Option<ExecutorId>) -> Result<Executor, EntryErrorKind> {
+    match executor_id {
+        Some(executor_id) => execution::executor(executor_id)
+            .ok_or_else(|| EntryErrorKind::ExecutorNotRegistered(executor_id)),
+        None => Ok(execution::global_executor()),
+    }
+}
+
+/// Server config entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerEntry {
+    #[serde(with = "crate::serde_util::ulid")]
+    reqrep_id: ReqRepId,
+    #[serde(default, with = "crate::serde_util::opt_ulid")]
+    executor_id: Option<ExecutorId>,
+    #[serde(default)]
+    socket: Option<config::SocketConfig>,
+    listener: ListenerConfig,
+}
+
+impl ServerEntry {
+    /// constructor
+    pub fn new(reqrep_id: ReqRepId, listener: ListenerConfig) -> ServerEntry {
+        ServerEntry {
+            reqrep_id,
+            executor_id: None,
+            socket: None,
+            listener,
+        }
+    }
+
+    /// The server's backend ReqRep service ID
+    pub fn reqrep_id(&self) -> ReqRepId {
+        self.reqrep_id
+    }
+
+    /// The executor used to run the server - if None, then the global executor is used
+    pub fn executor_id(&self) -> Option<ExecutorId> {
+        self.executor_id
+    }
+
+    /// Socket config
+    pub fn socket_config(&self) -> Option<&config::SocketConfig> {
+        self.socket.as_ref()
+    }
+
+    /// Listener config
+    pub fn listener_config(&self) -> &ListenerConfig {
+        &self.listener
+    }
+
+    /// Sets the executor used to run the server
+    pub fn set_executor_id(mut self, executor_id: ExecutorId) -> ServerEntry {
+        self.executor_id = Some(executor_id);
+        self
+    }
+
+    /// Sets the socket config
+    pub fn set_socket_config(mut self, socket: config::SocketConfig) -> ServerEntry {
+        self.socket = Some(socket);
+        self
+    }
+
+    fn validate(&self) -> Result<(), EntryErrorKind> {
+        if self.listener.parallelism() == 0 {
+            return Err(EntryErrorKind::InvalidParallelism);
+        }
+        lookup_executor(self.executor_id).map(|_| ())
+    }
+
+    fn spawn(
+        self,
+        service: ReqRep<nng::Message, nng::Message>,
+    ) -> Result<ServerHandle, EntryErrorKind> {
+        let executor = lookup_executor(self.executor_id)?;
+        server::spawn(self.socket, self.listener, service, executor)
+            .map_err(EntryErrorKind::ServerSpawnFailed)
+    }
+}
+
+/// Client config entry
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClientEntry {
+    #[serde(with = "crate::serde_util::ulid")]
+    reqrep_id: ReqRepId,
+    #[serde(default, with = "crate::serde_util::opt_ulid")]
+    executor_id: Option<ExecutorId>,
+    timer_buckets: Vec<Duration>,
+    #[serde(default)]
+    socket: Option<client::SocketConfig>,
+    dialer: DialerConfig,
+}
+
+impl ClientEntry {
+    /// constructor
+    /// - timer_buckets are used to configure the client's request timer metrics - see
+    ///   [metrics::timer_buckets()](https://docs.rs/oysterpack_trust/latest/oysterpack_trust/metrics/fn.timer_buckets.html)
+    pub fn new(
+        reqrep_id: ReqRepId,
+        timer_buckets: Vec<Duration>,
+        dialer: DialerConfig,
+    ) -> ClientEntry {
+        ClientEntry {
+            reqrep_id,
+            executor_id: None,
+            timer_buckets,
+            socket: None,
+            dialer,
+        }
+    }
+
+    /// The client ReqRepId
+    pub fn reqrep_id(&self) -> ReqRepId {
+        self.reqrep_id
+    }
+
+    /// The executor used to run the client - if None, then the global executor is used
+    pub fn executor_id(&self) -> Option<ExecutorId> {
+        self.executor_id
+    }
+
+    /// Metric timer buckets
+    pub fn timer_buckets(&self) -> &[Duration] {
+        &self.timer_buckets
+    }
+
+    /// Socket config
+    pub fn socket_config(&self) -> Option<&client::SocketConfig> {
+        self.socket.as_ref()
+    }
+
+    /// Dialer config
+    pub fn dialer_config(&self) -> &DialerConfig {
+        &self.dialer
+    }
+
+    /// Sets the executor used to run the client
+    pub fn set_executor_id(mut self, executor_id: ExecutorId) -> ClientEntry {
+        self.executor_id = Some(executor_id);
+        self
+    }
+
+    /// Sets the socket config
+    pub fn set_socket_config(mut self, socket: client::SocketConfig) -> ClientEntry {
+        self.socket = Some(socket);
+        self
+    }
+
+    fn validate(&self) -> Result<(), EntryErrorKind> {
+        if self.dialer.parallelism() == 0 {
+            return Err(EntryErrorKind::InvalidParallelism);
+        }
+        self.reqrep_config()?;
+        lookup_executor(self.executor_id).map(|_| ())
+    }
+
+    fn reqrep_config(&self) -> Result<ReqRepConfig, EntryErrorKind> {
+        metrics::timer_buckets(self.timer_buckets.clone())
+            .map(|timer_buckets| ReqRepConfig::new(self.reqrep_id, timer_buckets))
+            .map_err(|err| EntryErrorKind::InvalidTimerBuckets(err.to_string()))
+    }
+
+    fn register(self) -> Result<Client, EntryErrorKind> {
+        let reqrep_config = self.reqrep_config()?;
+        let executor = lookup_executor(self.executor_id)?;
+        client::register_client(reqrep_config, self.socket, self.dialer, executor)
+            .map_err(EntryErrorKind::ClientRegistrationFailed)
+    }
+}
+
+/// The servers and clients that were started
+#[derive(Debug, Default)]
+pub struct Bootstrap {
+    servers: Vec<ServerHandle>,
+    clients: Vec<Client>,
+}
+
+impl Bootstrap {
+    /// Server handles - in the order they were declared
+    pub fn servers(&self) -> &[ServerHandle] {
+        &self.servers
+    }
+
+    /// Server handles - in the order they were declared
+    pub fn servers_mut(&mut self) -> &mut [ServerHandle] {
+        &mut self.servers
+    }
+
+    /// Registered clients - in the order they were declared
+    pub fn clients(&self) -> &[Client] {
+        &self.clients
+    }
+
+    /// Signals the servers to stop, and unregisters the clients
+    pub fn stop(&mut self) {
+        for server in self.servers.iter_mut() {
+            if let Err(err) = server.stop_async() {
+                warn!("Failed to stop server: {}", err);
+            }
+        }
+        for client in self.clients.drain(..) {
+            client::unregister_client(client.id());
+        }
+    }
+
+    /// Returns the server handles and clients
+    pub fn into_parts(self) -> (Vec<ServerHandle>, Vec<Client>) {
+        (self.servers, self.clients)
+    }
+}
+
+/// Config document section
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Section {
+    /// `servers`
+    Servers,
+    /// `clients`
+    Clients,
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Section::Servers => f.write_str("servers"),
+            Section::Clients => f.write_str("clients"),
+        }
+    }
+}
+
+/// Identifies which config entry is invalid, and why, e.g., `servers[1]: Executor is not registered: ...`
+#[derive(Debug, Fail)]
+#[fail(display = "{}[{}]: {}", section, index, kind)]
+pub struct EntryError {
+    section: Section,
+    index: usize,
+    #[cause]
+    kind: EntryErrorKind,
+}
+
+impl EntryError {
+    fn new(section: Section, index: usize, kind: EntryErrorKind) -> EntryError {
+        EntryError {
+            section,
+            index,
+            kind,
+        }
+    }
+
+    /// the section that contains the entry
+    pub fn section(&self) -> Section {
+        self.section
+    }
+
+    /// the entry's index within the section
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// what is wrong with the entry
+    pub fn kind(&self) -> &EntryErrorKind {
+        &self.kind
+    }
+}
+
+/// Config entry errors
+#[derive(Debug, Fail)]
+pub enum EntryErrorKind {
+    /// The entry failed to deserialize
+    #[fail(display = "Invalid entry: {}", _0)]
+    InvalidEntry(String),
+    /// The referenced executor is not registered
+    #[fail(display = "Executor is not registered: {}", _0)]
+    ExecutorNotRegistered(ExecutorId),
+    /// Parallelism must be greater than 0
+    #[fail(display = "Parallelism must be greater than 0")]
+    InvalidParallelism,
+    /// Invalid metric timer buckets
+    #[fail(display = "Invalid timer buckets: {}", _0)]
+    InvalidTimerBuckets(String),
+    /// The client is declared more than once within the config
+    #[fail(display = "Duplicate client: {}", _0)]
+    DuplicateClient(ReqRepId),
+    /// The client is already registered
+    #[fail(display = "Client is already registered: {}", _0)]
+    ClientAlreadyRegistered(ReqRepId),
+    /// No backend service was provided for the server
+    #[fail(display = "Service not found: {}", _0)]
+    ServiceNotFound(ReqRepId),
+    /// Failed to spawn the server
+    #[fail(display = "Failed to spawn server: {}", _0)]
+    ServerSpawnFailed(#[cause] server::SpawnError),
+    /// Failed to register the client
+    #[fail(display = "Failed to register client: {}", _0)]
+    ClientRegistrationFailed(#[cause] ClientRegistrationError),
+}
+
+/// Config errors - displayed one per line, document errors first followed by the entry errors
+#[derive(Debug, Default)]
+pub struct ConfigErrors {
+    document_errors: Vec<String>,
+    entry_errors: Vec<EntryError>,
+}
+
+impl ConfigErrors {
+    /// document level errors, e.g., unknown fields, in the order they were found
+    pub fn document_errors(&self) -> &[String] {
+        &self.document_errors
+    }
+
+    /// entry errors in the order they were found
+    pub fn entry_errors(&self) -> &[EntryError] {
+        &self.entry_errors
+    }
+
+    fn add_entry_error(&mut self, section: Section, index: usize, kind: EntryErrorKind) {
+        self.entry_errors.push(EntryError::new(section, index, kind));
+    }
+
+    fn entry_error(section: Section, index: usize, kind: EntryErrorKind) -> BootstrapError {
+        let mut errors = ConfigErrors::default();
+        errors.add_entry_error(section, index, kind);
+        BootstrapError::InvalidConfig(errors)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.document_errors.is_empty() && self.entry_errors.is_empty()
+    }
+
+    fn into_result<T>(self, value: T) -> Result<T, BootstrapError> {
+        if self.is_empty() {
+            Ok(value)
+        } else {
+            Err(BootstrapError::InvalidConfig(self))
+        }
+    }
+}
+
+impl fmt::Display for ConfigErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let errors = self
+            .document_errors
+            .iter()
+            .map(|err| err as &dyn fmt::Display)
+            .chain(self.entry_errors.iter().map(|err| err as &dyn fmt::Display));
+        for (i, err) in errors.enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            write!(f, "{}", err)?;
+        }
+        Ok(())
+    }
+}
+
+/// Bootstrap errors
+#[derive(Debug, Fail)]
+pub enum BootstrapError {
+    /// Failed to read the config file
+    #[fail(display = "Failed to read config file: {:?}: {}", path, err)]
+    Io {
+        /// config file path
+        path: PathBuf,
+        /// cause
+        #[cause]
+        err: io::Error,
+    },
+    /// The config file extension must be `toml` or `json`
+    #[fail(display = "Unsupported config file type: {:?}", _0)]
+    UnsupportedFileType(PathBuf),
+    /// The document is not valid TOML
+    #[fail(display = "Invalid TOML: {}", _0)]
+    InvalidToml(#[cause] toml::de::Error),
+    /// The document is not valid JSON
+    #[fail(display = "Invalid JSON: {}", _0)]
+    InvalidJson(#[cause] serde_json::Error),
+    /// The document is not a table, i.e., it cannot be parsed any further
+    #[fail(display = "Invalid config document: {}", _0)]
+    InvalidDocument(String),
+    /// The config document or one or more entries are invalid
+    #[fail(display = "{}", _0)]
+    InvalidConfig(ConfigErrors),
+}
+
+impl BootstrapError {
+    /// Returns the document errors - if the config is invalid
+    pub fn document_errors(&self) -> &[String] {
+        match self {
+            BootstrapError::InvalidConfig(errors) => errors.document_errors(),
+            _ => &[],
+        }
+    }
+
+    /// Returns the entry errors - if the failure is entry specific
+    pub fn entry_errors(&self) -> &[EntryError] {
+        match self {
+            BootstrapError::InvalidConfig(errors) => errors.entry_errors(),
+            _ => &[],
+        }
+    }
+}
+
+#[allow(warnings)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configure_logging;
+    use futures::prelude::*;
+    use oysterpack_trust::concurrent::messaging::reqrep::{self, Processor};
+    use oysterpack_uid::ULID;
+    use std::collections::HashMap;
+
+    struct EchoService;
+    impl Processor<nng::Message, nng::Message> for EchoService {
+        fn process(&mut self, req: nng::Message) -> reqrep::FutureReply<nng::Message> {
+            async move { req }.boxed()
+        }
+    }
+
+    fn start_service(reqrep_id: ReqRepId) -> ReqRep<nng::Message, nng::Message> {
+        let timer_buckets = metrics::timer_buckets(vec![Duration::from_millis(1)]).unwrap();
+        ReqRepConfig::new(reqrep_id, timer_buckets)
+            .start_service(EchoService, execution::global_executor())
+            .unwrap()
+    }
+
+    #[test]
+    fn bootstrap_from_toml() {
+        configure_logging();
+        let mut executor = execution::global_executor();
+
+        // GIVEN: a TOML config that declares a server and a client
+        let reqrep_id = ReqRepId::generate();
+        let executor_id = ExecutorId::generate();
+        execution::ExecutorBuilder::new(executor_id)
+            .register()
+            .unwrap();
+        let url = format!("inproc://{}", ULID::generate());
+        let doc = format!(
+            r#"
+            [[servers]]
+            reqrep_id = "{reqrep_id}"
+            executor_id = "{executor_id}"
+
+            [servers.listener]
+            url = "{url}"
+            non_blocking = true
+            parallelism = 2
+
+            [[clients]]
+            reqrep_id = "{reqrep_id}"
+            timer_buckets = [{{secs = 0, nanos = 1000000}}]
+
+            [clients.dialer]
+            url = "{url}"
+            parallelism = 1
+            "#,
+            reqrep_id = reqrep_id,
+            executor_id = executor_id,
+            url = url
+        );
+        let config = BootstrapConfig::from_toml_str(&doc).unwrap();
+        assert_eq!(config.servers().len(), 1);
+        assert_eq!(config.servers()[0].executor_id(), Some(executor_id));
+        assert_eq!(config.clients().len(), 1);
+
+        // WHEN: the config is bootstrapped
+        let mut services = HashMap::new();
+        services.insert(reqrep_id, start_service(reqrep_id));
+        let mut bootstrap = config
+            .bootstrap(|reqrep_id| services.get(&reqrep_id).cloned())
+            .unwrap();
+
+        // THEN: the server is running
+        assert!(bootstrap.servers()[0].ping());
+        // AND: the client is registered
+        let mut client = client::client(reqrep_id).unwrap();
+        // AND: the client can send requests to the server
+        let reply = executor
+            .run(async move { await!(client.send_recv(nng::Message::new().unwrap())) })
+            .unwrap();
+        assert!(reply.is_ok());
+
+        // WHEN: the bootstrap is stopped
+        bootstrap.stop();
+        // THEN: the client is unregistered
+        assert!(client::client(reqrep_id).is_none());
+        let (servers, _) = bootstrap.into_parts();
+        for server in servers {
+            server.await_shutdown();
+        }
+    }
+
+    #[test]
+    fn bootstrap_entry_errors() {
+        configure_logging();
+
+        // GIVEN: a JSON config with invalid entries
+        let reqrep_id = ReqRepId::generate();
+        let unregistered_executor_id = ExecutorId::generate();
+        let url = format!("inproc://{}", ULID::generate());
+        let doc = format!(
+            r#"{{
+                "servers": [
+                    {{"reqrep_id": "not-a-ulid", "listener": {{"url": "{url}", "non_blocking": true, "parallelism": 1}}}},
+                    {{"reqrep_id": "{reqrep_id}", "executor_id": "{executor_id}", "listener": {{"url": "{url}", "non_blocking": true, "parallelism": 1}}}}
+                ],
+                "clients": [
+                    {{"reqrep_id": "{reqrep_id}", "timer_buckets": [], "dialer": {{"url": "{url}", "parallelism": 1}}}},
+                    {{"reqrep_id": "{reqrep_id}", "timer_buckets": [{{"secs": 1, "nanos": 0}}], "dialer": {{"url": "{url}", "parallelism": 0}}}},
+                    {{"reqrep_id": "{reqrep_id}", "unknown_entry_field": 1}}
+                ],
+                "unknown_field": true
+            }}"#,
+            reqrep_id = reqrep_id,
+            executor_id = unregistered_executor_id,
+            url = url
+        );
+
+        // WHEN: the document is parsed
+        // THEN: the unknown top level field is reported
+        let err = BootstrapConfig::from_json_str(&doc).unwrap_err();
+        info!("{}", err);
+        assert_eq!(err.document_errors().len(), 1);
+        assert!(err.document_errors()[0].contains("unknown_field"));
+        // AND: each entry that failed to deserialize is reported
+        let errors = err.entry_errors();
+        assert_eq!(
+            errors
+                .iter()
+                .map(|err| (err.section(), err.index()))
+                .collect::<Vec<_>>(),
+            vec![(Section::Servers, 0), (Section::Clients, 2)]
+        );
+        for err in errors {
+            match err.kind() {
+                EntryErrorKind::InvalidEntry(_) => (),
+                kind => panic!("unexpected error: {}", kind),
+            }
+        }
+
+        // GIVEN: the config only contains deserializable entries
+        let config = BootstrapConfig::default()
+            .add_server(
+                ServerEntry::new(
+                    reqrep_id,
+                    ListenerConfig::new(url::Url::parse(&url).unwrap()),
+                )
+                .set_executor_id(unregistered_executor_id),
+            )
+            .add_client(ClientEntry::new(
+                reqrep_id,
+                vec![],
+                DialerConfig::new(url::Url::parse(&url).unwrap()),
+            ))
+            .add_client(ClientEntry::new(
+                reqrep_id,
+                vec![Duration::from_millis(1)],
+                DialerConfig::new(url::Url::parse(&url).unwrap()),
+            ))
+            .add_client(ClientEntry::new(
+                reqrep_id,
+                vec![Duration::from_millis(1)],
+                DialerConfig::new(url::Url::parse(&url).unwrap()),
+            ));
+
+        // WHEN: the config is validated
+        let err = config.validate().unwrap_err();
+        info!("{}", err);
+        // THEN: each invalid entry is reported
+        let errors: Vec<_> = err
+            .entry_errors()
+            .iter()
+            .map(|err| (err.section(), err.index()))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (Section::Servers, 0),
+                (Section::Clients, 0),
+                (Section::Clients, 2)
+            ]
+        );
+        match err.entry_errors()[2].kind() {
+            EntryErrorKind::DuplicateClient(id) => assert_eq!(*id, reqrep_id),
+            kind => panic!("unexpected error: {}", kind),
+        }
+        // AND: nothing was started
+        assert!(client::client(reqrep_id).is_none());
+
+        // GIVEN: a config with an invalid client, and the server's service is not provided
+        let config = BootstrapConfig::default()
+            .add_server(ServerEntry::new(
+                reqrep_id,
+                ListenerConfig::new(url::Url::parse(&url).unwrap()),
+            ))
+            .add_client(ClientEntry::new(
+                reqrep_id,
+                vec![],
+                DialerConfig::new(url::Url::parse(&url).unwrap()),
+            ));
+        // WHEN: the config is bootstrapped
+        let err = config.bootstrap(|_| None).unwrap_err();
+        info!("{}", err);
+        // THEN: the validation error and the missing service are both reported
+        let errors = err.entry_errors();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].section(), Section::Clients);
+        match errors[0].kind() {
+            EntryErrorKind::InvalidTimerBuckets(_) => (),
+            kind => panic!("unexpected error: {}", kind),
+        }
+        assert_eq!(errors[1].section(), Section::Servers);
+        match errors[1].kind() {
+            EntryErrorKind::ServiceNotFound(id) => assert_eq!(*id, reqrep_id),
+            kind => panic!("unexpected error: {}", kind),
+        }
+    }
+}
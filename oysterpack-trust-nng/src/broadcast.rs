@@ -0,0 +1,145 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Provides a [Broadcaster](struct.Broadcaster.html), which is used to publish event streams, e.g.,
+//! pipe events and gossip membership events.
+//!
+//! Events are broadcast from nng callbacks and task event loops, which must not block. Thus,
+//! subscriber channels are bounded, and events are dropped for subscribers whose channel is full.
+
+use futures::channel::mpsc;
+use oysterpack_log::*;
+use parking_lot::Mutex;
+use std::{fmt, sync::Arc};
+
+/// Broadcasts events to all subscribers
+pub(crate) struct Broadcaster<T> {
+    /// used to identify the event stream in log messages
+    name: &'static str,
+    chan_buf_size: usize,
+    /// incremented when an event is dropped because the subscriber's channel is full
+    dropped_count: prometheus::IntCounter,
+    subscribers: Arc<Mutex<Vec<mpsc::Sender<T>>>>,
+}
+
+impl<T: Clone> Broadcaster<T> {
+    /// constructor
+    pub(crate) fn new(
+        name: &'static str,
+        chan_buf_size: usize,
+        dropped_count: prometheus::IntCounter,
+    ) -> Broadcaster<T> {
+        Broadcaster {
+            name,
+            chan_buf_size,
+            dropped_count,
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// subscribes to events
+    pub(crate) fn subscribe(&self) -> mpsc::Receiver<T> {
+        let (tx, rx) = mpsc::channel(self.chan_buf_size);
+        self.subscribers.lock().push(tx);
+        rx
+    }
+
+    /// subscribers that have been dropped are removed
+    pub(crate) fn broadcast(&self, event: T) {
+        let mut subscribers = self.subscribers.lock();
+        let mut i = 0;
+        while i < subscribers.len() {
+            match subscribers[i].try_send(event.clone()) {
+                Ok(_) => i += 1,
+                Err(ref err) if err.is_full() => {
+                    warn!("{} subscriber channel is full - the event was dropped", self.name);
+                    self.dropped_count.inc();
+                    i += 1;
+                }
+                Err(_) => {
+                    subscribers.swap_remove(i);
+                }
+            }
+        }
+    }
+
+    /// closes all subscriber streams
+    pub(crate) fn close(&self) {
+        self.subscribers.lock().clear();
+    }
+}
+
+impl<T> Clone for Broadcaster<T> {
+    fn clone(&self) -> Self {
+        Broadcaster {
+            name: self.name,
+            chan_buf_size: self.chan_buf_size,
+            dropped_count: self.dropped_count.clone(),
+            subscribers: self.subscribers.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Broadcaster<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Broadcaster({}, subscriber_count = {})",
+            self.name,
+            self.subscribers.lock().len()
+        )
+    }
+}
+
+#[allow(warnings)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream::StreamExt;
+    use oysterpack_trust::concurrent::execution::global_executor;
+
+    const CHAN_BUF_SIZE: usize = 8;
+
+    #[test]
+    fn broadcaster_drops_events_for_full_subscribers() {
+        let mut executor = global_executor();
+        let dropped_count = prometheus::IntCounter::new("dropped_count", "dropped_count").unwrap();
+        let broadcaster = Broadcaster::<usize>::new("test", CHAN_BUF_SIZE, dropped_count.clone());
+        let mut events = broadcaster.subscribe();
+
+        // WHEN: more events are broadcast than the subscriber channel can buffer
+        const EVENT_COUNT: usize = CHAN_BUF_SIZE * 2;
+        for i in 0..EVENT_COUNT {
+            broadcaster.broadcast(i);
+        }
+        // THEN: the broadcast does not block, and the events that did not fit are dropped
+        assert!(dropped_count.get() >= (EVENT_COUNT - CHAN_BUF_SIZE - 1) as i64);
+        // AND: the buffered events are received in order
+        broadcaster.close();
+        let events = executor.run(events.collect::<Vec<_>>());
+        assert!(events.len() <= CHAN_BUF_SIZE + 1);
+        for (i, event) in events.iter().enumerate() {
+            assert_eq!(*event, i);
+        }
+
+        // WHEN: the subscriber is dropped
+        let events = broadcaster.subscribe();
+        drop(events);
+        broadcaster.broadcast(0);
+        // THEN: it is unsubscribed
+        assert!(broadcaster.subscribers.lock().is_empty());
+    }
+}
@@ -0,0 +1,1220 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Provides lightweight cluster membership via peer gossip over an nng Bus0 mesh.
+//!
+//! ## Design
+//! Each node listens on a Bus0 socket, and dials the configured seed peers. A single gossip task owns
+//! the socket, and is driven by the heartbeat ticker, the Aio receive events, and commands sent via the
+//! [GossipHandle](struct.GossipHandle.html).
+//! - on each heartbeat, the node announces itself ([NodeInfo](struct.NodeInfo.html)) to its peers
+//!   - the heartbeat also lists the gossip URLs of the node's live members
+//! - Bus0 messages are only delivered to directly connected peers. Thus, when a node learns about a
+//!   member via gossip that it is not directly connected to, it dials the member, which forms the mesh.
+//! - members that have not sent a heartbeat within the failure timeout are declared failed
+//!   - the node stops dialing failed members, except for seeds. Seed dialers are never closed, i.e.,
+//!     the node reconnects to a seed when it is restarted, which prevents the cluster from being
+//!     permanently partitioned.
+//! - when a node is stopped, it announces that it is leaving the cluster
+//!
+//! Membership changes are published as [MembershipEvent](enum.MembershipEvent.html) streams. The
+//! streams are bounded: if a subscriber falls behind, then events are dropped.
+//!
+//! <pre>
+//! GossipHandle ---Command--> gossip task --Heartbeat--> Socket ---> peers
+//! MembershipEventStream <--- gossip task <------------- Socket <--- peers
+//! </pre>
+//!
+//! ## Config
+//! - [SocketConfig](../config/struct.SocketConfig.html)
+//! - [GossipConfig](struct.GossipConfig.html)
+//!   - [ListenerConfig](../reqrep/server/struct.ListenerConfig.html)
+//!
+//! ## Metrics
+//! - active number of socket connections - [ACTIVE_CONN_COUNT_METRIC_ID](constant.ACTIVE_CONN_COUNT_METRIC_ID.html)
+//! - number of live members - [MEMBER_COUNT_METRIC_ID](constant.MEMBER_COUNT_METRIC_ID.html)
+//! - total number of member failures - [MEMBER_FAILURE_COUNT_METRIC_ID](constant.MEMBER_FAILURE_COUNT_METRIC_ID.html)
+//! - total number of heartbeats sent - [HEARTBEAT_SENT_COUNT_METRIC_ID](constant.HEARTBEAT_SENT_COUNT_METRIC_ID.html)
+//! - total number of heartbeats received - [HEARTBEAT_RECV_COUNT_METRIC_ID](constant.HEARTBEAT_RECV_COUNT_METRIC_ID.html)
+//! - total number of membership events that were dropped because the subscriber's channel was full -
+//!   [DROPPED_MEMBERSHIP_EVENT_COUNT_METRIC_ID](constant.DROPPED_MEMBERSHIP_EVENT_COUNT_METRIC_ID.html)
+
+use crate::{
+    broadcast::Broadcaster,
+    config::{SocketConfig, SocketConfigError},
+    reqrep::{
+        client::{DialerConfig, DialerConfigError},
+        server::{ListenerConfig, ListenerConfigError},
+    },
+    task::{self, TaskHandle, TaskHandleError},
+    timer::delay,
+};
+use failure::Fail;
+use futures::{
+    channel::{mpsc, oneshot},
+    prelude::*,
+    sink::SinkExt,
+    stream::StreamExt,
+    task::SpawnExt,
+};
+use hashbrown::{HashMap, HashSet};
+use lazy_static::lazy_static;
+use oysterpack_log::*;
+use oysterpack_trust::{concurrent::execution::Executor, metrics};
+use oysterpack_uid::macros::ulid;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    time::{Duration, Instant, SystemTime},
+};
+
+lazy_static! {
+
+    /// the metric is incremented on nng::PipeEvent::AddPost and decremented on nng::PipeEvent::RemovePost
+    static ref ACTIVE_CONN_COUNT: prometheus::IntGaugeVec = metrics::registry().register_int_gauge_vec(
+        ACTIVE_CONN_COUNT_METRIC_ID,
+        "Active number of gossip socket connections",
+        &[CLUSTER_ID_LABEL_ID, INSTANCE_ID_LABEL_ID],
+        None
+    ).unwrap();
+
+    /// the metric is updated when a member joins, leaves, or fails
+    static ref MEMBER_COUNT: prometheus::IntGaugeVec = metrics::registry().register_int_gauge_vec(
+        MEMBER_COUNT_METRIC_ID,
+        "Number of live cluster members",
+        &[CLUSTER_ID_LABEL_ID, INSTANCE_ID_LABEL_ID],
+        None
+    ).unwrap();
+
+    /// the metric is incremented when a member has not sent a heartbeat within the failure timeout
+    static ref MEMBER_FAILURE_COUNT: prometheus::IntCounterVec = metrics::registry().register_int_counter_vec(
+        MEMBER_FAILURE_COUNT_METRIC_ID,
+        "Total number of cluster member failures",
+        &[CLUSTER_ID_LABEL_ID, INSTANCE_ID_LABEL_ID],
+        None
+    ).unwrap();
+
+    /// the metric is incremented when a heartbeat is sent
+    static ref HEARTBEAT_SENT_COUNT: prometheus::IntCounterVec = metrics::registry().register_int_counter_vec(
+        HEARTBEAT_SENT_COUNT_METRIC_ID,
+        "Total number of gossip heartbeats sent",
+        &[CLUSTER_ID_LABEL_ID, INSTANCE_ID_LABEL_ID],
+        None
+    ).unwrap();
+
+    /// the metric is incremented when a heartbeat is received from a cluster member
+    static ref HEARTBEAT_RECV_COUNT: prometheus::IntCounterVec = metrics::registry().register_int_counter_vec(
+        HEARTBEAT_RECV_COUNT_METRIC_ID,
+        "Total number of gossip heartbeats received",
+        &[CLUSTER_ID_LABEL_ID, INSTANCE_ID_LABEL_ID],
+        None
+    ).unwrap();
+
+    /// the metric is incremented when a membership event is dropped because the subscriber's channel is full
+    static ref DROPPED_MEMBERSHIP_EVENT_COUNT: prometheus::IntCounterVec = metrics::registry().register_int_counter_vec(
+        DROPPED_MEMBERSHIP_EVENT_COUNT_METRIC_ID,
+        "Total number of membership events that were dropped because the subscriber's channel was full",
+        &[CLUSTER_ID_LABEL_ID, INSTANCE_ID_LABEL_ID],
+        None
+    ).unwrap();
+
+}
+
+/// IntGaugeVec MetricId which is used to track the number of active gossip socket connections by ClusterId and InstanceId: `M01M57GBF5841FGQB183T1R8HYP`
+pub const ACTIVE_CONN_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166790554894958427499757564451899350);
+/// IntGaugeVec MetricId which is used to track the number of live members by ClusterId and InstanceId: `M01M57GBF4XR5CJ4PW3QPDWEZ6S`
+pub const MEMBER_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166790554882420435441900142775991513);
+/// IntCounterVec MetricId which is used to track the total number of member failures by ClusterId and InstanceId: `M01M57GBF509GA1T4RN9GJMVEZW`
+pub const MEMBER_FAILURE_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166790554885493422816231593740188668);
+/// IntCounterVec MetricId which is used to track the total number of heartbeats sent by ClusterId and InstanceId: `M01M57GBF54053PV297V45JZ35V`
+pub const HEARTBEAT_SENT_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166790554889975895190264612882713787);
+/// IntCounterVec MetricId which is used to track the total number of heartbeats received by ClusterId and InstanceId: `M01M57GBF563M78E91K6KQX8SWP`
+pub const HEARTBEAT_RECV_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166790554892524923471682981497956246);
+/// IntCounterVec MetricId which is used to track the total number of dropped membership events by ClusterId and InstanceId: `M01M57NH2WJZDVMR8R5DV76T2N4`
+pub const DROPPED_MEMBERSHIP_EVENT_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166797115642119907789097243176143524);
+
+/// Metric LabelId which is used to store a ClusterId: `L01M57GBF4RX7A23FHXWGP40W9T`
+pub const CLUSTER_ID_LABEL_ID: metrics::LabelId =
+    metrics::LabelId(2166790554876566969908394477460943162);
+/// Metric LabelId which is used to store an InstanceId: `L01M57GCZVA5R3MQ18GXHGW5HBE`
+pub const INSTANCE_ID_LABEL_ID: metrics::LabelId =
+    metrics::LabelId(2166790615169646436263903635798410606);
+
+/// Each cluster is uniquely identified by an ID. Gossip messages for other clusters are ignored.
+#[ulid]
+pub struct ClusterId(pub u128);
+
+/// Each node instance is uniquely identified by an ID
+#[ulid]
+pub struct InstanceId(pub u128);
+
+/// Node build info, e.g.,
+/// `BuildInfo::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))`
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct BuildInfo {
+    name: String,
+    version: String,
+    commit: Option<String>,
+}
+
+impl BuildInfo {
+    /// constructor
+    pub fn new(name: &str, version: &str) -> BuildInfo {
+        BuildInfo {
+            name: name.to_string(),
+            version: version.to_string(),
+            commit: None,
+        }
+    }
+
+    /// package name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// package version
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// source control commit that the node was built from
+    pub fn commit(&self) -> Option<&str> {
+        self.commit.as_ref().map(String::as_str)
+    }
+
+    /// sets the source control commit
+    pub fn set_commit(mut self, commit: &str) -> BuildInfo {
+        self.commit = Some(commit.to_string());
+        self
+    }
+}
+
+/// The info that a node announces to the cluster
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct NodeInfo {
+    #[serde(with = "crate::serde_util::ulid")]
+    instance_id: InstanceId,
+    build_info: BuildInfo,
+    #[serde(default, with = "crate::serde_util::urls")]
+    endpoints: Vec<url::Url>,
+}
+
+impl NodeInfo {
+    /// constructor
+    pub fn new(instance_id: InstanceId, build_info: BuildInfo) -> NodeInfo {
+        NodeInfo {
+            instance_id,
+            build_info,
+            endpoints: Vec::new(),
+        }
+    }
+
+    /// instance ID
+    pub fn instance_id(&self) -> InstanceId {
+        self.instance_id
+    }
+
+    /// build info
+    pub fn build_info(&self) -> &BuildInfo {
+        &self.build_info
+    }
+
+    /// the service endpoints that the node exposes, e.g., ReqRep server URLs
+    pub fn endpoints(&self) -> &[url::Url] {
+        &self.endpoints
+    }
+
+    /// adds a service endpoint - duplicates are ignored
+    pub fn add_endpoint(mut self, endpoint: url::Url) -> NodeInfo {
+        if !self.endpoints.contains(&endpoint) {
+            self.endpoints.push(endpoint);
+        }
+        self
+    }
+}
+
+/// Gossip config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GossipConfig {
+    #[serde(with = "crate::serde_util::ulid")]
+    cluster_id: ClusterId,
+    listener: ListenerConfig,
+    #[serde(default, with = "crate::serde_util::urls")]
+    seeds: Vec<url::Url>,
+    heartbeat_interval: Duration,
+    failure_timeout: Duration,
+}
+
+impl GossipConfig {
+    /// Default heartbeat interval: 1 sec
+    pub const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+    /// Default failure timeout: 5 sec
+    pub const DEFAULT_FAILURE_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// constructor
+    /// - the listener URL is the node's gossip URL, which is gossiped to the cluster, i.e., it must
+    ///   be reachable by the other nodes
+    pub fn new(cluster_id: ClusterId, listener: ListenerConfig) -> GossipConfig {
+        GossipConfig {
+            cluster_id,
+            listener,
+            seeds: Vec::new(),
+            heartbeat_interval: GossipConfig::DEFAULT_HEARTBEAT_INTERVAL,
+            failure_timeout: GossipConfig::DEFAULT_FAILURE_TIMEOUT,
+        }
+    }
+
+    /// ClusterId
+    pub fn cluster_id(&self) -> ClusterId {
+        self.cluster_id
+    }
+
+    /// Listener config
+    pub fn listener(&self) -> &ListenerConfig {
+        &self.listener
+    }
+
+    /// Seed peer gossip URLs, which are dialed when the node starts
+    /// - seeds are dialed for the lifetime of the node, i.e., even after they leave or fail
+    pub fn seeds(&self) -> &[url::Url] {
+        &self.seeds
+    }
+
+    /// How often the node announces itself
+    pub fn heartbeat_interval(&self) -> Duration {
+        self.heartbeat_interval
+    }
+
+    /// Members that have not sent a heartbeat within the failure timeout are declared failed
+    pub fn failure_timeout(&self) -> Duration {
+        self.failure_timeout
+    }
+
+    /// Adds a seed peer - duplicates are ignored
+    pub fn add_seed(mut self, url: url::Url) -> Self {
+        if !self.seeds.contains(&url) {
+            self.seeds.push(url);
+        }
+        self
+    }
+
+    /// Sets the heartbeat interval
+    pub fn set_heartbeat_interval(mut self, heartbeat_interval: Duration) -> Self {
+        self.heartbeat_interval = heartbeat_interval;
+        self
+    }
+
+    /// Sets the failure timeout - it should be a multiple of the heartbeat interval
+    pub fn set_failure_timeout(mut self, failure_timeout: Duration) -> Self {
+        self.failure_timeout = failure_timeout;
+        self
+    }
+}
+
+/// Spawns the gossip background task
+/// - the node joins the cluster by dialing the seed peers
+/// - returns a GossipHandle that is used to query cluster membership and to stop the node
+pub fn spawn(
+    socket_config: Option<SocketConfig>,
+    config: GossipConfig,
+    node: NodeInfo,
+    mut executor: Executor,
+) -> Result<GossipHandle, SpawnError> {
+    let cluster_id = config.cluster_id;
+    let url = config.listener.url().clone();
+    let gossip_metrics = GossipMetrics::new(cluster_id, node.instance_id);
+
+    let create_socket = || {
+        let active_conn_count = gossip_metrics.active_conn_count.clone();
+        let mut socket =
+            nng::Socket::new(nng::Protocol::Bus0).map_err(SpawnError::SocketCreateFailure)?;
+        socket.set_nonblocking(true);
+        socket
+            .pipe_notify(move |pipe, event| {
+                match event {
+                    nng::PipeEvent::AddPost => active_conn_count.inc(),
+                    nng::PipeEvent::RemovePost => active_conn_count.dec(),
+                    _ => (),
+                }
+                debug!("{:?} {:?}", pipe, event);
+            })
+            .map_err(SpawnError::SocketCreateFailure)?;
+        match socket_config {
+            Some(socket_config) => socket_config
+                .apply(socket)
+                .map_err(SpawnError::SocketConfigApplyFailed),
+            None => Ok(socket),
+        }
+    };
+
+    let socket = create_socket()?;
+    let listener = config
+        .listener
+        .start_listener(&socket)
+        .map_err(SpawnError::ListenerStartFailure)?;
+
+    let events = Broadcaster::new(
+        "MembershipEvent",
+        MEMBERSHIP_EVENT_CHAN_BUF_SIZE,
+        gossip_metrics.dropped_event_count.clone(),
+    );
+    let mut membership = Membership {
+        cluster_id,
+        node: node.clone(),
+        url: url.clone(),
+        failure_timeout: config.failure_timeout,
+        members: HashMap::new(),
+        seeds: config.seeds.iter().cloned().collect(),
+        dialers: HashMap::new(),
+        events: events.clone(),
+        metrics: gossip_metrics.clone(),
+    };
+    for seed in config.seeds.iter() {
+        let dialer = DialerConfig::new(seed.clone())
+            .start_dialer(&socket)
+            .map_err(SpawnError::DialerStartFailure)?;
+        membership.dialers.insert(seed.clone(), dialer);
+    }
+
+    // used to notify the gossip task when an Aio event has occurred, i.e., the Aio callback has been invoked
+    let (aio, aio_rx) =
+        task::aio_notifier("gossip task").map_err(SpawnError::AioCreateWithCallbackFailure)?;
+
+    let (command_tx, command_rx) = mpsc::channel::<GossipCommand>(1);
+
+    // the ticker has its own channel, i.e., it does not hold a command channel. Thus, when all
+    // GossipHandle(s) are dropped, the command stream ends, which stops the gossip task, which in turn
+    // stops the ticker.
+    let (mut heartbeat_tx, heartbeat_rx) = mpsc::channel::<()>(1);
+    let heartbeat_interval = config.heartbeat_interval;
+    executor
+        .spawn(
+            async move {
+                loop {
+                    await!(delay(heartbeat_interval));
+                    if await!(heartbeat_tx.send(())).is_err() {
+                        // the gossip task has stopped
+                        break;
+                    }
+                }
+            },
+        )
+        .map_err(|err| SpawnError::ExecutorSpawnError {
+            is_executor_shutdown: err.is_shutdown(),
+        })?;
+
+    let instance_id = node.instance_id;
+    let handle = executor
+        .spawn_with_handle(
+            async move {
+                debug!("Gossip({}, {}) is running ...", cluster_id, instance_id);
+                // fuse the streams that will be polled via futures::select! - per the documentation
+                let mut aio_rx = aio_rx.fuse();
+                let mut command_rx = command_rx.fuse();
+                let mut heartbeat_rx = heartbeat_rx.fuse();
+
+                let recv = || {
+                    if let Err(err) = socket.recv_async(&aio) {
+                        error!("Socket::recv_async() failed: {}", err);
+                    }
+                };
+
+                recv();
+                membership.send_heartbeat(&socket);
+                loop {
+                    futures::select! {
+                        event = aio_rx.next() => match event {
+                            // NOTE: aio.result().unwrap() is safe because we are being signalled
+                            // by the Aio callback to handle an Aio event
+                            Some(_) => match aio.result().unwrap() {
+                                Ok(_) => {
+                                    if let Some(msg) = aio.get_msg() {
+                                        membership.handle_msg(&socket, &msg);
+                                    }
+                                    recv();
+                                },
+                                Err(nng::Error::Closed) => break,
+                                Err(err) => {
+                                    error!("Aio error: {}", err);
+                                    recv();
+                                }
+                            },
+                            None => break
+                        },
+                        heartbeat = heartbeat_rx.next() => if heartbeat.is_some() {
+                            membership.send_heartbeat(&socket);
+                            membership.detect_failures();
+                        },
+                        command = command_rx.next() => match command {
+                            Some(GossipCommand::Members(reply_chan)) => {
+                                let _ = reply_chan.send(membership.members());
+                            },
+                            Some(GossipCommand::Ping(reply_chan)) => {
+                                let _ = reply_chan.send(());
+                            },
+                            Some(GossipCommand::Stop) | None => break,
+                        },
+                    }
+                }
+                debug!("Gossip({}, {}) is shutting down ...", cluster_id, instance_id);
+                membership.send(&socket, &GossipMessage::Leave {
+                    cluster_id,
+                    instance_id,
+                });
+                aio.cancel();
+                for (_, dialer) in membership.dialers.drain() {
+                    dialer.close();
+                }
+                listener.close();
+                socket.close();
+                membership.events.close();
+                membership.metrics.member_count.set(0);
+                debug!("Gossip({}, {}) is shut down", cluster_id, instance_id);
+            },
+        )
+        .map_err(|err| SpawnError::ExecutorSpawnError {
+            is_executor_shutdown: err.is_shutdown(),
+        })?;
+
+    Ok(GossipHandle {
+        cluster_id,
+        node,
+        url,
+        task: TaskHandle::new(handle, command_tx, executor),
+        events,
+        metrics: gossip_metrics,
+    })
+}
+
+/// Gossip wire messages, which are JSON encoded
+#[derive(Debug, Serialize, Deserialize)]
+enum GossipMessage {
+    Heartbeat {
+        #[serde(with = "crate::serde_util::ulid")]
+        cluster_id: ClusterId,
+        #[serde(with = "url_serde")]
+        gossip_url: url::Url,
+        node: NodeInfo,
+        /// the gossip URLs of the sender's live members
+        #[serde(with = "crate::serde_util::urls")]
+        members: Vec<url::Url>,
+    },
+    Leave {
+        #[serde(with = "crate::serde_util::ulid")]
+        cluster_id: ClusterId,
+        #[serde(with = "crate::serde_util::ulid")]
+        instance_id: InstanceId,
+    },
+}
+
+/// The member table, which is owned by the gossip task
+struct Membership {
+    cluster_id: ClusterId,
+    node: NodeInfo,
+    url: url::Url,
+    failure_timeout: Duration,
+    members: HashMap<InstanceId, MemberState>,
+    /// seed dialers are never closed - see `close_dialer()`
+    seeds: HashSet<url::Url>,
+    dialers: HashMap<url::Url, nng::Dialer>,
+    events: Broadcaster<MembershipEvent>,
+    metrics: GossipMetrics,
+}
+
+struct MemberState {
+    member: Member,
+    last_seen: Instant,
+}
+
+impl Membership {
+    fn send(&self, socket: &nng::Socket, msg: &GossipMessage) -> bool {
+        let bytes = match serde_json::to_vec(msg) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                error!("Failed to encode gossip message: {}", err);
+                return false;
+            }
+        };
+        let mut nng_msg = match nng::Message::with_capacity(bytes.len()) {
+            Ok(nng_msg) => nng_msg,
+            Err(err) => {
+                error!("Failed to create gossip message: {}", err);
+                return false;
+            }
+        };
+        if let Err(err) = nng_msg.push_back(&bytes) {
+            error!("Failed to create gossip message: {}", err);
+            return false;
+        }
+        // Bus0 sends are best effort, i.e., the message is dropped for peers that are not able to
+        // receive it
+        match socket.send(nng_msg) {
+            Ok(_) => true,
+            Err((_msg, err)) => {
+                debug!("Failed to send gossip message: {}", err);
+                false
+            }
+        }
+    }
+
+    fn send_heartbeat(&self, socket: &nng::Socket) {
+        let heartbeat = GossipMessage::Heartbeat {
+            cluster_id: self.cluster_id,
+            gossip_url: self.url.clone(),
+            node: self.node.clone(),
+            members: self
+                .members
+                .values()
+                .map(|state| state.member.gossip_url.clone())
+                .collect(),
+        };
+        if self.send(socket, &heartbeat) {
+            self.metrics.heartbeat_sent_count.inc();
+        }
+    }
+
+    fn handle_msg(&mut self, socket: &nng::Socket, msg: &nng::Message) {
+        let msg = match serde_json::from_slice::<GossipMessage>(&msg[..]) {
+            Ok(msg) => msg,
+            Err(err) => {
+                warn!("Invalid gossip message: {}", err);
+                return;
+            }
+        };
+        match msg {
+            GossipMessage::Heartbeat {
+                cluster_id,
+                gossip_url,
+                node,
+                members,
+            } => {
+                if cluster_id != self.cluster_id || node.instance_id == self.node.instance_id {
+                    return;
+                }
+                self.metrics.heartbeat_recv_count.inc();
+                self.heartbeat(gossip_url, node);
+                for url in members {
+                    self.dial(socket, url);
+                }
+            }
+            GossipMessage::Leave {
+                cluster_id,
+                instance_id,
+            } => {
+                if cluster_id != self.cluster_id {
+                    return;
+                }
+                if let Some(state) = self.members.remove(&instance_id) {
+                    // stop dialing the member because it left the cluster, unless it is a seed
+                    self.close_dialer(&state.member.gossip_url);
+                    self.metrics.member_count.set(self.members.len() as i64);
+                    self.events.broadcast(MembershipEvent::Left(state.member));
+                }
+            }
+        }
+    }
+
+    fn heartbeat(&mut self, gossip_url: url::Url, node: NodeInfo) {
+        let now = Instant::now();
+        let event = match self.members.get_mut(&node.instance_id) {
+            Some(state) => {
+                state.last_seen = now;
+                state.member.last_heartbeat = SystemTime::now();
+                if state.member.node != node || state.member.gossip_url != gossip_url {
+                    state.member.node = node;
+                    state.member.gossip_url = gossip_url;
+                    Some(MembershipEvent::Updated(state.member.clone()))
+                } else {
+                    None
+                }
+            }
+            None => {
+                let member = Member {
+                    node,
+                    gossip_url,
+                    last_heartbeat: SystemTime::now(),
+                };
+                self.members.insert(
+                    member.node.instance_id,
+                    MemberState {
+                        member: member.clone(),
+                        last_seen: now,
+                    },
+                );
+                self.metrics.member_count.set(self.members.len() as i64);
+                Some(MembershipEvent::Joined(member))
+            }
+        };
+        if let Some(event) = event {
+            self.events.broadcast(event);
+        }
+    }
+
+    /// dials members that were learned about via gossip, and are not directly connected
+    fn dial(&mut self, socket: &nng::Socket, url: url::Url) {
+        if url == self.url
+            || self.dialers.contains_key(&url)
+            || self
+                .members
+                .values()
+                .any(|state| state.member.gossip_url == url)
+        {
+            return;
+        }
+        match DialerConfig::new(url.clone()).start_dialer(socket) {
+            Ok(dialer) => {
+                debug!("Gossip({}): dialing {}", self.node.instance_id, url);
+                self.dialers.insert(url, dialer);
+            }
+            Err(err) => warn!("Failed to dial gossip member: {}: {}", url, err),
+        }
+    }
+
+    fn detect_failures(&mut self) {
+        let failure_timeout = self.failure_timeout;
+        let failed: Vec<InstanceId> = self
+            .members
+            .iter()
+            .filter(|(_, state)| state.last_seen.elapsed() > failure_timeout)
+            .map(|(instance_id, _)| *instance_id)
+            .collect();
+        for instance_id in failed {
+            if let Some(state) = self.members.remove(&instance_id) {
+                warn!(
+                    "Gossip({}): member failed: {}",
+                    self.node.instance_id, instance_id
+                );
+                // stop dialing the member, unless it is a seed - if it recovers, then it will be
+                // dialed again when it is learned about via gossip
+                self.close_dialer(&state.member.gossip_url);
+                self.metrics.member_failure_count.inc();
+                self.events.broadcast(MembershipEvent::Failed(state.member));
+            }
+        }
+        self.metrics.member_count.set(self.members.len() as i64);
+    }
+
+    /// closes the member's dialer, unless the member is a seed
+    /// - the seed dialer reconnects when the seed is restarted. Otherwise, a restarted seed would only
+    ///   rejoin if another member dials it, e.g., a 2 node cluster would be permanently partitioned.
+    fn close_dialer(&mut self, url: &url::Url) {
+        if self.seeds.contains(url) {
+            return;
+        }
+        if let Some(dialer) = self.dialers.remove(url) {
+            dialer.close();
+        }
+    }
+
+    fn members(&self) -> Vec<Member> {
+        self.members
+            .values()
+            .map(|state| state.member.clone())
+            .collect()
+    }
+}
+
+/// Cluster member
+#[derive(Debug, Clone)]
+pub struct Member {
+    node: NodeInfo,
+    gossip_url: url::Url,
+    last_heartbeat: SystemTime,
+}
+
+impl Member {
+    /// the info that the member announced
+    pub fn node(&self) -> &NodeInfo {
+        &self.node
+    }
+
+    /// the member's gossip URL
+    pub fn gossip_url(&self) -> &url::Url {
+        &self.gossip_url
+    }
+
+    /// when the last heartbeat was received from the member
+    pub fn last_heartbeat(&self) -> SystemTime {
+        self.last_heartbeat
+    }
+}
+
+/// Cluster membership change events
+#[derive(Debug, Clone)]
+pub enum MembershipEvent {
+    /// a new member has joined the cluster
+    Joined(Member),
+    /// the member's announced info has changed
+    Updated(Member),
+    /// the member has left the cluster
+    Left(Member),
+    /// the member has not sent a heartbeat within the failure timeout
+    Failed(Member),
+}
+
+impl MembershipEvent {
+    /// the member that the event applies to
+    pub fn member(&self) -> &Member {
+        match self {
+            MembershipEvent::Joined(member)
+            | MembershipEvent::Updated(member)
+            | MembershipEvent::Left(member)
+            | MembershipEvent::Failed(member) => member,
+        }
+    }
+}
+
+/// MembershipEvent stream
+pub type MembershipEventStream = mpsc::Receiver<MembershipEvent>;
+
+/// MembershipEvent subscriber channel buffer size
+/// - if a subscriber falls behind, then events are dropped - see
+///   [DROPPED_MEMBERSHIP_EVENT_COUNT_METRIC_ID](constant.DROPPED_MEMBERSHIP_EVENT_COUNT_METRIC_ID.html)
+pub const MEMBERSHIP_EVENT_CHAN_BUF_SIZE: usize = 64;
+
+/// Gossip task commands
+#[derive(Debug)]
+enum GossipCommand {
+    Members(oneshot::Sender<Vec<Member>>),
+    Ping(oneshot::Sender<()>),
+    Stop,
+}
+
+/// Gossip handle, which is used to query cluster membership and to stop the node
+///
+/// ## Stopping the node
+/// - [stop_async()](#method.stop_async) is used to signal the node to leave the cluster and stop
+#[derive(Debug, Clone)]
+pub struct GossipHandle {
+    cluster_id: ClusterId,
+    node: NodeInfo,
+    url: url::Url,
+    task: TaskHandle<GossipCommand>,
+    events: Broadcaster<MembershipEvent>,
+    metrics: GossipMetrics,
+}
+
+impl GossipHandle {
+    /// ClusterId
+    pub fn cluster_id(&self) -> ClusterId {
+        self.cluster_id
+    }
+
+    /// the info that this node announces
+    pub fn node(&self) -> &NodeInfo {
+        &self.node
+    }
+
+    /// the node's gossip URL
+    pub fn url(&self) -> &url::Url {
+        &self.url
+    }
+
+    /// returns true if the node has been signalled to stop
+    pub fn stop_signalled(&self) -> bool {
+        self.task.stop_signalled()
+    }
+
+    /// Returns GossipMetrics
+    pub fn metrics(&self) -> &GossipMetrics {
+        &self.metrics
+    }
+
+    /// Returns the live cluster members, excluding this node
+    pub fn members(&self) -> impl Future<Output = Result<Vec<Member>, GossipError>> {
+        let command_channel = self.task.command_channel();
+        async move {
+            let mut command_channel = command_channel.ok_or(GossipError::GossipStopped)?;
+            let (reply_chan, reply) = oneshot::channel();
+            await!(command_channel.send(GossipCommand::Members(reply_chan)))
+                .map_err(|_| GossipError::GossipStopped)?;
+            await!(reply).map_err(|_| GossipError::GossipStopped)
+        }
+    }
+
+    /// Subscribes to membership changes
+    /// - the stream ends when the node is stopped
+    /// - the stream is bounded: if the subscriber falls behind, then events are dropped - see
+    ///   [MEMBERSHIP_EVENT_CHAN_BUF_SIZE](constant.MEMBERSHIP_EVENT_CHAN_BUF_SIZE.html)
+    pub fn membership_events(&self) -> MembershipEventStream {
+        self.events.subscribe()
+    }
+
+    /// pings the gossip task to check if it is still alive
+    /// - returns true if the gossip task responds to the ping
+    pub fn ping(&self) -> bool {
+        self.task.ping(GossipCommand::Ping)
+    }
+
+    /// signals the node to leave the cluster and shutdown async
+    pub fn stop_async(&mut self) -> Result<bool, TaskHandleError> {
+        self.task.stop_async(GossipCommand::Stop)
+    }
+
+    /// Block the current thread until the gossip task has shutdown
+    ///
+    /// ## Notes
+    /// The node must be signaled to stop in order to shutdown.
+    pub fn await_shutdown(mut self) {
+        self.task.await_shutdown()
+    }
+}
+
+/// Gossip related errors
+#[derive(Debug, Fail, Clone)]
+pub enum GossipError {
+    /// The gossip task has been stopped
+    #[fail(display = "The gossip task has been stopped")]
+    GossipStopped,
+}
+
+/// Errors that could happen while trying to spawn the gossip task
+#[derive(Debug, Fail)]
+pub enum SpawnError {
+    /// Failed to create Socket
+    #[fail(display = "Failed to create Socket: {}", _0)]
+    SocketCreateFailure(#[cause] nng::Error),
+    /// Failed to create Aio
+    #[fail(display = "Failed to create Aio with callback: {}", _0)]
+    AioCreateWithCallbackFailure(#[cause] nng::Error),
+    /// An error that occurred during spawning.
+    #[fail(
+        display = "Spawning Future failed: executor shutdown = {}",
+        is_executor_shutdown
+    )]
+    ExecutorSpawnError {
+        /// whether spawning failed because the executor is shut down
+        is_executor_shutdown: bool,
+    },
+    /// Failed to start the listener
+    #[fail(display = "{}", _0)]
+    ListenerStartFailure(#[cause] ListenerConfigError),
+    /// Failed to dial a seed peer
+    #[fail(display = "{}", _0)]
+    DialerStartFailure(#[cause] DialerConfigError),
+    /// Failed to apply SocketConfig options
+    #[fail(display = "{}", _0)]
+    SocketConfigApplyFailed(#[cause] SocketConfigError),
+}
+
+/// Gossip metrics
+#[derive(Clone)]
+pub struct GossipMetrics {
+    active_conn_count: prometheus::IntGauge,
+    member_count: prometheus::IntGauge,
+    member_failure_count: prometheus::IntCounter,
+    heartbeat_sent_count: prometheus::IntCounter,
+    heartbeat_recv_count: prometheus::IntCounter,
+    dropped_event_count: prometheus::IntCounter,
+}
+
+impl GossipMetrics {
+    fn new(cluster_id: ClusterId, instance_id: InstanceId) -> Self {
+        let cluster_id_label = cluster_id.to_string();
+        let instance_id_label = instance_id.to_string();
+        let labels = &[cluster_id_label.as_str(), instance_id_label.as_str()];
+        Self {
+            active_conn_count: ACTIVE_CONN_COUNT.with_label_values(labels),
+            member_count: MEMBER_COUNT.with_label_values(labels),
+            member_failure_count: MEMBER_FAILURE_COUNT.with_label_values(labels),
+            heartbeat_sent_count: HEARTBEAT_SENT_COUNT.with_label_values(labels),
+            heartbeat_recv_count: HEARTBEAT_RECV_COUNT.with_label_values(labels),
+            dropped_event_count: DROPPED_MEMBERSHIP_EVENT_COUNT.with_label_values(labels),
+        }
+    }
+
+    /// Active number of socket connections
+    pub fn active_conn_count(&self) -> usize {
+        self.active_conn_count.get() as usize
+    }
+
+    /// Number of live members, excluding this node
+    pub fn member_count(&self) -> usize {
+        self.member_count.get() as usize
+    }
+
+    /// Total number of member failures
+    pub fn member_failure_count(&self) -> usize {
+        self.member_failure_count.get() as usize
+    }
+
+    /// Total number of heartbeats sent
+    pub fn heartbeat_sent_count(&self) -> usize {
+        self.heartbeat_sent_count.get() as usize
+    }
+
+    /// Total number of heartbeats received
+    pub fn heartbeat_recv_count(&self) -> usize {
+        self.heartbeat_recv_count.get() as usize
+    }
+
+    /// Total number of membership events that were dropped because the subscriber's channel was full
+    pub fn dropped_event_count(&self) -> usize {
+        self.dropped_event_count.get() as usize
+    }
+}
+
+impl fmt::Debug for GossipMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "GossipMetrics(active_conn_count = {}, member_count = {}, member_failure_count = {}, heartbeat_sent_count = {}, heartbeat_recv_count = {}, dropped_event_count = {})",
+            self.active_conn_count.get(),
+            self.member_count.get(),
+            self.member_failure_count.get(),
+            self.heartbeat_sent_count.get(),
+            self.heartbeat_recv_count.get(),
+            self.dropped_event_count.get()
+        )
+    }
+}
+
+#[allow(warnings)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configure_logging;
+    use oysterpack_trust::concurrent::execution::global_executor;
+    use oysterpack_uid::ULID;
+    use std::thread;
+
+    const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(20);
+    const FAILURE_TIMEOUT: Duration = Duration::from_millis(200);
+
+    fn spawn_node(cluster_id: ClusterId, seeds: &[url::Url]) -> GossipHandle {
+        let url = url::Url::parse(&format!("inproc://{}", ULID::generate())).unwrap();
+        spawn_node_at(cluster_id, url, seeds)
+    }
+
+    fn spawn_node_at(cluster_id: ClusterId, url: url::Url, seeds: &[url::Url]) -> GossipHandle {
+        let config = seeds.iter().cloned().fold(
+            GossipConfig::new(cluster_id, ListenerConfig::new(url))
+                .set_heartbeat_interval(HEARTBEAT_INTERVAL)
+                .set_failure_timeout(FAILURE_TIMEOUT),
+            |config, seed| config.add_seed(seed),
+        );
+        let node = NodeInfo::new(
+            InstanceId::generate(),
+            BuildInfo::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+        );
+        super::spawn(None, config, node, global_executor()).unwrap()
+    }
+
+    fn await_member_count(node: &GossipHandle, count: usize) -> Vec<Member> {
+        let mut executor = global_executor();
+        for _ in 0..100 {
+            let members = executor.run(node.members()).unwrap();
+            if members.len() == count {
+                return members;
+            }
+            thread::sleep(HEARTBEAT_INTERVAL);
+        }
+        panic!(
+            "member count did not reach {}: {:?}",
+            count,
+            executor.run(node.members())
+        );
+    }
+
+    #[test]
+    fn gossip_membership() {
+        configure_logging();
+        let cluster_id = ClusterId::generate();
+
+        // GIVEN: node A is running
+        let mut node_a = spawn_node(cluster_id, &[]);
+        let mut events = node_a.membership_events();
+        // AND: node B uses A as its seed
+        let mut node_b = spawn_node(cluster_id, &[node_a.url().clone()]);
+        // AND: node C uses B as its seed
+        let mut node_c = spawn_node(cluster_id, &[node_b.url().clone()]);
+
+        // THEN: all nodes discover each other via gossip
+        for node in &[&node_a, &node_b, &node_c] {
+            await_member_count(node, 2);
+        }
+        let members = await_member_count(&node_a, 2);
+        assert!(members
+            .iter()
+            .any(|member| member.node().instance_id() == node_c.node().instance_id()));
+        assert_eq!(node_a.metrics().member_count(), 2);
+        assert!(node_a.metrics().heartbeat_recv_count() > 0);
+        assert!(node_a.metrics().heartbeat_sent_count() > 0);
+        // AND: node A is notified that B and C joined
+        let mut joined = Vec::new();
+        while let Ok(Some(event)) = events.try_next() {
+            if let MembershipEvent::Joined(member) = event {
+                joined.push(member.node().instance_id());
+            }
+        }
+        assert!(joined.contains(&node_b.node().instance_id()));
+        assert!(joined.contains(&node_c.node().instance_id()));
+
+        // WHEN: node C is stopped
+        let node_c_instance_id = node_c.node().instance_id();
+        node_c.stop_async().unwrap();
+        node_c.await_shutdown();
+        // THEN: node C leaves the cluster
+        await_member_count(&node_a, 1);
+        await_member_count(&node_b, 1);
+        let mut left = false;
+        while let Ok(Some(event)) = events.try_next() {
+            if let MembershipEvent::Left(member) = event {
+                left = member.node().instance_id() == node_c_instance_id;
+            }
+        }
+        assert!(left);
+        // AND: leaving is not counted as a failure
+        assert_eq!(node_a.metrics().member_failure_count(), 0);
+
+        node_a.stop_async().unwrap();
+        node_b.stop_async().unwrap();
+        node_a.await_shutdown();
+        node_b.await_shutdown();
+    }
+
+    fn await_active_conn_count(node: &GossipHandle, count: usize) {
+        for _ in 0..100 {
+            if node.metrics().active_conn_count() == count {
+                return;
+            }
+            thread::sleep(HEARTBEAT_INTERVAL);
+        }
+        panic!(
+            "active connection count did not reach {}: {:?}",
+            count,
+            node.metrics()
+        );
+    }
+
+    #[test]
+    fn gossip_failure_detection() {
+        configure_logging();
+        let cluster_id = ClusterId::generate();
+
+        // GIVEN: a peer is listening
+        let peer_url = url::Url::parse(&format!("inproc://{}", ULID::generate())).unwrap();
+        let peer = nng::Socket::new(nng::Protocol::Bus0).unwrap();
+        peer.listen(peer_url.as_str()).unwrap();
+        // AND: node A uses the peer as its seed
+        let mut node_a = spawn_node(cluster_id, &[peer_url.clone()]);
+        let mut events = node_a.membership_events();
+        await_active_conn_count(&node_a, 1);
+
+        // WHEN: the peer sends a single heartbeat, and then goes silent
+        let peer_instance_id = InstanceId::generate();
+        let heartbeat = GossipMessage::Heartbeat {
+            cluster_id,
+            gossip_url: peer_url,
+            node: NodeInfo::new(peer_instance_id, BuildInfo::new("peer", "0.1.0")),
+            members: vec![],
+        };
+        let mut msg = nng::Message::new().unwrap();
+        msg.push_back(&serde_json::to_vec(&heartbeat).unwrap())
+            .unwrap();
+        peer.send(msg).unwrap();
+
+        // THEN: the peer joins
+        await_member_count(&node_a, 1);
+        // AND: the peer is declared failed after the failure timeout
+        await_member_count(&node_a, 0);
+        assert_eq!(node_a.metrics().member_failure_count(), 1);
+        let events: Vec<_> = events.take(2).collect::<Vec<_>>();
+        let events = global_executor().run(events);
+        match &events[..] {
+            [MembershipEvent::Joined(joined), MembershipEvent::Failed(failed)] => {
+                assert_eq!(joined.node().instance_id(), peer_instance_id);
+                assert_eq!(failed.node().instance_id(), peer_instance_id);
+            }
+            events => panic!("unexpected events: {:?}", events),
+        }
+        // AND: node A keeps the connection to the failed peer because the peer is a seed
+        assert_eq!(node_a.metrics().active_conn_count(), 1);
+
+        peer.close();
+        node_a.stop_async().unwrap();
+        node_a.await_shutdown();
+    }
+
+    #[test]
+    fn gossip_seed_restart() {
+        configure_logging();
+        let cluster_id = ClusterId::generate();
+
+        // GIVEN: node B uses node A as its seed
+        let seed_url = url::Url::parse(&format!("inproc://{}", ULID::generate())).unwrap();
+        let mut node_a = spawn_node_at(cluster_id, seed_url.clone(), &[]);
+        let mut node_b = spawn_node(cluster_id, &[seed_url.clone()]);
+        // AND: nodes A and B have discovered each other
+        await_member_count(&node_a, 1);
+        await_member_count(&node_b, 1);
+
+        // WHEN: node A is stopped
+        node_a.stop_async().unwrap();
+        node_a.await_shutdown();
+        // THEN: node A leaves the cluster
+        await_member_count(&node_b, 0);
+
+        // WHEN: node A is restarted on the same URL
+        let mut node_a = spawn_node_at(cluster_id, seed_url.clone(), &[]);
+        // THEN: node B reconnects to the seed, and the nodes rediscover each other
+        let members = await_member_count(&node_a, 1);
+        assert_eq!(members[0].node().instance_id(), node_b.node().instance_id());
+        let members = await_member_count(&node_b, 1);
+        assert_eq!(members[0].node().instance_id(), node_a.node().instance_id());
+
+        node_a.stop_async().unwrap();
+        node_b.stop_async().unwrap();
+        node_a.await_shutdown();
+        node_b.await_shutdown();
+    }
+
+    #[test]
+    fn gossip_stops_when_all_handles_are_dropped() {
+        configure_logging();
+        let cluster_id = ClusterId::generate();
+
+        // GIVEN: nodes A and B have discovered each other
+        let node_a = spawn_node(cluster_id, &[]);
+        let mut node_b = spawn_node(cluster_id, &[node_a.url().clone()]);
+        let mut events = node_b.membership_events();
+        await_member_count(&node_a, 1);
+        await_member_count(&node_b, 1);
+        let node_a_instance_id = node_a.node().instance_id();
+
+        // WHEN: all of node A's handles are dropped
+        drop(node_a);
+        // THEN: node A's gossip task stops, and node A leaves the cluster
+        await_member_count(&node_b, 0);
+        let mut left = false;
+        while let Ok(Some(event)) = events.try_next() {
+            if let MembershipEvent::Left(member) = event {
+                left = member.node().instance_id() == node_a_instance_id;
+            }
+        }
+        assert!(left);
+        assert_eq!(node_b.metrics().member_failure_count(), 0);
+
+        node_b.stop_async().unwrap();
+        node_b.await_shutdown();
+    }
+}
@@ -32,8 +32,26 @@
 #[macro_use]
 extern crate pretty_assertions;
 
+#[cfg(feature = "json")]
+pub mod bootstrap;
+mod broadcast;
 pub mod config;
+#[cfg(feature = "json")]
+pub mod gossip;
+pub mod message;
+pub mod pipe;
+pub mod pipeline;
+pub mod pubsub;
 pub mod reqrep;
+mod serde_util;
+pub mod survey;
+pub mod task;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod timer;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod ws;
 
 #[cfg(test)]
 fn log_config() -> oysterpack_log::LogConfig {
@@ -49,3 +67,31 @@ fn log_config() -> oysterpack_log::LogConfig {
 fn configure_logging() {
     oysterpack_log::init(log_config(), oysterpack_log::StderrLogger);
 }
+
+/// Polls the condition until it holds, for up to 5 secs
+/// - messages are processed asynchronously, i.e., metrics may lag behind the test
+#[cfg(test)]
+fn eventually<F: Fn() -> bool>(condition: F) -> bool {
+    (0..500).any(|_| {
+        if condition() {
+            return true;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        false
+    })
+}
+
+/// Returns the URL with the port that the listener is bound to
+/// - tests listen on port 0, which lets the OS pick a free port
+#[cfg(test)]
+fn bound_url(listener: &nng::Listener, url: &url::Url) -> url::Url {
+    use nng::options::Options;
+    let port = match listener.get_opt::<nng::options::LocalAddr>().unwrap() {
+        nng::SocketAddr::Inet(addr) => addr.port(),
+        nng::SocketAddr::Inet6(addr) => addr.port(),
+        addr => panic!("listener is not bound to an IP address: {:?}", addr),
+    };
+    let mut url = url.clone();
+    url.set_port(Some(port)).unwrap();
+    url
+}
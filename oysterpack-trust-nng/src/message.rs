@@ -0,0 +1,409 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Provides support for reusing nng message buffers.
+//! - [MessagePool](struct.MessagePool.html) pools `nng::Message`(s) by size class
+//! - [MessageWriter](struct.MessageWriter.html) is used to serialize directly into the message body,
+//!   i.e., without an intermediate buffer
+//!
+//! ## Message Ownership
+//! A message is released back to the pool by whoever owns it last. Messages that are sent are consumed
+//! by nng, and messages that are received are allocated by nng. Thus, the
+//! [global pool](fn.global_pool.html) is used as follows:
+//! - the [codecs](../reqrep/codec/index.html) encode requests and replies into pooled messages, and
+//!   release the messages that they decode
+//! - the client Aio event loop releases requests that fail to send, and replies whose requester has
+//!   gone away, e.g., the losing reply of a hedged request
+//! - the server Aio event loop releases replies that fail to send. Request messages are owned by the
+//!   ReqRep service.
+//! - raw clients own the reply messages, i.e., they should release replies once they are done with them
+//!
+//! ## Size Classes
+//! A message is acquired from the smallest size class that fits the requested length, and it is
+//! released to the smallest size class that fits its length. nng does not expose the message buffer
+//! capacity. Thus, before the message is pooled, its buffer is grown to the size class capacity,
+//! which does not reallocate messages that were acquired from the size class. Messages that are
+//! released are guaranteed to have at least the size class capacity.
+//! - messages that are larger than the largest size class are freed, i.e., the pool never retains
+//!   buffers that are larger than the largest size class
+//! - once a size class is full, released messages are freed
+
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, io,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+lazy_static! {
+    static ref GLOBAL_POOL: MessagePool = MessagePool::new(MessagePoolConfig::default());
+}
+
+/// Returns the global MessagePool, which is configured using the default MessagePoolConfig
+pub fn global_pool() -> MessagePool {
+    GLOBAL_POOL.clone()
+}
+
+/// Default size classes: 256 B, 1 KB, 4 KB, 16 KB, 64 KB
+pub const DEFAULT_SIZE_CLASSES: &[usize] = &[256, 1024, 4 * 1024, 16 * 1024, 64 * 1024];
+
+/// Default max number of messages that are pooled per size class
+pub const DEFAULT_MAX_POOLED_PER_CLASS: usize = 256;
+
+/// MessagePool config
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct MessagePoolConfig {
+    size_classes: Vec<usize>,
+    max_pooled_per_class: usize,
+}
+
+impl Default for MessagePoolConfig {
+    fn default() -> MessagePoolConfig {
+        MessagePoolConfig {
+            size_classes: DEFAULT_SIZE_CLASSES.to_vec(),
+            max_pooled_per_class: DEFAULT_MAX_POOLED_PER_CLASS,
+        }
+    }
+}
+
+impl MessagePoolConfig {
+    /// Message size classes in bytes
+    pub fn size_classes(&self) -> &[usize] {
+        &self.size_classes
+    }
+
+    /// Max number of messages that are pooled per size class
+    pub fn max_pooled_per_class(&self) -> usize {
+        self.max_pooled_per_class
+    }
+
+    /// Sets the size classes
+    /// - zero sizes and duplicates are ignored
+    pub fn set_size_classes(mut self, size_classes: Vec<usize>) -> Self {
+        self.size_classes = size_classes;
+        self
+    }
+
+    /// Sets the max number of messages that are pooled per size class
+    pub fn set_max_pooled_per_class(mut self, max_pooled_per_class: usize) -> Self {
+        self.max_pooled_per_class = max_pooled_per_class;
+        self
+    }
+}
+
+/// nng message pool
+/// - cloning is cheap, i.e., clones share the same pool
+#[derive(Clone)]
+pub struct MessagePool {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    size_classes: Vec<SizeClass>,
+    max_pooled_per_class: usize,
+    /// used to grow released messages to their size class capacity - sized to the largest size class
+    zeros: Vec<u8>,
+    hit_count: AtomicUsize,
+    miss_count: AtomicUsize,
+    release_count: AtomicUsize,
+    discard_count: AtomicUsize,
+}
+
+struct SizeClass {
+    size: usize,
+    messages: Mutex<Vec<nng::Message>>,
+}
+
+impl MessagePool {
+    /// constructor
+    pub fn new(config: MessagePoolConfig) -> MessagePool {
+        let mut sizes = config.size_classes;
+        sizes.retain(|size| *size > 0);
+        sizes.sort();
+        sizes.dedup();
+        let zeros = vec![0; sizes.last().cloned().unwrap_or(0)];
+        MessagePool {
+            inner: Arc::new(Inner {
+                size_classes: sizes
+                    .into_iter()
+                    .map(|size| SizeClass {
+                        size,
+                        messages: Mutex::new(Vec::new()),
+                    })
+                    .collect(),
+                max_pooled_per_class: config.max_pooled_per_class,
+                zeros,
+                hit_count: AtomicUsize::new(0),
+                miss_count: AtomicUsize::new(0),
+                release_count: AtomicUsize::new(0),
+                discard_count: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Returns an empty message with a buffer capacity of at least `len` bytes
+    /// - if the pool has no message available for the size class, then a new message is allocated
+    pub fn acquire(&self, len: usize) -> Result<nng::Message, nng::Error> {
+        match self.inner.size_classes.iter().find(|class| class.size >= len) {
+            Some(class) => {
+                if let Some(msg) = class.messages.lock().pop() {
+                    self.inner.hit_count.fetch_add(1, Ordering::Relaxed);
+                    return Ok(msg);
+                }
+                self.inner.miss_count.fetch_add(1, Ordering::Relaxed);
+                nng::Message::with_capacity(class.size)
+            }
+            None => {
+                self.inner.miss_count.fetch_add(1, Ordering::Relaxed);
+                nng::Message::with_capacity(len)
+            }
+        }
+    }
+
+    /// Returns a message that contains the specified bytes
+    pub fn acquire_with_body(&self, body: &[u8]) -> Result<nng::Message, nng::Error> {
+        let mut msg = self.acquire(body.len())?;
+        msg.push_back(body)?;
+        Ok(msg)
+    }
+
+    /// Returns the message to the pool
+    /// - the message header and body are cleared
+    /// - messages that are larger than the largest size class are freed
+    pub fn release(&self, mut msg: nng::Message) {
+        self.inner.release_count.fetch_add(1, Ordering::Relaxed);
+        let len = msg.len();
+        if let Some(class) = self.inner.size_classes.iter().find(|class| class.size >= len) {
+            let max_pooled_per_class = self.inner.max_pooled_per_class;
+            // the capacity is reserved outside of the lock because it may reallocate the buffer
+            if class.messages.lock().len() < max_pooled_per_class
+                && self.reserve(&mut msg, class.size).is_ok()
+            {
+                let mut messages = class.messages.lock();
+                if messages.len() < max_pooled_per_class {
+                    messages.push(msg);
+                    return;
+                }
+            }
+        }
+        self.inner.discard_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// clears the message, and ensures that its buffer capacity is at least `capacity` bytes
+    /// - the body is grown to the capacity, which only reallocates the buffer if it is too small
+    fn reserve(&self, msg: &mut nng::Message, capacity: usize) -> Result<(), nng::Error> {
+        let len = msg.len();
+        if len < capacity {
+            msg.push_back(&self.inner.zeros[..capacity - len])?;
+        }
+        msg.header_mut().clear();
+        msg.clear();
+        Ok(())
+    }
+
+    /// Number of messages that are currently pooled
+    pub fn pooled_count(&self) -> usize {
+        self.inner
+            .size_classes
+            .iter()
+            .map(|class| class.messages.lock().len())
+            .sum()
+    }
+
+    /// Returns the pool stats
+    pub fn stats(&self) -> MessagePoolStats {
+        MessagePoolStats {
+            hit_count: self.inner.hit_count.load(Ordering::Relaxed),
+            miss_count: self.inner.miss_count.load(Ordering::Relaxed),
+            release_count: self.inner.release_count.load(Ordering::Relaxed),
+            discard_count: self.inner.discard_count.load(Ordering::Relaxed),
+            pooled_count: self.pooled_count(),
+        }
+    }
+}
+
+impl fmt::Debug for MessagePool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let size_classes: Vec<usize> = self
+            .inner
+            .size_classes
+            .iter()
+            .map(|class| class.size)
+            .collect();
+        write!(
+            f,
+            "MessagePool(size_classes = {:?}, {:?})",
+            size_classes,
+            self.stats()
+        )
+    }
+}
+
+/// MessagePool stats
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct MessagePoolStats {
+    hit_count: usize,
+    miss_count: usize,
+    release_count: usize,
+    discard_count: usize,
+    pooled_count: usize,
+}
+
+impl MessagePoolStats {
+    /// Number of acquired messages that were taken from the pool
+    pub fn hit_count(&self) -> usize {
+        self.hit_count
+    }
+
+    /// Number of acquired messages that had to be allocated
+    pub fn miss_count(&self) -> usize {
+        self.miss_count
+    }
+
+    /// Number of messages that were released
+    pub fn release_count(&self) -> usize {
+        self.release_count
+    }
+
+    /// Number of released messages that were freed instead of being pooled
+    pub fn discard_count(&self) -> usize {
+        self.discard_count
+    }
+
+    /// Number of messages that are pooled
+    pub fn pooled_count(&self) -> usize {
+        self.pooled_count
+    }
+}
+
+/// Writes directly to the message body, which enables serializers to encode directly into the
+/// message, i.e., without an intermediate buffer.
+pub struct MessageWriter<'a> {
+    msg: &'a mut nng::Message,
+}
+
+impl<'a> fmt::Debug for MessageWriter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MessageWriter(len = {})", self.msg.len())
+    }
+}
+
+impl<'a> MessageWriter<'a> {
+    /// constructor
+    pub fn new(msg: &'a mut nng::Message) -> MessageWriter<'a> {
+        MessageWriter { msg }
+    }
+}
+
+impl<'a> io::Write for MessageWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.msg
+            .push_back(buf)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[allow(warnings)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configure_logging;
+    use oysterpack_log::*;
+    use std::io::Write;
+
+    #[test]
+    fn message_pool() {
+        configure_logging();
+
+        // GIVEN: a pool with 2 size classes, that pools at most 2 messages per class
+        let pool = MessagePool::new(
+            MessagePoolConfig::default()
+                .set_size_classes(vec![1024, 0, 64, 64])
+                .set_max_pooled_per_class(2),
+        );
+
+        // WHEN: a message is acquired from an empty pool
+        let msg = pool.acquire(10).unwrap();
+        // THEN: the message is allocated
+        assert_eq!(msg.len(), 0);
+        assert_eq!(pool.stats().miss_count(), 1);
+
+        // WHEN: a message that was acquired from the 64 B size class is released, while it only
+        // contains a few bytes
+        let mut msg = pool.acquire(10).unwrap();
+        msg.push_back(&[1; 10]).unwrap();
+        pool.release(msg);
+        // THEN: it is returned to its size class
+        assert_eq!(pool.pooled_count(), 1);
+        // AND: it is reused for the next acquire that fits the size class
+        let msg = pool.acquire(64).unwrap();
+        assert_eq!(msg.len(), 0);
+        assert_eq!(pool.stats().hit_count(), 1);
+        assert_eq!(pool.pooled_count(), 0);
+
+        // WHEN: a message that was not acquired from the pool, and that has a header, is released
+        let mut msg = nng::Message::new().unwrap();
+        msg.header_mut().push_back(&[1; 4]).unwrap();
+        msg.push_back(&[1; 100]).unwrap();
+        pool.release(msg);
+        // THEN: it is grown to the smallest size class that fits it, and pooled
+        let msg = pool.acquire(1024).unwrap();
+        assert_eq!(msg.len(), 0);
+        assert_eq!(pool.stats().hit_count(), 2);
+        // AND: its header is cleared
+        assert_eq!(msg.header().len(), 0);
+
+        // WHEN: a message that is larger than the largest size class is released
+        let msg = pool.acquire_with_body(&[1; 2048]).unwrap();
+        pool.release(msg);
+        // THEN: it is freed
+        assert_eq!(pool.pooled_count(), 0);
+        assert_eq!(pool.stats().discard_count(), 1);
+
+        // WHEN: more messages are released than the size class can hold
+        for _ in 0..3 {
+            let msg = pool.acquire_with_body(&[1; 1000]).unwrap();
+            pool.release(msg);
+        }
+        // THEN: the size class is capped
+        assert_eq!(pool.pooled_count(), 2);
+        let stats = pool.stats();
+        info!("{:?}", pool);
+        assert_eq!(stats.discard_count(), 2);
+        assert_eq!(stats.release_count(), 6);
+    }
+
+    #[test]
+    fn message_writer() {
+        let pool = global_pool();
+        let mut msg = pool.acquire(0).unwrap();
+        {
+            let mut writer = MessageWriter::new(&mut msg);
+            writer.write_all(b"hello ").unwrap();
+            serde_json::to_writer(&mut writer, "world").unwrap();
+        }
+        assert_eq!(&msg[..], b"hello \"world\"");
+    }
+}
@@ -0,0 +1,168 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Provides support for inspecting nng pipes, i.e., socket connections.
+//! - [PipeEvent](struct.PipeEvent.html) streams can be used for audit logging
+//! - [ConnectionFilter](trait.ConnectionFilter.html) is used to allow or deny connections, e.g.,
+//!   IP based access control
+//!
+//! ## Metrics
+//! - total number of pipe events that were dropped because the subscriber's channel was full -
+//!   [DROPPED_PIPE_EVENT_COUNT_METRIC_ID](constant.DROPPED_PIPE_EVENT_COUNT_METRIC_ID.html)
+
+use crate::broadcast::Broadcaster;
+use futures::channel::mpsc;
+use lazy_static::lazy_static;
+use nng::options::Options;
+use oysterpack_trust::metrics;
+use std::{net::IpAddr, time::SystemTime};
+
+lazy_static! {
+
+    /// the metric is incremented when a pipe event is dropped because the subscriber's channel is full
+    static ref DROPPED_PIPE_EVENT_COUNT: prometheus::IntCounter = metrics::registry().register_int_counter(
+        DROPPED_PIPE_EVENT_COUNT_METRIC_ID,
+        "Total number of pipe events that were dropped because the subscriber's channel was full",
+        None
+    ).unwrap();
+
+}
+
+/// IntCounter MetricId which is used to track the total number of dropped pipe events: `M01M57MXM176CW57371630M325R`
+pub const DROPPED_PIPE_EVENT_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166796344584659745212514812177778872);
+
+/// PipeEvent subscriber channel buffer size
+/// - if a subscriber falls behind, then events are dropped
+pub const PIPE_EVENT_CHAN_BUF_SIZE: usize = 64;
+
+/// Pipe, i.e., connection, info
+#[derive(Debug, Clone)]
+pub struct PipeInfo {
+    pipe_id: i32,
+    remote_addr: Option<nng::SocketAddr>,
+}
+
+impl PipeInfo {
+    pub(crate) fn new(pipe: nng::Pipe) -> PipeInfo {
+        PipeInfo {
+            pipe_id: pipe.id(),
+            remote_addr: pipe.get_opt::<nng::options::RemAddr>().ok(),
+        }
+    }
+
+    /// nng pipe ID
+    pub fn pipe_id(&self) -> i32 {
+        self.pipe_id
+    }
+
+    /// the peer's address, if available
+    pub fn remote_addr(&self) -> Option<&nng::SocketAddr> {
+        self.remote_addr.as_ref()
+    }
+
+    /// the peer's IP address - only available for TCP based transports
+    pub fn remote_ip(&self) -> Option<IpAddr> {
+        match self.remote_addr {
+            Some(nng::SocketAddr::Inet(addr)) => Some(IpAddr::V4(*addr.ip())),
+            Some(nng::SocketAddr::Inet6(addr)) => Some(IpAddr::V6(*addr.ip())),
+            _ => None,
+        }
+    }
+}
+
+/// Pipe event kind
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum PipeEventKind {
+    /// the pipe was added to the socket
+    Added,
+    /// the pipe was removed from the socket
+    Removed,
+    /// the pipe was rejected by the [ConnectionFilter](trait.ConnectionFilter.html), and closed
+    /// before being added to the socket
+    Rejected,
+}
+
+/// Pipe event
+#[derive(Debug, Clone)]
+pub struct PipeEvent {
+    kind: PipeEventKind,
+    pipe: PipeInfo,
+    timestamp: SystemTime,
+}
+
+impl PipeEvent {
+    pub(crate) fn new(kind: PipeEventKind, pipe: PipeInfo) -> PipeEvent {
+        PipeEvent {
+            kind,
+            pipe,
+            timestamp: SystemTime::now(),
+        }
+    }
+
+    /// event kind
+    pub fn kind(&self) -> PipeEventKind {
+        self.kind
+    }
+
+    /// pipe info
+    pub fn pipe(&self) -> &PipeInfo {
+        &self.pipe
+    }
+
+    /// when the event occurred
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+}
+
+/// PipeEvent stream
+pub type PipeEventStream = mpsc::Receiver<PipeEvent>;
+
+/// Used to allow or deny connections. The filter is applied before the pipe is added to the socket.
+///
+/// ## Notes
+/// - the filter is invoked from the nng pipe notify callback, thus it should return fast and must
+///   not block
+/// - any `Fn(&PipeInfo) -> bool` closure is a ConnectionFilter
+pub trait ConnectionFilter: Send + Sync + 'static {
+    /// returns true if the connection is allowed
+    fn allow(&self, pipe: &PipeInfo) -> bool;
+}
+
+impl<F> ConnectionFilter for F
+where
+    F: Fn(&PipeInfo) -> bool + Send + Sync + 'static,
+{
+    fn allow(&self, pipe: &PipeInfo) -> bool {
+        self(pipe)
+    }
+}
+
+/// Broadcasts PipeEvent(s) to all subscribers
+/// - events are broadcast from the nng pipe notify callback, which must not block. Thus, subscriber
+///   channels are bounded, and events are dropped for subscribers whose channel is full.
+pub(crate) type PipeEventBroadcaster = Broadcaster<PipeEvent>;
+
+impl Default for Broadcaster<PipeEvent> {
+    fn default() -> Self {
+        Broadcaster::new(
+            "PipeEvent",
+            PIPE_EVENT_CHAN_BUF_SIZE,
+            DROPPED_PIPE_EVENT_COUNT.clone(),
+        )
+    }
+}
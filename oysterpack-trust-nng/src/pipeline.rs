@@ -0,0 +1,38 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Provides support for the pipeline messaging protocol, which is used for work distribution.
+//! - work is pushed via a [Pusher](pusher/struct.Pusher.html)
+//!   - pushing applies backpressure, i.e., the push completes when the message has been queued to a
+//!     pull worker's pipe
+//! - pull workers feed messages into a [ReqRep](https://docs.rs/oysterpack_trust/latest/oysterpack_trust/concurrent/messaging/reqrep/struct.ReqRep.html)
+//!   Processor - see [puller::spawn()](puller/fn.spawn.html)
+//! - messages are load balanced across the connected pull workers
+
+pub mod puller;
+pub mod pusher;
+
+use oysterpack_trust::metrics;
+use oysterpack_uid::macros::ulid;
+use serde::{Deserialize, Serialize};
+
+/// Each pipeline is uniquely identified by an ID, which is used for tracking purposes, e.g., metrics
+#[ulid]
+pub struct PipelineId(pub u128);
+
+/// Metric LabelId which is used to store a PipelineId: `L01M57F9BYNH68STD7DC9V2ZB72`
+pub const PIPELINE_ID_LABEL_ID: metrics::LabelId =
+    metrics::LabelId(2166789204047789000515944519826386146);
@@ -0,0 +1,439 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Provides nng Pull0 pipeline workers.
+//!
+//! ## Design
+//! The puller follows the [reqrep server](../../reqrep/server/index.html) design. The puller dials
+//! into the pusher using a Pull0 socket. N number of worker tasks are spawned, based on
+//! [DialerConfig::parallelism()](../../reqrep/client/struct.DialerConfig.html#method.parallelism).
+//! Each worker has its own Aio, i.e., each worker has its own pending receive operation on the socket.
+//! The worker relays the received message to the backend ReqRep Processor, and only when the message
+//! has been processed does the worker receive the next message. Thus, the number of messages that are
+//! processed concurrently is bounded by the number of workers.
+//!
+//! <pre>
+//! pusher ---> Socket --> Aio Callback --> worker ---> Processor
+//! </pre>
+//!
+//! There is one additional controller task that is spawned. The puller's lifetime is coupled with the
+//! controller's lifetime. The controller responds to ping requests, and listens for a signal to stop.
+//! When signalled to stop, the controller closes the nng Dialer and Socket, which will cause the
+//! workers to exit.
+//!
+//! ## Config
+//! - [SocketConfig](../../config/struct.SocketConfig.html)
+//! - [DialerConfig](../../reqrep/client/struct.DialerConfig.html)
+//!
+//! ## Metrics
+//! - total number of messages received - [RECEIVED_MSG_COUNT_METRIC_ID](constant.RECEIVED_MSG_COUNT_METRIC_ID.html)
+//! - total number of messages processed - [PROCESSED_MSG_COUNT_METRIC_ID](constant.PROCESSED_MSG_COUNT_METRIC_ID.html)
+//! - the ReqRep service provides the message processing timer metrics
+
+use super::{PipelineId, PIPELINE_ID_LABEL_ID};
+use crate::{
+    config::{SocketConfig, SocketConfigError},
+    reqrep::client::{DialerConfig, DialerConfigError},
+    task::{self, TaskHandle, TaskHandleError},
+};
+use failure::Fail;
+use futures::{
+    channel::{mpsc, oneshot},
+    prelude::*,
+    sink::SinkExt,
+    stream::StreamExt,
+    task::SpawnExt,
+};
+use lazy_static::lazy_static;
+use oysterpack_log::*;
+use oysterpack_trust::{
+    concurrent::{execution::Executor, messaging::reqrep::ReqRep},
+    metrics,
+};
+use std::fmt;
+
+lazy_static! {
+
+    /// the metric is incremented when a message is received from the socket
+    static ref RECEIVED_MSG_COUNT: prometheus::IntCounterVec = metrics::registry().register_int_counter_vec(
+        RECEIVED_MSG_COUNT_METRIC_ID,
+        "Total number of messages received by pipeline workers",
+        &[PIPELINE_ID_LABEL_ID],
+        None
+    ).unwrap();
+
+    /// the metric is incremented when the Processor has processed the message
+    static ref PROCESSED_MSG_COUNT: prometheus::IntCounterVec = metrics::registry().register_int_counter_vec(
+        PROCESSED_MSG_COUNT_METRIC_ID,
+        "Total number of messages processed by pipeline workers",
+        &[PIPELINE_ID_LABEL_ID],
+        None
+    ).unwrap();
+
+}
+
+/// IntCounterVec MetricId which is used to track the total number of messages received by PipelineId: `M01M57F9BYSGAXNH8TWVFJ5S176`
+pub const RECEIVED_MSG_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166789204052592417051384870961186022);
+/// IntCounterVec MetricId which is used to track the total number of messages processed by PipelineId: `M01M57F9BZ1P8RY5P5W4S4Z2QY3`
+pub const PROCESSED_MSG_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166789204062487961507768399547162563);
+
+/// Spawns the pull workers
+/// - messages are fed into the ReqRep Processor
+/// - returns a PullerHandle that can be used to stop the pull workers
+pub fn spawn(
+    socket_config: Option<SocketConfig>,
+    dialer_config: DialerConfig,
+    pipeline_id: PipelineId,
+    service: ReqRep<nng::Message, ()>,
+    mut executor: Executor,
+) -> Result<PullerHandle, SpawnError> {
+    let parallelism = dialer_config.parallelism();
+    let puller_metrics = PullerMetrics::new(pipeline_id);
+
+    let create_socket = || {
+        let mut socket =
+            nng::Socket::new(nng::Protocol::Pull0).map_err(SpawnError::SocketCreateFailure)?;
+        socket.set_nonblocking(true);
+        match socket_config {
+            Some(socket_config) => socket_config
+                .apply(socket)
+                .map_err(SpawnError::SocketConfigApplyFailed),
+            None => Ok(socket),
+        }
+    };
+
+    let socket = create_socket()?;
+
+    // the worker Aio(s) are created before the dialer is started, i.e., if creating an Aio fails, then
+    // there is nothing to clean up
+    // - the Aio is used to notify the worker when an Aio event has occurred, i.e., the Aio callback has been invoked
+    let aios = (0..parallelism)
+        .map(|_| task::aio_notifier("pull worker"))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(SpawnError::AioCreateWithCallbackFailure)?;
+
+    let dialer = dialer_config
+        .start_dialer(&socket)
+        .map_err(SpawnError::DialerStartError)?;
+
+    // if a task fails to spawn, then closing the socket stops the worker tasks that have already been
+    // spawned - closing the socket also closes the dialer
+    let spawn_failed = |socket: &nng::Socket, err: futures::task::SpawnError| {
+        socket.clone().close();
+        SpawnError::ExecutorSpawnError {
+            is_executor_shutdown: err.is_shutdown(),
+        }
+    };
+
+    // spawns the worker tasks
+    // - each worker task has its own Aio and runs its own private event loop
+    // - the worker's job is to integrate nng with the backend ReqRep service - it simply relays nng
+    //   messages to the ReqRep service
+    for (i, (aio, mut aio_rx)) in aios.into_iter().enumerate() {
+        let worker_socket = socket.clone();
+        let mut service = service.clone();
+        let puller_metrics = puller_metrics.clone();
+        if let Err(err) = executor.spawn(
+            async move {
+                let recv = || {
+                    if let Err(err) = worker_socket.recv_async(&aio) {
+                        // TODO: trigger alert - async I/O errors need to be investigated
+                        error!("Socket::recv_async() failed: {}", err);
+                    }
+                };
+
+                recv();
+                debug!("Puller({}) worker #{} is running ...", pipeline_id, i);
+                // NOTE: aio.result().unwrap() is safe because we are being signalled
+                // by the Aio callback to handle an Aio event
+                while let Some(_) = await!(aio_rx.next()) {
+                    match aio.result().unwrap() {
+                        Ok(_) => {
+                            if let Some(msg) = aio.get_msg() {
+                                puller_metrics.received_msg_count.inc();
+                                match await!(service.send_recv(msg)) {
+                                    Ok(_) => puller_metrics.processed_msg_count.inc(),
+                                    Err(err) => error!(
+                                        "ReqRep::send_recv() failed: ReqRepId({}) : {}",
+                                        service.id(),
+                                        err
+                                    ),
+                                }
+                            }
+                            recv();
+                        }
+                        Err(nng::Error::Closed) => break,
+                        Err(err) => {
+                            error!("Aio error: {}", err);
+                            recv();
+                        }
+                    }
+                }
+                debug!("Puller({}) worker #{} is done", pipeline_id, i);
+            },
+        ) {
+            return Err(spawn_failed(&socket, err));
+        }
+    }
+
+    let (command_tx, mut command_rx) = mpsc::channel(1);
+    let controller_socket = socket.clone();
+    let handle = executor
+        .spawn_with_handle(
+            async move {
+                while let Some(cmd) = await!(command_rx.next()) {
+                    match cmd {
+                        PullerCommand::Ping(reply_chan) => {
+                            let _ = reply_chan.send(());
+                        }
+                        PullerCommand::Stop => break,
+                    }
+                }
+                debug!("Puller({}) is shutting down ...", pipeline_id);
+                dialer.close();
+                socket.close();
+                debug!("Puller({}) is shut down", pipeline_id);
+            },
+        )
+        .map_err(|err| spawn_failed(&controller_socket, err))?;
+
+    Ok(PullerHandle {
+        pipeline_id,
+        task: TaskHandle::new(handle, command_tx, executor),
+        metrics: puller_metrics,
+    })
+}
+
+/// Puller handle
+#[derive(Debug, Clone)]
+pub struct PullerHandle {
+    pipeline_id: PipelineId,
+    task: TaskHandle<PullerCommand>,
+    metrics: PullerMetrics,
+}
+
+impl PullerHandle {
+    /// Returns the PipelineId
+    pub fn pipeline_id(&self) -> PipelineId {
+        self.pipeline_id
+    }
+
+    /// returns true if the puller has been signalled to stop
+    pub fn stop_signalled(&self) -> bool {
+        self.task.stop_signalled()
+    }
+
+    /// Returns PullerMetrics
+    pub fn metrics(&self) -> &PullerMetrics {
+        &self.metrics
+    }
+
+    /// pings the puller to check if it is still alive
+    /// - returns true if the puller responds to the ping
+    pub fn ping(&self) -> bool {
+        self.task.ping(PullerCommand::Ping)
+    }
+
+    /// signals the puller to shutdown async
+    pub fn stop_async(&mut self) -> Result<bool, TaskHandleError> {
+        self.task.stop_async(PullerCommand::Stop)
+    }
+
+    /// Block the current thread until the puller has shutdown
+    ///
+    /// ## Notes
+    /// The puller must be signaled to stop in order to shutdown.
+    pub fn await_shutdown(mut self) {
+        self.task.await_shutdown()
+    }
+}
+
+/// Puller commands
+#[derive(Debug)]
+pub enum PullerCommand {
+    /// Ping the puller to check if it is still alive
+    Ping(oneshot::Sender<()>),
+    /// Signals the puller to shutdown
+    Stop,
+}
+
+/// Errors that could happen while trying to spawn the pull workers
+#[derive(Debug, Fail)]
+pub enum SpawnError {
+    /// Failed to create Socket
+    #[fail(display = "Failed to create Socket: {}", _0)]
+    SocketCreateFailure(#[cause] nng::Error),
+    /// Failed to apply SocketConfig options
+    #[fail(display = "{}", _0)]
+    SocketConfigApplyFailed(#[cause] SocketConfigError),
+    /// Failed to start the dialer
+    #[fail(display = "Failed to start dialer: {}", _0)]
+    DialerStartError(#[cause] DialerConfigError),
+    /// Failed to create Aio
+    #[fail(display = "Failed to create Aio with callback: {}", _0)]
+    AioCreateWithCallbackFailure(#[cause] nng::Error),
+    /// An error that occurred during spawning.
+    #[fail(
+        display = "Spawning Future failed: executor shutdown = {}",
+        is_executor_shutdown
+    )]
+    ExecutorSpawnError {
+        /// whether spawning failed because the executor is shut down
+        is_executor_shutdown: bool,
+    },
+}
+
+/// Puller metrics
+#[derive(Clone)]
+pub struct PullerMetrics {
+    received_msg_count: prometheus::IntCounter,
+    processed_msg_count: prometheus::IntCounter,
+}
+
+impl PullerMetrics {
+    fn new(pipeline_id: PipelineId) -> Self {
+        let pipeline_id_label = pipeline_id.to_string();
+        Self {
+            received_msg_count: RECEIVED_MSG_COUNT
+                .with_label_values(&[pipeline_id_label.as_str()]),
+            processed_msg_count: PROCESSED_MSG_COUNT
+                .with_label_values(&[pipeline_id_label.as_str()]),
+        }
+    }
+
+    /// Total number of messages received
+    pub fn received_msg_count(&self) -> usize {
+        self.received_msg_count.get() as usize
+    }
+
+    /// Total number of messages processed
+    pub fn processed_msg_count(&self) -> usize {
+        self.processed_msg_count.get() as usize
+    }
+}
+
+impl fmt::Debug for PullerMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "PullerMetrics(received_msg_count = {}, processed_msg_count = {})",
+            self.received_msg_count.get(),
+            self.processed_msg_count.get()
+        )
+    }
+}
+
+#[allow(warnings)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{configure_logging, pipeline::pusher, reqrep::server::ListenerConfig};
+    use futures::future::FutureExt;
+    use oysterpack_trust::concurrent::{
+        execution::{self, *},
+        messaging::reqrep::{self, *},
+    };
+    use oysterpack_uid::ULID;
+    use std::{
+        num::NonZeroUsize,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    struct CountingProcessor(Arc<AtomicUsize>);
+    impl Processor<nng::Message, ()> for CountingProcessor {
+        fn process(&mut self, _req: nng::Message) -> reqrep::FutureReply<()> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            async {}.boxed()
+        }
+    }
+
+    fn start_service(counter: Arc<AtomicUsize>) -> ReqRep<nng::Message, ()> {
+        let timer_buckets = metrics::timer_buckets(vec![
+            Duration::from_nanos(50),
+            Duration::from_nanos(100),
+            Duration::from_nanos(150),
+            Duration::from_nanos(200),
+        ])
+        .unwrap();
+        ReqRepConfig::new(ReqRepId::generate(), timer_buckets)
+            .start_service(CountingProcessor(counter), global_executor())
+            .unwrap()
+    }
+
+    #[test]
+    fn pipeline() {
+        configure_logging();
+        let mut executor = execution::global_executor();
+        let pipeline_id = PipelineId::generate();
+        let url = url::Url::parse(&format!("inproc://{}", ULID::generate())).unwrap();
+
+        // GIVEN: a pusher
+        let pusher = pusher::spawn(
+            None,
+            ListenerConfig::new(url.clone()),
+            pipeline_id,
+            pusher::DEFAULT_CHAN_BUF_SIZE,
+            executor.clone(),
+        )
+        .unwrap();
+
+        // AND: 2 pull workers
+        let counter = Arc::new(AtomicUsize::new(0));
+        let pullers: Vec<PullerHandle> = (0..2)
+            .map(|_| {
+                spawn(
+                    None,
+                    DialerConfig::new(url.clone()).set_parallelism(NonZeroUsize::new(2).unwrap()),
+                    pipeline_id,
+                    start_service(counter.clone()),
+                    executor.clone(),
+                )
+                .unwrap()
+            })
+            .collect();
+        assert!(pullers.iter().all(|puller| puller.ping()));
+
+        // WHEN: messages are pushed
+        const MSG_COUNT: usize = 100;
+        for _ in 0..MSG_COUNT {
+            executor
+                .run(pusher.push(nng::Message::new().unwrap()))
+                .unwrap();
+        }
+
+        // THEN: all messages are processed
+        let all_processed = || pullers[0].metrics().processed_msg_count() == MSG_COUNT;
+        assert!(crate::eventually(all_processed));
+        assert_eq!(counter.load(Ordering::SeqCst), MSG_COUNT);
+        // AND: the metrics are tracked per PipelineId
+        assert_eq!(pullers[0].metrics().processed_msg_count(), MSG_COUNT);
+        assert_eq!(pullers[0].metrics().received_msg_count(), MSG_COUNT);
+
+        // WHEN: the pull workers are stopped
+        for mut puller in pullers {
+            assert!(puller.stop_async().unwrap());
+            puller.await_shutdown();
+        }
+        // AND: the pusher is dropped
+        drop(pusher);
+    }
+}
@@ -0,0 +1,221 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Provides an nng Push0 pusher.
+//!
+//! ## Design
+//! The pusher listens on a Push0 socket, which pull workers dial into. A single pusher task owns the
+//! socket, and messages are relayed to the task via a bounded async channel. Each message is sent
+//! using [nng:Aio](https://docs.rs/nng/latest/nng/struct.Aio.html), and the push completes when the
+//! Aio send operation completes.
+//!
+//! ## Backpressure
+//! Push0 will block the send until a pull worker is ready to accept the message. Thus, if there are no
+//! pull workers connected or all pull workers are busy, then the push will wait asynchronously.
+//! The pusher task handles one message at a time, which means pending pushes queue up on the bounded
+//! channel.
+//!
+//! When all Pusher references are dropped, then the pusher task closes the nng Listener and Socket.
+//!
+//! ## Config
+//! - [SocketConfig](../../config/struct.SocketConfig.html)
+//! - [ListenerConfig](../../reqrep/server/struct.ListenerConfig.html)
+//!
+//! ## Metrics
+//! - total number of messages sent - [SENT_MSG_COUNT_METRIC_ID](constant.SENT_MSG_COUNT_METRIC_ID.html)
+
+use super::{PipelineId, PIPELINE_ID_LABEL_ID};
+use crate::{
+    config::{SocketConfig, SocketConfigError},
+    reqrep::server::{ListenerConfig, ListenerConfigError},
+    task,
+};
+use failure::Fail;
+use futures::{
+    channel::{mpsc, oneshot},
+    prelude::*,
+    sink::SinkExt,
+    stream::StreamExt,
+    task::SpawnExt,
+};
+use lazy_static::lazy_static;
+use oysterpack_log::*;
+use oysterpack_trust::{concurrent::execution::Executor, metrics};
+use std::fmt;
+
+lazy_static! {
+
+    /// the metric is incremented after the message has been successfully sent to a pull worker
+    static ref SENT_MSG_COUNT: prometheus::IntCounterVec = metrics::registry().register_int_counter_vec(
+        SENT_MSG_COUNT_METRIC_ID,
+        "Total number of messages pushed to pipeline workers",
+        &[PIPELINE_ID_LABEL_ID],
+        None
+    ).unwrap();
+
+}
+
+/// IntCounterVec MetricId which is used to track the total number of messages sent by PipelineId: `M01M57F9BYQ3AX53MZ8T4HXWZT9`
+pub const SENT_MSG_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166789204049683420360454621865344841);
+
+/// The default push channel buffer size
+pub const DEFAULT_CHAN_BUF_SIZE: usize = 1;
+
+/// Spawns the pusher background task
+/// - chan_buf_size is the push channel buffer size, which bounds the number of pending pushes
+pub fn spawn(
+    socket_config: Option<SocketConfig>,
+    listener_config: ListenerConfig,
+    pipeline_id: PipelineId,
+    chan_buf_size: usize,
+    mut executor: Executor,
+) -> Result<Pusher, SpawnError> {
+    let sent_msg_count = SENT_MSG_COUNT.with_label_values(&[pipeline_id.to_string().as_str()]);
+
+    let create_socket = || {
+        let mut socket =
+            nng::Socket::new(nng::Protocol::Push0).map_err(SpawnError::SocketCreateFailure)?;
+        socket.set_nonblocking(true);
+        match socket_config {
+            Some(socket_config) => socket_config
+                .apply(socket)
+                .map_err(SpawnError::SocketConfigApplyFailed),
+            None => Ok(socket),
+        }
+    };
+
+    let socket = create_socket()?;
+    let listener = listener_config
+        .start_listener(&socket)
+        .map_err(SpawnError::ListenerStartFailure)?;
+
+    // used to notify the pusher task when an Aio event has occurred, i.e., the Aio callback has been invoked
+    let (aio, mut aio_rx) =
+        task::aio_notifier("pusher").map_err(SpawnError::AioCreateWithCallbackFailure)?;
+
+    let (push_tx, mut push_rx) = mpsc::channel::<Push>(chan_buf_size);
+    executor
+        .spawn(
+            async move {
+                debug!("Pusher({}) is running ...", pipeline_id);
+                while let Some(Push { msg, reply_chan }) = await!(push_rx.next()) {
+                    let result = match socket.send_async(&aio, msg) {
+                        Ok(_) => match await!(aio_rx.next()) {
+                            // NOTE: aio.result().unwrap() is safe because we are being signalled
+                            // by the Aio callback to handle an Aio event
+                            Some(_) => aio.result().unwrap().map_err(PushError::SendFailed),
+                            None => Err(PushError::PusherClosed),
+                        },
+                        Err((_msg, err)) => Err(PushError::SendFailed(err)),
+                    };
+                    if result.is_ok() {
+                        sent_msg_count.inc();
+                    }
+                    let _ = reply_chan.send(result);
+                }
+                debug!("Pusher({}) is shutting down ...", pipeline_id);
+                listener.close();
+                socket.close();
+                debug!("Pusher({}) is shut down", pipeline_id);
+            },
+        )
+        .map_err(|err| SpawnError::ExecutorSpawnError {
+            is_executor_shutdown: err.is_shutdown(),
+        })?;
+
+    Ok(Pusher {
+        pipeline_id,
+        push_channel: push_tx,
+    })
+}
+
+/// Used to push messages to pipeline pull workers
+/// - when all Pusher references are dropped, then the pusher is closed
+#[derive(Clone)]
+pub struct Pusher {
+    pipeline_id: PipelineId,
+    push_channel: mpsc::Sender<Push>,
+}
+
+impl Pusher {
+    /// Returns the PipelineId
+    pub fn pipeline_id(&self) -> PipelineId {
+        self.pipeline_id
+    }
+
+    /// Pushes the message to the next available pull worker.
+    /// - the returned future completes when the message has been queued to a pull worker's pipe, i.e.,
+    ///   if no pull workers are connected or their pipes are full, then the push will wait
+    /// - completion does not mean that a pull worker has received or processed the message
+    pub fn push(&self, msg: nng::Message) -> impl Future<Output = Result<(), PushError>> {
+        let mut push_channel = self.push_channel.clone();
+        async move {
+            let (reply_chan, reply) = oneshot::channel();
+            await!(push_channel.send(Push { msg, reply_chan }))
+                .map_err(|_| PushError::PusherClosed)?;
+            await!(reply).map_err(|_| PushError::PusherClosed)?
+        }
+    }
+}
+
+impl fmt::Debug for Pusher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Pusher({})", self.pipeline_id)
+    }
+}
+
+struct Push {
+    msg: nng::Message,
+    reply_chan: oneshot::Sender<Result<(), PushError>>,
+}
+
+/// Push related errors
+#[derive(Debug, Fail, Clone)]
+pub enum PushError {
+    /// The pusher task is no longer running
+    #[fail(display = "The pusher is closed")]
+    PusherClosed,
+    /// Failed to send the message
+    #[fail(display = "Failed to send message: {}", _0)]
+    SendFailed(#[cause] nng::Error),
+}
+
+/// Errors that could happen while trying to spawn a pusher
+#[derive(Debug, Fail)]
+pub enum SpawnError {
+    /// Failed to create Socket
+    #[fail(display = "Failed to create Socket: {}", _0)]
+    SocketCreateFailure(#[cause] nng::Error),
+    /// Failed to create Aio
+    #[fail(display = "Failed to create Aio with callback: {}", _0)]
+    AioCreateWithCallbackFailure(#[cause] nng::Error),
+    /// An error that occurred during spawning.
+    #[fail(
+        display = "Spawning Future failed: executor shutdown = {}",
+        is_executor_shutdown
+    )]
+    ExecutorSpawnError {
+        /// whether spawning failed because the executor is shut down
+        is_executor_shutdown: bool,
+    },
+    /// Failed to start the listener
+    #[fail(display = "{}", _0)]
+    ListenerStartFailure(#[cause] ListenerConfigError),
+    /// Failed to apply SocketConfig options
+    #[fail(display = "{}", _0)]
+    SocketConfigApplyFailed(#[cause] SocketConfigError),
+}
@@ -0,0 +1,73 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Provides support for the publish/subscribe messaging protocol.
+//! - messages are published via a [PublisherHandle](publisher/struct.PublisherHandle.html)
+//! - subscribers receive messages via a [Subscription](subscriber/struct.Subscription.html), which is a futures `Stream`
+//! - messages are filtered by [Topic](struct.Topic.html), i.e., the topic is used as the message prefix.
+//!   Subscribers receive messages whose body starts with any of the topics they have subscribed to.
+//!   - the topic prefix is not stripped from the received message
+
+pub mod publisher;
+pub mod subscriber;
+
+use oysterpack_trust::metrics;
+use oysterpack_uid::macros::ulid;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Each publish/subscribe channel is uniquely identified by an ID, which is used for tracking purposes,
+/// e.g., metrics
+#[ulid]
+pub struct PubSubId(pub u128);
+
+/// Metric LabelId which is used to store a PubSubId: `L01M57F30X6NYX083EXEVE4EX29`
+pub const PUBSUB_ID_LABEL_ID: metrics::LabelId =
+    metrics::LabelId(2166788952689321707268910296234161225);
+
+/// Topics are used to filter messages. Published messages are prefixed with the topic.
+/// - an empty topic matches all messages
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize, Default)]
+pub struct Topic(Vec<u8>);
+
+impl Topic {
+    /// constructor
+    pub fn new<T: Into<Vec<u8>>>(topic: T) -> Topic {
+        Topic(topic.into())
+    }
+
+    /// Returns the topic bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// returns true if the message body starts with the topic
+    pub fn matches(&self, msg: &nng::Message) -> bool {
+        msg[..].starts_with(&self.0)
+    }
+}
+
+impl From<&str> for Topic {
+    fn from(topic: &str) -> Topic {
+        Topic::new(topic)
+    }
+}
+
+impl fmt::Display for Topic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&String::from_utf8_lossy(&self.0))
+    }
+}
@@ -0,0 +1,537 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Provides an nng Pub0 publisher.
+//!
+//! ## Design
+//! The publisher follows the [reqrep server](../../reqrep/server/index.html) design. The publisher
+//! listens on a Pub0 socket, which subscribers dial into. A single publisher task owns the socket
+//! and is driven by an async command channel:
+//! - publish requests are sent to the nng socket via [nng:Aio](https://docs.rs/nng/latest/nng/struct.Aio.html).
+//!   The Aio callback notifies the publisher task when the async send has completed.
+//! - ping requests - which can be used to check that the publisher is running
+//! - stop signal. Upon receiving the signal the publisher will
+//!   - close the nng Listener and Socket
+//!   - unregister the PublisherHandle from the global registry
+//!
+//! <pre>
+//! PublisherHandle ---(Topic + Message)--> publisher task --> Aio --> Socket ---> subscribers
+//! </pre>
+//!
+//! ## Notes
+//! - Pub0 never blocks: if a subscriber is not able to keep up, then messages are dropped for that
+//!   subscriber.
+//!
+//! ## Config
+//! - [SocketConfig](../../config/struct.SocketConfig.html)
+//! - [ListenerConfig](../../reqrep/server/struct.ListenerConfig.html)
+//!
+//! ## Metrics
+//! - active number of socket connections - [ACTIVE_CONN_COUNT_METRIC_ID](constant.ACTIVE_CONN_COUNT_METRIC_ID.html)
+//! - total number of socket connections that have been made since the publisher has started - [TOT_CONN_COUNT_METRIC_ID](constant.TOT_CONN_COUNT_METRIC_ID.html)
+//! - total number of messages that have been published - [PUBLISHED_MSG_COUNT_METRIC_ID](constant.PUBLISHED_MSG_COUNT_METRIC_ID.html)
+
+use super::{PubSubId, Topic, PUBSUB_ID_LABEL_ID};
+use crate::{
+    config::{SocketConfig, SocketConfigError},
+    reqrep::server::{ListenerConfig, ListenerConfigError},
+    task::{self, TaskHandle, TaskHandleError},
+};
+use failure::Fail;
+use futures::{prelude::*, sink::SinkExt, stream::StreamExt, task::SpawnExt};
+use hashbrown::HashMap;
+use lazy_static::lazy_static;
+use oysterpack_log::*;
+use oysterpack_trust::{concurrent::execution::Executor, metrics};
+use oysterpack_uid::ULID;
+use parking_lot::RwLock;
+use std::fmt;
+
+lazy_static! {
+
+    /// Global PublisherHandle registry
+    static ref PUBLISHER_HANDLES: RwLock<HashMap<ULID, PublisherHandle>> = RwLock::new(HashMap::new());
+
+    /// the metric is incremented on nng::PipeEvent::AddPost and decremented on nng::PipeEvent::RemovePost
+    static ref ACTIVE_CONN_COUNT: prometheus::IntGaugeVec = metrics::registry().register_int_gauge_vec(
+        ACTIVE_CONN_COUNT_METRIC_ID,
+        "Active number of publisher socket connections",
+        &[PUBSUB_ID_LABEL_ID],
+        None
+    ).unwrap();
+
+    /// the metric is incremented on nng::PipeEvent::AddPost
+    static ref TOT_CONN_COUNT: prometheus::IntCounterVec = metrics::registry().register_int_counter_vec(
+        TOT_CONN_COUNT_METRIC_ID,
+        "Total number of publisher socket connections since the publisher was started",
+        &[PUBSUB_ID_LABEL_ID],
+        None
+    ).unwrap();
+
+    /// the metric is incremented after the message has been successfully sent to the socket
+    static ref PUBLISHED_MSG_COUNT: prometheus::IntCounterVec = metrics::registry().register_int_counter_vec(
+        PUBLISHED_MSG_COUNT_METRIC_ID,
+        "Total number of published messages",
+        &[PUBSUB_ID_LABEL_ID],
+        None
+    ).unwrap();
+
+}
+
+/// IntGaugeVec MetricId which is used to track the number of active publisher socket connections by PubSubId: `M01M57F30X9A09B3GTF515RDCFK`
+pub const ACTIVE_CONN_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166788952692496773375912930494034419);
+/// IntCounterVec MetricId which is used to track the total number of publisher socket connections by PubSubId: `M01M57F30XB8G50CYBXAHQ105FC`
+pub const TOT_CONN_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166788952694857796700768201755071980);
+/// IntCounterVec MetricId which is used to track the total number of published messages by PubSubId: `M01M57F30XDT2KXR460F9Y5K2NH`
+pub const PUBLISHED_MSG_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166788952697939691177262865123478193);
+
+/// Spawns a publisher background task
+/// - returns a PublisherHandle that is used to publish messages and to stop the publisher
+///   - the PublisherHandle is registered globally
+///     - each publisher instance is assigned a ULID, which is used as the registry key
+///   - when the publisher is stopped, the PublisherHandle will be automatically unregistered
+pub fn spawn(
+    socket_config: Option<SocketConfig>,
+    listener_config: ListenerConfig,
+    pubsub_id: PubSubId,
+    mut executor: Executor,
+) -> Result<PublisherHandle, SpawnError> {
+    let (command_tx, mut command_rx) = futures::channel::mpsc::channel(1);
+
+    let url = listener_config.url().clone();
+    let publisher_metrics = PublisherMetrics::new(pubsub_id);
+    let publisher_handle_id = ULID::generate();
+
+    let create_socket = || {
+        let publisher_metrics = publisher_metrics.clone();
+        let mut socket =
+            nng::Socket::new(nng::Protocol::Pub0).map_err(SpawnError::SocketCreateFailure)?;
+        socket.set_nonblocking(true);
+        socket
+            .pipe_notify(move |pipe, event| {
+                match event {
+                    nng::PipeEvent::AddPost => {
+                        publisher_metrics.active_conn_count.inc();
+                        publisher_metrics.tot_conn_count.inc();
+                    }
+                    nng::PipeEvent::RemovePost => publisher_metrics.active_conn_count.dec(),
+                    _ => (),
+                }
+                debug!("{:?} {:?}", pipe, event);
+            })
+            .map_err(SpawnError::SocketCreateFailure)?;
+        match socket_config {
+            Some(socket_config) => socket_config
+                .apply(socket)
+                .map_err(SpawnError::SocketConfigApplyFailed),
+            None => Ok(socket),
+        }
+    };
+
+    let socket = create_socket()?;
+    let listener = listener_config
+        .start_listener(&socket)
+        .map_err(SpawnError::ListenerStartFailure)?;
+
+    // used to notify the publisher task when an Aio event has occurred, i.e., the Aio callback has been invoked
+    let (aio, mut aio_rx) =
+        task::aio_notifier("publisher").map_err(SpawnError::AioCreateWithCallbackFailure)?;
+
+    let published_msg_count = publisher_metrics.published_msg_count.clone();
+    let handle = executor
+        .spawn_with_handle(
+            async move {
+                debug!("Publisher({}) is running ...", pubsub_id);
+                while let Some(cmd) = await!(command_rx.next()) {
+                    match cmd {
+                        PublisherCommand::Publish(msg, reply_chan) => {
+                            let result = match socket.send_async(&aio, msg) {
+                                Ok(_) => match await!(aio_rx.next()) {
+                                    // NOTE: aio.result().unwrap() is safe because we are being signalled
+                                    // by the Aio callback to handle an Aio event
+                                    Some(_) => aio.result().unwrap().map_err(PublishError::SendFailed),
+                                    None => Err(PublishError::PublisherStopped),
+                                },
+                                Err((_msg, err)) => Err(PublishError::SendFailed(err)),
+                            };
+                            if result.is_ok() {
+                                published_msg_count.inc();
+                            }
+                            let _ = reply_chan.send(result);
+                        }
+                        PublisherCommand::Ping(reply_chan) => {
+                            let _ = reply_chan.send(());
+                        }
+                        PublisherCommand::Stop => break,
+                    }
+                }
+                debug!("Publisher({}) is shutting down ...", pubsub_id);
+                listener.close();
+                socket.close();
+                debug!("Publisher({}) is shut down", pubsub_id);
+                let mut publisher_handles = PUBLISHER_HANDLES.write();
+                publisher_handles.remove(&publisher_handle_id);
+            },
+        )
+        .map_err(|err| SpawnError::ExecutorSpawnError {
+            is_executor_shutdown: err.is_shutdown(),
+        })?;
+
+    let publisher_handle = PublisherHandle {
+        id: publisher_handle_id,
+        url,
+        pubsub_id,
+        task: TaskHandle::new(handle, command_tx, executor),
+        metrics: publisher_metrics,
+    };
+
+    let mut publisher_handles = PUBLISHER_HANDLES.write();
+    publisher_handles.insert(publisher_handle.id(), publisher_handle.clone());
+
+    Ok(publisher_handle)
+}
+
+/// Publisher handle
+/// - the publisher handle is globally registered using its ULID as the key
+///
+/// ## Stopping the publisher
+/// - [stop_async()](#method.stop_async) is used to signal the publisher to stop
+#[derive(Debug, Clone)]
+pub struct PublisherHandle {
+    id: ULID,
+    url: url::Url,
+    pubsub_id: PubSubId,
+    task: TaskHandle<PublisherCommand>,
+    metrics: PublisherMetrics,
+}
+
+impl PublisherHandle {
+    /// Returns the PublisherHandle ULID
+    pub fn id(&self) -> ULID {
+        self.id
+    }
+
+    /// Returns the URI that the publisher is listening on
+    pub fn url(&self) -> &url::Url {
+        &self.url
+    }
+
+    /// Returns the PubSubId
+    pub fn pubsub_id(&self) -> PubSubId {
+        self.pubsub_id
+    }
+
+    /// returns true if the publisher has been signalled to stop
+    pub fn stop_signalled(&self) -> bool {
+        self.task.stop_signalled()
+    }
+
+    /// Returns PublisherMetrics
+    pub fn metrics(&self) -> &PublisherMetrics {
+        &self.metrics
+    }
+
+    /// Publishes the message on the specified topic, i.e., the message is prefixed with the topic.
+    /// - the returned future completes when the message has been handed off to the nng socket
+    pub fn publish(
+        &self,
+        topic: &Topic,
+        mut msg: nng::Message,
+    ) -> impl Future<Output = Result<(), PublishError>> {
+        let command_channel = self.task.command_channel();
+        let prefixed = msg
+            .push_front(topic.as_bytes())
+            .map(|_| msg)
+            .map_err(PublishError::MessageAllocFailed);
+        async move {
+            let msg = prefixed?;
+            let mut command_channel = command_channel.ok_or(PublishError::PublisherStopped)?;
+            let (tx, rx) = futures::channel::oneshot::channel();
+            await!(command_channel.send(PublisherCommand::Publish(msg, tx)))
+                .map_err(|_| PublishError::PublisherStopped)?;
+            await!(rx).map_err(|_| PublishError::PublisherStopped)?
+        }
+    }
+
+    /// pings the publisher to check if it is still alive
+    /// - returns true if the publisher responds to the ping
+    pub fn ping(&self) -> bool {
+        self.task.ping(PublisherCommand::Ping)
+    }
+
+    /// signals the publisher to shutdown async
+    pub fn stop_async(&mut self) -> Result<bool, TaskHandleError> {
+        self.task.stop_async(PublisherCommand::Stop)
+    }
+
+    /// Block the current thread until the publisher has shutdown
+    ///
+    /// ## Notes
+    /// The publisher must be signaled to stop in order to shutdown.
+    pub fn await_shutdown(mut self) {
+        self.task.await_shutdown()
+    }
+
+    /// Returns the PublisherHandle - only if the publisher is still alive
+    pub fn get(id: ULID) -> Option<PublisherHandle> {
+        let publisher_handle = {
+            let publisher_handles = PUBLISHER_HANDLES.read();
+            publisher_handles.get(&id).cloned()
+        };
+
+        // check if the publisher is still alive
+        if let Some(publisher_handle) = publisher_handle {
+            if publisher_handle.ping() {
+                Some(publisher_handle)
+            } else {
+                // unregister the PublisherHandle because pinging the publisher failed
+                {
+                    let mut publisher_handles = PUBLISHER_HANDLES.write();
+                    publisher_handles.remove(&id);
+                }
+                None
+            }
+        } else {
+            None
+        }
+    }
+
+    /// returns all registered PublisherHandle(s)
+    pub fn all() -> Vec<PublisherHandle> {
+        PUBLISHER_HANDLES.read().values().cloned().collect()
+    }
+
+    /// Returns the list of registered PublisherHandle ULIDs along with the publisher's PubSubId
+    pub fn ids() -> Vec<(ULID, PubSubId)> {
+        let publisher_handles = PUBLISHER_HANDLES.read();
+        publisher_handles
+            .values()
+            .map(|publisher_handle| (publisher_handle.id, publisher_handle.pubsub_id))
+            .collect()
+    }
+
+    /// Returns PublisherHandle(s) that are registered for the specified PubSubId
+    pub fn get_by_pubsub_id(pubsub_id: PubSubId) -> Vec<PublisherHandle> {
+        let publisher_handles = PUBLISHER_HANDLES.read();
+        publisher_handles
+            .values()
+            .filter(|publisher_handle| publisher_handle.pubsub_id == pubsub_id)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Publisher commands
+#[derive(Debug)]
+pub enum PublisherCommand {
+    /// Publish the message
+    Publish(
+        nng::Message,
+        futures::channel::oneshot::Sender<Result<(), PublishError>>,
+    ),
+    /// Ping the publisher to check if it is still alive
+    Ping(futures::channel::oneshot::Sender<()>),
+    /// Signals the publisher to shutdown
+    Stop,
+}
+
+/// Publish related errors
+#[derive(Debug, Fail, Clone)]
+pub enum PublishError {
+    /// The publisher has been stopped
+    #[fail(display = "The publisher has been stopped")]
+    PublisherStopped,
+    /// Failed to prefix the message with the topic
+    #[fail(display = "Failed to prefix the message with the topic: {}", _0)]
+    MessageAllocFailed(#[cause] nng::Error),
+    /// Failed to send the message
+    #[fail(display = "Failed to send message: {}", _0)]
+    SendFailed(#[cause] nng::Error),
+}
+
+/// Errors that could happen while trying to spawn a publisher
+#[derive(Debug, Fail)]
+pub enum SpawnError {
+    /// Failed to create Socket
+    #[fail(display = "Failed to create Socket: {}", _0)]
+    SocketCreateFailure(#[cause] nng::Error),
+    /// Failed to create Aio
+    #[fail(display = "Failed to create Aio with callback: {}", _0)]
+    AioCreateWithCallbackFailure(#[cause] nng::Error),
+    /// An error that occurred during spawning.
+    #[fail(
+        display = "Spawning Future failed: executor shutdown = {}",
+        is_executor_shutdown
+    )]
+    ExecutorSpawnError {
+        /// whether spawning failed because the executor is shut down
+        is_executor_shutdown: bool,
+    },
+    /// Failed to start the listener
+    #[fail(display = "{}", _0)]
+    ListenerStartFailure(#[cause] ListenerConfigError),
+    /// Failed to apply SocketConfig options
+    #[fail(display = "{}", _0)]
+    SocketConfigApplyFailed(#[cause] SocketConfigError),
+}
+
+/// Publisher metrics
+#[derive(Clone)]
+pub struct PublisherMetrics {
+    active_conn_count: prometheus::IntGauge,
+    tot_conn_count: prometheus::IntCounter,
+    published_msg_count: prometheus::IntCounter,
+}
+
+impl PublisherMetrics {
+    fn new(pubsub_id: PubSubId) -> Self {
+        let pubsub_id_label = pubsub_id.to_string();
+        Self {
+            active_conn_count: ACTIVE_CONN_COUNT.with_label_values(&[pubsub_id_label.as_str()]),
+            tot_conn_count: TOT_CONN_COUNT.with_label_values(&[pubsub_id_label.as_str()]),
+            published_msg_count: PUBLISHED_MSG_COUNT
+                .with_label_values(&[pubsub_id_label.as_str()]),
+        }
+    }
+
+    /// Active number of socket connections
+    pub fn active_conn_count(&self) -> usize {
+        self.active_conn_count.get() as usize
+    }
+
+    /// Total number of socket connections since the publisher was started
+    pub fn tot_conn_count(&self) -> usize {
+        self.tot_conn_count.get() as usize
+    }
+
+    /// Total number of published messages
+    pub fn published_msg_count(&self) -> usize {
+        self.published_msg_count.get() as usize
+    }
+}
+
+impl fmt::Debug for PublisherMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "PublisherMetrics(active_conn_count = {}, tot_conn_count = {}, published_msg_count = {})",
+            self.active_conn_count.get(),
+            self.tot_conn_count.get(),
+            self.published_msg_count.get()
+        )
+    }
+}
+
+#[allow(warnings)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        configure_logging,
+        pubsub::subscriber::{self, Subscription},
+        reqrep::client::DialerConfig,
+    };
+    use futures::task::Poll;
+    use oysterpack_trust::concurrent::execution::{self, *};
+    use std::{thread, time::Duration};
+
+    /// polls the subscription without blocking
+    fn try_next(executor: &mut Executor, subscription: &mut Subscription) -> Option<nng::Message> {
+        executor.run(future::poll_fn(|waker| {
+            Poll::Ready(match subscription.poll_next_unpin(waker) {
+                Poll::Ready(msg) => msg,
+                Poll::Pending => None,
+            })
+        }))
+    }
+
+    #[test]
+    fn slow_subscriber_drops_messages() {
+        configure_logging();
+        let mut executor = execution::global_executor();
+        let pubsub_id = PubSubId::generate();
+
+        // GIVEN: the publisher is running
+        let url = url::Url::parse(&format!("inproc://{}", ULID::generate())).unwrap();
+        let mut publisher = spawn(
+            None,
+            ListenerConfig::new(url.clone()),
+            pubsub_id,
+            executor.clone(),
+        )
+        .unwrap();
+
+        // AND: a subscriber with a single message buffer, which is not being consumed
+        let mut subscription = subscriber::subscribe(
+            None,
+            DialerConfig::new(url.clone()),
+            pubsub_id,
+            vec![],
+            1,
+            executor.clone(),
+        )
+        .unwrap();
+        for _ in 0..100 {
+            if publisher.metrics().active_conn_count() == 1 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(publisher.metrics().active_conn_count(), 1);
+
+        // WHEN: more messages are published than the subscriber can buffer
+        const MSG_COUNT: usize = 10_000;
+        let topic = Topic::from("");
+        for i in 0..MSG_COUNT {
+            let mut msg = nng::Message::new().unwrap();
+            msg.push_back(i.to_string().as_bytes()).unwrap();
+            // THEN: the publisher is never blocked by the slow subscriber
+            executor.run(publisher.publish(&topic, msg)).unwrap();
+        }
+        assert_eq!(publisher.metrics().published_msg_count(), MSG_COUNT);
+
+        // AND: the messages that the subscriber could not keep up with were dropped
+        let mut received_count = 0;
+        let mut idle_count = 0;
+        while idle_count < 10 {
+            match try_next(&mut executor, &mut subscription) {
+                Some(_) => {
+                    received_count += 1;
+                    idle_count = 0;
+                }
+                None => {
+                    idle_count += 1;
+                    thread::sleep(Duration::from_millis(1));
+                }
+            }
+        }
+        assert!(received_count > 0);
+        assert!(
+            received_count < MSG_COUNT,
+            "received_count = {}",
+            received_count
+        );
+
+        assert!(publisher.stop_async().unwrap());
+        // stopping an already stopped publisher is a no-op
+        assert!(!publisher.stop_async().unwrap());
+        assert!(publisher.stop_signalled());
+        publisher.await_shutdown();
+    }
+}
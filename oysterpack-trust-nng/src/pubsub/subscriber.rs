@@ -0,0 +1,385 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Provides an nng Sub0 subscriber.
+//!
+//! [subscribe()](fn.subscribe.html) returns a [Subscription](struct.Subscription.html), which is a
+//! futures `Stream` of messages.
+//!
+//! ## Design
+//! The subscriber dials into the publisher using a Sub0 socket. A subscriber task runs an Aio event
+//! loop, which receives messages from the socket and forwards them to the Subscription via an async
+//! channel. The Subscription channel is bounded, thus if the Subscription is not able to keep up,
+//! then the subscriber task will stop receiving messages from the socket. In that case, nng will drop
+//! messages that the subscriber is not able to keep up with.
+//!
+//! <pre>
+//! publisher ---> Socket --> Aio Callback --> Aio Event Loop ---> Subscription (Stream)
+//! </pre>
+//!
+//! When the Subscription is dropped, the subscriber task will close the nng Dialer and Socket.
+//!
+//! ## Config
+//! - [SocketConfig](../../config/struct.SocketConfig.html)
+//! - [DialerConfig](../../reqrep/client/struct.DialerConfig.html)
+//!
+//! ## Metrics
+//! - active number of socket connections - [ACTIVE_CONN_COUNT_METRIC_ID](constant.ACTIVE_CONN_COUNT_METRIC_ID.html)
+//! - total number of messages received - [RECEIVED_MSG_COUNT_METRIC_ID](constant.RECEIVED_MSG_COUNT_METRIC_ID.html)
+
+use super::{PubSubId, Topic, PUBSUB_ID_LABEL_ID};
+use crate::{
+    config::{SocketConfig, SocketConfigError},
+    reqrep::client::{DialerConfig, DialerConfigError},
+    task,
+};
+use failure::Fail;
+use futures::{
+    channel::mpsc,
+    prelude::*,
+    sink::SinkExt,
+    stream::StreamExt,
+    task::{Poll, SpawnExt, Waker},
+};
+use lazy_static::lazy_static;
+use nng::options::Options;
+use oysterpack_log::*;
+use oysterpack_trust::{concurrent::execution::Executor, metrics};
+use std::{fmt, pin::Pin};
+
+lazy_static! {
+
+    /// the metric is incremented on nng::PipeEvent::AddPost and decremented on nng::PipeEvent::RemovePost
+    static ref ACTIVE_CONN_COUNT: prometheus::IntGaugeVec = metrics::registry().register_int_gauge_vec(
+        ACTIVE_CONN_COUNT_METRIC_ID,
+        "Active number of subscriber socket connections",
+        &[PUBSUB_ID_LABEL_ID],
+        None
+    ).unwrap();
+
+    /// the metric is incremented when a message is received from the socket
+    static ref RECEIVED_MSG_COUNT: prometheus::IntCounterVec = metrics::registry().register_int_counter_vec(
+        RECEIVED_MSG_COUNT_METRIC_ID,
+        "Total number of messages received by subscribers",
+        &[PUBSUB_ID_LABEL_ID],
+        None
+    ).unwrap();
+
+}
+
+/// IntGaugeVec MetricId which is used to track the number of active subscriber socket connections by PubSubId: `M01M57F30XFFHF75E2831Y7DQB7`
+pub const ACTIVE_CONN_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166788952699959509828664158244494695);
+/// IntCounterVec MetricId which is used to track the total number of messages received by PubSubId: `M01M57F30XH0XQXPDPVVR8FAVEQ`
+pub const RECEIVED_MSG_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166788952701825165713665024985492951);
+
+/// The default Subscription channel buffer size
+pub const DEFAULT_CHAN_BUF_SIZE: usize = 256;
+
+/// Subscribes to the specified topics.
+/// - if no topics are specified, then all messages are subscribed to
+/// - chan_buf_size is the Subscription channel buffer size
+pub fn subscribe(
+    socket_config: Option<SocketConfig>,
+    dialer_config: DialerConfig,
+    pubsub_id: PubSubId,
+    topics: Vec<Topic>,
+    chan_buf_size: usize,
+    mut executor: Executor,
+) -> Result<Subscription, SubscribeError> {
+    let active_conn_count =
+        ACTIVE_CONN_COUNT.with_label_values(&[pubsub_id.to_string().as_str()]);
+    let received_msg_count =
+        RECEIVED_MSG_COUNT.with_label_values(&[pubsub_id.to_string().as_str()]);
+
+    let create_socket = || {
+        let active_conn_count = active_conn_count.clone();
+        let mut socket =
+            nng::Socket::new(nng::Protocol::Sub0).map_err(SubscribeError::SocketCreateFailure)?;
+        socket.set_nonblocking(true);
+        socket
+            .pipe_notify(move |pipe, event| {
+                match event {
+                    nng::PipeEvent::AddPost => active_conn_count.inc(),
+                    nng::PipeEvent::RemovePost => active_conn_count.dec(),
+                    _ => (),
+                }
+                debug!("{:?} {:?}", pipe, event);
+            })
+            .map_err(SubscribeError::SocketCreateFailure)?;
+        let socket = match socket_config {
+            Some(socket_config) => socket_config
+                .apply(socket)
+                .map_err(SubscribeError::SocketConfigApplyFailed)?,
+            None => socket,
+        };
+        if topics.is_empty() {
+            socket
+                .set_opt::<nng::options::protocol::pubsub::Subscribe>(vec![])
+                .map_err(SubscribeError::SubscribeFailed)?;
+        }
+        for topic in topics.iter() {
+            socket
+                .set_opt::<nng::options::protocol::pubsub::Subscribe>(topic.as_bytes().to_vec())
+                .map_err(SubscribeError::SubscribeFailed)?;
+        }
+        Ok(socket)
+    };
+
+    let socket = create_socket()?;
+    let dialer = dialer_config
+        .start_dialer(&socket)
+        .map_err(SubscribeError::DialerStartError)?;
+
+    // used to notify the subscriber task when an Aio event has occurred, i.e., the Aio callback has been invoked
+    let (aio, aio_rx) =
+        task::aio_notifier("subscriber").map_err(SubscribeError::AioCreateWithCallbackFailure)?;
+
+    let (mut msg_tx, msg_rx) = mpsc::channel::<nng::Message>(chan_buf_size);
+    // used to notify the task that the Subscription has been dropped
+    let (stop_tx, stop_rx) = mpsc::channel::<()>(0);
+    executor
+        .spawn(
+            async move {
+                debug!("Subscriber({}) is running ...", pubsub_id);
+                // fuse the streams that will be polled via futures::select! - per the documentation
+                let mut aio_rx = aio_rx.fuse();
+                let mut stop_rx = stop_rx.fuse();
+
+                let recv = || {
+                    if let Err(err) = socket.recv_async(&aio) {
+                        // TODO: trigger alert - async I/O errors need to be investigated
+                        error!("Socket::recv_async() failed: {}", err);
+                    }
+                };
+
+                recv();
+                loop {
+                    futures::select! {
+                        event = aio_rx.next() => match event {
+                            // NOTE: aio.result().unwrap() is safe because we are being signalled
+                            // by the Aio callback to handle an Aio event
+                            Some(_) => match aio.result().unwrap() {
+                                Ok(_) => {
+                                    if let Some(msg) = aio.get_msg() {
+                                        received_msg_count.inc();
+                                        if await!(msg_tx.send(msg)).is_err() {
+                                            debug!("Subscription channel is disconnected - thus we are done");
+                                            break;
+                                        }
+                                    }
+                                    recv();
+                                },
+                                Err(nng::Error::Closed) => break,
+                                Err(err) => {
+                                    error!("Aio error: {}", err);
+                                    recv();
+                                }
+                            },
+                            None => break
+                        },
+                        _ = stop_rx.next() => break,
+                    }
+                }
+                aio.cancel();
+                dialer.close();
+                socket.close();
+                debug!("Subscriber({}) is done", pubsub_id);
+            },
+        )
+        .map_err(|err| SubscribeError::ExecutorSpawnError {
+            is_executor_shutdown: err.is_shutdown(),
+        })?;
+
+    Ok(Subscription {
+        pubsub_id,
+        msg_rx,
+        _stop_tx: stop_tx,
+    })
+}
+
+/// A Subscription is a futures Stream of messages.
+/// - when the Subscription is dropped, then the subscriber's Dialer and Socket are closed
+pub struct Subscription {
+    pubsub_id: PubSubId,
+    msg_rx: mpsc::Receiver<nng::Message>,
+    // when the Subscription is dropped, the sender is dropped, which signals the subscriber task to stop
+    _stop_tx: mpsc::Sender<()>,
+}
+
+impl Subscription {
+    /// Returns the PubSubId
+    pub fn pubsub_id(&self) -> PubSubId {
+        self.pubsub_id
+    }
+}
+
+impl Stream for Subscription {
+    type Item = nng::Message;
+
+    fn poll_next(mut self: Pin<&mut Self>, waker: &Waker) -> Poll<Option<Self::Item>> {
+        self.msg_rx.poll_next_unpin(waker)
+    }
+}
+
+impl fmt::Debug for Subscription {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Subscription({})", self.pubsub_id)
+    }
+}
+
+/// Errors that could happen while trying to subscribe
+#[derive(Debug, Fail)]
+pub enum SubscribeError {
+    /// Failed to create Socket
+    #[fail(display = "Failed to create Socket: {}", _0)]
+    SocketCreateFailure(#[cause] nng::Error),
+    /// Failed to apply SocketConfig options
+    #[fail(display = "{}", _0)]
+    SocketConfigApplyFailed(#[cause] SocketConfigError),
+    /// Failed to set the Subscribe socket option
+    #[fail(display = "Failed to subscribe to topic: {}", _0)]
+    SubscribeFailed(#[cause] nng::Error),
+    /// Failed to start the dialer
+    #[fail(display = "Failed to start dialer: {}", _0)]
+    DialerStartError(#[cause] DialerConfigError),
+    /// Failed to create Aio
+    #[fail(display = "Failed to create Aio with callback: {}", _0)]
+    AioCreateWithCallbackFailure(#[cause] nng::Error),
+    /// An error that occurred during spawning.
+    #[fail(
+        display = "Spawning Future failed: executor shutdown = {}",
+        is_executor_shutdown
+    )]
+    ExecutorSpawnError {
+        /// whether spawning failed because the executor is shut down
+        is_executor_shutdown: bool,
+    },
+}
+
+#[allow(warnings)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{configure_logging, pubsub::publisher, reqrep::server::ListenerConfig};
+    use oysterpack_trust::concurrent::execution::{self, *};
+    use oysterpack_uid::ULID;
+    use std::{thread, time::Duration};
+
+    fn publish(
+        publisher: &publisher::PublisherHandle,
+        executor: &mut Executor,
+        topic: &Topic,
+        body: &[u8],
+    ) {
+        let mut msg = nng::Message::new().unwrap();
+        msg.push_back(body).unwrap();
+        executor.run(publisher.publish(topic, msg)).unwrap();
+    }
+
+    #[test]
+    fn pubsub_topics() {
+        configure_logging();
+        let mut executor = execution::global_executor();
+        let pubsub_id = PubSubId::generate();
+
+        // GIVEN: the publisher is running
+        let url = url::Url::parse(&format!("inproc://{}", ULID::generate())).unwrap();
+        let mut publisher = publisher::spawn(
+            None,
+            ListenerConfig::new(url.clone()),
+            pubsub_id,
+            executor.clone(),
+        )
+        .unwrap();
+        assert!(publisher.ping());
+        assert!(publisher::PublisherHandle::get(publisher.id()).is_some());
+        assert_eq!(
+            publisher::PublisherHandle::get_by_pubsub_id(pubsub_id).len(),
+            1
+        );
+
+        // AND: subscribers are connected
+        let foo = Topic::from("foo");
+        let bar = Topic::from("bar");
+        let mut foo_subscription = subscribe(
+            None,
+            DialerConfig::new(url.clone()),
+            pubsub_id,
+            vec![foo.clone()],
+            DEFAULT_CHAN_BUF_SIZE,
+            executor.clone(),
+        )
+        .unwrap();
+        let mut all_subscription = subscribe(
+            None,
+            DialerConfig::new(url.clone()),
+            pubsub_id,
+            vec![],
+            DEFAULT_CHAN_BUF_SIZE,
+            executor.clone(),
+        )
+        .unwrap();
+        for _ in 0..100 {
+            if publisher.metrics().active_conn_count() == 2 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(publisher.metrics().active_conn_count(), 2);
+
+        // WHEN: messages are published on different topics
+        publish(&publisher, &mut executor, &bar, b"1");
+        publish(&publisher, &mut executor, &foo, b"2");
+
+        // THEN: the foo subscriber only receives foo messages, with the topic prefix
+        let msg = executor.run(foo_subscription.next()).unwrap();
+        assert_eq!(&msg[..], b"foo2");
+        assert!(foo.matches(&msg));
+        // AND: the subscriber that subscribed to all topics receives all messages
+        let msg = executor.run(all_subscription.next()).unwrap();
+        assert_eq!(&msg[..], b"bar1");
+        let msg = executor.run(all_subscription.next()).unwrap();
+        assert_eq!(&msg[..], b"foo2");
+        assert_eq!(publisher.metrics().published_msg_count(), 2);
+
+        // WHEN: a subscription is dropped
+        drop(all_subscription);
+        for _ in 0..100 {
+            if publisher.metrics().active_conn_count() == 1 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        // THEN: the subscriber connection is closed
+        assert_eq!(publisher.metrics().active_conn_count(), 1);
+
+        // WHEN: the publisher is stopped
+        assert!(publisher.stop_async().unwrap());
+        let publisher_id = publisher.id();
+        let handle = publisher.clone();
+        publisher.await_shutdown();
+        // THEN: the publisher is unregistered
+        assert!(publisher::PublisherHandle::get(publisher_id).is_none());
+        // AND: publishing fails
+        let mut msg = nng::Message::new().unwrap();
+        match executor.run(handle.publish(&foo, msg)) {
+            Err(publisher::PublishError::PublisherStopped) => (),
+            other => panic!("expected PublisherStopped: {:?}", other),
+        }
+    }
+}
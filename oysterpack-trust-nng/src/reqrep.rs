@@ -16,6 +16,12 @@
 
 //! Provides support for the request/reply messaging protocol.
 //! - the service client interface is defined by [Client](client/type.Client.html)
+//! - typed clients and services are supported via [codec](codec/index.html)
+//!   - typed service failures are reported to the client via the reply [envelope](envelope/index.html)
+//! - client retries, hedged requests, and circuit breaking are supported via [policy](policy/index.html)
 
 pub mod client;
+pub mod codec;
+pub mod envelope;
+pub mod policy;
 pub mod server;
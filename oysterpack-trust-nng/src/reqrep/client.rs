@@ -38,8 +38,45 @@
 //! When a Client submits a request, the request / reply workflow is serviced by one of the Aio callback
 //! tasks. If all Aio context tasks are busy, then requests will wait asynchronously in a non-blocking
 //! manner for an Aio context task.
-
-use crate::config::{self, SocketConfigError};
+//!
+//! ## Multiple Endpoints
+//! A client can dial multiple server endpoints - see [DialerConfig::add_endpoint()](struct.DialerConfig.html#method.add_endpoint).
+//! Each endpoint is dialed by its own nng::Dialer on the same socket. The Req0 socket load balances
+//! requests across all connected endpoints. Endpoints can be added and removed at runtime via
+//! [add_endpoint()](fn.add_endpoint.html) and [remove_endpoint()](fn.remove_endpoint.html).
+//!
+//! ## Endpoint Metrics
+//! - active number of connections per endpoint - [ENDPOINT_ACTIVE_CONN_COUNT_METRIC_ID](constant.ENDPOINT_ACTIVE_CONN_COUNT_METRIC_ID.html)
+//! - total number of connections per endpoint - [ENDPOINT_TOT_CONN_COUNT_METRIC_ID](constant.ENDPOINT_TOT_CONN_COUNT_METRIC_ID.html)
+//!
+//! ## Client Metrics
+//! All client metrics are labeled by ReqRepId - see [client_metrics()](fn.client_metrics.html)
+//! - Aio Context busy and idle counts - [AIO_CONTEXT_BUSY_COUNT_METRIC_ID](constant.AIO_CONTEXT_BUSY_COUNT_METRIC_ID.html),
+//!   [AIO_CONTEXT_IDLE_COUNT_METRIC_ID](constant.AIO_CONTEXT_IDLE_COUNT_METRIC_ID.html)
+//! - time spent waiting for an Aio Context - [AIO_CONTEXT_WAIT_TIMER_METRIC_ID](constant.AIO_CONTEXT_WAIT_TIMER_METRIC_ID.html)
+//! - request round trip time - [REQUEST_RTT_TIMER_METRIC_ID](constant.REQUEST_RTT_TIMER_METRIC_ID.html)
+//! - dialer reconnects - [DIALER_RECONNECT_COUNT_METRIC_ID](constant.DIALER_RECONNECT_COUNT_METRIC_ID.html)
+//! - RequestError counts by variant - [REQUEST_ERROR_COUNT_METRIC_ID](constant.REQUEST_ERROR_COUNT_METRIC_ID.html)
+//!
+//! The timer histograms are configured using the ReqRepConfig's metric timer buckets.
+//!
+//! ## Request Policy
+//! Retries, hedged requests, and a circuit breaker can be configured via
+//! [DialerConfig::set_request_policy()](struct.DialerConfig.html#method.set_request_policy) - see
+//! [policy](../policy/index.html).
+
+use crate::{
+    config::{self, SocketConfigError},
+    message,
+    reqrep::{
+        codec::CodecError,
+        policy::{PolicyHandler, RequestPolicy},
+        server::REQREP_LABEL_ID,
+    },
+    ws::{WsConfig, WsConfigError},
+};
+#[cfg(feature = "tls")]
+use crate::tls::{TlsConfig, TlsConfigError};
 use failure::Fail;
 use futures::{
     channel::{mpsc, oneshot},
@@ -48,15 +85,21 @@ use futures::{
     stream::StreamExt,
     task::SpawnExt,
 };
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
 use lazy_static::lazy_static;
 use nng::options::Options;
 use oysterpack_log::*;
-use oysterpack_trust::concurrent::{
-    execution::Executor,
-    messaging::reqrep::{self, ReqRep, ReqRepId},
+use oysterpack_trust::{
+    concurrent::{
+        execution::Executor,
+        messaging::{
+            errors::ChannelError,
+            reqrep::{self, ReqRep, ReqRepId},
+        },
+    },
+    metrics,
 };
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use serde::{Deserialize, Serialize};
 use std::{fmt, num::NonZeroUsize, panic::AssertUnwindSafe, sync::Arc, time::Duration};
 
@@ -66,8 +109,90 @@ lazy_static! {
 
     /// Global ReqRep nng client registry
     static ref CLIENTS: RwLock<HashMap<ReqRepId, Client>> = RwLock::new(HashMap::new());
+
+    /// the metric is incremented on nng::PipeEvent::AddPost and decremented on nng::PipeEvent::RemovePost
+    static ref ENDPOINT_ACTIVE_CONN_COUNT: prometheus::IntGaugeVec = metrics::registry().register_int_gauge_vec(
+        ENDPOINT_ACTIVE_CONN_COUNT_METRIC_ID,
+        "Active number of client connections per endpoint",
+        &[REQREP_LABEL_ID, ENDPOINT_LABEL_ID],
+        None
+    ).unwrap();
+
+    /// the metric is incremented on nng::PipeEvent::AddPost
+    static ref ENDPOINT_TOT_CONN_COUNT: prometheus::IntCounterVec = metrics::registry().register_int_counter_vec(
+        ENDPOINT_TOT_CONN_COUNT_METRIC_ID,
+        "Total number of client connections per endpoint",
+        &[REQREP_LABEL_ID, ENDPOINT_LABEL_ID],
+        None
+    ).unwrap();
+
+    /// Client metrics are registered once per ReqRepId, because the timer histograms are registered per ReqRepId
+    static ref CLIENT_METRICS: RwLock<HashMap<ReqRepId, ClientMetrics>> = RwLock::new(HashMap::new());
+
+    static ref AIO_CONTEXT_BUSY_COUNT: prometheus::IntGaugeVec = metrics::registry().register_int_gauge_vec(
+        AIO_CONTEXT_BUSY_COUNT_METRIC_ID,
+        "Number of client Aio Contexts that are processing a request",
+        &[REQREP_LABEL_ID],
+        None
+    ).unwrap();
+
+    static ref AIO_CONTEXT_IDLE_COUNT: prometheus::IntGaugeVec = metrics::registry().register_int_gauge_vec(
+        AIO_CONTEXT_IDLE_COUNT_METRIC_ID,
+        "Number of client Aio Contexts that are available to process requests",
+        &[REQREP_LABEL_ID],
+        None
+    ).unwrap();
+
+    /// the metric is incremented on nng::PipeEvent::AddPost, if the endpoint had previously been connected
+    static ref DIALER_RECONNECT_COUNT: prometheus::IntCounterVec = metrics::registry().register_int_counter_vec(
+        DIALER_RECONNECT_COUNT_METRIC_ID,
+        "Number of times the client reconnected to an endpoint",
+        &[REQREP_LABEL_ID],
+        None
+    ).unwrap();
+
+    static ref REQUEST_ERROR_COUNT: prometheus::IntCounterVec = metrics::registry().register_int_counter_vec(
+        REQUEST_ERROR_COUNT_METRIC_ID,
+        "Number of failed client requests by RequestError variant",
+        &[REQREP_LABEL_ID, REQUEST_ERROR_LABEL_ID],
+        None
+    ).unwrap();
 }
 
+/// IntGaugeVec MetricId for the number of busy Aio Contexts by ReqRepId: `M01M57FXC1KPASMB55DZ0QW6TKY`
+pub const AIO_CONTEXT_BUSY_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166789996443247408104075356012767870);
+/// IntGaugeVec MetricId for the number of idle Aio Contexts by ReqRepId: `M01M57FXC1NHE8R4RXV95HH14H1`
+pub const AIO_CONTEXT_IDLE_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166789996445480464642909796933079585);
+/// Histogram MetricId for the time spent waiting for an Aio Context in seconds: `M01M57FXC1REG2ARARR2DA4YVA4`
+/// - the ReqRepId is stored as a const label using [REQREP_LABEL_ID](../server/constant.REQREP_LABEL_ID.html)
+pub const AIO_CONTEXT_WAIT_TIMER_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166789996448996029692210187896581444);
+/// Histogram MetricId for the request round trip time in seconds: `M01M57FXC1VQCKWEGQEKRT7A400`
+/// - the ReqRepId is stored as a const label using [REQREP_LABEL_ID](../server/constant.REQREP_LABEL_ID.html)
+pub const REQUEST_RTT_TIMER_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166789996452958742759645255507972096);
+/// IntCounterVec MetricId for the number of dialer reconnects by ReqRepId: `M01M57FXC1XFT8Y6D17WTQ32AQC`
+pub const DIALER_RECONNECT_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166789996455090487411744770565941996);
+/// IntCounterVec MetricId for the number of failed requests by ReqRepId and RequestError variant: `M01M57FXC1ZQQV7R4RBJC3VJKC8`
+pub const REQUEST_ERROR_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166789996457807703829294255489764744);
+/// Metric LabelId which is used to store the RequestError variant name: `L01M57FXC1HSZJ982JYSG1XWDP4`
+pub const REQUEST_ERROR_LABEL_ID: metrics::LabelId =
+    metrics::LabelId(2166789996440967414636928260219221700);
+
+/// IntGaugeVec MetricId which is used to track the number of active connections by ReqRepId and endpoint URL: `M01M57FK1Q6T5THMW8M68H3XXJ8`
+pub const ENDPOINT_ACTIVE_CONN_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166789587520607392551252269408908872);
+/// IntCounterVec MetricId which is used to track the total number of connections by ReqRepId and endpoint URL: `M01M57FK1Q9GKPX5RPM5E8PXP4K`
+pub const ENDPOINT_TOT_CONN_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166789587523872774690819092777982099);
+/// Metric LabelId which is used to store the endpoint URL: `L01M57FK1Q4WP26GKAV6SPBJC9E`
+pub const ENDPOINT_LABEL_ID: metrics::LabelId =
+    metrics::LabelId(2166789587518284270553278829158805806);
+
 /// Client type alias
 pub type Client = ReqRep<nng::Message, Result<nng::Message, RequestError>>;
 
@@ -87,6 +212,7 @@ pub fn register_client(
     }
     let nng_client = NngClient::new(
         reqrep_service_config.reqrep_id(),
+        reqrep_service_config.metric_timer_buckets(),
         socket_config,
         dialer_config,
         executor.clone(),
@@ -119,21 +245,281 @@ pub fn registered_client_ids() -> Vec<ReqRepId> {
     CLIENTS.read().keys().cloned().collect()
 }
 
-/// The context that is required by the NngClient's backend service.
+/// Returns the client metrics - only if a client has been registered with the specified ReqRepId
+pub fn client_metrics(reqrep_id: ReqRepId) -> Option<ClientMetrics> {
+    CLIENT_METRICS.read().get(&reqrep_id).cloned()
+}
+
+/// Client metrics
 #[derive(Clone)]
+pub struct ClientMetrics {
+    reqrep_id: ReqRepId,
+    aio_context_busy_count: prometheus::IntGauge,
+    aio_context_idle_count: prometheus::IntGauge,
+    aio_context_wait_timer: prometheus::Histogram,
+    request_rtt_timer: prometheus::Histogram,
+    dialer_reconnect_count: prometheus::IntCounter,
+}
+
+impl ClientMetrics {
+    /// The metrics are registered once per ReqRepId, i.e., they are shared by re-created clients
+    /// - the histogram timers cannot be re-registered, thus re-created clients must use the same
+    ///   metric timer buckets
+    fn get_or_register(
+        reqrep_id: ReqRepId,
+        metric_timer_buckets: &[f64],
+    ) -> Result<ClientMetrics, NngClientError> {
+        let mut client_metrics = CLIENT_METRICS.write();
+        if let Some(metrics) = client_metrics.get(&reqrep_id) {
+            let registered_buckets = metrics.metric_timer_buckets();
+            if registered_buckets.as_slice() != metric_timer_buckets {
+                return Err(NngClientError::MetricTimerBucketsMismatch {
+                    registered: registered_buckets,
+                    requested: metric_timer_buckets.to_vec(),
+                });
+            }
+            return Ok(metrics.clone());
+        }
+        let reqrep_id_label = reqrep_id.to_string();
+        let labels = [reqrep_id_label.as_str()];
+        let register_timer = |metric_id, help| {
+            let mut const_labels = std::collections::HashMap::new();
+            const_labels.insert(REQREP_LABEL_ID, reqrep_id.to_string());
+            metrics::registry()
+                .register_histogram(
+                    metric_id,
+                    help,
+                    metric_timer_buckets.to_vec(),
+                    Some(const_labels),
+                )
+                .map_err(NngClientError::MetricRegistrationFailed)
+        };
+        let metrics = ClientMetrics {
+            reqrep_id,
+            aio_context_busy_count: AIO_CONTEXT_BUSY_COUNT.with_label_values(&labels),
+            aio_context_idle_count: AIO_CONTEXT_IDLE_COUNT.with_label_values(&labels),
+            aio_context_wait_timer: register_timer(
+                AIO_CONTEXT_WAIT_TIMER_METRIC_ID,
+                "Time spent waiting for a client Aio Context in seconds",
+            )?,
+            request_rtt_timer: register_timer(
+                REQUEST_RTT_TIMER_METRIC_ID,
+                "Client request round trip time in seconds",
+            )?,
+            dialer_reconnect_count: DIALER_RECONNECT_COUNT.with_label_values(&labels),
+        };
+        client_metrics.insert(reqrep_id, metrics.clone());
+        Ok(metrics)
+    }
+
+    /// The timer histogram buckets that the metrics were registered with
+    pub fn metric_timer_buckets(&self) -> Vec<f64> {
+        self.request_rtt_timer
+            .metric()
+            .get_histogram()
+            .get_bucket()
+            .iter()
+            .map(|bucket| bucket.get_upper_bound())
+            .collect()
+    }
+
+    /// Number of Aio Contexts that are processing a request
+    pub fn aio_context_busy_count(&self) -> usize {
+        self.aio_context_busy_count.get() as usize
+    }
+
+    /// Number of Aio Contexts that are available to process requests
+    pub fn aio_context_idle_count(&self) -> usize {
+        self.aio_context_idle_count.get() as usize
+    }
+
+    /// Histogram timer tracking the time spent waiting for an Aio Context
+    pub fn aio_context_wait_timer(&self) -> &prometheus::Histogram {
+        &self.aio_context_wait_timer
+    }
+
+    /// Histogram timer tracking the request round trip time
+    pub fn request_rtt_timer(&self) -> &prometheus::Histogram {
+        &self.request_rtt_timer
+    }
+
+    /// Number of times the client has reconnected to an endpoint
+    pub fn dialer_reconnect_count(&self) -> usize {
+        self.dialer_reconnect_count.get() as usize
+    }
+
+    /// Number of failed requests for the specified RequestError variant name - see [RequestError::name()](enum.RequestError.html#method.name)
+    pub fn request_error_count(&self, name: &str) -> usize {
+        REQUEST_ERROR_COUNT
+            .with_label_values(&[self.reqrep_id.to_string().as_str(), name])
+            .get() as usize
+    }
+
+    fn request_failed(&self, err: &RequestError) {
+        REQUEST_ERROR_COUNT
+            .with_label_values(&[self.reqrep_id.to_string().as_str(), err.name()])
+            .inc();
+    }
+
+    fn aio_context_busy(&self) {
+        self.aio_context_busy_count.inc();
+        self.aio_context_idle_count.dec();
+    }
+
+    fn aio_context_idle(&self) {
+        self.aio_context_busy_count.dec();
+        self.aio_context_idle_count.inc();
+    }
+}
+
+impl fmt::Debug for ClientMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ClientMetrics({}) aio_context_busy_count = {}, aio_context_idle_count = {}, dialer_reconnect_count = {}",
+            self.reqrep_id,
+            self.aio_context_busy_count(),
+            self.aio_context_idle_count(),
+            self.dialer_reconnect_count()
+        )
+    }
+}
+
+/// Dials the endpoint using a new nng::Dialer on the client's socket
+/// - the dialer is configured using the client's DialerConfig settings
+pub fn add_endpoint(reqrep_id: ReqRepId, url: url::Url) -> Result<(), EndpointError> {
+    let client_contexts = CLIENT_CONTEXTS.read();
+    let ctx = client_contexts
+        .get(&reqrep_id)
+        .ok_or(EndpointError::ClientNotFound(reqrep_id))?;
+    let mut dialers = ctx.dialers.write();
+    if dialers.contains_key(&url) {
+        return Err(EndpointError::EndpointAlreadyExists(url));
+    }
+    let dialer = ctx
+        .dialer_config
+        .start_endpoint_dialer(ctx.socket.as_ref().unwrap(), &url)
+        .map_err(EndpointError::DialerStartError)?;
+    dialers.insert(url, dialer);
+    Ok(())
+}
+
+/// Closes the endpoint's nng::Dialer
+/// - returns false if the client does not have a dialer for the endpoint
+pub fn remove_endpoint(reqrep_id: ReqRepId, url: &url::Url) -> Result<bool, EndpointError> {
+    let client_contexts = CLIENT_CONTEXTS.read();
+    let ctx = client_contexts
+        .get(&reqrep_id)
+        .ok_or(EndpointError::ClientNotFound(reqrep_id))?;
+    let dialer = ctx.dialers.write().remove(url);
+    match dialer {
+        Some(dialer) => {
+            dialer.close();
+            // pipe removal events for the endpoint are ignored from here on, i.e., the endpoint's
+            // metric series are removed and must not be re-created
+            let mut pipes = ctx.pipes.lock();
+            pipes.urls.retain(|_, pipe_url| pipe_url.as_str() != url.as_str());
+            pipes.connected.remove(url.as_str());
+            drop(pipes);
+            let reqrep_id = reqrep_id.to_string();
+            let labels = [reqrep_id.as_str(), url.as_str()];
+            let _ = ENDPOINT_ACTIVE_CONN_COUNT.remove_label_values(&labels);
+            let _ = ENDPOINT_TOT_CONN_COUNT.remove_label_values(&labels);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Returns the endpoints that the client is dialing, or None if the client is not registered
+pub fn endpoints(reqrep_id: ReqRepId) -> Option<Vec<Endpoint>> {
+    CLIENT_CONTEXTS.read().get(&reqrep_id).map(|ctx| {
+        ctx.dialers
+            .read()
+            .keys()
+            .map(|url| Endpoint::new(reqrep_id, url.clone()))
+            .collect()
+    })
+}
+
+/// Client endpoint, i.e., a server URL that the client is dialing
+#[derive(Clone)]
+pub struct Endpoint {
+    url: url::Url,
+    active_conn_count: prometheus::IntGauge,
+    tot_conn_count: prometheus::IntCounter,
+}
+
+impl Endpoint {
+    fn new(reqrep_id: ReqRepId, url: url::Url) -> Endpoint {
+        let reqrep_id = reqrep_id.to_string();
+        let labels = [reqrep_id.as_str(), url.as_str()];
+        Endpoint {
+            active_conn_count: ENDPOINT_ACTIVE_CONN_COUNT.with_label_values(&labels),
+            tot_conn_count: ENDPOINT_TOT_CONN_COUNT.with_label_values(&labels),
+            url,
+        }
+    }
+
+    /// endpoint URL
+    pub fn url(&self) -> &url::Url {
+        &self.url
+    }
+
+    /// returns true if there is at least 1 active connection to the endpoint
+    pub fn connected(&self) -> bool {
+        self.active_conn_count() > 0
+    }
+
+    /// active number of connections to the endpoint
+    pub fn active_conn_count(&self) -> u64 {
+        self.active_conn_count.get() as u64
+    }
+
+    /// total number of connections that have been made to the endpoint
+    pub fn tot_conn_count(&self) -> u64 {
+        self.tot_conn_count.get() as u64
+    }
+}
+
+impl fmt::Debug for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Endpoint({}) active_conn_count = {}",
+            self.url,
+            self.active_conn_count()
+        )
+    }
+}
+
+/// The context that is required by the NngClient's backend service.
 struct NngClientContext {
     id: ReqRepId,
     socket: Option<nng::Socket>,
-    dialer: Option<nng::Dialer>,
+    dialer_config: DialerConfig,
+    dialers: RwLock<HashMap<url::Url, nng::Dialer>>,
+    pipes: Arc<Mutex<ClientPipes>>,
     aio_context_pool_return: mpsc::Sender<mpsc::Sender<Request>>,
 }
 
+/// Tracks the client socket's pipes, i.e., connections, by endpoint URL
+#[derive(Debug, Default)]
+struct ClientPipes {
+    /// maps pipe ids to the URL of the endpoint that the pipe is connected to
+    urls: HashMap<i32, String>,
+    /// endpoints that this client instance has connected to, which is used to detect reconnects
+    connected: HashSet<String>,
+}
+
 /// nng client
 #[derive(Clone)]
 struct NngClient {
     id: ReqRepId,
     borrow: mpsc::Sender<oneshot::Sender<mpsc::Sender<Request>>>,
     request_sender_pool_task_stop_tx: mpsc::Sender<()>,
+    policy: Option<Arc<PolicyHandler>>,
+    metrics: ClientMetrics,
 }
 
 impl NngClient {
@@ -145,32 +531,100 @@ impl NngClient {
     /// will be registered, which corresponds to the number of Aio Context handler tasks spawned.
     fn new(
         id: ReqRepId,
+        metric_timer_buckets: &[f64],
         socket_config: Option<SocketConfig>,
         dialer_config: DialerConfig,
         mut executor: Executor,
     ) -> Result<Self, NngClientError> {
         let mut nng_client_executor = executor.clone();
         let parallelism = dialer_config.parallelism();
+        // the hedged request needs its own socket context
+        if let Some(policy) = dialer_config.request_policy() {
+            if policy.hedge().is_some() && parallelism < 2 {
+                return Err(NngClientError::InvalidRequestPolicy(
+                    "hedged requests require parallelism > 1".to_string(),
+                ));
+            }
+        }
+        let client_metrics = ClientMetrics::get_or_register(id, metric_timer_buckets)?;
+        client_metrics.aio_context_busy_count.set(0);
+        client_metrics.aio_context_idle_count.set(parallelism as i64);
+        let policy = dialer_config
+            .request_policy()
+            .cloned()
+            .map(|policy| Arc::new(PolicyHandler::new(id, policy)));
         let (aio_context_pool_return, aio_context_pool_borrow) =
             mpsc::channel::<mpsc::Sender<Request>>(parallelism);
 
+        let dialer_reconnect_count = client_metrics.dialer_reconnect_count.clone();
         let create_context = move || {
             let socket = SocketConfig::create_socket(socket_config)
                 .map_err(NngClientError::SocketCreateFailure)?;
-            let dialer = dialer_config
-                .start_dialer(&socket)
-                .map_err(NngClientError::DialerStartError)?;
+            let reqrep_id = id.to_string();
+            let pipes = Arc::new(Mutex::new(ClientPipes::default()));
+            let notify_pipes = pipes.clone();
+            socket
+                .pipe_notify(move |pipe, event| {
+                    // connection metrics are tracked per endpoint, i.e., by the dialer's URL
+                    match event {
+                        nng::PipeEvent::AddPost => {
+                            // the URL is mapped when the pipe is added because the dialer may
+                            // already be closed by the time the pipe is removed
+                            let url = match pipe
+                                .dialer()
+                                .and_then(|dialer| dialer.get_opt::<nng::options::Url>().ok())
+                            {
+                                Some(url) => url,
+                                None => return,
+                            };
+                            let labels = [reqrep_id.as_str(), url.as_str()];
+                            ENDPOINT_ACTIVE_CONN_COUNT.with_label_values(&labels).inc();
+                            ENDPOINT_TOT_CONN_COUNT.with_label_values(&labels).inc();
+                            debug!("{:?} {:?} {}", pipe, event, url);
+                            let mut pipes = notify_pipes.lock();
+                            // the endpoint metrics outlive the client instance, i.e., reconnects
+                            // are tracked per client instance
+                            if !pipes.connected.insert(url.clone()) {
+                                dialer_reconnect_count.inc();
+                            }
+                            pipes.urls.insert(pipe.id(), url);
+                        }
+                        nng::PipeEvent::RemovePost => {
+                            // pipes for removed endpoints are no longer mapped
+                            let url = notify_pipes.lock().urls.remove(&pipe.id());
+                            if let Some(url) = url {
+                                let labels = [reqrep_id.as_str(), url.as_str()];
+                                ENDPOINT_ACTIVE_CONN_COUNT.with_label_values(&labels).dec();
+                                debug!("{:?} {:?} {}", pipe, event, url);
+                            }
+                        }
+                        _ => (),
+                    }
+                })
+                .map_err(NngClientError::PipeNotifyFailed)?;
+
+            let mut dialers = HashMap::new();
+            for url in dialer_config.urls() {
+                let dialer = dialer_config
+                    .start_endpoint_dialer(&socket, url)
+                    .map_err(NngClientError::DialerStartError)?;
+                dialers.insert(url.clone(), dialer);
+            }
 
             Ok(NngClientContext {
                 id,
                 socket: Some(socket),
-                dialer: Some(dialer),
+                dialer_config,
+                dialers: RwLock::new(dialers),
+                pipes,
                 aio_context_pool_return,
             })
         };
 
+        let worker_metrics = client_metrics.clone();
         let mut start_workers = move |ctx: &NngClientContext| {
             for i in 0..parallelism {
+                let client_metrics = worker_metrics.clone();
                 // used to notify the workers when an Aio event has occurred, i.e., the Aio callback has been invoked
                 let (aio_tx, mut aio_rx) = futures::channel::mpsc::unbounded::<()>();
                 let aio_tx = AssertUnwindSafe(aio_tx);
@@ -182,7 +636,7 @@ impl NngClient {
                         // means the channel has been disconnected because the worker Future task has completed
                         // the server is either being stopped, or the worker has crashed
                         // TODO: we need a way to know if the server is being shutdown
-                        warn!("Failed to nofify worker of Aio event. This means the worker is not running. The Aio Context will be closed: {}", err);
+                        warn!("Failed to notify worker of Aio event. This means the worker is not running. The Aio Context will be closed: {}", err);
                         // TODO: will cloning the Context work ? Context::close() cannot be invoked from the callback because it consumes the Context
                         //       and rust won't allow it because the Context is being referenced by the FnMut closure
                         callback_ctx.clone().close();
@@ -203,6 +657,8 @@ impl NngClient {
                     debug!("[{}-{}] NngClient Aio Context task is running", id, i);
                     while let Some(mut req) = await!(req_rx.next()) {
                         debug!("[{}-{}] NngClient: processing request", id, i);
+                        client_metrics.aio_context_busy();
+                        let rtt_timer = client_metrics.request_rtt_timer.start_timer();
                         if let Some(msg) = req.msg.take() {
                             // send the request
                             match context.send(&aio, msg) {
@@ -225,7 +681,10 @@ impl NngClient {
                                                         Ok(_) => {
                                                             match aio.get_msg() {
                                                                 Some(reply) => {
-                                                                    let _ = req.reply_chan.send(Ok(reply));
+                                                                    // the requester has gone away, e.g., the losing hedged request
+                                                                    if let Err(Ok(reply)) = req.reply_chan.send(Ok(reply)) {
+                                                                        message::global_pool().release(reply);
+                                                                    }
                                                                 },
                                                                 None => {
                                                                     let _ = req.reply_chan.send(Err(RequestError::NoReplyMessage));
@@ -250,7 +709,8 @@ impl NngClient {
                                         }
                                     }
                                 },
-                                Err((_msg, err)) =>  {
+                                Err((msg, err)) =>  {
+                                    message::global_pool().release(msg);
                                     let _ = req.reply_chan.send(Err(RequestError::SendFailed(err)));
                                     aio.cancel();
                                 }
@@ -258,6 +718,8 @@ impl NngClient {
                         } else {
                             let _ = req.reply_chan.send(Err(RequestError::InvalidRequest("BUG: Request was received with no nng::Message".to_string())));
                         }
+                        rtt_timer.observe_duration();
+                        client_metrics.aio_context_idle();
                         // add a request Sender back to the pool, indicating the worker is now available
                         if let Err(err) = await!(aio_context_pool_return.send(req_tx.clone())) {
                             error!("[{}-{}] Failed to return request sender back to the pool: {}",id, i, err)
@@ -326,6 +788,8 @@ impl NngClient {
             id,
             borrow: borrow_tx,
             request_sender_pool_task_stop_tx,
+            policy,
+            metrics: client_metrics,
         })
     }
 }
@@ -341,30 +805,23 @@ impl reqrep::Processor<nng::Message, Result<nng::Message, RequestError>> for Nng
         &mut self,
         req: nng::Message,
     ) -> reqrep::FutureReply<Result<nng::Message, RequestError>> {
-        let mut borrow = self.borrow.clone();
-
-        async move {
-            let (borrow_tx, borrow_rx) = oneshot::channel();
-            if await!(borrow.send(borrow_tx)).is_err() {
-                return Err(RequestError::AioContextPoolChannelDisconnected);
+        let aio_context_wait_timer = self.metrics.aio_context_wait_timer.clone();
+        let reply = match self.policy.as_ref() {
+            Some(policy) => {
+                let borrow = self.borrow.clone();
+                policy.clone().execute(req, move |req| {
+                    send_request(borrow.clone(), req, aio_context_wait_timer.clone()).boxed()
+                })
             }
-
-            let (tx, rx) = oneshot::channel();
-            let request = Request {
-                msg: Some(req),
-                reply_chan: tx,
-            };
-
-            match await!(borrow_rx) {
-                Ok(ref mut sender) => match await!(sender.send(request)) {
-                    Ok(_) => match await!(rx) {
-                        Ok(result) => result,
-                        Err(_) => Err(RequestError::ReplyChannelClosed),
-                    },
-                    Err(err) => Err(RequestError::AioContextChannelDisconnected(err)),
-                },
-                Err(_) => Err(RequestError::AioContextPoolChannelDisconnected),
+            None => send_request(self.borrow.clone(), req, aio_context_wait_timer).boxed(),
+        };
+        let metrics = self.metrics.clone();
+        async move {
+            let reply = await!(reply);
+            if let Err(ref err) = reply {
+                metrics.request_failed(err);
             }
+            reply
         }
             .boxed()
     }
@@ -374,19 +831,56 @@ impl reqrep::Processor<nng::Message, Result<nng::Message, RequestError>> for Nng
         let mut client_contexts = CLIENT_CONTEXTS.write();
         if let Some(mut context) = client_contexts.remove(&self.id) {
             let context = Arc::get_mut(&mut context).unwrap();
-            context.dialer.take().unwrap().close();
-            debug!("NngClient({}): closed nng::Dialer", self.id);
+            for (url, dialer) in context.dialers.get_mut().drain() {
+                dialer.close();
+                debug!("NngClient({}): closed nng::Dialer: {}", self.id, url);
+            }
             context.socket.take().unwrap().close();
             debug!("NngClient({}): closed nng::Socket ", self.id);
             // shutdown the Sender<Request> pool task
             self.borrow.close_channel();
             self.request_sender_pool_task_stop_tx.close_channel();
             debug!("NngClient({}): closed channels", self.id);
+            self.metrics.aio_context_busy_count.set(0);
+            self.metrics.aio_context_idle_count.set(0);
         }
         debug!("NngClient({}) is destroyed", self.id);
     }
 }
 
+/// Sends the request to an Aio Context worker, which is borrowed from the Aio Context pool
+/// - aio_context_wait_timer is used to track the time spent waiting for an Aio Context worker
+async fn send_request(
+    mut borrow: mpsc::Sender<oneshot::Sender<mpsc::Sender<Request>>>,
+    req: nng::Message,
+    aio_context_wait_timer: prometheus::Histogram,
+) -> Result<nng::Message, RequestError> {
+    let timer = aio_context_wait_timer.start_timer();
+    let (borrow_tx, borrow_rx) = oneshot::channel();
+    if await!(borrow.send(borrow_tx)).is_err() {
+        return Err(RequestError::AioContextPoolChannelDisconnected);
+    }
+
+    let (tx, rx) = oneshot::channel();
+    let request = Request {
+        msg: Some(req),
+        reply_chan: tx,
+    };
+
+    let borrow_rx = await!(borrow_rx);
+    timer.observe_duration();
+    match borrow_rx {
+        Ok(ref mut sender) => match await!(sender.send(request)) {
+            Ok(_) => match await!(rx) {
+                Ok(result) => result,
+                Err(_) => Err(RequestError::ReplyChannelClosed),
+            },
+            Err(err) => Err(RequestError::AioContextChannelDisconnected(err)),
+        },
+        Err(_) => Err(RequestError::AioContextPoolChannelDisconnected),
+    }
+}
+
 /// Client registration errors
 #[derive(Debug, Fail)]
 pub enum ClientRegistrationError {
@@ -407,6 +901,9 @@ pub enum NngClientError {
     /// Failed to start Dialer
     #[fail(display = "Failed to start Dialer: {}", _0)]
     DialerStartError(#[cause] DialerConfigError),
+    /// Failed to register the socket pipe notify callback
+    #[fail(display = "Failed to register the socket pipe notify callback: {}", _0)]
+    PipeNotifyFailed(#[cause] nng::Error),
     /// Failed to create nng::Context
     #[fail(display = "Failed to create nng::Context: {}", _0)]
     NngContextCreateFailed(#[cause] nng::Error),
@@ -428,6 +925,37 @@ pub enum NngClientError {
         _0
     )]
     ReqRepServiceStartFailed(bool),
+    /// The request policy is not supported by the DialerConfig - see [HedgePolicy](../policy/struct.HedgePolicy.html)
+    #[fail(display = "Invalid request policy: {}", _0)]
+    InvalidRequestPolicy(String),
+    /// Failed to register the client metrics, e.g., the metric timer buckets are invalid
+    #[fail(display = "Failed to register client metrics: {}", _0)]
+    MetricRegistrationFailed(#[cause] prometheus::Error),
+    /// The client metrics are already registered for the ReqRepId using different metric timer buckets
+    #[fail(
+        display = "Client metrics are registered with timer buckets {:?}, but {:?} were requested",
+        registered, requested
+    )]
+    MetricTimerBucketsMismatch {
+        /// the buckets that the client metrics are registered with
+        registered: Vec<f64>,
+        /// the buckets that the re-created client was configured with
+        requested: Vec<f64>,
+    },
+}
+
+/// Endpoint related errors
+#[derive(Debug, Fail)]
+pub enum EndpointError {
+    /// The client is not registered
+    #[fail(display = "Client is not registered: {}", _0)]
+    ClientNotFound(ReqRepId),
+    /// The client is already dialing the endpoint
+    #[fail(display = "Endpoint already exists: {}", _0)]
+    EndpointAlreadyExists(url::Url),
+    /// Failed to start the endpoint dialer
+    #[fail(display = "Failed to start Dialer: {}", _0)]
+    DialerStartError(#[cause] DialerConfigError),
 }
 
 /// Request related errors
@@ -455,6 +983,47 @@ pub enum RequestError {
     /// No reply message
     #[fail(display = "BUG: No reply message was found - this should never happen")]
     NoReplyMessage,
+    /// Failed to encode the request - see [TypedClient](../codec/struct.TypedClient.html)
+    #[fail(display = "Failed to encode request: {}", _0)]
+    EncodeFailed(#[cause] CodecError),
+    /// Failed to decode the reply - see [TypedClient](../codec/struct.TypedClient.html)
+    #[fail(display = "Failed to decode reply: {}", _0)]
+    DecodeFailed(#[cause] CodecError),
+    /// The service failed to decode the request or encode the reply, which is reported via the reply
+    /// [envelope](../envelope/index.html) - see [TypedClient](../codec/struct.TypedClient.html)
+    #[fail(display = "Service codec failed: {}", _0)]
+    ServiceCodecFailed(#[cause] CodecError),
+    /// The ReqRep channel used to send the request to the client backend service failed
+    #[fail(display = "ReqRep channel failed: {}", _0)]
+    ReqRepChannelFailed(#[cause] ChannelError),
+    /// The circuit breaker is open - see [RequestPolicy](../policy/struct.RequestPolicy.html)
+    #[fail(display = "Circuit breaker is open: {}", _0)]
+    CircuitOpen(ReqRepId),
+    /// The server timed out processing the request, which is reported via the reply
+    /// [envelope](../envelope/index.html) - see [TypedClient](../codec/struct.TypedClient.html)
+    #[fail(display = "The server timed out processing the request")]
+    ProcessingTimeout,
+}
+
+impl RequestError {
+    /// Returns the variant name, which is used to label the [REQUEST_ERROR_COUNT_METRIC_ID](constant.REQUEST_ERROR_COUNT_METRIC_ID.html) metric
+    pub fn name(&self) -> &'static str {
+        match self {
+            RequestError::AioContextPoolChannelDisconnected => "AioContextPoolChannelDisconnected",
+            RequestError::AioContextChannelDisconnected(_) => "AioContextChannelDisconnected",
+            RequestError::ReplyChannelClosed => "ReplyChannelClosed",
+            RequestError::SendFailed(_) => "SendFailed",
+            RequestError::RecvFailed(_) => "RecvFailed",
+            RequestError::InvalidRequest(_) => "InvalidRequest",
+            RequestError::NoReplyMessage => "NoReplyMessage",
+            RequestError::EncodeFailed(_) => "EncodeFailed",
+            RequestError::DecodeFailed(_) => "DecodeFailed",
+            RequestError::ServiceCodecFailed(_) => "ServiceCodecFailed",
+            RequestError::ReqRepChannelFailed(_) => "ReqRepChannelFailed",
+            RequestError::CircuitOpen(_) => "CircuitOpen",
+            RequestError::ProcessingTimeout => "ProcessingTimeout",
+        }
+    }
 }
 
 struct Request {
@@ -463,7 +1032,7 @@ struct Request {
 }
 
 /// Socket Settings
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct SocketConfig {
     reconnect_min_time: Option<Duration>,
     reconnect_max_time: Option<Duration>,
@@ -579,12 +1148,21 @@ impl SocketConfig {
 pub struct DialerConfig {
     #[serde(with = "url_serde")]
     url: url::Url,
+    #[serde(default, with = "crate::serde_util::urls")]
+    endpoints: Vec<url::Url>,
     parallelism: usize,
     recv_max_size: Option<usize>,
     no_delay: Option<bool>,
     keep_alive: Option<bool>,
     reconnect_min_time: Option<Duration>,
     reconnect_max_time: Option<Duration>,
+    #[cfg(feature = "tls")]
+    #[serde(default)]
+    tls: Option<TlsConfig>,
+    #[serde(default)]
+    ws: Option<WsConfig>,
+    #[serde(default)]
+    request_policy: Option<RequestPolicy>,
 }
 
 impl DialerConfig {
@@ -593,12 +1171,17 @@ impl DialerConfig {
     pub fn new(url: url::Url) -> DialerConfig {
         DialerConfig {
             url,
+            endpoints: Vec::new(),
             recv_max_size: None,
             no_delay: None,
             keep_alive: None,
             parallelism: 1,
             reconnect_min_time: None,
             reconnect_max_time: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+            ws: None,
+            request_policy: None,
         }
     }
 
@@ -611,7 +1194,16 @@ impl DialerConfig {
     /// The returned handle controls the life of the dialer. If it is dropped, the dialer is shut down
     /// and no more messages will be received on it.
     pub fn start_dialer(self, socket: &nng::Socket) -> Result<nng::Dialer, DialerConfigError> {
-        let dialer_options = nng::DialerOptions::new(socket, self.url.as_str())
+        self.start_endpoint_dialer(socket, &self.url)
+    }
+
+    /// Starts a socket dialer for the specified endpoint URL, using this config's dialer settings
+    pub(crate) fn start_endpoint_dialer(
+        &self,
+        socket: &nng::Socket,
+        url: &url::Url,
+    ) -> Result<nng::Dialer, DialerConfigError> {
+        let dialer_options = nng::DialerOptions::new(socket, url.as_str())
             .map_err(DialerConfigError::DialerOptionsCreateFailed)?;
 
         if let Some(recv_max_size) = self.recv_max_size {
@@ -640,6 +1232,19 @@ impl DialerConfig {
             .set_opt::<nng::options::ReconnectMaxTime>(self.reconnect_max_time)
             .map_err(DialerConfigError::ReconnectMaxTime)?;
 
+        #[cfg(feature = "tls")]
+        {
+            if let Some(tls) = self.tls.as_ref() {
+                tls.apply_to_dialer(url, &dialer_options)
+                    .map_err(DialerConfigError::Tls)?;
+            }
+        }
+
+        if let Some(ws) = self.ws.as_ref() {
+            ws.apply_to_dialer(url, &dialer_options)
+                .map_err(DialerConfigError::Ws)?;
+        }
+
         dialer_options
             .start(true)
             .map_err(|(_options, err)| DialerConfigError::DialerStartError(err))
@@ -650,6 +1255,28 @@ impl DialerConfig {
         &self.url
     }
 
+    /// additional server endpoints that the client will dial
+    /// - see [add_endpoint()](#method.add_endpoint)
+    pub fn endpoints(&self) -> &[url::Url] {
+        &self.endpoints
+    }
+
+    /// all endpoint URLs that the client will dial, i.e., the primary URL followed by the additional endpoints
+    pub fn urls(&self) -> impl Iterator<Item = &url::Url> {
+        std::iter::once(&self.url).chain(self.endpoints.iter())
+    }
+
+    /// Adds an additional server endpoint - each endpoint is dialed by its own nng::Dialer on the
+    /// same socket, and requests are load balanced across the connected endpoints.
+    /// - duplicate URLs are ignored
+    pub fn add_endpoint(self, url: url::Url) -> Self {
+        let mut settings = self;
+        if settings.url != url && !settings.endpoints.contains(&url) {
+            settings.endpoints.push(url);
+        }
+        settings
+    }
+
     /// Max number of async IO operations that can be performed concurrently, which corresponds to the number
     /// of socket contexts that will be created.
     /// - default = 1
@@ -707,6 +1334,44 @@ impl DialerConfig {
         self.reconnect_max_time
     }
 
+    /// TLS config, which applies to `tls+tcp` and `wss` URLs
+    #[cfg(feature = "tls")]
+    pub fn tls(&self) -> Option<&TlsConfig> {
+        self.tls.as_ref()
+    }
+
+    /// WebSocket config, which applies to `ws` and `wss` URLs
+    pub fn ws(&self) -> Option<&WsConfig> {
+        self.ws.as_ref()
+    }
+
+    /// Client request policy, i.e., retries, hedged requests, and circuit breaker
+    pub fn request_policy(&self) -> Option<&RequestPolicy> {
+        self.request_policy.as_ref()
+    }
+
+    /// Sets the client request policy
+    pub fn set_request_policy(self, request_policy: RequestPolicy) -> Self {
+        let mut settings = self;
+        settings.request_policy = Some(request_policy);
+        settings
+    }
+
+    /// Sets the TLS config - the URL scheme must be `tls+tcp` or `wss`
+    #[cfg(feature = "tls")]
+    pub fn set_tls(self, tls: TlsConfig) -> Self {
+        let mut settings = self;
+        settings.tls = Some(tls);
+        settings
+    }
+
+    /// Sets the WebSocket config - the URL scheme must be `ws` or `wss`
+    pub fn set_ws(self, ws: WsConfig) -> Self {
+        let mut settings = self;
+        settings.ws = Some(ws);
+        settings
+    }
+
     /// Sets the maximum message size that the will be accepted from a remote peer.
     pub fn set_recv_max_size(self, recv_max_size: usize) -> Self {
         let mut settings = self;
@@ -773,6 +1438,13 @@ pub enum DialerConfigError {
     /// Failed to set the ReconnectMaxTime option
     #[fail(display = "Failed to set the ReconnectMaxTime option: {}", _0)]
     ReconnectMaxTime(#[cause] nng::Error),
+    /// Failed to apply the TLS config
+    #[cfg(feature = "tls")]
+    #[fail(display = "Failed to apply the TLS config: {}", _0)]
+    Tls(#[cause] TlsConfigError),
+    /// Failed to apply the WebSocket config
+    #[fail(display = "Failed to apply the WebSocket config: {}", _0)]
+    Ws(#[cause] WsConfigError),
     /// Failed to start Dialer
     #[fail(display = "Failed to start Dialer: {}", _0)]
     DialerStartError(#[cause] nng::Error),
@@ -913,7 +1585,7 @@ mod tests {
                 break;
             }
             info!("waiting for NngClient Aio Context handler tasks to exit: executor.active_task_count() = {}", executor.task_active_count());
-            thread::sleep_ms(5);
+            thread::sleep(Duration::from_millis(5));
             thread::yield_now();
         }
         // TODO: this sometimes fails, which means there is a bug
@@ -1000,7 +1672,7 @@ mod tests {
             if executor.task_active_count() == expected_task_count {
                 break;
             }
-            thread::sleep_ms(1);
+            thread::sleep(Duration::from_millis(1));
         }
         assert_eq!(executor.task_active_count(), expected_task_count);
     }
@@ -1092,4 +1764,280 @@ mod tests {
             .unwrap();
         info!("reply = {:?}", reply.unwrap());
     }
+
+    #[test]
+    fn multiple_endpoints() {
+        configure_logging();
+        let mut executor = execution::global_executor();
+
+        // GIVEN: 2 servers are running
+        let start_server_on = |url: &url::Url| {
+            server::spawn(
+                None,
+                server::ListenerConfig::new(url.clone()),
+                start_server(),
+                global_executor(),
+            )
+            .unwrap()
+        };
+        let url_1 = url::Url::parse(&format!("inproc://{}", ULID::generate())).unwrap();
+        let url_2 = url::Url::parse(&format!("inproc://{}", ULID::generate())).unwrap();
+        let server_1 = start_server_on(&url_1);
+        let server_2 = start_server_on(&url_2);
+
+        // WHEN: the client is started with the first endpoint
+        let reqrep_id = ReqRepId::generate();
+        let (mut client, client_executor_id) = start_client(reqrep_id, url_1.clone());
+        // AND: the second endpoint is added at runtime
+        super::add_endpoint(reqrep_id, url_2.clone()).unwrap();
+        match super::add_endpoint(reqrep_id, url_2.clone()) {
+            Err(EndpointError::EndpointAlreadyExists(_)) => (),
+            other => panic!("expected EndpointAlreadyExists: {:?}", other),
+        }
+
+        // THEN: the client connects to both endpoints
+        let all_connected = || {
+            super::endpoints(reqrep_id)
+                .unwrap()
+                .iter()
+                .all(Endpoint::connected)
+        };
+        for _ in 0..10 {
+            if all_connected() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        let endpoints = super::endpoints(reqrep_id).unwrap();
+        info!("{:?}", endpoints);
+        assert_eq!(endpoints.len(), 2);
+        assert!(all_connected());
+
+        // AND: requests are processed
+        for _ in 0..10 {
+            let mut client = client.clone();
+            executor
+                .run(async move { await!(client.send_recv(nng::Message::new().unwrap())) })
+                .unwrap()
+                .unwrap();
+        }
+
+        // WHEN: the second endpoint is removed
+        assert!(super::remove_endpoint(reqrep_id, &url_2).unwrap());
+        assert!(!super::remove_endpoint(reqrep_id, &url_2).unwrap());
+        // THEN: the client only dials the first endpoint
+        let endpoints = super::endpoints(reqrep_id).unwrap();
+        assert_eq!(endpoints.len(), 1);
+        assert_eq!(endpoints[0].url(), &url_1);
+        // AND: the first endpoint is still connected
+        assert_eq!(endpoints[0].active_conn_count(), 1);
+        // AND: the removed endpoint's metric series are removed, and are not re-created when its
+        //      pipe is removed
+        let endpoint_series_count = |url: &url::Url| {
+            use prometheus::core::Collector;
+            ENDPOINT_ACTIVE_CONN_COUNT
+                .collect()
+                .iter()
+                .chain(ENDPOINT_TOT_CONN_COUNT.collect().iter())
+                .flat_map(|mf| mf.get_metric().iter())
+                .filter(|metric| {
+                    metric
+                        .get_label()
+                        .iter()
+                        .any(|label| label.get_value() == url.as_str())
+                })
+                .count()
+        };
+        assert_eq!(endpoint_series_count(&url_2), 0);
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(endpoint_series_count(&url_2), 0);
+        assert_eq!(endpoint_series_count(&url_1), 2);
+    }
+
+    #[test]
+    fn nng_client_metrics() {
+        configure_logging();
+        let mut executor = execution::global_executor();
+
+        // GIVEN: the server is running
+        let url = url::Url::parse(&format!("inproc://{}", ULID::generate())).unwrap();
+        let mut server_handle = server::spawn(
+            None,
+            server::ListenerConfig::new(url.clone()),
+            start_server(),
+            global_executor(),
+        )
+        .unwrap();
+        assert!(server_handle.ping());
+
+        // GIVEN: the client is registered
+        let reqrep_id = ReqRepId::generate();
+        let (mut client, _client_executor_id) = start_client(reqrep_id, url.clone());
+        let metrics = super::client_metrics(reqrep_id).unwrap();
+        assert_eq!(metrics.aio_context_idle_count(), 1);
+        assert_eq!(metrics.aio_context_busy_count(), 0);
+
+        // WHEN: requests are sent
+        const REQUEST_COUNT: u64 = 10;
+        for _ in 0..REQUEST_COUNT {
+            let mut client = client.clone();
+            executor
+                .run(async move { await!(client.send_recv(nng::Message::new().unwrap())) })
+                .unwrap()
+                .unwrap();
+        }
+
+        // THEN: the request timers are updated
+        info!("{:?}", metrics);
+        assert_eq!(
+            metrics.request_rtt_timer().get_sample_count(),
+            REQUEST_COUNT
+        );
+        assert_eq!(
+            metrics.aio_context_wait_timer().get_sample_count(),
+            REQUEST_COUNT
+        );
+        // AND: the Aio Context is idle
+        assert_eq!(metrics.aio_context_idle_count(), 1);
+        assert_eq!(metrics.aio_context_busy_count(), 0);
+        // AND: no requests failed
+        assert_eq!(metrics.request_error_count("SendFailed"), 0);
+        assert_eq!(metrics.dialer_reconnect_count(), 0);
+    }
+
+    #[test]
+    fn recreated_client_does_not_count_first_connection_as_reconnect() {
+        configure_logging();
+
+        // GIVEN: the server is running
+        let url = url::Url::parse(&format!("inproc://{}", ULID::generate())).unwrap();
+        let mut server_handle = server::spawn(
+            None,
+            server::ListenerConfig::new(url.clone()),
+            start_server(),
+            global_executor(),
+        )
+        .unwrap();
+        assert!(server_handle.ping());
+        let reqrep_id = ReqRepId::generate();
+        let tot_conn_count = || {
+            super::endpoints(reqrep_id)
+                .and_then(|endpoints| endpoints.first().map(Endpoint::tot_conn_count))
+                .unwrap_or(0)
+        };
+        let await_tot_conn_count = |count: u64| {
+            for _ in 0..100 {
+                if tot_conn_count() >= count {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(5));
+            }
+            assert_eq!(tot_conn_count(), count);
+        };
+
+        // AND: a client has connected to the server
+        let (client, _client_executor_id) = start_client(reqrep_id, url.clone());
+        await_tot_conn_count(1);
+
+        // WHEN: the client is re-created using the same ReqRepId
+        super::unregister_client(reqrep_id);
+        drop(client);
+        let (_client, _client_executor_id) = start_client(reqrep_id, url.clone());
+        // AND: the re-created client connects to the server
+        await_tot_conn_count(2);
+
+        // THEN: the re-created client's first connection is not counted as a reconnect
+        let metrics = super::client_metrics(reqrep_id).unwrap();
+        assert_eq!(metrics.dialer_reconnect_count(), 0);
+
+        assert!(server_handle.stop_async().unwrap());
+        server_handle.await_shutdown();
+    }
+
+    #[test]
+    fn client_metric_timer_buckets_must_match_on_re_registration() {
+        configure_logging();
+        let reqrep_id = ReqRepId::generate();
+        let url = url::Url::parse(&format!("inproc://{}", ULID::generate())).unwrap();
+        let register = |timer_buckets: Vec<Duration>| {
+            super::register_client(
+                ReqRepConfig::new(reqrep_id, metrics::timer_buckets(timer_buckets).unwrap()),
+                None,
+                DialerConfig::new(url.clone()),
+                execution::global_executor(),
+            )
+        };
+
+        // GIVEN: a client was registered and then unregistered
+        let client = register(vec![Duration::from_millis(10)]).unwrap();
+        super::unregister_client(reqrep_id);
+        drop(client);
+
+        // WHEN: the client is re-registered with different timer buckets
+        let result = register(vec![Duration::from_millis(20)]);
+        // THEN: registration fails, i.e., the new buckets are not silently ignored
+        match result {
+            Err(ClientRegistrationError::NngError(
+                NngClientError::MetricTimerBucketsMismatch {
+                    registered,
+                    requested,
+                },
+            )) => {
+                assert_eq!(registered, vec![0.01]);
+                assert_eq!(requested, vec![0.02]);
+            }
+            other => panic!(
+                "expected MetricTimerBucketsMismatch, but got: {:?}",
+                other.map(|_| ())
+            ),
+        }
+
+        // WHEN: the client is re-registered with the same timer buckets
+        // THEN: the registered metrics are reused
+        register(vec![Duration::from_millis(10)]).unwrap();
+        super::unregister_client(reqrep_id);
+    }
+
+    #[test]
+    fn hedged_request_requires_parallelism() {
+        configure_logging();
+        use crate::reqrep::policy::{HedgePolicy, RequestPolicy};
+        let timer_buckets = metrics::timer_buckets(vec![Duration::from_nanos(50)]).unwrap();
+        let url = url::Url::parse(&format!("inproc://{}", ULID::generate())).unwrap();
+        // GIVEN: a hedge policy with the default parallelism of 1
+        let dialer_config = DialerConfig::new(url).set_request_policy(
+            RequestPolicy::default().set_hedge(HedgePolicy::new(Duration::from_millis(5))),
+        );
+        // WHEN: the client is registered
+        let result = super::register_client(
+            ReqRepConfig::new(ReqRepId::generate(), timer_buckets),
+            None,
+            dialer_config,
+            execution::global_executor(),
+        );
+        // THEN: registration is rejected
+        match result {
+            Err(ClientRegistrationError::NngError(NngClientError::InvalidRequestPolicy(_))) => (),
+            other => panic!("expected InvalidRequestPolicy, but got: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn dialer_config_endpoints() {
+        let url_1 = url::Url::parse("tcp://127.0.0.1:5000").unwrap();
+        let url_2 = url::Url::parse("tcp://127.0.0.1:5001").unwrap();
+        let dialer_config = DialerConfig::new(url_1.clone())
+            .add_endpoint(url_2.clone())
+            .add_endpoint(url_2.clone())
+            .add_endpoint(url_1.clone());
+        assert_eq!(dialer_config.endpoints(), &[url_2.clone()]);
+        assert_eq!(
+            dialer_config.urls().cloned().collect::<Vec<_>>(),
+            vec![url_1, url_2]
+        );
+
+        let json = serde_json::to_string(&dialer_config).unwrap();
+        let dialer_config: DialerConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(dialer_config.urls().count(), 2);
+    }
 }
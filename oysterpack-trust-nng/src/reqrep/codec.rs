@@ -0,0 +1,583 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Provides a typed request/reply layer on top of the nng [Client](../client/type.Client.html) and
+//! server, which only deal with raw `nng::Message`(s).
+//!
+//! - a [Codec](trait.Codec.html) is used to encode and decode messages
+//!   - [JsonCodec](struct.JsonCodec.html) - requires the `json` feature, which is enabled by default
+//!   - [BincodeCodec](struct.BincodeCodec.html) - requires the `bincode` feature
+//!   - [CborCodec](struct.CborCodec.html) - requires the `serde_cbor` feature
+//!   - [ProtobufCodec](struct.ProtobufCodec.html) - requires the `protobuf` feature
+//! - [TypedClient](struct.TypedClient.html) wraps a [Client](../client/type.Client.html)
+//!   - encode and decode failures are mapped to [RequestError](../client/enum.RequestError.html)
+//! - replies are wrapped in a status [envelope](../envelope/index.html), i.e., if the service fails
+//!   to decode the request or encode the reply, then the client receives a
+//!   [RequestError::ServiceCodecFailed](../client/enum.RequestError.html#variant.ServiceCodecFailed)
+//! - messages are encoded directly into messages that are acquired from the
+//!   [global message pool](../../message/fn.global_pool.html), and decoded messages are released
+//!   back to the pool
+//! - [CodecProcessor](struct.CodecProcessor.html) adapts a typed `Processor<Req, Rep>` into a
+//!   `Processor<nng::Message, nng::Message>`, which can be used to start the ReqRep service for the
+//!   [server](../server/fn.spawn.html)
+//!   - the replies are already sealed, thus when a processing timeout is configured, the server
+//!     listener must be flagged via [ListenerConfig::set_sealed_replies()](../server/struct.ListenerConfig.html#method.set_sealed_replies)
+//!
+//! ## Example
+//! ```no_run
+//! # #![feature(await_macro, async_await, futures_api)]
+//! # use oysterpack_trust_nng::reqrep::{client::*, codec::*};
+//! # use oysterpack_trust::concurrent::messaging::reqrep::ReqRepId;
+//! # async fn example() {
+//! let client = client(ReqRepId(1871557337320005579010710867531265404)).unwrap();
+//! let mut client = TypedClient::<String, usize, _>::new(client, JsonCodec);
+//! let len: usize = await!(client.send_recv("hello".to_string())).unwrap();
+//! # }
+//! ```
+
+use super::{
+    client::{Client, RequestError},
+    envelope::{self, ReplyStatus},
+};
+use crate::message::{self, MessageWriter};
+use failure::Fail;
+use futures::future::FutureExt;
+use oysterpack_log::*;
+use oysterpack_trust::concurrent::messaging::reqrep::{FutureReply, PanicError, Processor, ReqRepId};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fmt, marker::PhantomData};
+
+/// Used to encode and decode nng messages
+pub trait Codec<T>: Clone + Send + Sync + 'static {
+    /// encodes the value into an nng message
+    fn encode(&self, value: &T) -> Result<nng::Message, CodecError>;
+
+    /// decodes the nng message body bytes
+    fn decode(&self, bytes: &[u8]) -> Result<T, CodecError>;
+}
+
+/// JSON codec
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+#[cfg(feature = "json")]
+impl<T: Serialize + DeserializeOwned> Codec<T> for JsonCodec {
+    fn encode(&self, value: &T) -> Result<nng::Message, CodecError> {
+        let mut msg = new_message(0)?;
+        serde_json::to_writer(MessageWriter::new(&mut msg), value)
+            .map_err(|err| CodecError::EncodeFailed(err.to_string()))?;
+        Ok(msg)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<T, CodecError> {
+        serde_json::from_slice(bytes).map_err(|err| CodecError::DecodeFailed(err.to_string()))
+    }
+}
+
+/// [bincode](https://crates.io/crates/bincode) codec
+#[cfg(feature = "bincode")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BincodeCodec;
+
+#[cfg(feature = "bincode")]
+impl<T: Serialize + DeserializeOwned> Codec<T> for BincodeCodec {
+    fn encode(&self, value: &T) -> Result<nng::Message, CodecError> {
+        let len = bincode::serialized_size(value)
+            .map_err(|err| CodecError::EncodeFailed(err.to_string()))?;
+        let mut msg = new_message(len as usize)?;
+        bincode::serialize_into(MessageWriter::new(&mut msg), value)
+            .map_err(|err| CodecError::EncodeFailed(err.to_string()))?;
+        Ok(msg)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<T, CodecError> {
+        bincode::deserialize(bytes).map_err(|err| CodecError::DecodeFailed(err.to_string()))
+    }
+}
+
+/// [CBOR](https://crates.io/crates/serde_cbor) codec
+#[cfg(feature = "serde_cbor")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CborCodec;
+
+#[cfg(feature = "serde_cbor")]
+impl<T: Serialize + DeserializeOwned> Codec<T> for CborCodec {
+    fn encode(&self, value: &T) -> Result<nng::Message, CodecError> {
+        let mut msg = new_message(0)?;
+        serde_cbor::to_writer(&mut MessageWriter::new(&mut msg), value)
+            .map_err(|err| CodecError::EncodeFailed(err.to_string()))?;
+        Ok(msg)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<T, CodecError> {
+        serde_cbor::from_slice(bytes).map_err(|err| CodecError::DecodeFailed(err.to_string()))
+    }
+}
+
+/// [protobuf](https://crates.io/crates/protobuf) codec
+#[cfg(feature = "protobuf")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProtobufCodec;
+
+#[cfg(feature = "protobuf")]
+impl<T: protobuf::Message> Codec<T> for ProtobufCodec {
+    fn encode(&self, value: &T) -> Result<nng::Message, CodecError> {
+        let mut msg = new_message(value.compute_size() as usize)?;
+        value
+            .write_to_writer(&mut MessageWriter::new(&mut msg))
+            .map_err(|err| CodecError::EncodeFailed(err.to_string()))?;
+        Ok(msg)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<T, CodecError> {
+        protobuf::parse_from_bytes(bytes)
+            .map_err(|err| CodecError::DecodeFailed(err.to_string()))
+    }
+}
+
+/// the message is acquired from the global message pool - values are encoded directly into the
+/// message body
+fn new_message(len: usize) -> Result<nng::Message, CodecError> {
+    message::global_pool()
+        .acquire(len)
+        .map_err(CodecError::MessageAllocFailed)
+}
+
+/// Typed nng ReqRep client
+/// - requests are encoded and replies are decoded using the Codec
+pub struct TypedClient<Req, Rep, C>
+where
+    C: Codec<Req> + Codec<Rep>,
+{
+    client: Client,
+    codec: C,
+    _phantom: PhantomData<fn(Req) -> Rep>,
+}
+
+impl<Req, Rep, C> TypedClient<Req, Rep, C>
+where
+    Req: Send + 'static,
+    Rep: Send + 'static,
+    C: Codec<Req> + Codec<Rep>,
+{
+    /// constructor
+    pub fn new(client: Client, codec: C) -> Self {
+        TypedClient {
+            client,
+            codec,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Returns the ReqRepId
+    pub fn id(&self) -> ReqRepId {
+        self.client.id()
+    }
+
+    /// Returns the underlying untyped client
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Sends the request and awaits the reply
+    /// - the reply envelope status is mapped to a [RequestError](../client/enum.RequestError.html)
+    pub async fn send_recv(&mut self, req: Req) -> Result<Rep, RequestError> {
+        let msg = Codec::<Req>::encode(&self.codec, &req).map_err(RequestError::EncodeFailed)?;
+        let rep =
+            await!(self.client.send_recv(msg)).map_err(RequestError::ReqRepChannelFailed)??;
+        let result = self.open_reply(&rep);
+        message::global_pool().release(rep);
+        result
+    }
+
+    fn open_reply(&self, rep: &nng::Message) -> Result<Rep, RequestError> {
+        let (status, payload) = envelope::open(&rep[..]).map_err(RequestError::DecodeFailed)?;
+        let details = || String::from_utf8_lossy(payload).to_string();
+        match status {
+            ReplyStatus::Ok => {
+                Codec::<Rep>::decode(&self.codec, payload).map_err(RequestError::DecodeFailed)
+            }
+            ReplyStatus::RequestDecodeFailed => Err(RequestError::ServiceCodecFailed(
+                CodecError::DecodeFailed(details()),
+            )),
+            ReplyStatus::ReplyEncodeFailed => Err(RequestError::ServiceCodecFailed(
+                CodecError::EncodeFailed(details()),
+            )),
+            ReplyStatus::ProcessingTimeout => Err(RequestError::ProcessingTimeout),
+        }
+    }
+}
+
+impl<Req, Rep, C> Clone for TypedClient<Req, Rep, C>
+where
+    C: Codec<Req> + Codec<Rep>,
+{
+    fn clone(&self) -> Self {
+        TypedClient {
+            client: self.client.clone(),
+            codec: self.codec.clone(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<Req, Rep, C> fmt::Debug for TypedClient<Req, Rep, C>
+where
+    C: Codec<Req> + Codec<Rep>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TypedClient")
+            .field("client", &self.client)
+            .finish()
+    }
+}
+
+/// Adapts a typed `Processor<Req, Rep>` into a `Processor<nng::Message, nng::Message>`
+/// - replies are wrapped in a status [envelope](../envelope/index.html)
+/// - if the request fails to decode, or the reply fails to encode, then the error status is replied,
+///   which the [TypedClient](struct.TypedClient.html) maps to
+///   [RequestError::ServiceCodecFailed](../client/enum.RequestError.html#variant.ServiceCodecFailed)
+/// - the request message buffer is recycled as the error reply, i.e., replying an error never
+///   requires a message allocation
+pub struct CodecProcessor<Req, Rep, P, C>
+where
+    Req: fmt::Debug + Send + 'static,
+    Rep: fmt::Debug + Send + 'static,
+    P: Processor<Req, Rep>,
+    C: Codec<Req> + Codec<Rep>,
+{
+    processor: P,
+    codec: C,
+    _phantom: PhantomData<fn(Req) -> Rep>,
+}
+
+impl<Req, Rep, P, C> CodecProcessor<Req, Rep, P, C>
+where
+    Req: fmt::Debug + Send + 'static,
+    Rep: fmt::Debug + Send + 'static,
+    P: Processor<Req, Rep>,
+    C: Codec<Req> + Codec<Rep>,
+{
+    /// constructor
+    pub fn new(processor: P, codec: C) -> Self {
+        CodecProcessor {
+            processor,
+            codec,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<Req, Rep, P, C> Processor<nng::Message, nng::Message> for CodecProcessor<Req, Rep, P, C>
+where
+    Req: fmt::Debug + Send + 'static,
+    Rep: fmt::Debug + Send + 'static,
+    P: Processor<Req, Rep>,
+    C: Codec<Req> + Codec<Rep>,
+{
+    fn process(&mut self, req: nng::Message) -> FutureReply<nng::Message> {
+        match Codec::<Req>::decode(&self.codec, &req[..]) {
+            Ok(decoded) => {
+                // the request buffer is retained in case the reply fails to encode
+                let rep = self.processor.process(decoded);
+                let codec = self.codec.clone();
+                async move {
+                    let rep = await!(rep);
+                    match Codec::<Rep>::encode(&codec, &rep) {
+                        Ok(mut rep) => match envelope::seal_ok(&mut rep) {
+                            Ok(_) => {
+                                message::global_pool().release(req);
+                                rep
+                            }
+                            Err(err) => {
+                                error!("Failed to seal reply: {}", err);
+                                message::global_pool().release(rep);
+                                error_reply(req, ReplyStatus::ReplyEncodeFailed, &err.to_string())
+                            }
+                        },
+                        Err(err) => {
+                            error!("Failed to encode reply: {}", err);
+                            error_reply(req, ReplyStatus::ReplyEncodeFailed, &err.to_string())
+                        }
+                    }
+                }
+                    .boxed()
+            }
+            Err(err) => {
+                warn!("Failed to decode request: {}", err);
+                let rep = error_reply(req, ReplyStatus::RequestDecodeFailed, &err.to_string());
+                async move { rep }.boxed()
+            }
+        }
+    }
+
+    fn init(&mut self) {
+        self.processor.init()
+    }
+
+    fn destroy(&mut self) {
+        self.processor.destroy()
+    }
+
+    fn panicked(&mut self, err: PanicError) {
+        self.processor.panicked(err)
+    }
+}
+
+impl<Req, Rep, P, C> fmt::Debug for CodecProcessor<Req, Rep, P, C>
+where
+    Req: fmt::Debug + Send + 'static,
+    Rep: fmt::Debug + Send + 'static,
+    P: Processor<Req, Rep>,
+    C: Codec<Req> + Codec<Rep>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("CodecProcessor")
+    }
+}
+
+/// the message buffer is reused for the error reply
+/// - if the error details fail to be written, then the client receives an invalid envelope, which is
+///   reported as a decode failure
+fn error_reply(mut msg: nng::Message, status: ReplyStatus, details: &str) -> nng::Message {
+    if let Err(err) = envelope::seal_error(&mut msg, status, details) {
+        error!("Failed to write {:?} reply: {}", status, err);
+    }
+    msg
+}
+
+/// Codec related errors
+#[derive(Debug, Fail, Clone)]
+pub enum CodecError {
+    /// Failed to encode the message
+    #[fail(display = "Failed to encode message: {}", _0)]
+    EncodeFailed(String),
+    /// Failed to decode the message
+    #[fail(display = "Failed to decode message: {}", _0)]
+    DecodeFailed(String),
+    /// Failed to allocate the nng message
+    #[fail(display = "Failed to allocate nng message: {}", _0)]
+    MessageAllocFailed(#[cause] nng::Error),
+}
+
+#[allow(warnings)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        configure_logging,
+        reqrep::{client, server},
+    };
+    use oysterpack_trust::{
+        concurrent::{
+            execution::{self, *},
+            messaging::reqrep::{self, *},
+        },
+        metrics,
+    };
+    use oysterpack_uid::ULID;
+    use serde::Deserialize;
+    use std::time::Duration;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Greeting {
+        name: String,
+    }
+
+    struct GreetingService;
+    impl Processor<Greeting, String> for GreetingService {
+        fn process(&mut self, req: Greeting) -> reqrep::FutureReply<String> {
+            async move { format!("Hello {}", req.name) }.boxed()
+        }
+    }
+
+    fn timer_buckets() -> Vec<f64> {
+        metrics::timer_buckets(vec![
+            Duration::from_nanos(50),
+            Duration::from_nanos(100),
+            Duration::from_nanos(150),
+            Duration::from_nanos(200),
+        ])
+        .unwrap()
+    }
+
+    fn greeting() -> Greeting {
+        Greeting {
+            name: "Alfio".to_string(),
+        }
+    }
+
+    /// encodes and decodes the value, and checks that an empty message fails to decode
+    fn check_roundtrip<T, C>(codec: C, value: T)
+    where
+        T: fmt::Debug + PartialEq,
+        C: Codec<T>,
+    {
+        let msg = codec.encode(&value).unwrap();
+        let decoded: T = codec.decode(&msg[..]).unwrap();
+        assert_eq!(decoded, value);
+
+        match codec.decode(&[0xff]) {
+            Err(CodecError::DecodeFailed(_)) => (),
+            other => panic!("expected DecodeFailed: {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_codec_roundtrip() {
+        check_roundtrip(JsonCodec, greeting());
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn bincode_codec_roundtrip() {
+        check_roundtrip(BincodeCodec, greeting());
+    }
+
+    #[cfg(feature = "serde_cbor")]
+    #[test]
+    fn cbor_codec_roundtrip() {
+        check_roundtrip(CborCodec, greeting());
+    }
+
+    #[cfg(feature = "protobuf")]
+    #[test]
+    fn protobuf_codec_roundtrip() {
+        let mut value = protobuf::well_known_types::StringValue::new();
+        value.set_value("Alfio".to_string());
+        check_roundtrip(ProtobufCodec, value);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn typed_client_server() {
+        configure_logging();
+        let mut executor = execution::global_executor();
+
+        // GIVEN: a typed service is running
+        let url = url::Url::parse(&format!("inproc://{}", ULID::generate())).unwrap();
+        let reqrep_id = ReqRepId::generate();
+        let service = ReqRepConfig::new(reqrep_id, timer_buckets())
+            .start_service(
+                CodecProcessor::new(GreetingService, JsonCodec),
+                global_executor(),
+            )
+            .unwrap();
+        let mut server_handle = server::spawn(
+            None,
+            server::ListenerConfig::new(url.clone()),
+            service,
+            global_executor(),
+        )
+        .unwrap();
+
+        // AND: a typed client is connected to the server
+        let client = client::register_client(
+            ReqRepConfig::new(reqrep_id, timer_buckets()),
+            None,
+            client::DialerConfig::new(url),
+            global_executor(),
+        )
+        .unwrap();
+        let mut client = TypedClient::<Greeting, String, _>::new(client, JsonCodec);
+
+        // WHEN: a typed request is sent
+        let rep = executor.run(
+            async move {
+                await!(client.send_recv(Greeting {
+                    name: "Alfio".to_string()
+                }))
+            },
+        );
+        // THEN: the typed reply is received
+        assert_eq!(rep.unwrap(), "Hello Alfio");
+
+        // WHEN: the client sends a request that the server fails to decode
+        let mut client =
+            TypedClient::<String, String, _>::new(client::client(reqrep_id).unwrap(), JsonCodec);
+        let rep = executor.run(async move { await!(client.send_recv("Alfio".to_string())) });
+        // THEN: the service failure is reported explicitly
+        match rep {
+            Err(RequestError::ServiceCodecFailed(CodecError::DecodeFailed(_))) => (),
+            other => panic!("expected ServiceCodecFailed: {:?}", other),
+        }
+
+        client::unregister_client(reqrep_id);
+        server_handle.stop_async().unwrap();
+        server_handle.await_shutdown();
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn typed_client_processing_timeout() {
+        configure_logging();
+        let mut executor = execution::global_executor();
+
+        struct SlowGreetingService;
+        impl Processor<Greeting, String> for SlowGreetingService {
+            fn process(&mut self, req: Greeting) -> reqrep::FutureReply<String> {
+                async move {
+                    await!(crate::timer::delay(Duration::from_millis(200)));
+                    format!("Hello {}", req.name)
+                }
+                    .boxed()
+            }
+        }
+
+        // GIVEN: a slow typed service is running with a processing timeout
+        let url = url::Url::parse(&format!("inproc://{}", ULID::generate())).unwrap();
+        let reqrep_id = ReqRepId::generate();
+        let service = ReqRepConfig::new(reqrep_id, timer_buckets())
+            .start_service(
+                CodecProcessor::new(SlowGreetingService, JsonCodec),
+                global_executor(),
+            )
+            .unwrap();
+        let mut server_handle = server::spawn(
+            None,
+            server::ListenerConfig::new(url.clone())
+                .set_processing_timeout(Duration::from_millis(10))
+                .set_sealed_replies(true),
+            service,
+            global_executor(),
+        )
+        .unwrap();
+
+        // AND: a typed client is connected to the server
+        let client = client::register_client(
+            ReqRepConfig::new(reqrep_id, timer_buckets()),
+            None,
+            client::DialerConfig::new(url),
+            global_executor(),
+        )
+        .unwrap();
+        let mut client = TypedClient::<Greeting, String, _>::new(client, JsonCodec);
+
+        // WHEN: a request is sent that takes longer than the processing timeout
+        let rep = executor.run(async move { await!(client.send_recv(greeting())) });
+        // THEN: the processing timeout is reported explicitly
+        match rep {
+            Err(RequestError::ProcessingTimeout) => (),
+            other => panic!("expected ProcessingTimeout: {:?}", other),
+        }
+
+        client::unregister_client(reqrep_id);
+        server_handle.stop_async().unwrap();
+        server_handle.await_shutdown();
+    }
+}
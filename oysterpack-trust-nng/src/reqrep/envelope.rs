@@ -0,0 +1,125 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Reply status envelope, which enables typed services to report failures to the client explicitly.
+//! - the first byte of the reply message body is the [ReplyStatus](enum.ReplyStatus.html)
+//! - if the status is `Ok`, then the rest of the message body is the encoded reply
+//! - otherwise, the rest of the message body is a UTF-8 description of the failure
+//!
+//! The envelope is applied by [CodecProcessor](../codec/struct.CodecProcessor.html) and opened by
+//! [TypedClient](../codec/struct.TypedClient.html), which maps error statuses to
+//! [RequestError](../client/enum.RequestError.html) variants. Thus, an empty reply payload is never
+//! confused with a failure.
+//!
+//! The server also uses the envelope to reply with the `ProcessingTimeout` status when the service
+//! does not reply within the configured processing timeout. When a processing timeout is configured,
+//! the server seals raw service replies with the `Ok` status, i.e., every reply is sealed - see
+//! [server](../server/index.html#processing-timeout).
+
+use super::codec::CodecError;
+
+/// Reply status, which is encoded as the first byte of the reply message body
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ReplyStatus {
+    /// The request was processed - the payload is the encoded reply
+    Ok = 0,
+    /// The service failed to decode the request
+    RequestDecodeFailed = 1,
+    /// The service failed to encode the reply
+    ReplyEncodeFailed = 2,
+    /// The server timed out waiting for the service to process the request
+    ProcessingTimeout = 3,
+}
+
+impl ReplyStatus {
+    /// Returns the status for the envelope status byte
+    pub fn from_u8(status: u8) -> Option<ReplyStatus> {
+        match status {
+            0 => Some(ReplyStatus::Ok),
+            1 => Some(ReplyStatus::RequestDecodeFailed),
+            2 => Some(ReplyStatus::ReplyEncodeFailed),
+            3 => Some(ReplyStatus::ProcessingTimeout),
+            _ => None,
+        }
+    }
+}
+
+/// Prepends the `Ok` status to the encoded reply
+pub fn seal_ok(msg: &mut nng::Message) -> Result<(), nng::Error> {
+    msg.push_front(&[ReplyStatus::Ok as u8])
+}
+
+/// Replaces the message body with the error status followed by the error details
+/// - the message buffer is reused, i.e., the request message can be recycled as the error reply
+pub fn seal_error(
+    msg: &mut nng::Message,
+    status: ReplyStatus,
+    details: &str,
+) -> Result<(), nng::Error> {
+    msg.clear();
+    msg.push_back(&[status as u8])?;
+    msg.push_back(details.as_bytes())
+}
+
+/// Opens the envelope, returning the status and the payload
+/// - an empty message or an unknown status is reported as a `CodecError::DecodeFailed`
+pub fn open(msg: &[u8]) -> Result<(ReplyStatus, &[u8]), CodecError> {
+    match msg.split_first() {
+        Some((status, payload)) => match ReplyStatus::from_u8(*status) {
+            Some(status) => Ok((status, payload)),
+            None => Err(CodecError::DecodeFailed(format!(
+                "invalid reply envelope status: {}",
+                status
+            ))),
+        },
+        None => Err(CodecError::DecodeFailed("empty reply envelope".to_string())),
+    }
+}
+
+#[allow(warnings)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reply_envelope() {
+        // GIVEN: an empty reply payload
+        let mut msg = nng::Message::new().unwrap();
+        // WHEN: it is sealed with the Ok status
+        seal_ok(&mut msg).unwrap();
+        // THEN: the envelope opens as Ok with an empty payload
+        let (status, payload) = open(&msg[..]).unwrap();
+        assert_eq!(status, ReplyStatus::Ok);
+        assert!(payload.is_empty());
+
+        // WHEN: the message is sealed as an error
+        seal_error(&mut msg, ReplyStatus::RequestDecodeFailed, "bad request").unwrap();
+        // THEN: the envelope opens with the error status and details
+        let (status, payload) = open(&msg[..]).unwrap();
+        assert_eq!(status, ReplyStatus::RequestDecodeFailed);
+        assert_eq!(payload, b"bad request");
+
+        // THEN: an empty message or an unknown status is not a valid envelope
+        match open(&[]) {
+            Err(CodecError::DecodeFailed(_)) => (),
+            other => panic!("expected DecodeFailed: {:?}", other),
+        }
+        match open(&[u8::max_value()]) {
+            Err(CodecError::DecodeFailed(_)) => (),
+            other => panic!("expected DecodeFailed: {:?}", other),
+        }
+    }
+}
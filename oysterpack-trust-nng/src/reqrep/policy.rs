@@ -0,0 +1,670 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Provides client side request policies, which are configured via [DialerConfig::set_request_policy()](../client/struct.DialerConfig.html#method.set_request_policy).
+//! - [RetryPolicy](struct.RetryPolicy.html) - failed requests are retried with exponential backoff
+//!   - *NOTE*: retries should only be configured for clients whose requests are idempotent
+//! - [HedgePolicy](struct.HedgePolicy.html) - if the reply has not been received within the hedge
+//!   delay, then a second request is sent and the first reply wins
+//! - [CircuitBreakerConfig](struct.CircuitBreakerConfig.html) - once the number of consecutive failed
+//!   requests reaches the failure threshold, the circuit is opened and requests fail fast with
+//!   [RequestError::CircuitOpen](../client/enum.RequestError.html#variant.CircuitOpen). After the
+//!   reset timeout, the circuit is half-open: a single trial request is let through - if it succeeds
+//!   the circuit is closed, otherwise the circuit is re-opened.
+//!
+//! Only transient errors are retried and count as circuit breaker failures, i.e.,
+//! [RequestError::SendFailed](../client/enum.RequestError.html#variant.SendFailed) and
+//! [RequestError::RecvFailed](../client/enum.RequestError.html#variant.RecvFailed).
+//!
+//! ## Metrics
+//! - number of retries - [RETRY_COUNT_METRIC_ID](constant.RETRY_COUNT_METRIC_ID.html)
+//! - number of hedged requests - [HEDGED_REQUEST_COUNT_METRIC_ID](constant.HEDGED_REQUEST_COUNT_METRIC_ID.html)
+//! - circuit breaker state - [CIRCUIT_BREAKER_STATE_METRIC_ID](constant.CIRCUIT_BREAKER_STATE_METRIC_ID.html)
+//! - number of requests rejected by the open circuit breaker - [CIRCUIT_BREAKER_REJECTED_COUNT_METRIC_ID](constant.CIRCUIT_BREAKER_REJECTED_COUNT_METRIC_ID.html)
+
+use super::{client::RequestError, server::REQREP_LABEL_ID};
+use crate::timer::delay;
+use futures::future::{self, Either, FutureExt};
+use lazy_static::lazy_static;
+use oysterpack_log::*;
+use oysterpack_trust::{
+    concurrent::messaging::reqrep::{FutureReply, ReqRepId},
+    metrics,
+};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+lazy_static! {
+
+    static ref RETRY_COUNT: prometheus::IntCounterVec = metrics::registry().register_int_counter_vec(
+        RETRY_COUNT_METRIC_ID,
+        "Number of request retries",
+        &[REQREP_LABEL_ID],
+        None
+    ).unwrap();
+
+    static ref HEDGED_REQUEST_COUNT: prometheus::IntCounterVec = metrics::registry().register_int_counter_vec(
+        HEDGED_REQUEST_COUNT_METRIC_ID,
+        "Number of hedged requests",
+        &[REQREP_LABEL_ID],
+        None
+    ).unwrap();
+
+    static ref CIRCUIT_BREAKER_STATE: prometheus::IntGaugeVec = metrics::registry().register_int_gauge_vec(
+        CIRCUIT_BREAKER_STATE_METRIC_ID,
+        "Circuit breaker state: 0 = closed, 1 = open, 2 = half-open",
+        &[REQREP_LABEL_ID],
+        None
+    ).unwrap();
+
+    static ref CIRCUIT_BREAKER_REJECTED_COUNT: prometheus::IntCounterVec = metrics::registry().register_int_counter_vec(
+        CIRCUIT_BREAKER_REJECTED_COUNT_METRIC_ID,
+        "Number of requests rejected because the circuit breaker is open",
+        &[REQREP_LABEL_ID],
+        None
+    ).unwrap();
+
+}
+
+/// IntCounterVec MetricId for request retries by ReqRepId: `M01M57FCARC3WR67S2N21XNXWGY`
+pub const RETRY_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166789321408596278573202502741586462);
+/// IntCounterVec MetricId for hedged requests by ReqRepId: `M01M57FCARE64DWFEJ9G3HMWHHX`
+pub const HEDGED_REQUEST_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166789321411098752621422610215749181);
+/// IntGaugeVec MetricId for the circuit breaker state by ReqRepId: `M01M57FCARHAJW6BNRW1H3K7E01`
+/// - 0 = closed, 1 = open, 2 = half-open
+pub const CIRCUIT_BREAKER_STATE_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166789321414893701992455087471179777);
+/// IntCounterVec MetricId for requests rejected by the open circuit breaker by ReqRepId: `M01M57FCARKPXQWPMJEY4P5KH9S`
+pub const CIRCUIT_BREAKER_REJECTED_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166789321417777728613665438544020793);
+
+/// Client request policy
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, Default)]
+pub struct RequestPolicy {
+    retry: Option<RetryPolicy>,
+    hedge: Option<HedgePolicy>,
+    circuit_breaker: Option<CircuitBreakerConfig>,
+}
+
+impl RequestPolicy {
+    /// Retry policy
+    pub fn retry(&self) -> Option<&RetryPolicy> {
+        self.retry.as_ref()
+    }
+
+    /// Hedge policy
+    pub fn hedge(&self) -> Option<&HedgePolicy> {
+        self.hedge.as_ref()
+    }
+
+    /// Circuit breaker config
+    pub fn circuit_breaker(&self) -> Option<&CircuitBreakerConfig> {
+        self.circuit_breaker.as_ref()
+    }
+
+    /// Sets the retry policy - only configure retries if the client's requests are idempotent
+    pub fn set_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Sets the hedge policy - only configure hedging if the client's requests are idempotent
+    pub fn set_hedge(mut self, hedge: HedgePolicy) -> Self {
+        self.hedge = Some(hedge);
+        self
+    }
+
+    /// Sets the circuit breaker config
+    pub fn set_circuit_breaker(mut self, circuit_breaker: CircuitBreakerConfig) -> Self {
+        self.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+}
+
+/// Retry policy with exponential backoff
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    backoff_multiplier: u32,
+}
+
+impl RetryPolicy {
+    /// constructor
+    ///
+    /// ## Default settings
+    /// - initial_backoff = 10 ms
+    /// - max_backoff = 1 sec
+    /// - backoff_multiplier = 2
+    pub fn new(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+            backoff_multiplier: 2,
+        }
+    }
+
+    /// max number of times a request will be retried
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    /// backoff before the first retry
+    pub fn initial_backoff(&self) -> Duration {
+        self.initial_backoff
+    }
+
+    /// max backoff between retries
+    pub fn max_backoff(&self) -> Duration {
+        self.max_backoff
+    }
+
+    /// the backoff is multiplied by this factor after each retry
+    pub fn backoff_multiplier(&self) -> u32 {
+        self.backoff_multiplier
+    }
+
+    /// Returns the backoff for the specified retry, where retry 0 is the first retry
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let multiplier = self
+            .backoff_multiplier
+            .checked_pow(retry)
+            .unwrap_or(u32::max_value());
+        self.initial_backoff
+            .checked_mul(multiplier)
+            .map(|backoff| cmp::min(backoff, self.max_backoff))
+            .unwrap_or(self.max_backoff)
+    }
+
+    /// Sets the initial backoff
+    pub fn set_initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    /// Sets the max backoff
+    pub fn set_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Sets the backoff multiplier
+    pub fn set_backoff_multiplier(mut self, backoff_multiplier: u32) -> Self {
+        self.backoff_multiplier = backoff_multiplier;
+        self
+    }
+}
+
+/// Hedge policy
+/// - the hedged request is sent on a different socket context than the primary request. Thus, the
+///   client must be configured with [parallelism](../client/struct.DialerConfig.html#method.parallelism)
+///   greater than 1, otherwise the client will fail to start with
+///   [NngClientError::InvalidRequestPolicy](../client/enum.NngClientError.html#variant.InvalidRequestPolicy)
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct HedgePolicy {
+    delay: Duration,
+}
+
+impl HedgePolicy {
+    /// constructor
+    /// - delay is how long to wait for the reply before sending the hedged request
+    pub fn new(delay: Duration) -> HedgePolicy {
+        HedgePolicy { delay }
+    }
+
+    /// how long to wait for the reply before sending the hedged request
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+}
+
+/// Circuit breaker config
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CircuitBreakerConfig {
+    failure_threshold: u32,
+    reset_timeout: Duration,
+}
+
+impl CircuitBreakerConfig {
+    /// constructor
+    /// - failure_threshold is the number of consecutive failures that will open the circuit
+    /// - reset_timeout is how long the circuit stays open before moving to half-open
+    pub fn new(failure_threshold: u32, reset_timeout: Duration) -> CircuitBreakerConfig {
+        CircuitBreakerConfig {
+            failure_threshold,
+            reset_timeout,
+        }
+    }
+
+    /// number of consecutive failures that will open the circuit
+    pub fn failure_threshold(&self) -> u32 {
+        self.failure_threshold
+    }
+
+    /// how long the circuit stays open before moving to half-open
+    pub fn reset_timeout(&self) -> Duration {
+        self.reset_timeout
+    }
+}
+
+/// Circuit breaker state, which is reported via the [CIRCUIT_BREAKER_STATE_METRIC_ID](constant.CIRCUIT_BREAKER_STATE_METRIC_ID.html) gauge
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum CircuitState {
+    /// requests are let through
+    Closed,
+    /// requests fail fast
+    Open,
+    /// a single trial request is let through
+    HalfOpen,
+}
+
+impl CircuitState {
+    /// the gauge metric value
+    pub fn metric_value(self) -> i64 {
+        match self {
+            CircuitState::Closed => 0,
+            CircuitState::Open => 1,
+            CircuitState::HalfOpen => 2,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum BreakerState {
+    Closed { consecutive_failures: u32 },
+    Open { opened_at: Instant },
+    HalfOpen { trial_started_at: Instant },
+}
+
+struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    state: Mutex<BreakerState>,
+    state_gauge: prometheus::IntGauge,
+}
+
+impl CircuitBreaker {
+    fn new(config: CircuitBreakerConfig, state_gauge: prometheus::IntGauge) -> CircuitBreaker {
+        state_gauge.set(CircuitState::Closed.metric_value());
+        CircuitBreaker {
+            config,
+            state: Mutex::new(BreakerState::Closed {
+                consecutive_failures: 0,
+            }),
+            state_gauge,
+        }
+    }
+
+    /// returns true if the request is let through
+    fn try_acquire(&self) -> bool {
+        let mut state = self.state.lock();
+        match *state {
+            BreakerState::Closed { .. } => true,
+            BreakerState::Open { opened_at } if opened_at.elapsed() >= self.config.reset_timeout => {
+                *state = BreakerState::HalfOpen {
+                    trial_started_at: Instant::now(),
+                };
+                self.state_gauge.set(CircuitState::HalfOpen.metric_value());
+                true
+            }
+            // while half-open, only the trial request is let through - unless the trial request was
+            // abandoned, i.e., the request future was dropped before it completed
+            BreakerState::HalfOpen { trial_started_at }
+                if trial_started_at.elapsed() >= self.config.reset_timeout =>
+            {
+                *state = BreakerState::HalfOpen {
+                    trial_started_at: Instant::now(),
+                };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn on_success(&self) {
+        let mut state = self.state.lock();
+        *state = BreakerState::Closed {
+            consecutive_failures: 0,
+        };
+        self.state_gauge.set(CircuitState::Closed.metric_value());
+    }
+
+    fn on_failure(&self) {
+        let mut state = self.state.lock();
+        let open = match *state {
+            BreakerState::Closed {
+                consecutive_failures,
+            } => {
+                let consecutive_failures = consecutive_failures + 1;
+                *state = BreakerState::Closed {
+                    consecutive_failures,
+                };
+                consecutive_failures >= self.config.failure_threshold
+            }
+            BreakerState::HalfOpen { .. } => true,
+            BreakerState::Open { .. } => false,
+        };
+        if open {
+            *state = BreakerState::Open {
+                opened_at: Instant::now(),
+            };
+            self.state_gauge.set(CircuitState::Open.metric_value());
+        }
+    }
+}
+
+/// Enforces the RequestPolicy for a client
+pub(crate) struct PolicyHandler {
+    reqrep_id: ReqRepId,
+    policy: RequestPolicy,
+    circuit_breaker: Option<CircuitBreaker>,
+    retry_count: prometheus::IntCounter,
+    hedged_request_count: prometheus::IntCounter,
+    rejected_count: prometheus::IntCounter,
+}
+
+impl PolicyHandler {
+    pub(crate) fn new(reqrep_id: ReqRepId, policy: RequestPolicy) -> PolicyHandler {
+        let reqrep_id_label = reqrep_id.to_string();
+        let labels = [reqrep_id_label.as_str()];
+        PolicyHandler {
+            reqrep_id,
+            circuit_breaker: policy.circuit_breaker.clone().map(|config| {
+                CircuitBreaker::new(config, CIRCUIT_BREAKER_STATE.with_label_values(&labels))
+            }),
+            policy,
+            retry_count: RETRY_COUNT.with_label_values(&labels),
+            hedged_request_count: HEDGED_REQUEST_COUNT.with_label_values(&labels),
+            rejected_count: CIRCUIT_BREAKER_REJECTED_COUNT.with_label_values(&labels),
+        }
+    }
+
+    /// Executes the request according to the policy
+    /// - attempt is used to send a single request
+    pub(crate) fn execute<F>(
+        self: Arc<Self>,
+        req: nng::Message,
+        attempt: F,
+    ) -> FutureReply<Result<nng::Message, RequestError>>
+    where
+        F: Fn(nng::Message) -> FutureReply<Result<nng::Message, RequestError>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let attempt = Arc::new(attempt);
+        async move {
+            if let Some(circuit_breaker) = self.circuit_breaker.as_ref() {
+                if !circuit_breaker.try_acquire() {
+                    self.rejected_count.inc();
+                    return Err(RequestError::CircuitOpen(self.reqrep_id));
+                }
+            }
+
+            let max_retries = self.policy.retry.as_ref().map_or(0, RetryPolicy::max_retries);
+            let mut retry = 0;
+            let result = loop {
+                let result = match self.policy.hedge.as_ref() {
+                    Some(hedge) => {
+                        await!(self.hedged_attempt(req.clone(), hedge.delay, attempt.clone()))
+                    }
+                    None => await!(attempt(req.clone())),
+                };
+                match result {
+                    Err(ref err) if is_transient(err) && retry < max_retries => {
+                        let backoff = self.policy.retry.as_ref().unwrap().backoff(retry);
+                        debug!(
+                            "ReqRepId({}) request failed - retry #{} in {:?}: {}",
+                            self.reqrep_id,
+                            retry + 1,
+                            backoff,
+                            err
+                        );
+                        await!(delay(backoff));
+                        self.retry_count.inc();
+                        retry += 1;
+                    }
+                    result => break result,
+                }
+            };
+
+            if let Some(circuit_breaker) = self.circuit_breaker.as_ref() {
+                match result {
+                    Err(ref err) if is_transient(err) => circuit_breaker.on_failure(),
+                    _ => circuit_breaker.on_success(),
+                }
+            }
+            result
+        }
+            .boxed()
+    }
+
+    /// sends the request, and if the reply is not received within the hedge delay, then a second
+    /// request is sent - the first successful reply wins
+    fn hedged_attempt<F>(
+        &self,
+        req: nng::Message,
+        hedge_delay: Duration,
+        attempt: Arc<F>,
+    ) -> FutureReply<Result<nng::Message, RequestError>>
+    where
+        F: Fn(nng::Message) -> FutureReply<Result<nng::Message, RequestError>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let primary = attempt(req.clone());
+        let hedge_delay = delay(hedge_delay).boxed();
+        let hedged_request_count = self.hedged_request_count.clone();
+        async move {
+            match await!(future::select(primary, hedge_delay)) {
+                Either::Left((result, _)) => result,
+                Either::Right((_, primary)) => {
+                    hedged_request_count.inc();
+                    let hedged = attempt(req);
+                    match await!(future::select(primary, hedged)) {
+                        Either::Left((Ok(rep), _)) | Either::Right((Ok(rep), _)) => Ok(rep),
+                        Either::Left((Err(_), other)) | Either::Right((Err(_), other)) => {
+                            await!(other)
+                        }
+                    }
+                }
+            }
+        }
+            .boxed()
+    }
+}
+
+/// Only send and receive failures are considered transient
+fn is_transient(err: &RequestError) -> bool {
+    match err {
+        RequestError::SendFailed(_) | RequestError::RecvFailed(_) => true,
+        _ => false,
+    }
+}
+
+#[allow(warnings)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configure_logging;
+    use oysterpack_trust::concurrent::execution::global_executor;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn handler(policy: RequestPolicy) -> Arc<PolicyHandler> {
+        Arc::new(PolicyHandler::new(ReqRepId::generate(), policy))
+    }
+
+    fn circuit_state(handler: &PolicyHandler) -> i64 {
+        handler
+            .circuit_breaker
+            .as_ref()
+            .unwrap()
+            .state_gauge
+            .get()
+    }
+
+    /// fails the first `failures` attempts with a transient error
+    fn flaky_attempt(
+        failures: usize,
+        attempts: Arc<AtomicUsize>,
+    ) -> impl Fn(nng::Message) -> FutureReply<Result<nng::Message, RequestError>>
+           + Send
+           + Sync
+           + 'static {
+        move |msg| {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if attempt < failures {
+                    Err(RequestError::SendFailed(nng::Error::Closed))
+                } else {
+                    Ok(msg)
+                }
+            }
+                .boxed()
+        }
+    }
+
+    #[test]
+    fn retry_backoff() {
+        let retry = RetryPolicy::new(5)
+            .set_initial_backoff(Duration::from_millis(10))
+            .set_max_backoff(Duration::from_millis(50));
+        assert_eq!(retry.backoff(0), Duration::from_millis(10));
+        assert_eq!(retry.backoff(1), Duration::from_millis(20));
+        assert_eq!(retry.backoff(2), Duration::from_millis(40));
+        assert_eq!(retry.backoff(3), Duration::from_millis(50));
+        assert_eq!(retry.backoff(100), Duration::from_millis(50));
+    }
+
+    #[test]
+    fn retries_transient_errors() {
+        configure_logging();
+        let mut executor = global_executor();
+        let handler = handler(
+            RequestPolicy::default()
+                .set_retry(RetryPolicy::new(3).set_initial_backoff(Duration::from_millis(1))),
+        );
+
+        // WHEN: the first 2 attempts fail
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let result = executor.run(
+            handler
+                .clone()
+                .execute(nng::Message::new().unwrap(), flaky_attempt(2, attempts.clone())),
+        );
+        // THEN: the request succeeds after 2 retries
+        assert!(result.is_ok());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(handler.retry_count.get(), 2);
+
+        // WHEN: all attempts fail
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let result = executor.run(
+            handler
+                .clone()
+                .execute(nng::Message::new().unwrap(), flaky_attempt(10, attempts.clone())),
+        );
+        // THEN: the request fails after the max number of retries
+        match result {
+            Err(RequestError::SendFailed(_)) => (),
+            other => panic!("expected SendFailed: {:?}", other.map(|_| ())),
+        }
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn circuit_breaker() {
+        configure_logging();
+        let mut executor = global_executor();
+        let reset_timeout = Duration::from_millis(20);
+        let handler = handler(
+            RequestPolicy::default().set_circuit_breaker(CircuitBreakerConfig::new(2, reset_timeout)),
+        );
+        assert_eq!(circuit_state(&handler), CircuitState::Closed.metric_value());
+
+        // WHEN: consecutive requests fail
+        let attempts = Arc::new(AtomicUsize::new(0));
+        for _ in 0..2 {
+            let _ = executor.run(
+                handler
+                    .clone()
+                    .execute(nng::Message::new().unwrap(), flaky_attempt(2, attempts.clone())),
+            );
+        }
+        // THEN: the circuit is opened
+        assert_eq!(circuit_state(&handler), CircuitState::Open.metric_value());
+        // AND: requests fail fast
+        match executor.run(
+            handler
+                .clone()
+                .execute(nng::Message::new().unwrap(), flaky_attempt(0, attempts.clone())),
+        ) {
+            Err(RequestError::CircuitOpen(_)) => (),
+            other => panic!("expected CircuitOpen: {:?}", other.map(|_| ())),
+        }
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(handler.rejected_count.get(), 1);
+
+        // WHEN: the reset timeout expires and the trial request succeeds
+        std::thread::sleep(reset_timeout);
+        assert!(executor
+            .run(
+                handler
+                    .clone()
+                    .execute(nng::Message::new().unwrap(), flaky_attempt(0, attempts.clone())),
+            )
+            .is_ok());
+        // THEN: the circuit is closed
+        assert_eq!(circuit_state(&handler), CircuitState::Closed.metric_value());
+    }
+
+    #[test]
+    fn hedged_request() {
+        configure_logging();
+        let mut executor = global_executor();
+        let handler = handler(
+            RequestPolicy::default().set_hedge(HedgePolicy::new(Duration::from_millis(5))),
+        );
+
+        // GIVEN: the first attempt never completes
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let slow_attempt = {
+            let attempts = attempts.clone();
+            move |msg: nng::Message| -> FutureReply<Result<nng::Message, RequestError>> {
+                if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                    future::pending().boxed()
+                } else {
+                    async move { Ok(msg) }.boxed()
+                }
+            }
+        };
+        // THEN: the hedged request wins
+        assert!(executor
+            .run(handler.clone().execute(nng::Message::new().unwrap(), slow_attempt))
+            .is_ok());
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(handler.hedged_request_count.get(), 1);
+    }
+}
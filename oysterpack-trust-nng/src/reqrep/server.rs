@@ -52,6 +52,9 @@
 //! ## Config
 //! - [SocketConfig](../../config/struct.SocketConfig.html)
 //! - [ListenerConfig](struct.ListenerConfig.html)
+//!   - [TlsConfig](../../tls/struct.TlsConfig.html) for `tls+tcp` and `wss` URLs, which requires the `tls` feature
+//!   - [WsConfig](../../ws/struct.WsConfig.html) for `ws` and `wss` URLs, which enables browser and
+//!     external clients to connect to the server
 //!
 //! ## Metrics
 //! - active number of socket connections - [ACTIVE_CONN_COUNT_METRIC_ID](constant.ACTIVE_CONN_COUNT_METRIC_ID.html)
@@ -59,12 +62,60 @@
 //! - total number of connections that have been initiated since the server has started - [TOT_CONN_INITIATE_COUNT_METRIC_ID](constant.TOT_CONN_INITIATE_COUNT_METRIC_ID.html)
 //!   - this may be greater that the total number of socket connections - a connection may close before
 //!     being added to the socket
+//! - total number of requests whose processing timed out - [PROCESSING_TIMEOUT_COUNT_METRIC_ID](constant.PROCESSING_TIMEOUT_COUNT_METRIC_ID.html)
 //! - the ReqRep service provides the message processing metrics
-
-use crate::config::{SocketConfig, SocketConfigError};
+//!
+//! ## Connection Events and Access Control
+//! - [ServerHandle::pipe_events()](struct.ServerHandle.html#method.pipe_events) is used to subscribe
+//!   to the server's [PipeEvent](../../pipe/struct.PipeEvent.html) stream, e.g., for audit logging
+//! - [spawn_with_connection_filter()](fn.spawn_with_connection_filter.html) is used to spawn a server
+//!   that allows or denies connections via a [ConnectionFilter](../../pipe/trait.ConnectionFilter.html)
+//!
+//! ## Processing Timeout
+//! By default, the Aio event loop waits indefinitely for the ReqRep service to reply. A processing
+//! timeout can be configured via [ListenerConfig::set_processing_timeout()](struct.ListenerConfig.html#method.set_processing_timeout).
+//! When the timeout expires, the pending reply is abandoned, and the server replies with the
+//! [ReplyStatus::ProcessingTimeout](../envelope/enum.ReplyStatus.html#variant.ProcessingTimeout)
+//! [envelope](../envelope/index.html), which [TypedClient](../codec/struct.TypedClient.html) reports
+//! as [RequestError::ProcessingTimeout](../client/enum.RequestError.html#variant.ProcessingTimeout).
+//!
+//! In order for clients to distinguish the timeout reply from a service reply, every reply is sealed
+//! in the envelope when a processing timeout is configured:
+//! - by default, the service replies are assumed to be raw messages, which the server seals with the
+//!   `Ok` status. Raw clients open every reply via [envelope::open()](../envelope/fn.open.html).
+//! - services that seal their own replies, i.e., services that are backed by a
+//!   [CodecProcessor](../codec/struct.CodecProcessor.html), must be flagged via
+//!   [ListenerConfig::set_sealed_replies()](struct.ListenerConfig.html#method.set_sealed_replies)
+//!
+//! Without a processing timeout, replies are sent as is.
+//!
+//! The ReqRep service is not interrupted: it processes requests one at a time, and it will finish
+//! processing the timed out request, whose reply is then discarded. Thus, requests that are queued
+//! behind a slow request may also time out.
+
+use crate::{
+    config::{SocketConfig, SocketConfigError},
+    message,
+    pipe::{
+        ConnectionFilter, PipeEvent, PipeEventBroadcaster, PipeEventKind, PipeEventStream,
+        PipeInfo,
+    },
+    reqrep::envelope::{self, ReplyStatus},
+    task,
+    timer::delay,
+    ws::{WsConfig, WsConfigError},
+};
+#[cfg(feature = "tls")]
+use crate::tls::{TlsConfig, TlsConfigError};
 use failure::Fail;
-use futures::{future::FutureExt, prelude::*, sink::SinkExt, stream::StreamExt, task::SpawnExt};
-use hashbrown::HashMap;
+use futures::{
+    future::{self, Either, FutureExt},
+    prelude::*,
+    sink::SinkExt,
+    stream::StreamExt,
+    task::SpawnExt,
+};
+use hashbrown::{HashMap, HashSet};
 use lazy_static::lazy_static;
 use nng::options::Options;
 use oysterpack_log::*;
@@ -76,9 +127,18 @@ use oysterpack_trust::{
     metrics,
 };
 use oysterpack_uid::ULID;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use serde::{Deserialize, Serialize};
-use std::{fmt, num::NonZeroUsize, panic::AssertUnwindSafe};
+use std::{
+    cell::Cell,
+    fmt,
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 lazy_static! {
 
@@ -86,6 +146,7 @@ lazy_static! {
     static ref SERVER_HANDLES: RwLock<HashMap<ULID, ServerHandle>> = RwLock::new(HashMap::new());
 
     /// the metric is incremented on nng::PipeEvent::AddPost and decremented on nng::PipeEvent::RemovePost
+    /// - pipes that were rejected before being added to the socket are not counted
     static ref ACTIVE_CONN_COUNT: prometheus::IntGaugeVec = metrics::registry().register_int_gauge_vec(
         ACTIVE_CONN_COUNT_METRIC_ID,
         "Active number of socket connections",
@@ -111,6 +172,14 @@ lazy_static! {
         None
     ).unwrap();
 
+    /// the metric is incremented when the ReqRep service fails to reply within the configured processing timeout
+    static ref PROCESSING_TIMEOUT_COUNT: prometheus::IntCounterVec = metrics::registry().register_int_counter_vec(
+        PROCESSING_TIMEOUT_COUNT_METRIC_ID,
+        "Total number of requests whose processing timed out",
+        &[REQREP_LABEL_ID],
+        None
+    ).unwrap();
+
 }
 
 /// IntGaugeVec MetricId which is used to track the total number of active socket connections by ReqRepId
@@ -122,11 +191,15 @@ pub const TOT_CONN_COUNT_METRIC_ID: metrics::MetricId =
 /// IntCounterVec MetricId which is used to track the total number of connection that have been initiated by ReqRepId
 pub const TOT_CONN_INITIATE_COUNT_METRIC_ID: metrics::MetricId =
     metrics::MetricId(1873172273925609759145190455058277250);
+/// IntCounterVec MetricId which is used to track the total number of requests whose processing timed out by ReqRepId: `M01M57FN5DTX2E4THBH3H1GQFVD`
+pub const PROCESSING_TIMEOUT_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166789671337961655587501644251381613);
 
 /// Metric LabelId which is used to store a ReqRepId
 /// - this is used by the following metrics:
 ///   - IntGaugeVec(ACTIVE_CONN_COUNT_METRIC_ID)
 ///   - IntCounterVec(TOT_CONN_COUNT_METRIC_ID)
+///   - IntCounterVec(PROCESSING_TIMEOUT_COUNT_METRIC_ID)
 pub const REQREP_LABEL_ID: metrics::LabelId =
     metrics::LabelId(1873168278096570673538811977244540631);
 
@@ -145,37 +218,142 @@ pub const REQREP_LABEL_ID: metrics::LabelId =
 ///     - handles server management commands
 ///       - responds to ping requests
 ///       - listens for a stop signal from the ServerHandle
+///       - admin commands, e.g., pause / resume, change parallelism - see [ServerHandle](struct.ServerHandle.html)
 pub fn spawn(
     socket_config: Option<SocketConfig>,
     listener_config: ListenerConfig,
     service: ReqRep<nng::Message, nng::Message>,
-    mut executor: Executor,
+    executor: Executor,
+) -> Result<ServerHandle, SpawnError> {
+    spawn_server(socket_config, listener_config, service, None, None, executor)
+}
+
+/// Spawns a server background task, which only accepts connections that are allowed by the specified
+/// ConnectionFilter - see [spawn()](fn.spawn.html)
+/// - connections that are denied are closed before being added to the socket, and are reported as
+///   [PipeEventKind::Rejected](../../pipe/enum.PipeEventKind.html#variant.Rejected) events
+pub fn spawn_with_connection_filter(
+    socket_config: Option<SocketConfig>,
+    listener_config: ListenerConfig,
+    service: ReqRep<nng::Message, nng::Message>,
+    connection_filter: Arc<dyn ConnectionFilter>,
+    executor: Executor,
+) -> Result<ServerHandle, SpawnError> {
+    spawn_server(
+        socket_config,
+        listener_config,
+        service,
+        Some(connection_filter),
+        None,
+        executor,
+    )
+}
+
+/// Spawns a server background task, which applies the RequestInterceptor to each request before it
+/// is forwarded to the ReqRep service - see [spawn()](fn.spawn.html)
+/// - used by the test harness to inject faults
+#[cfg(any(test, feature = "testing"))]
+pub(crate) fn spawn_with_interceptor(
+    socket_config: Option<SocketConfig>,
+    listener_config: ListenerConfig,
+    service: ReqRep<nng::Message, nng::Message>,
+    interceptor: Arc<dyn RequestInterceptor>,
+    executor: Executor,
+) -> Result<ServerHandle, SpawnError> {
+    spawn_server(
+        socket_config,
+        listener_config,
+        service,
+        None,
+        Some(interceptor),
+        executor,
+    )
+}
+
+/// Intercepts requests before they are forwarded to the ReqRep service
+#[cfg_attr(not(any(test, feature = "testing")), allow(dead_code))]
+pub(crate) trait RequestInterceptor: Send + Sync + 'static {
+    /// Decides how the request is handled
+    fn intercept(&self, req: &nng::Message) -> Interception;
+}
+
+/// How an intercepted request is handled
+#[cfg_attr(not(any(test, feature = "testing")), allow(dead_code))]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum Interception {
+    /// The request is forwarded to the ReqRep service
+    Forward,
+    /// The request is forwarded to the ReqRep service after the delay
+    Delay(Duration),
+    /// The request is dropped, i.e., it is not processed and no reply is sent
+    Drop,
+}
+
+fn spawn_server(
+    socket_config: Option<SocketConfig>,
+    listener_config: ListenerConfig,
+    service: ReqRep<nng::Message, nng::Message>,
+    connection_filter: Option<Arc<dyn ConnectionFilter>>,
+    interceptor: Option<Arc<dyn RequestInterceptor>>,
+    executor: Executor,
 ) -> Result<ServerHandle, SpawnError> {
     let (server_command_tx, mut server_command_rx) = futures::channel::mpsc::channel(1);
 
     let reqrep_id = service.id();
     let url = listener_config.url.clone();
     let parallelism = listener_config.parallelism();
+    let processing_timeout = listener_config.processing_timeout();
+    let seal_replies = processing_timeout.is_some() && !listener_config.sealed_replies();
     let server_metrics = ServerMetrics::new(reqrep_id);
     let server_handle_id = ULID::generate();
+    let pipe_events = PipeEventBroadcaster::default();
 
     let create_socket = || {
         let server_metrics = server_metrics.clone();
+        let pipe_events = pipe_events.clone();
+        // ids of the pipes that have been added to the socket
+        // - RemovePost is also fired for pipes that were rejected during AddPre, i.e., that were never added
+        let admitted_pipes = Mutex::new(HashSet::<i32>::new());
         let mut socket =
             nng::Socket::new(nng::Protocol::Rep0).map_err(SpawnError::SocketCreateFailure)?;
         socket.set_nonblocking(true);
         socket
             .pipe_notify(move |pipe, event| {
+                debug!("{:?} {:?}", pipe, event);
                 match event {
                     nng::PipeEvent::AddPost => {
+                        admitted_pipes.lock().insert(pipe.id());
                         server_metrics.active_conn_count.inc();
                         server_metrics.tot_conn_count.inc();
+                        pipe_events.broadcast(PipeEvent::new(
+                            PipeEventKind::Added,
+                            PipeInfo::new(pipe),
+                        ));
+                    }
+                    nng::PipeEvent::RemovePost => {
+                        if admitted_pipes.lock().remove(&pipe.id()) {
+                            server_metrics.active_conn_count.dec();
+                            pipe_events.broadcast(PipeEvent::new(
+                                PipeEventKind::Removed,
+                                PipeInfo::new(pipe),
+                            ));
+                        }
+                    }
+                    nng::PipeEvent::AddPre => {
+                        server_metrics.tot_conn_initiate_count.inc();
+                        if let Some(connection_filter) = connection_filter.as_ref() {
+                            let pipe_info = PipeInfo::new(pipe);
+                            if !connection_filter.allow(&pipe_info) {
+                                debug!("connection was rejected: {:?}", pipe_info);
+                                // closing the pipe during AddPre prevents it from being added to the socket
+                                pipe.close();
+                                pipe_events
+                                    .broadcast(PipeEvent::new(PipeEventKind::Rejected, pipe_info));
+                            }
+                        }
                     }
-                    nng::PipeEvent::RemovePost => server_metrics.active_conn_count.dec(),
-                    nng::PipeEvent::AddPre => server_metrics.tot_conn_initiate_count.inc(),
                     _ => (),
                 }
-                debug!("{:?} {:?}", pipe, event);
             })
             .map_err(SpawnError::SocketCreateFailure)?;
         match socket_config {
@@ -192,164 +370,117 @@ pub fn spawn(
             .map_err(SpawnError::ListenerStartFailure)
     };
 
-    // spawns the worker tasks
-    // - each Aio Context is serviced by its own private event loop running as a future
-    // - the worker tasks will wait to be signalled via the returned channels to start listening on the Socket
-    // - the worker's job is to integrate nng with the backend ReqRep service - it simply relays nng
-    //   request messages to the ReqRep service, and then sends back the reply message returned from
-    //   the ReqRep service
-    //
-    // Socket ---> Aio callback ---> worker --- nng::Message --> ReqRep service
-    // Socket <----nng::message----- worker <-- nng::Message --- ReqRep service
-    let mut create_workers =
-        |socket: &nng::Socket| -> Result<Vec<futures::channel::oneshot::Sender<()>>, SpawnError> {
-            let mut worker_start_chans = Vec::with_capacity(parallelism);
-            for i in 0..parallelism {
-                // used to signal the workers to start listening, i.e., start receiving messages
-                let (start_tx, start_rx) = futures::channel::oneshot::channel::<()>();
-                worker_start_chans.push(start_tx);
-                // used to notify the workers when an Aio event has occurred, i.e., the Aio callback has been invoked
-                let (aio_tx, mut aio_rx) = futures::channel::mpsc::unbounded::<()>();
-                let aio_tx = AssertUnwindSafe(aio_tx);
-                let ctx = nng::Context::new(socket).map_err(SpawnError::ContextCreateFailure)?;
-                let callback_ctx = ctx.clone();
-                let aio = nng::Aio::with_callback(move |_aio| {
-                    if let Err(err) = aio_tx.unbounded_send(()) {
-                        // means the channel has been disconnected because the worker Future task has completed
-                        // the server is either being stopped, or the worker has crashed
-                        // TODO: we need a way to know if the server is being shutdown
-                        warn!("Failed to nofify worker of Aio event. This means the worker is not running. The Aio Context will be closed: {}", err);
-                        // TODO: will cloning the Context work ? Context::close() cannot be invoked from the callback because it consumes the Context
-                        //       and rust won't allow it because the Context is being referenced by the FnMut closure
-                        callback_ctx.clone().close();
-                        // TODO: send an alert - if the worker crashed, i.e., panicked, then it may need to be restarted
-                    }
-                }).map_err(SpawnError::AioCreateWithCallbackFailure)?;
-                let mut service_client = service.clone();
-                executor
-                    .spawn(
-                        async move {
-                            debug!("worker #{} is awaiting signal to start listening ...", i);
-                            match await!(start_rx) {
-                                Ok(_) => {
-                                    debug!("worker #{} is starting ...", i);
-                                    let mut state = AioState::Recv;
-
-                                    let recv = |state: AioState| {
-                                        if let Err(err) = ctx.recv(&aio) {
-                                            // TODO: trigger alert - async I/O errors need to be investigated
-                                            error!("{:?}: Context::recv() failed: {}", state, err);
-                                        }
-                                        AioState::Recv
-                                    };
-
-                                    let send = |state: AioState, msg: nng::Message| {
-                                        if let Err((_msg, err)) = ctx.send(&aio, msg) {
-                                            // TODO: trigger alert - async I/O errors need to be investigated
-                                            error!("{:?}: Context::send() failed: {}", state, err);
-                                            aio.cancel();
-                                            return recv(state);
-                                        }
-                                        AioState::Send
-                                    };
-
-                                    let reqrep_send_recv_failed = |state, err, reqrep_id| {
-                                        error!(
-                                            "ReqRep::send_recv() failed: ReqRepId({}) : {}",
-                                            reqrep_id, err
-                                        );
-                                        aio.cancel();
-                                        recv(state)
-                                    };
+    let server_state = Arc::new(ServerState::new(parallelism));
+    let mut worker_factory = WorkerFactory {
+        service,
+        processing_timeout,
+        seal_replies,
+        interceptor,
+        processing_timeout_count: server_metrics.processing_timeout_count.clone(),
+        server_state: server_state.clone(),
+        executor: executor.clone(),
+    };
 
-                                    let no_msg_available = |state| {
-                                        warn!("{:?} Expected a message to be available", state);
-                                        aio.cancel();
-                                        recv(state)
-                                    };
-
-                                    let handle_aio_error = |state, err: nng::Error| match err {
-                                        nng::Error::Closed => AioState::Closed,
-                                        _ => {
-                                            error!("{:?}: Aio error: {}", state, err);
-                                            aio.cancel();
-                                            recv(state)
-                                        }
-                                    };
-
-                                    // start listening
-                                    recv(state);
-                                    debug!("worker #{} is listening ...", i);
-                                    // NOTE: aio.result().unwrap() is safe because we are being signalled
-                                    // by the Aio callback to handle an Aio event
-                                    while let Some(_) = await!(aio_rx.next()) {
-                                        state = match state {
-                                            AioState::Recv => match aio.result().unwrap() {
-                                                Ok(_) => match aio.get_msg() {
-                                                    Some(msg) => {
-                                                        match await!(service_client.send_recv(msg))
-                                                        {
-                                                            Ok(reply) => send(state, reply),
-                                                            Err(err) => reqrep_send_recv_failed(
-                                                                state,
-                                                                err,
-                                                                service_client.id(),
-                                                            ),
-                                                        }
-                                                    }
-                                                    None => no_msg_available(state),
-                                                },
-                                                Err(err) => handle_aio_error(state, err),
-                                            },
-                                            AioState::Send => match aio.result().unwrap() {
-                                                Ok(_) => recv(state),
-                                                Err(err) => handle_aio_error(state, err),
-                                            },
-                                            // this state will never be matched against, but we must fulfill the match contract
-                                            AioState::Closed => break,
-                                        };
-                                        if state == AioState::Closed {
-                                            break;
-                                        }
-                                    }
-                                    debug!("worker #{} task is done", i);
-                                }
-                                Err(_) => {
-                                    debug!("worker #{} task was cancelled", i);
-                                }
-                            }
-                        },
-                    )
-                    .map_err(|err| SpawnError::ExecutorSpawnError {
-                        is_executor_shutdown: err.is_shutdown(),
-                    })?;
-            }
-            Ok(worker_start_chans)
-        };
+    let create_workers = |socket: &nng::Socket,
+                          worker_factory: &mut WorkerFactory|
+     -> Result<Vec<WorkerHandle>, SpawnError> {
+        let mut workers = Vec::with_capacity(parallelism);
+        for i in 0..parallelism {
+            workers.push(worker_factory.spawn(i, socket)?);
+        }
+        Ok(workers)
+    };
 
-    let start_workers = |worker_start_chans: Vec<futures::channel::oneshot::Sender<()>>,
-                         socket: nng::Socket,
-                         listener: nng::Listener,
-                         mut executor: Executor| {
+    // the server controller task handles the server commands
+    let start_controller = |mut workers: Vec<WorkerHandle>,
+                            socket: nng::Socket,
+                            listener: nng::Listener,
+                            mut worker_factory: WorkerFactory,
+                            mut executor: Executor| {
+        let pipe_events = pipe_events.clone();
+        let server_state = server_state.clone();
+        let server_metrics = server_metrics.clone();
         executor.spawn_with_handle(async move{
-            for c in worker_start_chans {
-                if c.send(()).is_err() {
-                    // TODO: trigger alert - this should never happen
-                    error!("Unable to send worker start signal because the channel has been disconnected");
-                }
+            for worker in workers.iter_mut() {
+                worker.start();
             }
+            let mut next_worker_id = workers.len();
             debug!("Server({}) is running ...", reqrep_id);
             while let Some(cmd) = await!(server_command_rx.next()) {
                 match cmd {
                     ServerCommand::Ping(reply_chan) => {
                         let _ = reply_chan.send(());
                     },
+                    ServerCommand::Pause(reply_chan) => {
+                        debug!("Server({}) is pausing ...", reqrep_id);
+                        let paused = server_state.pause();
+                        if paused {
+                            for worker in workers.iter() {
+                                worker.send(WorkerCommand::Pause);
+                            }
+                        }
+                        let _ = reply_chan.send(paused);
+                    },
+                    ServerCommand::Resume(reply_chan) => {
+                        debug!("Server({}) is resuming ...", reqrep_id);
+                        let resumed = server_state.resume();
+                        if resumed {
+                            for worker in workers.iter() {
+                                worker.send(WorkerCommand::Resume);
+                            }
+                        }
+                        let _ = reply_chan.send(resumed);
+                    },
+                    ServerCommand::SetParallelism(parallelism, reply_chan) => {
+                        let parallelism = parallelism.get();
+                        let mut result = Ok(());
+                        while workers.len() < parallelism {
+                            match worker_factory.spawn(next_worker_id, &socket) {
+                                Ok(mut worker) => {
+                                    worker.start();
+                                    workers.push(worker);
+                                    next_worker_id += 1;
+                                },
+                                Err(err) => {
+                                    result = Err(err);
+                                    break;
+                                }
+                            }
+                        }
+                        while workers.len() > parallelism {
+                            // the retired worker exits on its own once its in-flight request has completed
+                            let _ = workers.pop().unwrap().retire();
+                        }
+                        server_state.parallelism.store(workers.len(), Ordering::SeqCst);
+                        debug!("Server({}) parallelism = {}", reqrep_id, workers.len());
+                        let _ = reply_chan.send(result);
+                    },
+                    ServerCommand::Stats(reply_chan) => {
+                        let _ = reply_chan.send(ServerStats {
+                            active_conn_count: server_metrics.active_conn_count(),
+                            parallelism: workers.len(),
+                            worker_count: server_state.worker_count(),
+                            in_flight_count: server_state.in_flight_count(),
+                            processed_count: server_state.processed_count(),
+                            paused: server_state.paused(),
+                        });
+                    },
+                    ServerCommand::DrainAndStop(timeout, reply_chan) => {
+                        debug!("Server({}) is draining ...", reqrep_id);
+                        // stop receiving new requests, and let the in-flight requests complete
+                        let workers_done = workers.drain(..).map(WorkerHandle::retire).collect();
+                        let drained = await!(await_drained(workers_done, timeout));
+                        debug!("Server({}) drained = {}", reqrep_id, drained);
+                        let _ = reply_chan.send(drained);
+                        break
+                    },
                     ServerCommand::Stop => break
                 }
             }
             debug!("Server({}) is shutting down ...", reqrep_id);
             listener.close();
             socket.close();
+            pipe_events.close();
             debug!("Server({}) is shut down", reqrep_id);
             let mut server_handles = SERVER_HANDLES.write();
             server_handles.remove(&server_handle_id);
@@ -359,19 +490,20 @@ pub fn spawn(
     };
 
     let socket = create_socket()?;
-    let worker_start_chans = create_workers(&socket)?;
+    let workers = create_workers(&socket, &mut worker_factory)?;
     let listener = start_listener(&socket)?;
-    let handle = start_workers(worker_start_chans, socket, listener, executor.clone())?;
+    let handle = start_controller(workers, socket, listener, worker_factory, executor.clone())?;
 
     let server_handle = ServerHandle {
         id: server_handle_id,
         url,
         reqrep_id,
-        parallelism: NonZeroUsize::new(parallelism).unwrap(),
         handle: Some(handle.shared()),
         server_command_channel: Some(server_command_tx),
         executor,
         metrics: server_metrics,
+        pipe_events,
+        state: server_state,
     };
 
     let mut server_handles = SERVER_HANDLES.write();
@@ -380,22 +512,462 @@ pub fn spawn(
     Ok(server_handle)
 }
 
+/// Spawns the worker tasks
+/// - each Aio Context is serviced by its own private event loop running as a future
+/// - the worker tasks will wait to be signalled via WorkerHandle::start() to start listening on the Socket
+/// - the worker's job is to integrate nng with the backend ReqRep service - it simply relays nng
+///   request messages to the ReqRep service, and then sends back the reply message returned from
+///   the ReqRep service
+/// - the worker owns its Aio Context, i.e., only the worker closes its Context when it exits
+///
+/// <pre>
+/// Socket ---> Aio callback ---> worker --- nng::Message --> ReqRep service
+/// Socket <----nng::message----- worker <-- nng::Message --- ReqRep service
+/// </pre>
+struct WorkerFactory {
+    service: ReqRep<nng::Message, nng::Message>,
+    processing_timeout: Option<Duration>,
+    /// if true, then the service replies are sealed in the reply envelope with the `Ok` status
+    seal_replies: bool,
+    interceptor: Option<Arc<dyn RequestInterceptor>>,
+    processing_timeout_count: prometheus::IntCounter,
+    server_state: Arc<ServerState>,
+    executor: Executor,
+}
+
+impl WorkerFactory {
+    fn spawn(&mut self, i: usize, socket: &nng::Socket) -> Result<WorkerHandle, SpawnError> {
+        // used to signal the worker to start listening, i.e., start receiving messages
+        let (start_tx, start_rx) = futures::channel::oneshot::channel::<()>();
+        // the channel is unbounded because the server controller must not block on a busy worker
+        let (command_tx, command_rx) = futures::channel::mpsc::unbounded::<WorkerCommand>();
+        // signals that the worker task has exited
+        let (done_tx, done_rx) = futures::channel::oneshot::channel::<()>();
+        let ctx = nng::Context::new(socket).map_err(SpawnError::ContextCreateFailure)?;
+        // used to notify the worker when an Aio event has occurred, i.e., the Aio callback has been invoked
+        let (aio, aio_rx) =
+            task::aio_notifier("server worker").map_err(SpawnError::AioCreateWithCallbackFailure)?;
+
+        let worker = WorkerHandle {
+            start: Some(start_tx),
+            command_channel: command_tx,
+            done: done_rx,
+        };
+        let busy = AtomicBool::new(false);
+        let mut service_client = self.service.clone();
+        let processing_timeout = self.processing_timeout;
+        let seal_replies = self.seal_replies;
+        let interceptor = self.interceptor.clone();
+        let processing_timeout_count = self.processing_timeout_count.clone();
+        let server_state = self.server_state.clone();
+        let worker_server_state = self.server_state.clone();
+        worker_server_state.worker_count.fetch_add(1, Ordering::SeqCst);
+        let result = self.executor.spawn(
+            async move {
+                debug!("worker #{} is awaiting signal to start listening ...", i);
+                if await!(start_rx).is_ok() {
+                    debug!("worker #{} is starting ...", i);
+                    // fuse the streams that will be polled via futures::select! - per the documentation
+                    let mut aio_rx = aio_rx.fuse();
+                    let mut command_rx = command_rx.fuse();
+                    let paused = Cell::new(server_state.paused());
+                    let retired = Cell::new(false);
+
+                    // tracks whether the worker is processing a request
+                    let set_busy = |is_busy: bool| {
+                        if busy.swap(is_busy, Ordering::SeqCst) != is_busy {
+                            if is_busy {
+                                server_state.in_flight_count.fetch_add(1, Ordering::SeqCst);
+                            } else {
+                                server_state.in_flight_count.fetch_sub(1, Ordering::SeqCst);
+                            }
+                        }
+                    };
+
+                    // receives the next request, unless the worker is retired or paused
+                    let recv = |state: AioState| {
+                        set_busy(false);
+                        if retired.get() {
+                            debug!("worker #{} has been retired", i);
+                            return AioState::Closed;
+                        }
+                        if paused.get() {
+                            debug!("worker #{} is paused", i);
+                            return AioState::Idle;
+                        }
+                        if let Err(err) = ctx.recv(&aio) {
+                            // TODO: trigger alert - async I/O errors need to be investigated
+                            error!("{:?}: Context::recv() failed: {}", state, err);
+                        }
+                        AioState::Recv
+                    };
+
+                    let send = |state: AioState, msg: nng::Message| {
+                        if let Err((msg, err)) = ctx.send(&aio, msg) {
+                            message::global_pool().release(msg);
+                            // TODO: trigger alert - async I/O errors need to be investigated
+                            error!("{:?}: Context::send() failed: {}", state, err);
+                            aio.cancel();
+                            return recv(state);
+                        }
+                        AioState::Send
+                    };
+
+                    let send_reply = |state: AioState, mut reply: nng::Message| {
+                        if seal_replies {
+                            if let Err(err) = envelope::seal_ok(&mut reply) {
+                                message::global_pool().release(reply);
+                                error!("{:?}: Failed to seal reply: {}", state, err);
+                                aio.cancel();
+                                return recv(state);
+                            }
+                        }
+                        send(state, reply)
+                    };
+
+                    let reqrep_send_recv_failed = |state, err, reqrep_id| {
+                        error!(
+                            "ReqRep::send_recv() failed: ReqRepId({}) : {}",
+                            reqrep_id, err
+                        );
+                        aio.cancel();
+                        recv(state)
+                    };
+
+                    let processing_timed_out = |state| {
+                        processing_timeout_count.inc();
+                        let timeout_reply = || {
+                            let mut reply = message::global_pool().acquire(0)?;
+                            envelope::seal_error(
+                                &mut reply,
+                                ReplyStatus::ProcessingTimeout,
+                                "request processing timed out",
+                            )?;
+                            Ok::<_, nng::Error>(reply)
+                        };
+                        match timeout_reply() {
+                            Ok(reply) => send(state, reply),
+                            Err(err) => {
+                                error!("{:?}: Failed to create timeout reply: {}", state, err);
+                                aio.cancel();
+                                recv(state)
+                            }
+                        }
+                    };
+
+                    let intercept = |req: &nng::Message| match interceptor.as_ref() {
+                        Some(interceptor) => interceptor.intercept(req),
+                        None => Interception::Forward,
+                    };
+
+                    // the REP context discards the dropped request when the next request is received
+                    let drop_request = |state, req: nng::Message| {
+                        debug!("{:?}: request was dropped by the interceptor", state);
+                        message::global_pool().release(req);
+                        recv(state)
+                    };
+
+                    let no_msg_available = |state| {
+                        warn!("{:?} Expected a message to be available", state);
+                        aio.cancel();
+                        recv(state)
+                    };
+
+                    let handle_aio_error = |state, err: nng::Error| match err {
+                        nng::Error::Closed => AioState::Closed,
+                        // the pending receive was cancelled because the worker was paused or retired
+                        nng::Error::Canceled => recv(state),
+                        _ => {
+                            error!("{:?}: Aio error: {}", state, err);
+                            aio.cancel();
+                            recv(state)
+                        }
+                    };
+
+                    // start listening
+                    let mut state = recv(AioState::Idle);
+                    debug!("worker #{} is listening ...", i);
+                    while state != AioState::Closed {
+                        futures::select! {
+                            cmd = command_rx.next() => match cmd {
+                                Some(WorkerCommand::Pause) => {
+                                    paused.set(true);
+                                    // the cancelled receive is handled as an Aio event
+                                    if state == AioState::Recv {
+                                        aio.cancel();
+                                    }
+                                }
+                                Some(WorkerCommand::Resume) => {
+                                    paused.set(false);
+                                    if state == AioState::Idle {
+                                        state = recv(state);
+                                    }
+                                }
+                                // the server controller has dropped the worker handle
+                                Some(WorkerCommand::Retire) | None => {
+                                    retired.set(true);
+                                    match state {
+                                        AioState::Recv => aio.cancel(),
+                                        AioState::Idle => state = AioState::Closed,
+                                        // the worker exits once the reply has been sent
+                                        _ => (),
+                                    }
+                                }
+                            },
+                            aio_event = aio_rx.next() => {
+                                if aio_event.is_none() {
+                                    break;
+                                }
+                                // NOTE: aio.result().unwrap() is safe because we are being signalled
+                                // by the Aio callback to handle an Aio event
+                                state = match state {
+                                    AioState::Recv => match aio.result().unwrap() {
+                                        Ok(_) => match aio.get_msg() {
+                                            Some(msg) => match intercept(&msg) {
+                                                Interception::Drop => drop_request(state, msg),
+                                                interception => {
+                                                    set_busy(true);
+                                                    if let Interception::Delay(delay_by) = interception {
+                                                        await!(delay(delay_by));
+                                                    }
+                                                    let reqrep_id = service_client.id();
+                                                    let reply = match processing_timeout {
+                                                        Some(timeout) => {
+                                                            let reply =
+                                                                Box::pin(service_client.send_recv(msg));
+                                                            let timeout = Box::pin(delay(timeout));
+                                                            // when the timeout expires, the reply future is dropped, but
+                                                            // the ReqRep service still completes processing the request
+                                                            match await!(future::select(reply, timeout)) {
+                                                                Either::Left((reply, _)) => Some(reply),
+                                                                Either::Right(_) => None,
+                                                            }
+                                                        }
+                                                        None => Some(await!(service_client.send_recv(msg))),
+                                                    };
+                                                    match reply {
+                                                        Some(Ok(reply)) => send_reply(state, reply),
+                                                        Some(Err(err)) => {
+                                                            reqrep_send_recv_failed(state, err, reqrep_id)
+                                                        }
+                                                        None => processing_timed_out(state),
+                                                    }
+                                                }
+                                            },
+                                            None => no_msg_available(state),
+                                        },
+                                        Err(err) => handle_aio_error(state, err),
+                                    },
+                                    AioState::Send => match aio.result().unwrap() {
+                                        Ok(_) => {
+                                            server_state.processed_count.fetch_add(1, Ordering::SeqCst);
+                                            recv(state)
+                                        }
+                                        Err(err) => handle_aio_error(state, err),
+                                    },
+                                    // no Aio operation is pending
+                                    AioState::Idle | AioState::Closed => state,
+                                };
+                            },
+                        }
+                    }
+                    set_busy(false);
+                    debug!("worker #{} task is done", i);
+                } else {
+                    debug!("worker #{} task was cancelled", i);
+                }
+                ctx.close();
+                server_state.worker_count.fetch_sub(1, Ordering::SeqCst);
+                let _ = done_tx.send(());
+            },
+        );
+        if let Err(err) = result {
+            worker_server_state
+                .worker_count
+                .fetch_sub(1, Ordering::SeqCst);
+            return Err(SpawnError::ExecutorSpawnError {
+                is_executor_shutdown: err.is_shutdown(),
+            });
+        }
+        Ok(worker)
+    }
+}
+
+/// Commands that the server controller sends to a worker
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum WorkerCommand {
+    /// stop receiving requests - the pending receive, if any, is cancelled
+    Pause,
+    /// resume receiving requests
+    Resume,
+    /// exit once the in-flight request, if any, has completed
+    Retire,
+}
+
+/// Used by the server controller to manage a worker
+struct WorkerHandle {
+    start: Option<futures::channel::oneshot::Sender<()>>,
+    command_channel: futures::channel::mpsc::UnboundedSender<WorkerCommand>,
+    done: futures::channel::oneshot::Receiver<()>,
+}
+
+impl WorkerHandle {
+    /// signals the worker to start listening
+    fn start(&mut self) {
+        if let Some(start) = self.start.take() {
+            if start.send(()).is_err() {
+                // TODO: trigger alert - this should never happen
+                error!("Unable to send worker start signal because the channel has been disconnected");
+            }
+        }
+    }
+
+    fn send(&self, cmd: WorkerCommand) {
+        if self.command_channel.unbounded_send(cmd).is_err() {
+            debug!("{:?} was not sent because the worker has exited", cmd);
+        }
+    }
+
+    /// Signals the worker to exit once its in-flight request, if any, has completed.
+    /// - returns a future that completes when the worker task has exited
+    fn retire(self) -> futures::channel::oneshot::Receiver<()> {
+        self.send(WorkerCommand::Retire);
+        self.done
+    }
+}
+
+/// Server state that is shared by the server controller and the workers
+#[derive(Debug)]
+struct ServerState {
+    parallelism: AtomicUsize,
+    worker_count: AtomicUsize,
+    in_flight_count: AtomicUsize,
+    processed_count: AtomicUsize,
+    paused: AtomicBool,
+}
+
+impl ServerState {
+    fn new(parallelism: usize) -> ServerState {
+        ServerState {
+            parallelism: AtomicUsize::new(parallelism),
+            worker_count: AtomicUsize::new(0),
+            in_flight_count: AtomicUsize::new(0),
+            processed_count: AtomicUsize::new(0),
+            paused: AtomicBool::new(false),
+        }
+    }
+
+    fn parallelism(&self) -> usize {
+        self.parallelism.load(Ordering::SeqCst)
+    }
+
+    fn worker_count(&self) -> usize {
+        self.worker_count.load(Ordering::SeqCst)
+    }
+
+    fn in_flight_count(&self) -> usize {
+        self.in_flight_count.load(Ordering::SeqCst)
+    }
+
+    fn processed_count(&self) -> usize {
+        self.processed_count.load(Ordering::SeqCst)
+    }
+
+    fn paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// returns false if the server was already paused
+    fn pause(&self) -> bool {
+        !self.paused.swap(true, Ordering::SeqCst)
+    }
+
+    /// returns false if the server was not paused
+    fn resume(&self) -> bool {
+        self.paused.swap(false, Ordering::SeqCst)
+    }
+}
+
+/// returns true if the retired workers exited, i.e., their in-flight requests completed, before the
+/// timeout expired
+async fn await_drained(
+    workers_done: Vec<futures::channel::oneshot::Receiver<()>>,
+    timeout: Duration,
+) -> bool {
+    let drained = future::join_all(workers_done);
+    let timeout = Box::pin(delay(timeout));
+    match await!(future::select(drained, timeout)) {
+        Either::Left(_) => true,
+        Either::Right(_) => false,
+    }
+}
+
+/// Server stats snapshot
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ServerStats {
+    active_conn_count: usize,
+    parallelism: usize,
+    worker_count: usize,
+    in_flight_count: usize,
+    processed_count: usize,
+    paused: bool,
+}
+
+impl ServerStats {
+    /// Active number of socket connections
+    pub fn active_conn_count(&self) -> usize {
+        self.active_conn_count
+    }
+
+    /// Number of Aio Context workers
+    pub fn parallelism(&self) -> usize {
+        self.parallelism
+    }
+
+    /// Number of running worker tasks, which includes retired workers that have not yet exited
+    pub fn worker_count(&self) -> usize {
+        self.worker_count
+    }
+
+    /// Number of requests that are currently being processed
+    pub fn in_flight_count(&self) -> usize {
+        self.in_flight_count
+    }
+
+    /// Total number of requests that have been processed, i.e., replies sent, since the server was started
+    pub fn processed_count(&self) -> usize {
+        self.processed_count
+    }
+
+    /// true if the server is paused
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+}
+
 /// Server handle
 /// - the server handle is globally registered using its ULID as the key
 ///
 ///
 /// ## Stopping the server
 /// - [stop_async()](#method.stop_async) is used to signal the server to stop
+/// - [drain_and_stop()](#method.drain_and_stop) is used to let in-flight requests complete before stopping
+///
+/// ## Admin commands
+/// - [pause()](#method.pause) / [resume()](#method.resume)
+/// - [set_parallelism()](#method.set_parallelism)
+/// - [stats()](#method.stats)
 #[derive(Debug, Clone)]
 pub struct ServerHandle {
     id: ULID,
     url: url::Url,
     reqrep_id: ReqRepId,
-    parallelism: NonZeroUsize,
     handle: Option<future::Shared<future::RemoteHandle<()>>>,
     server_command_channel: Option<futures::channel::mpsc::Sender<ServerCommand>>,
     executor: Executor,
     metrics: ServerMetrics,
+    pipe_events: PipeEventBroadcaster,
+    state: Arc<ServerState>,
 }
 
 impl ServerHandle {
@@ -418,7 +990,7 @@ impl ServerHandle {
     ///
     /// This is *NOT* the number of threads in use, but instead represents outstanding work items.
     pub fn parallelism(&self) -> usize {
-        self.parallelism.get()
+        self.state.parallelism()
     }
 
     /// returns true if the server has been signalled to stop
@@ -431,6 +1003,14 @@ impl ServerHandle {
         &self.metrics
     }
 
+    /// Subscribes to the server's pipe, i.e., connection, events
+    /// - the stream ends when the server is shut down
+    /// - the stream is bounded: if the subscriber falls behind, then events are dropped - see
+    ///   [DROPPED_PIPE_EVENT_COUNT_METRIC_ID](../../pipe/constant.DROPPED_PIPE_EVENT_COUNT_METRIC_ID.html)
+    pub fn pipe_events(&self) -> PipeEventStream {
+        self.pipe_events.subscribe()
+    }
+
     /// pings the server to check if it is still alive
     /// - returns true if the server responds to the ping
     ///
@@ -481,6 +1061,72 @@ impl ServerHandle {
         Ok(false)
     }
 
+    /// Pauses the server. While the server is paused, the workers stop receiving requests, i.e.,
+    /// requests are queued by the socket until the server is resumed. In-flight requests are completed.
+    /// - returns false if the server was already paused
+    pub fn pause(&self) -> impl Future<Output = Result<bool, ServerHandleError>> {
+        self.send_command(ServerCommand::Pause)
+    }
+
+    /// Resumes the server
+    /// - returns false if the server was not paused
+    pub fn resume(&self) -> impl Future<Output = Result<bool, ServerHandleError>> {
+        self.send_command(ServerCommand::Resume)
+    }
+
+    /// Changes the number of Aio Context workers, i.e., the number of outstanding requests that the
+    /// server can handle at a given time.
+    /// - when parallelism is reduced, the retired workers exit after their in-flight request has
+    ///   completed - see [ServerStats::worker_count()](struct.ServerStats.html#method.worker_count)
+    pub fn set_parallelism(
+        &self,
+        parallelism: NonZeroUsize,
+    ) -> impl Future<Output = Result<(), ServerHandleError>> {
+        let reply = self.send_command(|reply_chan| {
+            ServerCommand::SetParallelism(parallelism, reply_chan)
+        });
+        async move {
+            await!(reply)?
+                .map_err(|err| ServerHandleError(format!("failed to spawn worker: {}", err)))
+        }
+    }
+
+    /// Returns a server stats snapshot
+    pub fn stats(&self) -> impl Future<Output = Result<ServerStats, ServerHandleError>> {
+        self.send_command(ServerCommand::Stats)
+    }
+
+    /// Signals the server to stop receiving requests, waits for in-flight requests to complete, and
+    /// then shuts down the server.
+    /// - returns true if all in-flight requests completed, i.e., all workers exited, before the
+    ///   timeout expired
+    /// - the server is shutdown when the timeout expires, even if requests are still in flight
+    pub fn drain_and_stop(
+        &mut self,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<bool, ServerHandleError>> {
+        let reply = self.send_command(|reply_chan| ServerCommand::DrainAndStop(timeout, reply_chan));
+        self.server_command_channel.take();
+        reply
+    }
+
+    fn send_command<T, F>(&self, command: F) -> impl Future<Output = Result<T, ServerHandleError>>
+    where
+        T: Send + 'static,
+        F: FnOnce(futures::channel::oneshot::Sender<T>) -> ServerCommand,
+    {
+        let server_command_channel = self.server_command_channel.clone();
+        let (reply_chan, reply) = futures::channel::oneshot::channel();
+        let command = command(reply_chan);
+        async move {
+            let mut server_command_channel = server_command_channel
+                .ok_or_else(|| ServerHandleError("server has been signalled to stop".to_string()))?;
+            await!(server_command_channel.send(command))
+                .map_err(|_| ServerHandleError("server is not running".to_string()))?;
+            await!(reply).map_err(|_| ServerHandleError("server is not running".to_string()))
+        }
+    }
+
     /// Block the current thread until the server has shutdown
     ///
     /// ## Notes
@@ -553,6 +1199,20 @@ pub enum ServerCommand {
     Ping(futures::channel::oneshot::Sender<()>),
     /// Signals the server to shutdown
     Stop,
+    /// Pause the server - replies false if the server was already paused
+    Pause(futures::channel::oneshot::Sender<bool>),
+    /// Resume the server - replies false if the server was not paused
+    Resume(futures::channel::oneshot::Sender<bool>),
+    /// Change the number of Aio Context workers
+    SetParallelism(
+        NonZeroUsize,
+        futures::channel::oneshot::Sender<Result<(), SpawnError>>,
+    ),
+    /// Get a server stats snapshot
+    Stats(futures::channel::oneshot::Sender<ServerStats>),
+    /// Wait for in-flight requests to complete, and then shutdown the server - replies true if
+    /// all in-flight requests completed before the timeout expired
+    DrainAndStop(Duration, futures::channel::oneshot::Sender<bool>),
 }
 
 /// Errors that could happen while trying to spawn a server
@@ -590,6 +1250,8 @@ pub enum SpawnError {
 /// Aio state for socket context
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 enum AioState {
+    /// no aio operation is pending, i.e., the worker is paused
+    Idle,
     /// aio receive operation is in progress
     Recv,
     /// aio send operation is in progress
@@ -604,6 +1266,7 @@ pub struct ServerMetrics {
     active_conn_count: prometheus::IntGauge,
     tot_conn_count: prometheus::IntCounter,
     tot_conn_initiate_count: prometheus::IntCounter,
+    processing_timeout_count: prometheus::IntCounter,
 }
 
 impl ServerMetrics {
@@ -614,6 +1277,8 @@ impl ServerMetrics {
             tot_conn_count: TOT_CONN_COUNT.with_label_values(&[reqrep_id_label.as_str()]),
             tot_conn_initiate_count: TOT_CONN_INITIATE_COUNT
                 .with_label_values(&[reqrep_id_label.as_str()]),
+            processing_timeout_count: PROCESSING_TIMEOUT_COUNT
+                .with_label_values(&[reqrep_id_label.as_str()]),
         }
     }
 
@@ -631,14 +1296,20 @@ impl ServerMetrics {
     pub fn tot_conn_initiate_count(&self) -> usize {
         self.tot_conn_initiate_count.get() as usize
     }
+
+    /// Total number of requests whose processing timed out since the server was started
+    pub fn processing_timeout_count(&self) -> usize {
+        self.processing_timeout_count.get() as usize
+    }
 }
 
 impl fmt::Debug for ServerMetrics {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f,"ServerMetrics(active_conn_count = {}, tot_conn_count = {}, tot_conn_initiate_count = {})",
+        write!(f,"ServerMetrics(active_conn_count = {}, tot_conn_count = {}, tot_conn_initiate_count = {}, processing_timeout_count = {})",
                self.active_conn_count.get(),
                self.tot_conn_count.get(),
-               self.tot_conn_initiate_count.get()
+               self.tot_conn_initiate_count.get(),
+               self.processing_timeout_count.get()
         )
     }
 }
@@ -653,6 +1324,15 @@ pub struct ListenerConfig {
     keep_alive: Option<bool>,
     non_blocking: bool,
     parallelism: usize,
+    #[cfg(feature = "tls")]
+    #[serde(default)]
+    tls: Option<TlsConfig>,
+    #[serde(default)]
+    processing_timeout: Option<Duration>,
+    #[serde(default)]
+    sealed_replies: bool,
+    #[serde(default)]
+    ws: Option<WsConfig>,
 }
 
 impl ListenerConfig {
@@ -670,6 +1350,11 @@ impl ListenerConfig {
             keep_alive: None,
             non_blocking: true,
             parallelism: num_cpus::get() + 1,
+            #[cfg(feature = "tls")]
+            tls: None,
+            processing_timeout: None,
+            sealed_replies: false,
+            ws: None,
         }
     }
 
@@ -707,6 +1392,19 @@ impl ListenerConfig {
                 .map_err(ListenerConfigError::TcpKeepAlive)?;
         }
 
+        #[cfg(feature = "tls")]
+        {
+            if let Some(tls) = self.tls.as_ref() {
+                tls.apply_to_listener(self.url(), &options)
+                    .map_err(ListenerConfigError::Tls)?;
+            }
+        }
+
+        if let Some(ws) = self.ws.as_ref() {
+            ws.apply_to_listener(self.url(), &options)
+                .map_err(ListenerConfigError::Ws)?;
+        }
+
         options
             .start(self.non_blocking)
             .map_err(|(_options, err)| ListenerConfigError::ListenerStartFailed(err))
@@ -762,6 +1460,60 @@ impl ListenerConfig {
         self.keep_alive
     }
 
+    /// TLS config, which applies to `tls+tcp` and `wss` URLs
+    #[cfg(feature = "tls")]
+    pub fn tls(&self) -> Option<&TlsConfig> {
+        self.tls.as_ref()
+    }
+
+    /// WebSocket config, which applies to `ws` and `wss` URLs
+    pub fn ws(&self) -> Option<&WsConfig> {
+        self.ws.as_ref()
+    }
+
+    /// Max amount of time the server will wait for the ReqRep service to reply.
+    /// - if not set, then the server will wait indefinitely
+    pub fn processing_timeout(&self) -> Option<Duration> {
+        self.processing_timeout
+    }
+
+    /// Sets the processing timeout. When the timeout expires, then the server replies with
+    /// the [ReplyStatus::ProcessingTimeout](../envelope/enum.ReplyStatus.html#variant.ProcessingTimeout) envelope.
+    /// - unless the service seals its own replies, the server seals every service reply with the
+    ///   `Ok` status - see [set_sealed_replies()](#method.set_sealed_replies)
+    pub fn set_processing_timeout(mut self, processing_timeout: Duration) -> Self {
+        self.processing_timeout = Some(processing_timeout);
+        self
+    }
+
+    /// Returns true if the ReqRep service seals its own replies in the reply envelope
+    pub fn sealed_replies(&self) -> bool {
+        self.sealed_replies
+    }
+
+    /// Flags the ReqRep service as sealing its own replies in the reply [envelope](../envelope/index.html),
+    /// e.g., services that are backed by a [CodecProcessor](../codec/struct.CodecProcessor.html).
+    /// - when a processing timeout is configured, the server only seals the replies of services that
+    ///   do not seal their own replies
+    /// - default = false
+    pub fn set_sealed_replies(mut self, sealed_replies: bool) -> Self {
+        self.sealed_replies = sealed_replies;
+        self
+    }
+
+    /// Sets the TLS config - the URL scheme must be `tls+tcp` or `wss`
+    #[cfg(feature = "tls")]
+    pub fn set_tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Sets the WebSocket config - the URL scheme must be `ws` or `wss`
+    pub fn set_ws(mut self, ws: WsConfig) -> Self {
+        self.ws = Some(ws);
+        self
+    }
+
     /// Sets the maximum message size that the will be accepted from a remote peer.
     pub fn set_recv_max_size(mut self, recv_max_size: usize) -> Self {
         self.recv_max_size = Some(recv_max_size);
@@ -814,6 +1566,13 @@ pub enum ListenerConfigError {
     /// Failed to set the TcpKeepAlive Socket option
     #[fail(display = "Failed to set the TcpKeepAlive Socket option: {}", _0)]
     TcpKeepAlive(#[cause] nng::Error),
+    /// Failed to apply the TLS config
+    #[cfg(feature = "tls")]
+    #[fail(display = "Failed to apply the TLS config: {}", _0)]
+    Tls(#[cause] TlsConfigError),
+    /// Failed to apply the WebSocket config
+    #[fail(display = "Failed to apply the WebSocket config: {}", _0)]
+    Ws(#[cause] WsConfigError),
 }
 
 #[allow(warnings)]
@@ -1077,9 +1836,326 @@ mod tests {
             if executor.task_active_count() == expected_task_count {
                 break;
             }
-            thread::sleep_ms(1);
+            thread::sleep(Duration::from_millis(1));
         }
         assert_eq!(executor.task_active_count(), expected_task_count);
     }
 
+    #[test]
+    fn nng_server_processing_timeout() {
+        configure_logging();
+
+        struct SlowService;
+        impl Processor<nng::Message, nng::Message> for SlowService {
+            fn process(&mut self, req: nng::Message) -> reqrep::FutureReply<nng::Message> {
+                async move {
+                    await!(delay(Duration::from_millis(200)));
+                    req
+                }
+                    .boxed()
+            }
+        }
+
+        // GIVEN: the server is running with a processing timeout
+        let url = url::Url::parse(&format!("inproc://{}", ULID::generate())).unwrap();
+        let timer_buckets = metrics::timer_buckets(vec![Duration::from_millis(100)]).unwrap();
+        let service = ReqRepConfig::new(ReqRepId::generate(), timer_buckets)
+            .start_service(SlowService, global_executor())
+            .unwrap();
+        let mut server_handle = super::spawn(
+            None,
+            ListenerConfig::new(url.clone()).set_processing_timeout(Duration::from_millis(10)),
+            service,
+            global_executor(),
+        )
+        .unwrap();
+        assert!(server_handle.ping());
+
+        // WHEN: a client submits a request that takes longer than the processing timeout
+        let mut s = nng::Socket::new(nng::Protocol::Req0).unwrap();
+        s.dial(url.as_str()).unwrap();
+        s.send(nng::Message::new().unwrap()).unwrap();
+        // THEN: the server replies with the processing timeout status
+        let reply = s.recv().unwrap();
+        let (status, _details) = envelope::open(&reply[..]).unwrap();
+        assert_eq!(status, ReplyStatus::ProcessingTimeout);
+        // AND: the timeout is counted
+        assert_eq!(server_handle.metrics().processing_timeout_count(), 1);
+
+        assert!(server_handle.stop_async().unwrap());
+        server_handle.await_shutdown();
+    }
+
+    #[test]
+    fn nng_server_processing_timeout_seals_raw_replies() {
+        configure_logging();
+
+        // replies with a raw message whose first byte is the ProcessingTimeout status byte
+        struct TimeoutLookalikeService;
+        impl Processor<nng::Message, nng::Message> for TimeoutLookalikeService {
+            fn process(&mut self, _req: nng::Message) -> reqrep::FutureReply<nng::Message> {
+                async move {
+                    let mut reply = nng::Message::new().unwrap();
+                    reply
+                        .push_back(&[ReplyStatus::ProcessingTimeout as u8, 1, 2])
+                        .unwrap();
+                    reply
+                }
+                    .boxed()
+            }
+        }
+
+        // GIVEN: a raw service is running with a processing timeout
+        let url = url::Url::parse(&format!("inproc://{}", ULID::generate())).unwrap();
+        let timer_buckets = metrics::timer_buckets(vec![Duration::from_millis(100)]).unwrap();
+        let service = ReqRepConfig::new(ReqRepId::generate(), timer_buckets)
+            .start_service(TimeoutLookalikeService, global_executor())
+            .unwrap();
+        let mut server_handle = super::spawn(
+            None,
+            ListenerConfig::new(url.clone()).set_processing_timeout(Duration::from_secs(5)),
+            service,
+            global_executor(),
+        )
+        .unwrap();
+        assert!(server_handle.ping());
+
+        // WHEN: the service replies with a raw message that starts with the timeout status byte
+        let mut s = nng::Socket::new(nng::Protocol::Req0).unwrap();
+        s.dial(url.as_str()).unwrap();
+        s.send(nng::Message::new().unwrap()).unwrap();
+        // THEN: the reply is sealed with the Ok status, i.e., it is not confused with a timeout
+        let reply = s.recv().unwrap();
+        let (status, payload) = envelope::open(&reply[..]).unwrap();
+        assert_eq!(status, ReplyStatus::Ok);
+        assert_eq!(payload, &[ReplyStatus::ProcessingTimeout as u8, 1, 2]);
+        assert_eq!(server_handle.metrics().processing_timeout_count(), 0);
+
+        assert!(server_handle.stop_async().unwrap());
+        server_handle.await_shutdown();
+    }
+
+    #[test]
+    fn nng_server_pipe_events() {
+        configure_logging();
+        let mut executor = global_executor();
+
+        // GIVEN: the server is running
+        let url = url::Url::parse(&format!("inproc://{}", ULID::generate())).unwrap();
+        let mut server_handle = super::spawn(
+            None,
+            ListenerConfig::new(url.clone()),
+            start_service(),
+            global_executor(),
+        )
+        .unwrap();
+        assert!(server_handle.ping());
+        // AND: pipe events are subscribed to
+        let mut pipe_events = server_handle.pipe_events();
+
+        // WHEN: a client connects and then disconnects
+        let mut s = nng::Socket::new(nng::Protocol::Req0).unwrap();
+        s.dial(url.as_str()).unwrap();
+        s.send(nng::Message::new().unwrap()).unwrap();
+        let _ = s.recv().unwrap();
+        s.close();
+
+        // THEN: the pipe added and removed events are received
+        let event = executor.run(pipe_events.next()).unwrap();
+        assert_eq!(event.kind(), PipeEventKind::Added);
+        let pipe_id = event.pipe().pipe_id();
+        let event = executor.run(pipe_events.next()).unwrap();
+        assert_eq!(event.kind(), PipeEventKind::Removed);
+        assert_eq!(event.pipe().pipe_id(), pipe_id);
+
+        // WHEN: the server is stopped
+        assert!(server_handle.stop_async().unwrap());
+        server_handle.await_shutdown();
+        // THEN: the pipe event stream ends
+        assert!(executor.run(pipe_events.next()).is_none());
+    }
+
+    #[test]
+    fn nng_server_connection_filter() {
+        configure_logging();
+        let mut executor = global_executor();
+
+        // GIVEN: the server is running with a connection filter that denies the first connection
+        let url = url::Url::parse(&format!("inproc://{}", ULID::generate())).unwrap();
+        let allow = AtomicBool::new(false);
+        let mut server_handle = super::spawn_with_connection_filter(
+            None,
+            ListenerConfig::new(url.clone()),
+            start_service(),
+            Arc::new(move |_pipe: &PipeInfo| allow.swap(true, Ordering::SeqCst)),
+            global_executor(),
+        )
+        .unwrap();
+        assert!(server_handle.ping());
+        let mut pipe_events = server_handle.pipe_events();
+
+        // WHEN: a client dials the server in the background, i.e., the client will redial
+        let mut s = nng::Socket::new(nng::Protocol::Req0).unwrap();
+        let _dialer = nng::DialerOptions::new(&s, url.as_str())
+            .unwrap()
+            .start(true)
+            .unwrap();
+
+        // THEN: the first connection is rejected
+        let event = executor.run(pipe_events.next()).unwrap();
+        assert_eq!(event.kind(), PipeEventKind::Rejected);
+        // AND: the redialed connection is added
+        let event = executor.run(pipe_events.next()).unwrap();
+        assert_eq!(event.kind(), PipeEventKind::Added);
+        let pipe_id = event.pipe().pipe_id();
+        assert_eq!(server_handle.metrics().tot_conn_count(), 1);
+
+        // WHEN: the client disconnects
+        s.close();
+        // THEN: only the added connection is removed, i.e., the rejected connection is not counted
+        let event = executor.run(pipe_events.next()).unwrap();
+        assert_eq!(event.kind(), PipeEventKind::Removed);
+        assert_eq!(event.pipe().pipe_id(), pipe_id);
+        assert_eq!(server_handle.metrics().active_conn_count(), 0);
+
+        assert!(server_handle.stop_async().unwrap());
+        server_handle.await_shutdown();
+    }
+
+    #[test]
+    fn nng_server_admin_commands() {
+        configure_logging();
+        let mut executor = global_executor();
+
+        // GIVEN: the server is running
+        let url = url::Url::parse(&format!("inproc://{}", ULID::generate())).unwrap();
+        let mut server_handle = super::spawn(
+            None,
+            ListenerConfig::new(url.clone()).set_aio_count(NonZeroUsize::new(1).unwrap()),
+            start_service(),
+            global_executor(),
+        )
+        .unwrap();
+        assert!(server_handle.ping());
+        let stats = executor.run(server_handle.stats()).unwrap();
+        assert_eq!(stats.parallelism(), 1);
+        assert!(!stats.paused());
+
+        // WHEN: the parallelism is increased
+        executor
+            .run(server_handle.set_parallelism(NonZeroUsize::new(4).unwrap()))
+            .unwrap();
+        // THEN: the server has 4 workers
+        assert_eq!(server_handle.parallelism(), 4);
+        assert_eq!(executor.run(server_handle.stats()).unwrap().parallelism(), 4);
+        // WHEN: the parallelism is decreased
+        executor
+            .run(server_handle.set_parallelism(NonZeroUsize::new(2).unwrap()))
+            .unwrap();
+        // THEN: the server has 2 workers
+        assert_eq!(server_handle.parallelism(), 2);
+        // AND: the retired idle workers exit
+        let mut worker_count = || executor.run(server_handle.stats()).unwrap().worker_count();
+        for _ in 0..100 {
+            if worker_count() == 2 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(worker_count(), 2);
+
+        // WHEN: the server is paused
+        assert!(executor.run(server_handle.pause()).unwrap());
+        assert!(!executor.run(server_handle.pause()).unwrap());
+        assert!(executor.run(server_handle.stats()).unwrap().paused());
+        // AND: a client sends a request
+        let mut s = nng::Socket::new(nng::Protocol::Req0).unwrap();
+        s.dial(url.as_str()).unwrap();
+        s.send(nng::Message::new().unwrap()).unwrap();
+        // THEN: the request is not received while the server is paused
+        thread::sleep(Duration::from_millis(20));
+        let stats = executor.run(server_handle.stats()).unwrap();
+        assert_eq!(stats.in_flight_count(), 0);
+        assert_eq!(stats.processed_count(), 0);
+        // WHEN: the server is resumed
+        assert!(executor.run(server_handle.resume()).unwrap());
+        // THEN: the request is processed
+        let _ = s.recv().unwrap();
+        for _ in 0..10 {
+            if executor.run(server_handle.stats()).unwrap().processed_count() == 1 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        let stats = executor.run(server_handle.stats()).unwrap();
+        assert_eq!(stats.processed_count(), 1);
+        assert_eq!(stats.in_flight_count(), 0);
+
+        // WHEN: the server is drained and stopped
+        assert!(executor
+            .run(server_handle.drain_and_stop(Duration::from_secs(1)))
+            .unwrap());
+        assert!(server_handle.stop_signalled());
+        // THEN: the server shuts down
+        server_handle.await_shutdown();
+        // AND: all workers have exited
+        assert_eq!(server_handle.state.worker_count(), 0);
+    }
+
+    #[test]
+    fn nng_server_drain_in_flight_requests() {
+        configure_logging();
+        let mut executor = global_executor();
+
+        struct SlowService;
+        impl Processor<nng::Message, nng::Message> for SlowService {
+            fn process(&mut self, req: nng::Message) -> reqrep::FutureReply<nng::Message> {
+                async move {
+                    await!(delay(Duration::from_millis(50)));
+                    req
+                }
+                    .boxed()
+            }
+        }
+
+        // GIVEN: the server is running
+        let url = url::Url::parse(&format!("inproc://{}", ULID::generate())).unwrap();
+        let timer_buckets = metrics::timer_buckets(vec![Duration::from_millis(100)]).unwrap();
+        let service = ReqRepConfig::new(ReqRepId::generate(), timer_buckets)
+            .start_service(SlowService, global_executor())
+            .unwrap();
+        let mut server_handle = super::spawn(
+            None,
+            ListenerConfig::new(url.clone()).set_aio_count(NonZeroUsize::new(2).unwrap()),
+            service,
+            global_executor(),
+        )
+        .unwrap();
+        assert!(server_handle.ping());
+
+        // AND: a request is in flight
+        let mut s = nng::Socket::new(nng::Protocol::Req0).unwrap();
+        s.dial(url.as_str()).unwrap();
+        s.send(nng::Message::new().unwrap()).unwrap();
+        let mut in_flight_count = || executor.run(server_handle.stats()).unwrap().in_flight_count();
+        for _ in 0..100 {
+            if in_flight_count() == 1 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(in_flight_count(), 1);
+
+        // WHEN: the server is drained and stopped
+        assert!(executor
+            .run(server_handle.drain_and_stop(Duration::from_secs(1)))
+            .unwrap());
+        // THEN: the in-flight request completed
+        let _ = s.recv().unwrap();
+        assert_eq!(server_handle.state.processed_count(), 1);
+        assert_eq!(server_handle.state.in_flight_count(), 0);
+        // AND: all workers, i.e., the busy and the idle worker, have exited
+        assert_eq!(server_handle.state.worker_count(), 0);
+        server_handle.await_shutdown();
+    }
 }
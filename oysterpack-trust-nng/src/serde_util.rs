@@ -0,0 +1,97 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! serde helpers
+
+/// ULID based IDs are serialized as ULID strings
+pub(crate) mod ulid {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+    use std::{fmt::Display, str::FromStr};
+
+    pub fn serialize<T, S>(id: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Display,
+        S: Serializer,
+    {
+        serializer.collect_str(id)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        let id = String::deserialize(deserializer)?;
+        id.parse()
+            .map_err(|err| de::Error::custom(format!("invalid ULID: {}: {}", id, err)))
+    }
+}
+
+/// Optional ULID based IDs are serialized as ULID strings
+pub(crate) mod opt_ulid {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::{fmt::Display, str::FromStr};
+
+    pub fn serialize<T, S>(id: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Display,
+        S: Serializer,
+    {
+        match id {
+            Some(id) => serializer.collect_str(id),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Wrapper<T>(#[serde(with = "crate::serde_util::ulid")] T)
+        where
+            T: FromStr,
+            T::Err: Display;
+
+        let id: Option<Wrapper<T>> = Option::deserialize(deserializer)?;
+        Ok(id.map(|Wrapper(id)| id))
+    }
+}
+
+/// serde support for `Vec<url::Url>` - URLs are serialized as strings
+pub(crate) mod urls {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(urls: &[url::Url], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(urls.iter().map(url::Url::as_str))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<url::Url>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|url| url::Url::parse(url).map_err(de::Error::custom))
+            .collect()
+    }
+}
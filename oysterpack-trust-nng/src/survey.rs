@@ -0,0 +1,37 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Provides support for the survey messaging protocol, which is used to query all nodes in a cluster,
+//! e.g., "what's your health / version", and collect the answers within a deadline.
+//! - surveys are sent via a [SurveyorHandle](surveyor/struct.SurveyorHandle.html), which returns a
+//!   stream of responses that ends when the survey time expires
+//! - respondents reply to surveys via a [ReqRep](https://docs.rs/oysterpack_trust/latest/oysterpack_trust/concurrent/messaging/reqrep/struct.ReqRep.html)
+//!   backend service - see [respondent::spawn()](respondent/fn.spawn.html)
+
+pub mod respondent;
+pub mod surveyor;
+
+use oysterpack_trust::metrics;
+use oysterpack_uid::macros::ulid;
+use serde::{Deserialize, Serialize};
+
+/// Each survey channel is uniquely identified by an ID, which is used for tracking purposes, e.g., metrics
+#[ulid]
+pub struct SurveyId(pub u128);
+
+/// Metric LabelId which is used to store a SurveyId: `L01M57F6B58HM7S3G5WVH7M4522`
+pub const SURVEY_ID_LABEL_ID: metrics::LabelId =
+    metrics::LabelId(2166789084222705028336608522360984642);
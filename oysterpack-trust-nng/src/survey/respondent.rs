@@ -0,0 +1,485 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Provides an nng Respondent0 respondent.
+//!
+//! ## Design
+//! The respondent dials into the surveyor using a Respondent0 socket. A respondent task runs an Aio
+//! event loop, which relays surveys to the backend ReqRep service, and then sends back the response
+//! returned from the ReqRep service.
+//! - each survey is processed on its own task, i.e., the respondent can be stopped while a survey is
+//!   being processed
+//!
+//! <pre>
+//! surveyor ---Survey--> Socket --> Aio Event Loop ---Survey--> service
+//! surveyor <--Response- Socket <-- Aio Event Loop <--Response- service
+//! </pre>
+//!
+//! ## Config
+//! - [SocketConfig](../../config/struct.SocketConfig.html)
+//! - [DialerConfig](../../reqrep/client/struct.DialerConfig.html)
+//!
+//! ## Metrics
+//! - active number of socket connections - [ACTIVE_CONN_COUNT_METRIC_ID](constant.ACTIVE_CONN_COUNT_METRIC_ID.html)
+//! - total number of surveys that have been responded to - [RESPONSE_COUNT_METRIC_ID](constant.RESPONSE_COUNT_METRIC_ID.html)
+//! - the ReqRep service provides the message processing metrics
+
+use super::{SurveyId, SURVEY_ID_LABEL_ID};
+use crate::{
+    config::{SocketConfig, SocketConfigError},
+    reqrep::client::{DialerConfig, DialerConfigError},
+    task::{self, TaskHandle, TaskHandleError},
+};
+use failure::Fail;
+use futures::{channel::mpsc, prelude::*, stream::StreamExt, task::SpawnExt};
+use lazy_static::lazy_static;
+use oysterpack_log::*;
+use oysterpack_trust::{
+    concurrent::{execution::Executor, messaging::reqrep::ReqRep},
+    metrics,
+};
+use std::fmt;
+
+lazy_static! {
+
+    /// the metric is incremented on nng::PipeEvent::AddPost and decremented on nng::PipeEvent::RemovePost
+    static ref ACTIVE_CONN_COUNT: prometheus::IntGaugeVec = metrics::registry().register_int_gauge_vec(
+        ACTIVE_CONN_COUNT_METRIC_ID,
+        "Active number of respondent socket connections",
+        &[SURVEY_ID_LABEL_ID],
+        None
+    ).unwrap();
+
+    /// the metric is incremented when the survey response has been sent
+    static ref RESPONSE_COUNT: prometheus::IntCounterVec = metrics::registry().register_int_counter_vec(
+        RESPONSE_COUNT_METRIC_ID,
+        "Total number of surveys that have been responded to",
+        &[SURVEY_ID_LABEL_ID],
+        None
+    ).unwrap();
+
+}
+
+/// IntGaugeVec MetricId which is used to track the number of active respondent socket connections by SurveyId: `M01M57F6B5HF80ZR5BEARZM4YXH`
+pub const ACTIVE_CONN_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166789084233495385157492633080331185);
+/// IntCounterVec MetricId which is used to track the total number of surveys responded to by SurveyId: `M01M57F6B5KRHHN2S5ZNQMR5SCP`
+pub const RESPONSE_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166789084236264487397850848346432918);
+
+/// Spawns a respondent background task
+/// - the respondent responds to surveys using the specified ReqRep service
+/// - returns a RespondentHandle that can be used to stop the respondent
+pub fn spawn(
+    socket_config: Option<SocketConfig>,
+    dialer_config: DialerConfig,
+    survey_id: SurveyId,
+    service: ReqRep<nng::Message, nng::Message>,
+    mut executor: Executor,
+) -> Result<RespondentHandle, SpawnError> {
+    let survey_id_label = survey_id.to_string();
+    let active_conn_count = ACTIVE_CONN_COUNT.with_label_values(&[survey_id_label.as_str()]);
+    let response_count = RESPONSE_COUNT.with_label_values(&[survey_id_label.as_str()]);
+
+    let create_socket = || {
+        let active_conn_count = active_conn_count.clone();
+        let mut socket = nng::Socket::new(nng::Protocol::Respondent0)
+            .map_err(SpawnError::SocketCreateFailure)?;
+        socket.set_nonblocking(true);
+        socket
+            .pipe_notify(move |pipe, event| {
+                match event {
+                    nng::PipeEvent::AddPost => active_conn_count.inc(),
+                    nng::PipeEvent::RemovePost => active_conn_count.dec(),
+                    _ => (),
+                }
+                debug!("{:?} {:?}", pipe, event);
+            })
+            .map_err(SpawnError::SocketCreateFailure)?;
+        match socket_config {
+            Some(socket_config) => socket_config
+                .apply(socket)
+                .map_err(SpawnError::SocketConfigApplyFailed),
+            None => Ok(socket),
+        }
+    };
+
+    let socket = create_socket()?;
+    let dialer = dialer_config
+        .start_dialer(&socket)
+        .map_err(SpawnError::DialerStartError)?;
+
+    // used to notify the respondent task when an Aio event has occurred, i.e., the Aio callback has been invoked
+    let (aio, aio_rx) =
+        task::aio_notifier("respondent").map_err(SpawnError::AioCreateWithCallbackFailure)?;
+
+    // used to signal the respondent task to stop
+    let (stop_tx, stop_rx) = mpsc::channel::<()>(0);
+    // the service is invoked on its own task, which sends back the response via this channel, i.e.,
+    // the service is not awaited inline, which would prevent the respondent from being stopped while
+    // a survey is being processed
+    let (response_tx, response_rx) = mpsc::channel::<Option<nng::Message>>(1);
+    let mut service_executor = executor.clone();
+    let handle = executor
+        .spawn_with_handle(
+            async move {
+                debug!("Respondent({}) is running ...", survey_id);
+                // fuse the streams that will be polled via futures::select! - per the documentation
+                let mut aio_rx = aio_rx.fuse();
+                let mut stop_rx = stop_rx.fuse();
+                let mut response_rx = response_rx.fuse();
+
+                let recv = || {
+                    if let Err(err) = socket.recv_async(&aio) {
+                        // TODO: trigger alert - async I/O errors need to be investigated
+                        error!("Socket::recv_async() failed: {}", err);
+                    }
+                    AioState::Recv
+                };
+
+                let mut state = recv();
+                loop {
+                    futures::select! {
+                        event = aio_rx.next() => {
+                            if event.is_none() {
+                                break;
+                            }
+                            // NOTE: aio.result().unwrap() is safe because we are being signalled
+                            // by the Aio callback to handle an Aio event
+                            state = match (state, aio.result().unwrap()) {
+                                (_, Err(nng::Error::Closed)) => break,
+                                (AioState::Recv, Ok(_)) => match aio.get_msg() {
+                                    Some(survey) => {
+                                        let mut service = service.clone();
+                                        let mut response_tx = response_tx.clone();
+                                        let process = async move {
+                                            let response = match await!(service.send_recv(survey)) {
+                                                Ok(response) => Some(response),
+                                                Err(err) => {
+                                                    error!("ReqRep::send_recv() failed: ReqRepId({}) : {}", service.id(), err);
+                                                    None
+                                                }
+                                            };
+                                            // the result can be ignored because if the channel is disconnected
+                                            // then it means the respondent has stopped
+                                            let _ = await!(response_tx.send(response));
+                                        };
+                                        match service_executor.spawn(process) {
+                                            Ok(_) => AioState::Processing,
+                                            Err(err) => {
+                                                error!("Failed to spawn survey processing task: {:?}", err);
+                                                recv()
+                                            }
+                                        }
+                                    }
+                                    None => {
+                                        warn!("{:?} Expected a message to be available", state);
+                                        recv()
+                                    }
+                                },
+                                (AioState::Send, Ok(_)) => {
+                                    response_count.inc();
+                                    recv()
+                                }
+                                (AioState::Processing, Ok(_)) => {
+                                    warn!("Unexpected Aio event while the survey is being processed");
+                                    AioState::Processing
+                                }
+                                (state, Err(err)) => {
+                                    // the survey may have expired before the response was sent
+                                    debug!("{:?}: Aio error: {}", state, err);
+                                    recv()
+                                }
+                            };
+                        },
+                        response = response_rx.next() => {
+                            state = match response {
+                                Some(Some(response)) => match socket.send_async(&aio, response) {
+                                    Ok(_) => AioState::Send,
+                                    Err((_msg, err)) => {
+                                        error!("Socket::send_async() failed: {}", err);
+                                        recv()
+                                    }
+                                },
+                                Some(None) => recv(),
+                                None => break,
+                            };
+                        },
+                        _ = stop_rx.next() => break,
+                    }
+                }
+                debug!("Respondent({}) is shutting down ...", survey_id);
+                aio.cancel();
+                dialer.close();
+                socket.close();
+                debug!("Respondent({}) is shut down", survey_id);
+            },
+        )
+        .map_err(|err| SpawnError::ExecutorSpawnError {
+            is_executor_shutdown: err.is_shutdown(),
+        })?;
+
+    Ok(RespondentHandle {
+        survey_id,
+        task: TaskHandle::new(handle, stop_tx, executor),
+    })
+}
+
+/// Respondent handle
+/// - the respondent will also stop when all RespondentHandle(s) are dropped
+#[derive(Debug, Clone)]
+pub struct RespondentHandle {
+    survey_id: SurveyId,
+    task: TaskHandle<()>,
+}
+
+impl RespondentHandle {
+    /// Returns the SurveyId
+    pub fn survey_id(&self) -> SurveyId {
+        self.survey_id
+    }
+
+    /// returns true if the respondent has been signalled to stop
+    pub fn stop_signalled(&self) -> bool {
+        self.task.stop_signalled()
+    }
+
+    /// signals the respondent to shutdown async
+    pub fn stop_async(&mut self) -> Result<bool, TaskHandleError> {
+        self.task.stop_async(())
+    }
+
+    /// Block the current thread until the respondent has shutdown
+    ///
+    /// ## Notes
+    /// The respondent must be signaled to stop in order to shutdown.
+    pub fn await_shutdown(mut self) {
+        self.task.await_shutdown()
+    }
+}
+
+/// Aio state for the respondent socket
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+enum AioState {
+    /// aio receive operation is in progress
+    Recv,
+    /// the survey is being processed by the service - no aio operation is in progress
+    Processing,
+    /// aio send operation is in progress
+    Send,
+}
+
+/// Errors that could happen while trying to spawn a respondent
+#[derive(Debug, Fail)]
+pub enum SpawnError {
+    /// Failed to create Socket
+    #[fail(display = "Failed to create Socket: {}", _0)]
+    SocketCreateFailure(#[cause] nng::Error),
+    /// Failed to apply SocketConfig options
+    #[fail(display = "{}", _0)]
+    SocketConfigApplyFailed(#[cause] SocketConfigError),
+    /// Failed to start the dialer
+    #[fail(display = "Failed to start dialer: {}", _0)]
+    DialerStartError(#[cause] DialerConfigError),
+    /// Failed to create Aio
+    #[fail(display = "Failed to create Aio with callback: {}", _0)]
+    AioCreateWithCallbackFailure(#[cause] nng::Error),
+    /// An error that occurred during spawning.
+    #[fail(
+        display = "Spawning Future failed: executor shutdown = {}",
+        is_executor_shutdown
+    )]
+    ExecutorSpawnError {
+        /// whether spawning failed because the executor is shut down
+        is_executor_shutdown: bool,
+    },
+}
+
+#[allow(warnings)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{configure_logging, reqrep::server::ListenerConfig, survey::surveyor};
+    use oysterpack_trust::concurrent::{
+        execution::{self, *},
+        messaging::reqrep::{self, *},
+    };
+    use oysterpack_uid::ULID;
+    use std::{thread, time::Duration};
+
+    /// responds with the node name
+    struct HealthService(&'static str);
+    impl Processor<nng::Message, nng::Message> for HealthService {
+        fn process(&mut self, _req: nng::Message) -> reqrep::FutureReply<nng::Message> {
+            let name = self.0;
+            async move {
+                let mut msg = nng::Message::new().unwrap();
+                msg.push_back(name.as_bytes()).unwrap();
+                msg
+            }
+                .boxed()
+        }
+    }
+
+    fn start_service(name: &'static str) -> ReqRep<nng::Message, nng::Message> {
+        let timer_buckets = metrics::timer_buckets(vec![
+            Duration::from_nanos(50),
+            Duration::from_nanos(100),
+            Duration::from_nanos(150),
+            Duration::from_nanos(200),
+        ])
+        .unwrap();
+        ReqRepConfig::new(ReqRepId::generate(), timer_buckets)
+            .start_service(HealthService(name), global_executor())
+            .unwrap()
+    }
+
+    #[test]
+    fn survey() {
+        configure_logging();
+        let mut executor = execution::global_executor();
+        let survey_id = SurveyId::generate();
+
+        // GIVEN: the surveyor is running
+        let url = url::Url::parse(&format!("inproc://{}", ULID::generate())).unwrap();
+        let mut surveyor =
+            surveyor::spawn(None, ListenerConfig::new(url.clone()), survey_id, executor.clone())
+                .unwrap();
+        assert!(surveyor.ping());
+
+        // AND: 2 respondents are connected
+        let respondents: Vec<RespondentHandle> = ["node-1", "node-2"]
+            .iter()
+            .map(|name| {
+                spawn(
+                    None,
+                    DialerConfig::new(url.clone()),
+                    survey_id,
+                    start_service(name),
+                    executor.clone(),
+                )
+                .unwrap()
+            })
+            .collect();
+        for _ in 0..100 {
+            if surveyor.metrics().active_conn_count() == 2 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(surveyor.metrics().active_conn_count(), 2);
+
+        // WHEN: a survey is run
+        let responses = executor.run(
+            async {
+                let responses = await!(
+                    surveyor.survey(nng::Message::new().unwrap(), Duration::from_millis(100))
+                )
+                .unwrap();
+                await!(responses.collect::<Vec<_>>())
+            },
+        );
+        // THEN: all respondents responded before the survey expired
+        let mut names: Vec<String> = responses
+            .iter()
+            .map(|msg| String::from_utf8(msg[..].to_vec()).unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["node-1".to_string(), "node-2".to_string()]);
+        assert_eq!(surveyor.metrics().survey_count(), 1);
+        assert_eq!(surveyor.metrics().response_count(), 2);
+
+        // WHEN: the respondents are stopped
+        for mut respondent in respondents {
+            assert!(respondent.stop_async().unwrap());
+            respondent.await_shutdown();
+        }
+        // THEN: the survey completes with no responses
+        let responses = executor.run(
+            async {
+                let responses = await!(
+                    surveyor.survey(nng::Message::new().unwrap(), Duration::from_millis(20))
+                )
+                .unwrap();
+                await!(responses.collect::<Vec<_>>())
+            },
+        );
+        assert!(responses.is_empty());
+
+        // WHEN: the surveyor is stopped
+        let surveyor_id = surveyor.id();
+        assert!(surveyor.stop_async().unwrap());
+        surveyor.await_shutdown();
+        // THEN: the surveyor is unregistered
+        assert!(surveyor::SurveyorHandle::get(surveyor_id).is_none());
+    }
+
+    /// never responds - the reply senders are held until the service is dropped
+    struct StuckService(Vec<futures::channel::oneshot::Sender<nng::Message>>);
+    impl Processor<nng::Message, nng::Message> for StuckService {
+        fn process(&mut self, _req: nng::Message) -> reqrep::FutureReply<nng::Message> {
+            let (tx, rx) = futures::channel::oneshot::channel();
+            self.0.push(tx);
+            async move { await!(rx).unwrap() }.boxed()
+        }
+    }
+
+    #[test]
+    fn stop_while_processing_survey() {
+        configure_logging();
+        let mut executor = execution::global_executor();
+        let survey_id = SurveyId::generate();
+
+        // GIVEN: a respondent whose service never responds
+        let url = url::Url::parse(&format!("inproc://{}", ULID::generate())).unwrap();
+        let mut surveyor =
+            surveyor::spawn(None, ListenerConfig::new(url.clone()), survey_id, executor.clone())
+                .unwrap();
+        let timer_buckets = metrics::timer_buckets(vec![Duration::from_millis(1)]).unwrap();
+        let service = ReqRepConfig::new(ReqRepId::generate(), timer_buckets)
+            .start_service(StuckService(vec![]), global_executor())
+            .unwrap();
+        let mut respondent = spawn(
+            None,
+            DialerConfig::new(url.clone()),
+            survey_id,
+            service,
+            executor.clone(),
+        )
+        .unwrap();
+        for _ in 0..100 {
+            if surveyor.metrics().active_conn_count() == 1 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(surveyor.metrics().active_conn_count(), 1);
+
+        // AND: the respondent is processing a survey
+        let responses = executor
+            .run(surveyor.survey(nng::Message::new().unwrap(), Duration::from_millis(100)))
+            .unwrap();
+        assert!(executor.run(responses.collect::<Vec<_>>()).is_empty());
+
+        // WHEN: the respondent is stopped
+        assert!(respondent.stop_async().unwrap());
+        // THEN: the respondent shuts down, i.e., it is not blocked by the service
+        respondent.await_shutdown();
+
+        assert!(surveyor.stop_async().unwrap());
+        surveyor.await_shutdown();
+    }
+}
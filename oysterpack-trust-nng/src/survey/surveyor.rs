@@ -0,0 +1,653 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Provides an nng Surveyor0 surveyor.
+//!
+//! ## Design
+//! The surveyor follows the [reqrep server](../../reqrep/server/index.html) design. The surveyor
+//! listens on a Surveyor0 socket, which respondents dial into. A single surveyor task owns the socket
+//! and is driven by an async command channel:
+//! - survey requests - the survey is sent to all connected respondents, and responses are forwarded
+//!   to the [SurveyResponses](struct.SurveyResponses.html) stream until the survey time expires
+//!   - surveys are run one at a time, i.e., if a survey is in progress then the next survey will wait
+//!     for the current survey to complete
+//!   - responses are forwarded without waiting on the stream - if the stream buffer is full, then
+//!     the response is dropped
+//! - ping requests - which can be used to check that the surveyor is running
+//! - stop signal, which interrupts the running survey. Upon receiving the signal the surveyor will
+//!   - cancel the running survey and any pending surveys, i.e., their response streams end
+//!   - close the nng Listener and Socket
+//!   - unregister the SurveyorHandle from the global registry
+//!
+//! <pre>
+//! SurveyorHandle ---Survey--> surveyor task --> Socket ---> respondents
+//! SurveyResponses <--Stream-- surveyor task <-- Socket <--- respondents
+//! </pre>
+//!
+//! ## Config
+//! - [SocketConfig](../../config/struct.SocketConfig.html)
+//! - [ListenerConfig](../../reqrep/server/struct.ListenerConfig.html)
+//!
+//! ## Metrics
+//! - active number of socket connections - [ACTIVE_CONN_COUNT_METRIC_ID](constant.ACTIVE_CONN_COUNT_METRIC_ID.html)
+//! - total number of surveys - [SURVEY_COUNT_METRIC_ID](constant.SURVEY_COUNT_METRIC_ID.html)
+//! - total number of survey responses - [RESPONSE_COUNT_METRIC_ID](constant.RESPONSE_COUNT_METRIC_ID.html)
+
+use super::{SurveyId, SURVEY_ID_LABEL_ID};
+use crate::{
+    config::{SocketConfig, SocketConfigError},
+    reqrep::server::{ListenerConfig, ListenerConfigError},
+    task::{self, TaskHandle, TaskHandleError},
+};
+use failure::Fail;
+use futures::{
+    channel::mpsc,
+    prelude::*,
+    sink::SinkExt,
+    stream::StreamExt,
+    task::{Poll, SpawnExt, Waker},
+};
+use hashbrown::HashMap;
+use lazy_static::lazy_static;
+use nng::options::Options;
+use oysterpack_log::*;
+use oysterpack_trust::{concurrent::execution::Executor, metrics};
+use oysterpack_uid::ULID;
+use parking_lot::RwLock;
+use std::{collections::VecDeque, fmt, pin::Pin, time::Duration};
+
+lazy_static! {
+
+    /// Global SurveyorHandle registry
+    static ref SURVEYOR_HANDLES: RwLock<HashMap<ULID, SurveyorHandle>> = RwLock::new(HashMap::new());
+
+    /// the metric is incremented on nng::PipeEvent::AddPost and decremented on nng::PipeEvent::RemovePost
+    static ref ACTIVE_CONN_COUNT: prometheus::IntGaugeVec = metrics::registry().register_int_gauge_vec(
+        ACTIVE_CONN_COUNT_METRIC_ID,
+        "Active number of surveyor socket connections",
+        &[SURVEY_ID_LABEL_ID],
+        None
+    ).unwrap();
+
+    /// the metric is incremented when the survey has been sent
+    static ref SURVEY_COUNT: prometheus::IntCounterVec = metrics::registry().register_int_counter_vec(
+        SURVEY_COUNT_METRIC_ID,
+        "Total number of surveys",
+        &[SURVEY_ID_LABEL_ID],
+        None
+    ).unwrap();
+
+    /// the metric is incremented when a survey response is received
+    static ref RESPONSE_COUNT: prometheus::IntCounterVec = metrics::registry().register_int_counter_vec(
+        RESPONSE_COUNT_METRIC_ID,
+        "Total number of survey responses",
+        &[SURVEY_ID_LABEL_ID],
+        None
+    ).unwrap();
+
+}
+
+/// IntGaugeVec MetricId which is used to track the number of active surveyor socket connections by SurveyId: `M01M57F6B5BA0C0431VGKCTDF9Q`
+pub const ACTIVE_CONN_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166789084226043897105650369046166839);
+/// IntCounterVec MetricId which is used to track the total number of surveys by SurveyId: `M01M57F6B5DAR0TYM0N891S6NDK`
+pub const SURVEY_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166789084228489671153725149497480627);
+/// IntCounterVec MetricId which is used to track the total number of survey responses by SurveyId: `M01M57F6B5FY41KG76KK10BN1RR`
+pub const RESPONSE_COUNT_METRIC_ID: metrics::MetricId =
+    metrics::MetricId(2166789084231639517902006237500442392);
+
+/// The default SurveyResponses channel buffer size
+pub const DEFAULT_RESPONSES_CHAN_BUF_SIZE: usize = 64;
+
+/// Spawns a surveyor background task
+/// - returns a SurveyorHandle that is used to run surveys and to stop the surveyor
+///   - the SurveyorHandle is registered globally
+///     - each surveyor instance is assigned a ULID, which is used as the registry key
+///   - when the surveyor is stopped, the SurveyorHandle will be automatically unregistered
+pub fn spawn(
+    socket_config: Option<SocketConfig>,
+    listener_config: ListenerConfig,
+    survey_id: SurveyId,
+    mut executor: Executor,
+) -> Result<SurveyorHandle, SpawnError> {
+    let (command_tx, command_rx) = mpsc::channel(1);
+
+    let url = listener_config.url().clone();
+    let surveyor_metrics = SurveyorMetrics::new(survey_id);
+    let surveyor_handle_id = ULID::generate();
+
+    let create_socket = || {
+        let surveyor_metrics = surveyor_metrics.clone();
+        let mut socket =
+            nng::Socket::new(nng::Protocol::Surveyor0).map_err(SpawnError::SocketCreateFailure)?;
+        socket.set_nonblocking(true);
+        socket
+            .pipe_notify(move |pipe, event| {
+                match event {
+                    nng::PipeEvent::AddPost => surveyor_metrics.active_conn_count.inc(),
+                    nng::PipeEvent::RemovePost => surveyor_metrics.active_conn_count.dec(),
+                    _ => (),
+                }
+                debug!("{:?} {:?}", pipe, event);
+            })
+            .map_err(SpawnError::SocketCreateFailure)?;
+        match socket_config {
+            Some(socket_config) => socket_config
+                .apply(socket)
+                .map_err(SpawnError::SocketConfigApplyFailed),
+            None => Ok(socket),
+        }
+    };
+
+    let socket = create_socket()?;
+    let listener = listener_config
+        .start_listener(&socket)
+        .map_err(SpawnError::ListenerStartFailure)?;
+
+    // used to notify the surveyor task when an Aio event has occurred, i.e., the Aio callback has been invoked
+    let (aio, aio_rx) =
+        task::aio_notifier("surveyor").map_err(SpawnError::AioCreateWithCallbackFailure)?;
+
+    let metrics = surveyor_metrics.clone();
+    let handle = executor
+        .spawn_with_handle(
+            async move {
+                debug!("Surveyor({}) is running ...", survey_id);
+                // fuse the streams that will be polled via futures::select! - per the documentation
+                let mut aio_rx = aio_rx.fuse();
+                let mut command_rx = command_rx.fuse();
+
+                let start_survey = |survey: Survey| {
+                    if let Err(err) = socket
+                        .set_opt::<nng::options::protocol::survey::SurveyTime>(Some(
+                            survey.survey_time,
+                        ))
+                    {
+                        error!("Failed to set SurveyTime socket option: {}", err);
+                        return SurveyState::Idle;
+                    }
+                    match socket.send_async(&aio, survey.msg) {
+                        Ok(_) => SurveyState::Sending(survey.responses),
+                        Err((_msg, err)) => {
+                            error!("Socket::send_async() failed: {}", err);
+                            SurveyState::Idle
+                        }
+                    }
+                };
+
+                let recv = |responses: mpsc::Sender<nng::Message>| {
+                    if let Err(err) = socket.recv_async(&aio) {
+                        error!("Socket::recv_async() failed: {}", err);
+                        return SurveyState::Idle;
+                    }
+                    SurveyState::Receiving(responses)
+                };
+
+                // surveys that are waiting for the running survey to complete
+                let mut pending_surveys = VecDeque::new();
+                let mut state = SurveyState::Idle;
+                loop {
+                    futures::select! {
+                        cmd = command_rx.next() => match cmd {
+                            Some(SurveyorCommand::Survey {
+                                msg,
+                                survey_time,
+                                responses,
+                            }) => {
+                                let survey = Survey {
+                                    msg,
+                                    survey_time,
+                                    responses,
+                                };
+                                match state {
+                                    SurveyState::Idle => state = start_survey(survey),
+                                    _ => pending_surveys.push_back(survey),
+                                }
+                            }
+                            Some(SurveyorCommand::Ping(reply_chan)) => {
+                                let _ = reply_chan.send(());
+                            }
+                            Some(SurveyorCommand::Stop) | None => break,
+                        },
+                        event = aio_rx.next() => {
+                            if event.is_none() {
+                                break;
+                            }
+                            // NOTE: aio.result().unwrap() is safe because we are being signalled
+                            // by the Aio callback to handle an Aio event
+                            state = match (state, aio.result().unwrap()) {
+                                (_, Err(nng::Error::Closed)) => break,
+                                (SurveyState::Sending(responses), Ok(_)) => {
+                                    metrics.survey_count.inc();
+                                    recv(responses)
+                                }
+                                (SurveyState::Receiving(mut responses), Ok(_)) => {
+                                    match aio.get_msg() {
+                                        Some(msg) => {
+                                            metrics.response_count.inc();
+                                            // responses are not awaited, i.e., a slow SurveyResponses
+                                            // consumer must not block the surveyor from being stopped
+                                            match responses.try_send(msg) {
+                                                Ok(_) => recv(responses),
+                                                Err(ref err) if err.is_disconnected() => {
+                                                    debug!("SurveyResponses stream was dropped - the survey is cancelled");
+                                                    SurveyState::Idle
+                                                }
+                                                Err(_) => {
+                                                    warn!("SurveyResponses stream is full - the response is dropped");
+                                                    recv(responses)
+                                                }
+                                            }
+                                        }
+                                        None => recv(responses),
+                                    }
+                                }
+                                (SurveyState::Receiving(_), Err(nng::Error::TimedOut)) => {
+                                    SurveyState::Idle
+                                }
+                                (SurveyState::Sending(_), Err(err)) => {
+                                    error!("Failed to send survey: {}", err);
+                                    SurveyState::Idle
+                                }
+                                (_, Err(err)) => {
+                                    error!("Aio error: {}", err);
+                                    SurveyState::Idle
+                                }
+                                (SurveyState::Idle, Ok(_)) => {
+                                    warn!("Unexpected Aio event - no survey is running");
+                                    SurveyState::Idle
+                                }
+                            };
+                            // start the next survey
+                            while let SurveyState::Idle = state {
+                                match pending_surveys.pop_front() {
+                                    Some(survey) => state = start_survey(survey),
+                                    None => break,
+                                }
+                            }
+                        },
+                    }
+                }
+                // cancel the running survey - dropping the responses channels ends the SurveyResponses streams
+                aio.cancel();
+                drop(state);
+                drop(pending_surveys);
+                debug!("Surveyor({}) is shutting down ...", survey_id);
+                listener.close();
+                socket.close();
+                debug!("Surveyor({}) is shut down", survey_id);
+                let mut surveyor_handles = SURVEYOR_HANDLES.write();
+                surveyor_handles.remove(&surveyor_handle_id);
+            },
+        )
+        .map_err(|err| SpawnError::ExecutorSpawnError {
+            is_executor_shutdown: err.is_shutdown(),
+        })?;
+
+    let surveyor_handle = SurveyorHandle {
+        id: surveyor_handle_id,
+        url,
+        survey_id,
+        task: TaskHandle::new(handle, command_tx, executor),
+        metrics: surveyor_metrics,
+    };
+
+    let mut surveyor_handles = SURVEYOR_HANDLES.write();
+    surveyor_handles.insert(surveyor_handle.id(), surveyor_handle.clone());
+
+    Ok(surveyor_handle)
+}
+
+/// Surveyor handle
+/// - the surveyor handle is globally registered using its ULID as the key
+///
+/// ## Stopping the surveyor
+/// - [stop_async()](#method.stop_async) is used to signal the surveyor to stop
+#[derive(Debug, Clone)]
+pub struct SurveyorHandle {
+    id: ULID,
+    url: url::Url,
+    survey_id: SurveyId,
+    task: TaskHandle<SurveyorCommand>,
+    metrics: SurveyorMetrics,
+}
+
+impl SurveyorHandle {
+    /// Returns the SurveyorHandle ULID
+    pub fn id(&self) -> ULID {
+        self.id
+    }
+
+    /// Returns the URI that the surveyor is listening on
+    pub fn url(&self) -> &url::Url {
+        &self.url
+    }
+
+    /// Returns the SurveyId
+    pub fn survey_id(&self) -> SurveyId {
+        self.survey_id
+    }
+
+    /// returns true if the surveyor has been signalled to stop
+    pub fn stop_signalled(&self) -> bool {
+        self.task.stop_signalled()
+    }
+
+    /// Returns SurveyorMetrics
+    pub fn metrics(&self) -> &SurveyorMetrics {
+        &self.metrics
+    }
+
+    /// Submits the survey, which will be sent to all connected respondents.
+    /// - the returned stream yields the survey responses, and ends when the survey time expires
+    /// - dropping the stream cancels the survey
+    pub fn survey(
+        &self,
+        msg: nng::Message,
+        survey_time: Duration,
+    ) -> impl Future<Output = Result<SurveyResponses, SurveyError>> {
+        let command_channel = self.task.command_channel();
+        async move {
+            let mut command_channel = command_channel.ok_or(SurveyError::SurveyorStopped)?;
+            let (responses, responses_rx) = mpsc::channel(DEFAULT_RESPONSES_CHAN_BUF_SIZE);
+            await!(command_channel.send(SurveyorCommand::Survey {
+                msg,
+                survey_time,
+                responses
+            }))
+            .map_err(|_| SurveyError::SurveyorStopped)?;
+            Ok(SurveyResponses {
+                responses: responses_rx,
+            })
+        }
+    }
+
+    /// pings the surveyor to check if it is still alive
+    /// - returns true if the surveyor responds to the ping
+    pub fn ping(&self) -> bool {
+        self.task.ping(SurveyorCommand::Ping)
+    }
+
+    /// signals the surveyor to shutdown async
+    pub fn stop_async(&mut self) -> Result<bool, TaskHandleError> {
+        self.task.stop_async(SurveyorCommand::Stop)
+    }
+
+    /// Block the current thread until the surveyor has shutdown
+    ///
+    /// ## Notes
+    /// The surveyor must be signaled to stop in order to shutdown.
+    pub fn await_shutdown(mut self) {
+        self.task.await_shutdown()
+    }
+
+    /// Returns the SurveyorHandle - only if the surveyor is still alive
+    pub fn get(id: ULID) -> Option<SurveyorHandle> {
+        let surveyor_handle = {
+            let surveyor_handles = SURVEYOR_HANDLES.read();
+            surveyor_handles.get(&id).cloned()
+        };
+
+        // check if the surveyor is still alive
+        if let Some(surveyor_handle) = surveyor_handle {
+            if surveyor_handle.ping() {
+                Some(surveyor_handle)
+            } else {
+                // unregister the SurveyorHandle because pinging the surveyor failed
+                {
+                    let mut surveyor_handles = SURVEYOR_HANDLES.write();
+                    surveyor_handles.remove(&id);
+                }
+                None
+            }
+        } else {
+            None
+        }
+    }
+
+    /// returns all registered SurveyorHandle(s)
+    pub fn all() -> Vec<SurveyorHandle> {
+        SURVEYOR_HANDLES.read().values().cloned().collect()
+    }
+
+    /// Returns SurveyorHandle(s) that are registered for the specified SurveyId
+    pub fn get_by_survey_id(survey_id: SurveyId) -> Vec<SurveyorHandle> {
+        let surveyor_handles = SURVEYOR_HANDLES.read();
+        surveyor_handles
+            .values()
+            .filter(|surveyor_handle| surveyor_handle.survey_id == survey_id)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Survey responses stream, which ends when the survey time expires
+pub struct SurveyResponses {
+    responses: mpsc::Receiver<nng::Message>,
+}
+
+impl Stream for SurveyResponses {
+    type Item = nng::Message;
+
+    fn poll_next(mut self: Pin<&mut Self>, waker: &Waker) -> Poll<Option<Self::Item>> {
+        self.responses.poll_next_unpin(waker)
+    }
+}
+
+impl fmt::Debug for SurveyResponses {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SurveyResponses")
+    }
+}
+
+/// A survey that is waiting to be run
+struct Survey {
+    msg: nng::Message,
+    survey_time: Duration,
+    responses: mpsc::Sender<nng::Message>,
+}
+
+/// Surveyor Aio state
+enum SurveyState {
+    /// no survey is running
+    Idle,
+    /// the survey is being sent
+    Sending(mpsc::Sender<nng::Message>),
+    /// survey responses are being received until the survey time expires
+    Receiving(mpsc::Sender<nng::Message>),
+}
+
+/// Surveyor commands
+#[derive(Debug)]
+pub enum SurveyorCommand {
+    /// Run the survey
+    Survey {
+        /// survey message
+        msg: nng::Message,
+        /// how long to wait for responses
+        survey_time: Duration,
+        /// used to send back the responses
+        responses: mpsc::Sender<nng::Message>,
+    },
+    /// Ping the surveyor to check if it is still alive
+    Ping(futures::channel::oneshot::Sender<()>),
+    /// Signals the surveyor to shutdown
+    Stop,
+}
+
+/// Survey related errors
+#[derive(Debug, Fail, Clone)]
+pub enum SurveyError {
+    /// The surveyor has been stopped
+    #[fail(display = "The surveyor has been stopped")]
+    SurveyorStopped,
+}
+
+/// Errors that could happen while trying to spawn a surveyor
+#[derive(Debug, Fail)]
+pub enum SpawnError {
+    /// Failed to create Socket
+    #[fail(display = "Failed to create Socket: {}", _0)]
+    SocketCreateFailure(#[cause] nng::Error),
+    /// Failed to create Aio
+    #[fail(display = "Failed to create Aio with callback: {}", _0)]
+    AioCreateWithCallbackFailure(#[cause] nng::Error),
+    /// An error that occurred during spawning.
+    #[fail(
+        display = "Spawning Future failed: executor shutdown = {}",
+        is_executor_shutdown
+    )]
+    ExecutorSpawnError {
+        /// whether spawning failed because the executor is shut down
+        is_executor_shutdown: bool,
+    },
+    /// Failed to start the listener
+    #[fail(display = "{}", _0)]
+    ListenerStartFailure(#[cause] ListenerConfigError),
+    /// Failed to apply SocketConfig options
+    #[fail(display = "{}", _0)]
+    SocketConfigApplyFailed(#[cause] SocketConfigError),
+}
+
+/// Surveyor metrics
+#[derive(Clone)]
+pub struct SurveyorMetrics {
+    active_conn_count: prometheus::IntGauge,
+    survey_count: prometheus::IntCounter,
+    response_count: prometheus::IntCounter,
+}
+
+impl SurveyorMetrics {
+    fn new(survey_id: SurveyId) -> Self {
+        let survey_id_label = survey_id.to_string();
+        Self {
+            active_conn_count: ACTIVE_CONN_COUNT.with_label_values(&[survey_id_label.as_str()]),
+            survey_count: SURVEY_COUNT.with_label_values(&[survey_id_label.as_str()]),
+            response_count: RESPONSE_COUNT.with_label_values(&[survey_id_label.as_str()]),
+        }
+    }
+
+    /// Active number of socket connections
+    pub fn active_conn_count(&self) -> usize {
+        self.active_conn_count.get() as usize
+    }
+
+    /// Total number of surveys
+    pub fn survey_count(&self) -> usize {
+        self.survey_count.get() as usize
+    }
+
+    /// Total number of survey responses
+    pub fn response_count(&self) -> usize {
+        self.response_count.get() as usize
+    }
+}
+
+impl fmt::Debug for SurveyorMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "SurveyorMetrics(active_conn_count = {}, survey_count = {}, response_count = {})",
+            self.active_conn_count.get(),
+            self.survey_count.get(),
+            self.response_count.get()
+        )
+    }
+}
+
+#[allow(warnings)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configure_logging;
+    use oysterpack_trust::concurrent::execution::{self, *};
+    use std::time::Instant;
+
+    fn start_surveyor(executor: &Executor) -> SurveyorHandle {
+        let url = url::Url::parse(&format!("inproc://{}", ULID::generate())).unwrap();
+        spawn(
+            None,
+            ListenerConfig::new(url),
+            SurveyId::generate(),
+            executor.clone(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn survey_expires() {
+        configure_logging();
+        let mut executor = execution::global_executor();
+
+        // GIVEN: a surveyor with no respondents
+        let mut surveyor = start_surveyor(&executor);
+        assert!(surveyor.ping());
+        assert!(SurveyorHandle::get(surveyor.id()).is_some());
+        assert_eq!(SurveyorHandle::get_by_survey_id(surveyor.survey_id()).len(), 1);
+
+        // WHEN: 2 surveys are submitted back to back
+        let first = executor
+            .run(surveyor.survey(nng::Message::new().unwrap(), Duration::from_millis(20)))
+            .unwrap();
+        let second = executor
+            .run(surveyor.survey(nng::Message::new().unwrap(), Duration::from_millis(20)))
+            .unwrap();
+        // THEN: both response streams end when their survey time expires
+        assert!(executor.run(first.collect::<Vec<_>>()).is_empty());
+        assert!(executor.run(second.collect::<Vec<_>>()).is_empty());
+        assert_eq!(surveyor.metrics().survey_count(), 2);
+        assert_eq!(surveyor.metrics().response_count(), 0);
+
+        let surveyor_id = surveyor.id();
+        assert!(surveyor.stop_async().unwrap());
+        surveyor.await_shutdown();
+        assert!(SurveyorHandle::get(surveyor_id).is_none());
+    }
+
+    #[test]
+    fn stop_interrupts_running_survey() {
+        configure_logging();
+        let mut executor = execution::global_executor();
+
+        // GIVEN: a survey is running, which will not expire for a long time
+        let mut surveyor = start_surveyor(&executor);
+        let running = executor
+            .run(surveyor.survey(nng::Message::new().unwrap(), Duration::from_secs(60)))
+            .unwrap();
+        // AND: a survey is pending
+        let pending = executor
+            .run(surveyor.survey(nng::Message::new().unwrap(), Duration::from_secs(60)))
+            .unwrap();
+        // AND: the surveyor is responsive while the survey is running
+        assert!(surveyor.ping());
+
+        // WHEN: the surveyor is stopped
+        let now = Instant::now();
+        assert!(surveyor.stop_async().unwrap());
+        surveyor.clone().await_shutdown();
+        // THEN: the surveyor shuts down without waiting for the survey to expire
+        assert!(now.elapsed() < Duration::from_secs(10));
+        // AND: the running and pending survey response streams end
+        assert!(executor.run(running.collect::<Vec<_>>()).is_empty());
+        assert!(executor.run(pending.collect::<Vec<_>>()).is_empty());
+        // AND: surveys are rejected
+        match executor.run(surveyor.survey(nng::Message::new().unwrap(), Duration::from_secs(1))) {
+            Err(SurveyError::SurveyorStopped) => (),
+            other => panic!("expected SurveyorStopped: {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,163 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Provides the plumbing that is shared by the nng socket tasks, i.e., the publisher, subscriber,
+//! surveyor, respondent, pusher, puller, and gossip tasks.
+//!
+//! Each socket task is a single background task that owns the nng socket:
+//! - async I/O is driven via an [nng:Aio](https://docs.rs/nng/latest/nng/struct.Aio.html), whose
+//!   callback notifies the task via the [Aio notifier](fn.aio_notifier.html) channel
+//! - the task is controlled via an async command channel, which is wrapped by a [TaskHandle](struct.TaskHandle.html)
+
+use failure::Fail;
+use futures::{
+    channel::{mpsc, oneshot},
+    future::{self, FutureExt},
+    sink::SinkExt,
+    task::SpawnExt,
+};
+use oysterpack_log::*;
+use oysterpack_trust::concurrent::execution::Executor;
+use std::{fmt, panic::AssertUnwindSafe};
+
+/// Creates an Aio whose callback notifies the task when an Aio event has occurred
+/// - `task` is used for logging purposes
+pub(crate) fn aio_notifier(
+    task: &'static str,
+) -> Result<(nng::Aio, mpsc::UnboundedReceiver<()>), nng::Error> {
+    let (aio_tx, aio_rx) = mpsc::unbounded::<()>();
+    let aio_tx = AssertUnwindSafe(aio_tx);
+    let aio = nng::Aio::with_callback(move |_aio| {
+        if let Err(err) = aio_tx.unbounded_send(()) {
+            // means the channel has been disconnected because the task has completed
+            warn!(
+                "Failed to notify {} of Aio event. This means the {} is not running: {}",
+                task, task, err
+            );
+        }
+    })?;
+    Ok((aio, aio_rx))
+}
+
+/// Handle to a background task that is driven by an async command channel
+/// - cloning is cheap, i.e., clones share the same task
+/// - once a handle has been signalled to stop, it can no longer send commands to the task
+pub struct TaskHandle<Cmd> {
+    handle: Option<future::Shared<future::RemoteHandle<()>>>,
+    command_channel: Option<mpsc::Sender<Cmd>>,
+    executor: Executor,
+}
+
+impl<Cmd: Send + 'static> TaskHandle<Cmd> {
+    /// constructor
+    pub(crate) fn new(
+        handle: future::RemoteHandle<()>,
+        command_channel: mpsc::Sender<Cmd>,
+        executor: Executor,
+    ) -> Self {
+        TaskHandle {
+            handle: Some(handle.shared()),
+            command_channel: Some(command_channel),
+            executor,
+        }
+    }
+
+    /// returns true if the task has been signalled to stop
+    pub fn stop_signalled(&self) -> bool {
+        self.command_channel.is_none()
+    }
+
+    /// Returns the command channel - returns None if the task has been signalled to stop
+    pub(crate) fn command_channel(&self) -> Option<mpsc::Sender<Cmd>> {
+        self.command_channel.clone()
+    }
+
+    /// pings the task to check if it is still alive
+    /// - `ping` is used to create the ping command, which the task is expected to reply to
+    /// - returns true if the task responds to the ping
+    pub(crate) fn ping(&self, ping: fn(oneshot::Sender<()>) -> Cmd) -> bool {
+        match self.command_channel() {
+            Some(mut command_channel) => {
+                let mut executor = self.executor.clone();
+                executor.run(
+                    async move {
+                        let (tx, rx) = oneshot::channel();
+                        if await!(command_channel.send(ping(tx))).is_ok() {
+                            await!(rx).is_ok()
+                        } else {
+                            false
+                        }
+                    },
+                )
+            }
+            None => false,
+        }
+    }
+
+    /// sends the stop command async
+    /// - returns false if the task has already been signalled to stop
+    pub(crate) fn stop_async(&mut self, stop: Cmd) -> Result<bool, TaskHandleError> {
+        if let Some(mut c) = self.command_channel.take() {
+            self.executor
+                .spawn(
+                    async move {
+                        // the result can be ignored because if the channel is disconnected then it
+                        // means the task has stopped
+                        let _ = await!(c.send(stop));
+                    },
+                )
+                .map_err(|err| {
+                    if err.is_shutdown() {
+                        TaskHandleError("executor is shutdown".to_string())
+                    } else {
+                        TaskHandleError("executor failed to spawn the task".to_string())
+                    }
+                })?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Block the current thread until the task has completed
+    pub(crate) fn await_shutdown(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            self.executor.run(async { await!(handle) });
+        }
+    }
+}
+
+impl<Cmd> Clone for TaskHandle<Cmd> {
+    fn clone(&self) -> Self {
+        TaskHandle {
+            handle: self.handle.clone(),
+            command_channel: self.command_channel.clone(),
+            executor: self.executor.clone(),
+        }
+    }
+}
+
+impl<Cmd> fmt::Debug for TaskHandle<Cmd> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TaskHandle")
+            .field("stop_signalled", &self.command_channel.is_none())
+            .finish()
+    }
+}
+
+/// TaskHandle error
+#[derive(Fail, Debug, Clone)]
+#[fail(display = "TaskHandle error: {}", _0)]
+pub struct TaskHandleError(String);
@@ -0,0 +1,530 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Test support for nng ReqRep services.
+//!
+//! A [TestHarness](struct.TestHarness.html) spins up a server on a unique `inproc://` or `ipc://`
+//! address, registers a client that dials the server, and tears both down when the harness is dropped.
+//! The harness server is a standard [server](../reqrep/server/index.html), whose ReqRep service
+//! runs the specified
+//! [Processor](https://docs.rs/oysterpack_trust/latest/oysterpack_trust/concurrent/messaging/reqrep/trait.Processor.html).
+//!
+//! The module is available to the crate's own tests, and to other crates via the `testing` feature.
+//!
+//! ## Fault Injection
+//! Faults are injected into the harness server via the [FaultInjector](struct.FaultInjector.html),
+//! and are applied by the server before the request is forwarded to the ReqRep service. This makes
+//! it possible to deterministically test client retry and error handling:
+//! - [Fault::DropReply](enum.Fault.html#variant.DropReply) - the client never receives the reply
+//! - [Fault::Delay](enum.Fault.html#variant.Delay) - the reply is delayed
+//! - [Fault::CloseConnection](enum.Fault.html#variant.CloseConnection) - the connection is closed
+//!   before the reply is sent
+
+use crate::{
+    reqrep::{
+        client::{self, Client, ClientRegistrationError, DialerConfig},
+        policy::RequestPolicy,
+        server::{self, Interception, ListenerConfig, RequestInterceptor, ServerHandle},
+    },
+};
+use failure::Fail;
+use oysterpack_log::*;
+use oysterpack_trust::{
+    concurrent::{
+        execution::global_executor,
+        messaging::reqrep::{Processor, ReqRepConfig, ReqRepId},
+    },
+    metrics,
+};
+use oysterpack_uid::ULID;
+use parking_lot::Mutex;
+use std::{
+    collections::VecDeque,
+    fmt, fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// The transport used to connect the harness client to the harness server
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Transport {
+    /// `inproc://{ULID}`
+    Inproc,
+    /// `ipc://{temp dir}/{ULID}.ipc`
+    Ipc,
+}
+
+impl Transport {
+    /// Returns a unique URL for the transport
+    pub fn unique_url(self) -> url::Url {
+        match self {
+            Transport::Inproc => {
+                url::Url::parse(&format!("inproc://{}", ULID::generate())).unwrap()
+            }
+            Transport::Ipc => {
+                let path = std::env::temp_dir().join(format!("{}.ipc", ULID::generate()));
+                url::Url::parse(&format!("ipc://{}", path.display())).unwrap()
+            }
+        }
+    }
+}
+
+/// Faults that can be injected into the harness server
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Fault {
+    /// The request is dropped by the server without being forwarded to the ReqRep service, i.e., the
+    /// reply is never sent. The client will wait until the request times out - see [SocketConfig::set_recv_timeout()](../config/struct.SocketConfig.html#method.set_recv_timeout).
+    DropReply,
+    /// The request is forwarded to the ReqRep service after the delay. Only the server worker that
+    /// received the request is delayed, i.e., requests received by other workers are not delayed.
+    Delay(Duration),
+    /// The connection is closed without processing the request. The nng Req0 client will reconnect
+    /// and resend the request.
+    CloseConnection,
+}
+
+/// Used to inject faults into the harness server
+/// - queued faults are applied in FIFO order, 1 per request
+/// - once the queue is empty, the persistent fault (if set) is applied to every request
+#[derive(Debug, Clone, Default)]
+pub struct FaultInjector {
+    faults: Arc<Mutex<Faults>>,
+    request_count: Arc<AtomicUsize>,
+    fault_count: Arc<AtomicUsize>,
+}
+
+#[derive(Debug, Default)]
+struct Faults {
+    queue: VecDeque<Fault>,
+    persistent: Option<Fault>,
+}
+
+impl FaultInjector {
+    /// Queues a fault that is applied to the next request, i.e., the fault is applied once
+    pub fn inject(&self, fault: Fault) {
+        self.faults.lock().queue.push_back(fault);
+    }
+
+    /// Queues the fault to be applied to the next `count` requests
+    pub fn inject_n(&self, fault: Fault, count: usize) {
+        let mut faults = self.faults.lock();
+        for _ in 0..count {
+            faults.queue.push_back(fault);
+        }
+    }
+
+    /// Applies the fault to every request, once the queued faults have been applied
+    pub fn inject_always(&self, fault: Fault) {
+        self.faults.lock().persistent = Some(fault);
+    }
+
+    /// Clears all faults
+    pub fn clear(&self) {
+        let mut faults = self.faults.lock();
+        faults.queue.clear();
+        faults.persistent = None;
+    }
+
+    /// Number of queued faults that have not yet been applied
+    pub fn pending_fault_count(&self) -> usize {
+        self.faults.lock().queue.len()
+    }
+
+    /// Total number of requests that the server has received
+    pub fn request_count(&self) -> usize {
+        self.request_count.load(Ordering::SeqCst)
+    }
+
+    /// Total number of faults that have been applied
+    pub fn fault_count(&self) -> usize {
+        self.fault_count.load(Ordering::SeqCst)
+    }
+
+    fn next_fault(&self) -> Option<Fault> {
+        self.request_count.fetch_add(1, Ordering::SeqCst);
+        let fault = {
+            let mut faults = self.faults.lock();
+            faults.queue.pop_front().or(faults.persistent)
+        };
+        if fault.is_some() {
+            self.fault_count.fetch_add(1, Ordering::SeqCst);
+        }
+        fault
+    }
+}
+
+/// TestHarness config
+#[derive(Debug)]
+pub struct TestHarnessConfig {
+    transport: Transport,
+    client_socket_config: Option<client::SocketConfig>,
+    request_policy: Option<RequestPolicy>,
+}
+
+impl TestHarnessConfig {
+    /// constructor
+    pub fn new(transport: Transport) -> TestHarnessConfig {
+        TestHarnessConfig {
+            transport,
+            client_socket_config: None,
+            request_policy: None,
+        }
+    }
+
+    /// Sets the client socket config, e.g., to configure the request timeout
+    pub fn set_client_socket_config(mut self, socket_config: client::SocketConfig) -> Self {
+        self.client_socket_config = Some(socket_config);
+        self
+    }
+
+    /// Sets the client request policy
+    pub fn set_request_policy(mut self, request_policy: RequestPolicy) -> Self {
+        self.request_policy = Some(request_policy);
+        self
+    }
+
+    /// Spawns the server and registers the client
+    /// - the client is run on the global executor
+    pub fn spawn<P>(self, processor: P) -> Result<TestHarness, TestHarnessError>
+    where
+        P: Processor<nng::Message, nng::Message> + Send + 'static,
+    {
+        let reqrep_id = ReqRepId::generate();
+        let url = self.transport.unique_url();
+        let ipc_path = match self.transport {
+            Transport::Ipc => Some(PathBuf::from(url.path())),
+            Transport::Inproc => None,
+        };
+
+        let faults = FaultInjector::default();
+        let mut server = HarnessServer::spawn(reqrep_id, &url, processor, faults.clone())?;
+
+        let dialer_config = match self.request_policy {
+            Some(request_policy) => {
+                DialerConfig::new(url.clone()).set_request_policy(request_policy)
+            }
+            None => DialerConfig::new(url.clone()),
+        };
+        let client = match client::register_client(
+            ReqRepConfig::new(reqrep_id, timer_buckets()),
+            self.client_socket_config,
+            dialer_config,
+            global_executor(),
+        ) {
+            Ok(client) => client,
+            Err(err) => {
+                server.stop();
+                return Err(TestHarnessError::ClientRegistrationFailed(err));
+            }
+        };
+
+        Ok(TestHarness {
+            reqrep_id,
+            url,
+            ipc_path,
+            client,
+            server,
+            faults,
+        })
+    }
+}
+
+fn timer_buckets() -> Vec<f64> {
+    metrics::timer_buckets(vec![
+        Duration::from_micros(100),
+        Duration::from_millis(1),
+        Duration::from_millis(10),
+        Duration::from_millis(100),
+        Duration::from_secs(1),
+    ])
+    .unwrap()
+}
+
+/// The harness server is a standard [server](../reqrep/server/index.html) spawned on the global
+/// executor. Faults are applied by the server before the request is forwarded to the ReqRep service.
+#[derive(Debug)]
+struct HarnessServer {
+    handle: Option<ServerHandle>,
+}
+
+impl HarnessServer {
+    fn spawn<P>(
+        reqrep_id: ReqRepId,
+        url: &url::Url,
+        processor: P,
+        faults: FaultInjector,
+    ) -> Result<HarnessServer, TestHarnessError>
+    where
+        P: Processor<nng::Message, nng::Message> + Send + 'static,
+    {
+        let interceptor = FaultInjectingInterceptor {
+            url: url.clone(),
+            faults,
+        };
+        let service = ReqRepConfig::new(reqrep_id, timer_buckets())
+            .start_service(processor, global_executor())
+            .map_err(|err| TestHarnessError::ServiceStartFailed(err.is_shutdown()))?;
+        let handle = server::spawn_with_interceptor(
+            None,
+            ListenerConfig::new(url.clone()),
+            service,
+            Arc::new(interceptor),
+            global_executor(),
+        )
+        .map_err(TestHarnessError::ServerSpawnFailed)?;
+        Ok(HarnessServer {
+            handle: Some(handle),
+        })
+    }
+
+    fn stop(&mut self) {
+        if let Some(mut handle) = self.handle.take() {
+            if let Err(err) = handle.stop_async() {
+                warn!("TestHarness({}): failed to stop server: {}", handle.url(), err);
+                return;
+            }
+            handle.await_shutdown();
+        }
+    }
+}
+
+/// Applies the injected faults before the request is forwarded to the ReqRep service
+struct FaultInjectingInterceptor {
+    url: url::Url,
+    faults: FaultInjector,
+}
+
+impl RequestInterceptor for FaultInjectingInterceptor {
+    fn intercept(&self, req: &nng::Message) -> Interception {
+        match self.faults.next_fault() {
+            None => Interception::Forward,
+            Some(Fault::Delay(duration)) => Interception::Delay(duration),
+            Some(Fault::DropReply) => {
+                debug!("TestHarness({}): reply was dropped", self.url);
+                Interception::Drop
+            }
+            Some(Fault::CloseConnection) => {
+                match req.pipe() {
+                    Some(pipe) => pipe.close(),
+                    None => warn!("TestHarness({}): request has no pipe to close", self.url),
+                }
+                // the client resends the request once it has reconnected
+                Interception::Drop
+            }
+        }
+    }
+}
+
+/// Runs a server and a registered client that dials the server.
+/// - when the harness is dropped, the client is unregistered and the server is stopped
+pub struct TestHarness {
+    reqrep_id: ReqRepId,
+    url: url::Url,
+    ipc_path: Option<PathBuf>,
+    client: Client,
+    server: HarnessServer,
+    faults: FaultInjector,
+}
+
+impl TestHarness {
+    /// Spawns a harness using the default config - see [TestHarnessConfig](struct.TestHarnessConfig.html)
+    pub fn spawn<P>(transport: Transport, processor: P) -> Result<TestHarness, TestHarnessError>
+    where
+        P: Processor<nng::Message, nng::Message> + Send + 'static,
+    {
+        TestHarnessConfig::new(transport).spawn(processor)
+    }
+
+    /// The client's ReqRepId
+    pub fn reqrep_id(&self) -> ReqRepId {
+        self.reqrep_id
+    }
+
+    /// The URL the server is listening on
+    pub fn url(&self) -> &url::Url {
+        &self.url
+    }
+
+    /// The registered client
+    pub fn client(&self) -> Client {
+        self.client.clone()
+    }
+
+    /// Used to inject faults into the server
+    pub fn faults(&self) -> &FaultInjector {
+        &self.faults
+    }
+
+    /// Unregisters the client, and stops the server
+    pub fn teardown(self) {
+        drop(self)
+    }
+
+    fn stop(&mut self) {
+        client::unregister_client(self.reqrep_id);
+        self.server.stop();
+        if let Some(path) = self.ipc_path.take() {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+impl Drop for TestHarness {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl fmt::Debug for TestHarness {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TestHarness({}, {})", self.reqrep_id, self.url)
+    }
+}
+
+/// TestHarness errors
+#[derive(Debug, Fail)]
+pub enum TestHarnessError {
+    /// Failed to start the server's ReqRep service
+    #[fail(
+        display = "Failed to start ReqRep service: executor is shutdown = {}",
+        _0
+    )]
+    ServiceStartFailed(bool),
+    /// Failed to spawn the server
+    #[fail(display = "Failed to spawn server: {}", _0)]
+    ServerSpawnFailed(#[cause] server::SpawnError),
+    /// Failed to register the client
+    #[fail(display = "Failed to register client: {}", _0)]
+    ClientRegistrationFailed(#[cause] ClientRegistrationError),
+}
+
+#[allow(warnings)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::SocketConfig,
+        configure_logging,
+        reqrep::{client::RequestError, policy::RetryPolicy},
+    };
+    use futures::prelude::*;
+    use oysterpack_trust::concurrent::messaging::reqrep::{self, metrics::processor_panic_count};
+
+    struct EchoService;
+    impl Processor<nng::Message, nng::Message> for EchoService {
+        fn process(&mut self, req: nng::Message) -> reqrep::FutureReply<nng::Message> {
+            async move { req }.boxed()
+        }
+    }
+
+    #[test]
+    fn harness_inproc_and_ipc() {
+        configure_logging();
+        let mut executor = global_executor();
+
+        for transport in vec![Transport::Inproc, Transport::Ipc] {
+            // GIVEN: the harness is running
+            let harness = TestHarness::spawn(transport, EchoService).unwrap();
+            // AND: the client is registered
+            assert!(client::client(harness.reqrep_id()).is_some());
+
+            // WHEN: a request is sent
+            let mut client = harness.client();
+            let reply = executor
+                .run(async move { await!(client.send_recv(nng::Message::new().unwrap())) })
+                .unwrap();
+            // THEN: the server replies
+            assert!(reply.is_ok());
+            assert_eq!(harness.faults().request_count(), 1);
+
+            // WHEN: the harness is torn down
+            let reqrep_id = harness.reqrep_id();
+            harness.teardown();
+            // THEN: the client is unregistered
+            assert!(client::client(reqrep_id).is_none());
+        }
+    }
+
+    #[test]
+    fn harness_fault_injection() {
+        configure_logging();
+        let mut executor = global_executor();
+
+        // GIVEN: the client request timeout is 50 ms, and transient errors are retried once
+        let harness = TestHarnessConfig::new(Transport::Inproc)
+            .set_client_socket_config(
+                client::SocketConfig::default().set_socket_config(
+                    SocketConfig::default().set_recv_timeout(Duration::from_millis(50)),
+                ),
+            )
+            .set_request_policy(RequestPolicy::default().set_retry(RetryPolicy::new(1)))
+            .spawn(EchoService)
+            .unwrap();
+
+        // WHEN: the next reply is dropped
+        harness.faults().inject(Fault::DropReply);
+        let mut client = harness.client();
+        let reply = executor
+            .run(async move { await!(client.send_recv(nng::Message::new().unwrap())) })
+            .unwrap();
+        // THEN: the request is retried, and the retry succeeds
+        assert!(reply.is_ok());
+        assert_eq!(harness.faults().request_count(), 2);
+        assert_eq!(harness.faults().fault_count(), 1);
+
+        // WHEN: every reply is dropped
+        harness.faults().inject_always(Fault::DropReply);
+        let mut client = harness.client();
+        let reply = executor
+            .run(async move { await!(client.send_recv(nng::Message::new().unwrap())) })
+            .unwrap();
+        // THEN: the request fails after the retry
+        match reply {
+            Err(RequestError::RecvFailed(_)) => (),
+            other => panic!("unexpected reply: {:?}", other),
+        }
+        // AND: dropped replies are not reported as ReqRep service panics
+        assert_eq!(processor_panic_count(harness.reqrep_id()), 0);
+        harness.faults().clear();
+
+        // WHEN: the reply is delayed
+        harness.faults().inject(Fault::Delay(Duration::from_millis(10)));
+        let mut client = harness.client();
+        let reply = executor
+            .run(async move { await!(client.send_recv(nng::Message::new().unwrap())) })
+            .unwrap();
+        // THEN: the reply is received
+        assert!(reply.is_ok());
+
+        // WHEN: the connection is closed before the reply is sent
+        harness.faults().inject(Fault::CloseConnection);
+        let request_count = harness.faults().request_count();
+        let mut client = harness.client();
+        let reply = executor
+            .run(async move { await!(client.send_recv(nng::Message::new().unwrap())) })
+            .unwrap();
+        // THEN: the client reconnects and the request is resent
+        assert!(reply.is_ok());
+        assert_eq!(harness.faults().request_count(), request_count + 2);
+        assert_eq!(harness.faults().pending_fault_count(), 0);
+
+        harness.teardown();
+    }
+}
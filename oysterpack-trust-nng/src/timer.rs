@@ -0,0 +1,264 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Provides an async [delay()](fn.delay.html), which is shared by the nng tasks, e.g., request policy
+//! backoff and hedging, server processing timeouts, and gossip heartbeats.
+//!
+//! Delays are driven by a single shared timer thread, which completes each delay when its deadline
+//! is reached.
+//! - each thread registers delays via its own clone of the timer channel sender, i.e., no lock is
+//!   taken per delay
+//! - dropping a delay before its deadline removes its timer entry
+
+use futures::{
+    channel::oneshot,
+    prelude::*,
+    task::{Poll, Waker},
+};
+use lazy_static::lazy_static;
+use oysterpack_log::*;
+use parking_lot::Mutex;
+use std::{
+    collections::{BTreeMap, HashMap},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+lazy_static! {
+    /// The timer thread's channel sender, which is cloned once per thread
+    static ref TIMER: Mutex<mpsc::Sender<TimerCommand>> = Mutex::new(spawn_timer());
+}
+
+thread_local! {
+    /// Used to register delays with the timer thread - see [delay()](fn.delay.html)
+    static TIMER_SENDER: mpsc::Sender<TimerCommand> = TIMER.lock().clone();
+}
+
+/// Used to assign a unique id to each delay
+static DELAY_SEQ: AtomicUsize = AtomicUsize::new(0);
+
+enum TimerCommand {
+    Schedule {
+        id: usize,
+        deadline: Instant,
+        timer: oneshot::Sender<()>,
+    },
+    Cancel(usize),
+    /// Replies whether the delay is scheduled
+    #[cfg(test)]
+    IsScheduled(usize, mpsc::Sender<bool>),
+}
+
+/// Sends the command to the timer thread - returns false if the timer thread is not running
+fn send(command: TimerCommand) -> bool {
+    TIMER_SENDER
+        .try_with(move |timer| timer.send(command).is_ok())
+        .unwrap_or(false)
+}
+
+/// Async delay
+/// - the delay is scheduled when it is created
+/// - no nng Aio is allocated per delay
+/// - if the delay is dropped before its deadline, then its timer entry is removed
+pub(crate) fn delay(duration: Duration) -> Delay {
+    let id = DELAY_SEQ.fetch_add(1, Ordering::Relaxed);
+    let (timer, rx) = oneshot::channel();
+    let deadline = Instant::now() + duration;
+    if send(TimerCommand::Schedule {
+        id,
+        deadline,
+        timer,
+    }) {
+        Delay { id, rx: Some(rx) }
+    } else {
+        error!("The timer thread is not running");
+        Delay { id, rx: None }
+    }
+}
+
+/// Delay future - see [delay()](fn.delay.html)
+#[derive(Debug)]
+pub(crate) struct Delay {
+    id: usize,
+    /// set to None once the delay has completed
+    rx: Option<oneshot::Receiver<()>>,
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, waker: &Waker) -> Poll<()> {
+        let poll = match self.rx.as_mut() {
+            // the sender is only dropped when the timer fires
+            Some(rx) => Pin::new(rx).poll(waker).map(|_| ()),
+            None => Poll::Ready(()),
+        };
+        if poll.is_ready() {
+            self.rx = None;
+        }
+        poll
+    }
+}
+
+impl Drop for Delay {
+    fn drop(&mut self) {
+        if self.rx.is_some() {
+            send(TimerCommand::Cancel(self.id));
+        }
+    }
+}
+
+/// spawns the timer thread, which completes delays when their deadline is reached
+fn spawn_timer() -> mpsc::Sender<TimerCommand> {
+    let (tx, rx) = mpsc::channel::<TimerCommand>();
+    thread::Builder::new()
+        .name("oysterpack-nng-timer".to_string())
+        .spawn(move || {
+            // timers are keyed by deadline - the delay id keeps keys unique
+            let mut timers = BTreeMap::<(Instant, usize), oneshot::Sender<()>>::new();
+            // delay id -> deadline, which is used to remove cancelled timers
+            let mut deadlines = HashMap::<usize, Instant>::new();
+            loop {
+                let now = Instant::now();
+                while let Some(key) = timers.keys().next().cloned() {
+                    if key.0 > now {
+                        break;
+                    }
+                    deadlines.remove(&key.1);
+                    if let Some(timer) = timers.remove(&key) {
+                        let _ = timer.send(());
+                    }
+                }
+                let command = match timers.keys().next() {
+                    Some((deadline, _)) => match rx.recv_timeout(*deadline - now) {
+                        Ok(command) => command,
+                        Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                        Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                    },
+                    None => match rx.recv() {
+                        Ok(command) => command,
+                        Err(_) => break,
+                    },
+                };
+                match command {
+                    TimerCommand::Schedule {
+                        id,
+                        deadline,
+                        timer,
+                    } => {
+                        deadlines.insert(id, deadline);
+                        timers.insert((deadline, id), timer);
+                    }
+                    TimerCommand::Cancel(id) => {
+                        if let Some(deadline) = deadlines.remove(&id) {
+                            timers.remove(&(deadline, id));
+                        }
+                    }
+                    #[cfg(test)]
+                    TimerCommand::IsScheduled(id, reply) => {
+                        let _ = reply.send(deadlines.contains_key(&id));
+                    }
+                }
+            }
+        })
+        .expect("failed to spawn the timer thread");
+    tx
+}
+
+#[allow(warnings)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configure_logging;
+    use futures::future::{self, FutureExt};
+    use oysterpack_trust::concurrent::execution::global_executor;
+
+    #[test]
+    fn delays_complete_in_deadline_order() {
+        configure_logging();
+        let mut executor = global_executor();
+        let start = Instant::now();
+        // GIVEN: a long delay and a short delay
+        let long_delay = delay(Duration::from_millis(50)).boxed();
+        let short_delay = delay(Duration::from_millis(5)).boxed();
+        // WHEN: they race
+        match executor.run(future::select(long_delay, short_delay)) {
+            // THEN: the short delay completes first
+            future::Either::Right((_, long_delay)) => {
+                assert!(start.elapsed() >= Duration::from_millis(5));
+                // AND: the long delay still completes after its deadline
+                executor.run(long_delay);
+                assert!(start.elapsed() >= Duration::from_millis(50));
+            }
+            future::Either::Left(_) => panic!("the long delay completed first"),
+        }
+    }
+
+    fn is_scheduled(id: usize) -> bool {
+        let (tx, rx) = mpsc::channel();
+        assert!(send(TimerCommand::IsScheduled(id, tx)));
+        rx.recv().unwrap()
+    }
+
+    #[test]
+    fn dropped_delays_are_removed() {
+        configure_logging();
+        // GIVEN: a delay that is scheduled far into the future
+        let long_delay = delay(Duration::from_secs(3600));
+        let id = long_delay.id;
+        assert!(is_scheduled(id));
+        // WHEN: the delay is dropped
+        drop(long_delay);
+        // THEN: its timer entry is removed
+        assert!(!is_scheduled(id));
+    }
+
+    #[test]
+    fn completed_delays_are_removed() {
+        configure_logging();
+        let mut executor = global_executor();
+        // GIVEN: a short delay
+        let short_delay = delay(Duration::from_millis(1));
+        let id = short_delay.id;
+        // WHEN: the delay completes
+        executor.run(short_delay);
+        // THEN: its timer entry is removed
+        assert!(!is_scheduled(id));
+    }
+
+    #[test]
+    fn delays_can_be_scheduled_from_many_threads() {
+        configure_logging();
+        // GIVEN: delays that are scheduled from different threads
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                thread::spawn(|| {
+                    let mut executor = global_executor();
+                    executor.run(delay(Duration::from_millis(5)));
+                })
+            })
+            .collect();
+        // THEN: each delay completes
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,368 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! TLS transport configuration, which applies to `tls+tcp://` and `wss://` URLs.
+//!
+//! This module requires the `tls` feature, because the nng library must be built with TLS support,
+//! i.e., with `NNG_ENABLE_TLS` enabled, which depends on [mbedTLS](https://tls.mbed.org/).
+//!
+//! - refer to the nng [TLS transport](https://nanomsg.github.io/nng/man/v1.1.0/nng_tls.7.html) for details
+//! - certificates and keys are specified as PEM encoded files
+//!   - the cert key file contains the certificate chain followed by the private key
+//!   - the CA file contains the bundle of trusted CA certificates used to validate the peer
+//! - by default, servers do not verify clients and clients require the server certificate to be verified
+//!
+//! ## Example
+//! ```no_run
+//! # use oysterpack_trust_nng::tls::*;
+//! let server_tls = TlsConfig::new()
+//!     .set_cert_key_file("/etc/app/tls/server.pem")
+//!     .set_ca_file("/etc/app/tls/ca.pem")
+//!     .set_verify_mode(TlsVerifyMode::Required);
+//! let client_tls = TlsConfig::new()
+//!     .set_cert_key_file("/etc/app/tls/client.pem")
+//!     .set_ca_file("/etc/app/tls/ca.pem")
+//!     .set_server_name("server.app.local");
+//! ```
+
+use crate::ws::WSS_URL_SCHEME;
+use failure::Fail;
+use nng::options::{
+    transport::tls::{AuthMode, CaFile, CertKeyFile, ServerName},
+    Options, SetOpt,
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// The TLS URL scheme
+pub const TLS_URL_SCHEME: &str = "tls+tcp";
+
+/// TLS config
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, Default)]
+pub struct TlsConfig {
+    cert_key_file: Option<PathBuf>,
+    ca_file: Option<PathBuf>,
+    verify_mode: Option<TlsVerifyMode>,
+    server_name: Option<String>,
+}
+
+impl TlsConfig {
+    /// constructor
+    pub fn new() -> TlsConfig {
+        TlsConfig::default()
+    }
+
+    /// Applies the TLS config to the listener
+    pub(crate) fn apply_to_listener(
+        &self,
+        url: &url::Url,
+        options: &nng::ListenerOptions,
+    ) -> Result<(), TlsConfigError> {
+        self.apply(url, options)
+    }
+
+    /// Applies the TLS config to the dialer
+    pub(crate) fn apply_to_dialer(
+        &self,
+        url: &url::Url,
+        options: &nng::DialerOptions,
+    ) -> Result<(), TlsConfigError> {
+        self.apply(url, options)?;
+        if let Some(server_name) = self.server_name.as_ref() {
+            options
+                .set_opt::<ServerName>(server_name.clone())
+                .map_err(TlsConfigError::ServerName)?;
+        }
+        Ok(())
+    }
+
+    /// Applies the options that are common to listeners and dialers
+    fn apply<O>(&self, url: &url::Url, options: &O) -> Result<(), TlsConfigError>
+    where
+        O: Options + SetOpt<CertKeyFile> + SetOpt<CaFile> + SetOpt<AuthMode>,
+    {
+        Self::check_url(url)?;
+        if let Some(file) = self.cert_key_file.as_ref() {
+            options
+                .set_opt::<CertKeyFile>(Self::path_to_string(file)?)
+                .map_err(TlsConfigError::CertKeyFile)?;
+        }
+        if let Some(file) = self.ca_file.as_ref() {
+            options
+                .set_opt::<CaFile>(Self::path_to_string(file)?)
+                .map_err(TlsConfigError::CaFile)?;
+        }
+        if let Some(verify_mode) = self.verify_mode {
+            options
+                .set_opt::<AuthMode>(verify_mode.nng_auth_mode())
+                .map_err(TlsConfigError::AuthMode)?;
+        }
+        Ok(())
+    }
+
+    fn check_url(url: &url::Url) -> Result<(), TlsConfigError> {
+        if url.scheme() == TLS_URL_SCHEME || url.scheme() == WSS_URL_SCHEME {
+            Ok(())
+        } else {
+            Err(TlsConfigError::InvalidUrlScheme(url.clone()))
+        }
+    }
+
+    fn path_to_string(path: &Path) -> Result<String, TlsConfigError> {
+        path.to_str()
+            .map(str::to_string)
+            .ok_or_else(|| TlsConfigError::InvalidPath(path.to_path_buf()))
+    }
+
+    /// PEM file containing the certificate chain followed by the private key
+    pub fn cert_key_file(&self) -> Option<&Path> {
+        self.cert_key_file.as_ref().map(PathBuf::as_path)
+    }
+
+    /// Sets the PEM file containing the certificate chain followed by the private key
+    /// - required for servers
+    pub fn set_cert_key_file<P: AsRef<Path>>(self, file: P) -> TlsConfig {
+        let mut this = self;
+        this.cert_key_file = Some(file.as_ref().to_path_buf());
+        this
+    }
+
+    /// PEM file containing the trusted CA certificates, which are used to validate the peer
+    pub fn ca_file(&self) -> Option<&Path> {
+        self.ca_file.as_ref().map(PathBuf::as_path)
+    }
+
+    /// Sets the PEM file containing the trusted CA certificates, which are used to validate the peer
+    pub fn set_ca_file<P: AsRef<Path>>(self, file: P) -> TlsConfig {
+        let mut this = self;
+        this.ca_file = Some(file.as_ref().to_path_buf());
+        this
+    }
+
+    /// Peer verification mode - if not set, then the nng default applies, i.e., servers do not verify
+    /// clients and clients require servers to be verified
+    pub fn verify_mode(&self) -> Option<TlsVerifyMode> {
+        self.verify_mode
+    }
+
+    /// Sets the peer verification mode
+    pub fn set_verify_mode(self, verify_mode: TlsVerifyMode) -> TlsConfig {
+        let mut this = self;
+        this.verify_mode = Some(verify_mode);
+        this
+    }
+
+    /// The name of the server, which is used to verify the server certificate
+    /// - only applies to dialers
+    /// - defaults to the URL host
+    pub fn server_name(&self) -> Option<&str> {
+        self.server_name.as_ref().map(String::as_str)
+    }
+
+    /// Sets the name of the server, which is used to verify the server certificate
+    pub fn set_server_name<Name: AsRef<str>>(self, server_name: Name) -> TlsConfig {
+        let mut this = self;
+        this.server_name = Some(server_name.as_ref().to_string());
+        this
+    }
+}
+
+/// TLS peer verification mode
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum TlsVerifyMode {
+    /// No authentication of the peer is performed
+    None,
+    /// The peer certificate is verified if it is presented
+    Optional,
+    /// The peer must present a valid certificate
+    Required,
+}
+
+impl TlsVerifyMode {
+    /// maps to nng's `nng_tls_auth_mode`
+    fn nng_auth_mode(self) -> i32 {
+        match self {
+            TlsVerifyMode::None => 0,
+            TlsVerifyMode::Optional => 1,
+            TlsVerifyMode::Required => 2,
+        }
+    }
+}
+
+/// TLS config related errors
+#[derive(Debug, Fail)]
+pub enum TlsConfigError {
+    /// TLS config only applies to `tls+tcp` and `wss` URLs
+    #[fail(display = "TLS config requires a `tls+tcp` or `wss` URL: {}", _0)]
+    InvalidUrlScheme(url::Url),
+    /// File paths must be valid UTF-8
+    #[fail(display = "Invalid file path: {:?}", _0)]
+    InvalidPath(PathBuf),
+    /// Failed to set the CertKeyFile option
+    #[fail(display = "Failed to set the CertKeyFile option: {}", _0)]
+    CertKeyFile(#[cause] nng::Error),
+    /// Failed to set the CaFile option
+    #[fail(display = "Failed to set the CaFile option: {}", _0)]
+    CaFile(#[cause] nng::Error),
+    /// Failed to set the AuthMode option
+    #[fail(display = "Failed to set the AuthMode option: {}", _0)]
+    AuthMode(#[cause] nng::Error),
+    /// Failed to set the ServerName option
+    #[fail(display = "Failed to set the ServerName option: {}", _0)]
+    ServerName(#[cause] nng::Error),
+}
+
+#[allow(warnings)]
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::reqrep::{client::DialerConfig, server::ListenerConfig};
+    use crate::{bound_url, configure_logging};
+    use oysterpack_uid::ULID;
+    use std::fs;
+
+    /// Generated self-signed certificate files
+    pub(crate) struct TestCerts {
+        pub(crate) dir: PathBuf,
+        pub(crate) cert_key_file: PathBuf,
+        pub(crate) ca_file: PathBuf,
+    }
+
+    impl TestCerts {
+        /// generates a self-signed certificate for `localhost` and `127.0.0.1`
+        pub(crate) fn generate() -> TestCerts {
+            let subject_alt_names = &["localhost".to_string(), "127.0.0.1".to_string()];
+            let cert = rcgen::generate_simple_self_signed(subject_alt_names);
+            let cert_pem = cert.serialize_pem();
+            let key_pem = cert.serialize_private_key_pem();
+
+            let dir = std::env::temp_dir().join(ULID::generate().to_string());
+            fs::create_dir_all(&dir).unwrap();
+            let cert_key_file = dir.join("cert_key.pem");
+            fs::write(&cert_key_file, format!("{}{}", cert_pem, key_pem)).unwrap();
+            let ca_file = dir.join("ca.pem");
+            fs::write(&ca_file, cert_pem).unwrap();
+            TestCerts {
+                dir,
+                cert_key_file,
+                ca_file,
+            }
+        }
+
+        pub(crate) fn server_tls_config(&self) -> TlsConfig {
+            TlsConfig::new().set_cert_key_file(&self.cert_key_file)
+        }
+
+        pub(crate) fn client_tls_config(&self) -> TlsConfig {
+            TlsConfig::new()
+                .set_ca_file(&self.ca_file)
+                .set_server_name("localhost")
+                .set_verify_mode(TlsVerifyMode::Required)
+        }
+    }
+
+    impl Drop for TestCerts {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    /// returns a `tls+tcp` URL using port 0, i.e., the OS assigns the port when the listener binds
+    /// - use [bound_url()](../../fn.bound_url.html) to get the URL that the client should dial
+    pub(crate) fn tls_url() -> url::Url {
+        url::Url::parse("tls+tcp://127.0.0.1:0").unwrap()
+    }
+
+    #[test]
+    fn tls_reqrep() {
+        configure_logging();
+
+        let certs = TestCerts::generate();
+        let url = tls_url();
+
+        let server = nng::Socket::new(nng::Protocol::Rep0).unwrap();
+        let listener = ListenerConfig::new(url.clone())
+            .set_non_blocking(false)
+            .set_tls(certs.server_tls_config())
+            .start_listener(&server)
+            .unwrap();
+        let url = bound_url(&listener, &url);
+
+        let client = nng::Socket::new(nng::Protocol::Req0).unwrap();
+        let _dialer = DialerConfig::new(url.clone())
+            .set_tls(certs.client_tls_config())
+            .start_dialer(&client)
+            .unwrap();
+
+        let mut req = nng::Message::new().unwrap();
+        req.push_back(b"ping").unwrap();
+        client.send(req).unwrap();
+        let req = server.recv().unwrap();
+        assert_eq!(&req[..], b"ping");
+        server.send(req).unwrap();
+        let rep = client.recv().unwrap();
+        assert_eq!(&rep[..], b"ping");
+    }
+
+    #[test]
+    fn tls_client_rejects_untrusted_server() {
+        configure_logging();
+
+        let server_certs = TestCerts::generate();
+        let client_certs = TestCerts::generate();
+        let url = tls_url();
+
+        let server = nng::Socket::new(nng::Protocol::Rep0).unwrap();
+        let listener = ListenerConfig::new(url.clone())
+            .set_non_blocking(false)
+            .set_tls(server_certs.server_tls_config())
+            .start_listener(&server)
+            .unwrap();
+        let url = bound_url(&listener, &url);
+
+        // GIVEN: the client trusts a different CA
+        let client = nng::Socket::new(nng::Protocol::Req0).unwrap();
+        let dialer_options = nng::DialerOptions::new(&client, url.as_str()).unwrap();
+        client_certs
+            .client_tls_config()
+            .apply_to_dialer(&url, &dialer_options)
+            .unwrap();
+
+        // WHEN: the client dials the server synchronously
+        // THEN: the TLS handshake fails because the server certificate could not be verified
+        match dialer_options.start(false) {
+            Err((_, nng::Error::PeerAuth)) => (),
+            Err((_, err)) => panic!("expected PeerAuth error: {}", err),
+            Ok(_) => panic!("the client should not have connected to an untrusted server"),
+        }
+    }
+
+    #[test]
+    fn tls_config_requires_tls_url() {
+        let certs = TestCerts::generate();
+        let url = url::Url::parse("tcp://127.0.0.1:5555").unwrap();
+        let server = nng::Socket::new(nng::Protocol::Rep0).unwrap();
+        match ListenerConfig::new(url)
+            .set_tls(certs.server_tls_config())
+            .start_listener(&server)
+        {
+            Err(crate::reqrep::server::ListenerConfigError::Tls(
+                TlsConfigError::InvalidUrlScheme(_),
+            )) => (),
+            other => panic!("expected InvalidUrlScheme error: {:?}", other.map(|_| ())),
+        }
+    }
+}
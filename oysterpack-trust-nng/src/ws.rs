@@ -0,0 +1,291 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! WebSocket transport configuration, which applies to `ws://` and `wss://` URLs.
+//!
+//! The WebSocket transport enables browser and external clients, which can't speak raw nng TCP, to
+//! connect to nng based services unchanged.
+//! - refer to the nng [WebSocket transport](https://nanomsg.github.io/nng/man/v1.1.0/nng_ws.7.html) for details
+//! - the HTTP path is specified by the URL, e.g., `ws://0.0.0.0:8080/api/echo`
+//!   - multiple services can share the same host and port, as long as they are bound to different paths
+//!   - if the URL has no path, then the path defaults to `/`
+//! - headers are applied as HTTP response headers by listeners, and as HTTP request headers by dialers
+//! - `wss://` URLs are secured via [TlsConfig](../tls/struct.TlsConfig.html), which requires the `tls` feature
+//!
+//! ## Origin Checking
+//! The same-origin policy does not apply to WebSockets, i.e., CORS response headers, such as
+//! `Access-Control-Allow-Origin`, have no effect. Browsers send the page's `Origin` header with the
+//! WebSocket upgrade request, but the nng WebSocket listener does not check it. Thus, a web page
+//! from any origin can connect to the listener. If access needs to be restricted, then:
+//! - put the listener behind a reverse proxy that checks the `Origin` header, or
+//! - use a [ConnectionFilter](../pipe/trait.ConnectionFilter.html) to allow connections by IP address,
+//!   and/or require client certificates via `wss://`
+//!
+//! ## Web Clients
+//! Web clients need to speak the [SP over WebSocket](https://github.com/nanomsg/nanomsg/blob/master/rfc/sp-websocket-mapping-01.txt)
+//! mapping:
+//! - the WebSocket sub-protocol is `<server protocol>.sp.nanomsg.org`, e.g., `rep.sp.nanomsg.org`
+//!   for a ReqRep server
+//! - each message is sent as a binary frame
+//! - ReqRep requests are prefixed with a 4 byte big endian request ID, which has the high bit set.
+//!   The reply is prefixed with the same request ID.
+//!
+//! See `examples/ws_reqrep_server.rs` and `examples/ws_reqrep_client.html`.
+//!
+//! ## Example
+//! ```no_run
+//! # use oysterpack_trust_nng::{reqrep::server::ListenerConfig, ws::*};
+//! let url = url::Url::parse("ws://0.0.0.0:8080/api/echo").unwrap();
+//! let listener_config = ListenerConfig::new(url)
+//!     .set_ws(WsConfig::new().add_header("X-Service-Name", "echo"));
+//! ```
+
+use failure::Fail;
+use nng::options::{
+    transport::websocket::{RequestHeaders, ResponseHeaders},
+    Options,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The WebSocket URL scheme
+pub const WS_URL_SCHEME: &str = "ws";
+
+/// The secure WebSocket URL scheme
+pub const WSS_URL_SCHEME: &str = "wss";
+
+/// Returns true if the URL scheme is `ws` or `wss`
+pub fn is_ws_url(url: &url::Url) -> bool {
+    url.scheme() == WS_URL_SCHEME || url.scheme() == WSS_URL_SCHEME
+}
+
+/// WebSocket config
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, Default)]
+pub struct WsConfig {
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+}
+
+impl WsConfig {
+    /// constructor
+    pub fn new() -> WsConfig {
+        WsConfig::default()
+    }
+
+    /// Applies the WebSocket config to the listener, i.e., the headers are sent as HTTP response headers
+    pub(crate) fn apply_to_listener(
+        &self,
+        url: &url::Url,
+        options: &nng::ListenerOptions,
+    ) -> Result<(), WsConfigError> {
+        Self::check_url(url)?;
+        if let Some(headers) = self.headers_string()? {
+            options
+                .set_opt::<ResponseHeaders>(headers)
+                .map_err(WsConfigError::ResponseHeaders)?;
+        }
+        Ok(())
+    }
+
+    /// Applies the WebSocket config to the dialer, i.e., the headers are sent as HTTP request headers
+    pub(crate) fn apply_to_dialer(
+        &self,
+        url: &url::Url,
+        options: &nng::DialerOptions,
+    ) -> Result<(), WsConfigError> {
+        Self::check_url(url)?;
+        if let Some(headers) = self.headers_string()? {
+            options
+                .set_opt::<RequestHeaders>(headers)
+                .map_err(WsConfigError::RequestHeaders)?;
+        }
+        Ok(())
+    }
+
+    fn check_url(url: &url::Url) -> Result<(), WsConfigError> {
+        if is_ws_url(url) {
+            Ok(())
+        } else {
+            Err(WsConfigError::InvalidUrlScheme(url.clone()))
+        }
+    }
+
+    /// formats the headers as expected by nng, i.e., `name: value` lines separated by CRLF
+    fn headers_string(&self) -> Result<Option<String>, WsConfigError> {
+        if self.headers.is_empty() {
+            return Ok(None);
+        }
+        let mut headers = String::new();
+        for (name, value) in self.headers.iter() {
+            let valid_name = !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c));
+            let valid_value = !value.contains(|c| c == '\r' || c == '\n');
+            if !(valid_name && valid_value) {
+                return Err(WsConfigError::InvalidHeader(name.clone()));
+            }
+            headers.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        Ok(Some(headers))
+    }
+
+    /// HTTP headers
+    pub fn headers(&self) -> &BTreeMap<String, String> {
+        &self.headers
+    }
+
+    /// Adds an HTTP header - if the header already exists, then its value is replaced
+    pub fn add_header<Name: AsRef<str>, Value: AsRef<str>>(
+        self,
+        name: Name,
+        value: Value,
+    ) -> WsConfig {
+        let mut this = self;
+        this.headers
+            .insert(name.as_ref().to_string(), value.as_ref().to_string());
+        this
+    }
+}
+
+/// WebSocket config related errors
+#[derive(Debug, Fail)]
+pub enum WsConfigError {
+    /// WebSocket config only applies to `ws` and `wss` URLs
+    #[fail(display = "WebSocket config requires a `ws` or `wss` URL: {}", _0)]
+    InvalidUrlScheme(url::Url),
+    /// Header names must be valid HTTP tokens, and header values must not contain CR or LF
+    #[fail(display = "Invalid HTTP header: {}", _0)]
+    InvalidHeader(String),
+    /// Failed to set the RequestHeaders option
+    #[fail(display = "Failed to set the RequestHeaders option: {}", _0)]
+    RequestHeaders(#[cause] nng::Error),
+    /// Failed to set the ResponseHeaders option
+    #[fail(display = "Failed to set the ResponseHeaders option: {}", _0)]
+    ResponseHeaders(#[cause] nng::Error),
+}
+
+#[allow(warnings)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reqrep::{
+        client::DialerConfig,
+        server::{ListenerConfig, ListenerConfigError},
+    };
+    use crate::{bound_url, configure_logging};
+    #[cfg(feature = "tls")]
+    use crate::tls::tests::TestCerts;
+    use oysterpack_uid::ULID;
+
+    /// returns a WebSocket URL using port 0, i.e., the OS picks a free port when the listener is
+    /// started - see `crate::bound_url()`
+    fn ws_url(scheme: &str, path: &str) -> url::Url {
+        url::Url::parse(&format!("{}://127.0.0.1:0{}", scheme, path)).unwrap()
+    }
+
+    fn send_recv(client: &nng::Socket, server: &nng::Socket) {
+        let mut req = nng::Message::new().unwrap();
+        req.push_back(b"ping").unwrap();
+        client.send(req).unwrap();
+        let req = server.recv().unwrap();
+        assert_eq!(&req[..], b"ping");
+        server.send(req).unwrap();
+        let rep = client.recv().unwrap();
+        assert_eq!(&rep[..], b"ping");
+    }
+
+    #[test]
+    fn ws_reqrep() {
+        configure_logging();
+
+        // GIVEN: a server that is listening on a ws URL with a path
+        let url = ws_url(WS_URL_SCHEME, "/api/echo");
+        let server = nng::Socket::new(nng::Protocol::Rep0).unwrap();
+        let listener = ListenerConfig::new(url.clone())
+            .set_non_blocking(false)
+            .set_ws(WsConfig::new().add_header("X-Service-Name", "echo"))
+            .start_listener(&server)
+            .unwrap();
+        let url = bound_url(&listener, &url);
+
+        // WHEN: a client dials the server
+        let client = nng::Socket::new(nng::Protocol::Req0).unwrap();
+        let _dialer = DialerConfig::new(url.clone())
+            .set_ws(WsConfig::new().add_header("X-Client-Id", ULID::generate().to_string()))
+            .start_dialer(&client)
+            .unwrap();
+
+        // THEN: request/reply messaging works over the WebSocket transport
+        send_recv(&client, &server);
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn wss_reqrep() {
+        configure_logging();
+
+        // GIVEN: a server that is listening on a wss URL
+        let certs = TestCerts::generate();
+        let url = ws_url(WSS_URL_SCHEME, "/api/echo");
+        let server = nng::Socket::new(nng::Protocol::Rep0).unwrap();
+        let listener = ListenerConfig::new(url.clone())
+            .set_non_blocking(false)
+            .set_tls(certs.server_tls_config())
+            .start_listener(&server)
+            .unwrap();
+        let url = bound_url(&listener, &url);
+
+        // WHEN: a client dials the server using TLS
+        let client = nng::Socket::new(nng::Protocol::Req0).unwrap();
+        let _dialer = DialerConfig::new(url.clone())
+            .set_tls(certs.client_tls_config())
+            .start_dialer(&client)
+            .unwrap();
+
+        // THEN: request/reply messaging works over the secure WebSocket transport
+        send_recv(&client, &server);
+    }
+
+    #[test]
+    fn ws_config_errors() {
+        let server = nng::Socket::new(nng::Protocol::Rep0).unwrap();
+
+        // WHEN: the WebSocket config is applied to a non ws URL
+        let url = url::Url::parse("tcp://127.0.0.1:5555").unwrap();
+        match ListenerConfig::new(url)
+            .set_ws(WsConfig::new())
+            .start_listener(&server)
+        {
+            // THEN: the listener fails to start
+            Err(ListenerConfigError::Ws(WsConfigError::InvalidUrlScheme(_))) => (),
+            other => panic!("expected InvalidUrlScheme error: {:?}", other.map(|_| ())),
+        }
+
+        // WHEN: a header value contains a line break
+        let url = ws_url(WS_URL_SCHEME, "/");
+        match ListenerConfig::new(url)
+            .set_ws(WsConfig::new().add_header("X-Injected", "a\r\nb: c"))
+            .start_listener(&server)
+        {
+            // THEN: the listener fails to start
+            Err(ListenerConfigError::Ws(WsConfigError::InvalidHeader(name))) => {
+                assert_eq!(name, "X-Injected")
+            }
+            other => panic!("expected InvalidHeader error: {:?}", other.map(|_| ())),
+        }
+    }
+}
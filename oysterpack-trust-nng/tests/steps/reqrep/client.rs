@@ -14,21 +14,28 @@
  *    limitations under the License.
  */
 
+use futures::prelude::*;
 use oysterpack_trust::{
     concurrent::{
         execution::global_executor,
-        messaging::reqrep::{ReqRepConfig, ReqRepId},
+        messaging::reqrep::{self, Processor, ReqRepConfig, ReqRepId},
     },
     metrics,
 };
-use oysterpack_trust_nng::reqrep::client::{self, Client, ClientRegistrationError};
+use oysterpack_trust_nng::{
+    reqrep::client::{self, Client, ClientRegistrationError},
+    testing::{TestHarness, Transport},
+};
 use std::time::Duration;
-use url::Url;
 
 pub mod registry;
 
-fn server_url(reqrep_id: ReqRepId) -> Url {
-    Url::parse(format!("inproc://{}", reqrep_id).as_str()).unwrap()
+struct EchoService;
+
+impl Processor<nng::Message, nng::Message> for EchoService {
+    fn process(&mut self, req: nng::Message) -> reqrep::FutureReply<nng::Message> {
+        async move { req }.boxed()
+    }
 }
 
 fn timer_buckets() -> Vec<f64> {
@@ -47,26 +54,29 @@ fn timer_buckets() -> Vec<f64> {
     .unwrap()
 }
 
-fn register_basic_client(reqrep_id: ReqRepId) -> Client {
-    client::register_client(
-        ReqRepConfig::new(reqrep_id, timer_buckets()),
-        None,
-        client::DialerConfig::new(server_url(reqrep_id)),
-        global_executor(),
-    )
-    .unwrap()
+/// spawns a server and registers a client that dials the server
+fn spawn_harness() -> TestHarness {
+    TestHarness::spawn(Transport::Inproc, EchoService).unwrap()
 }
 
-fn try_register_basic_client(reqrep_id: ReqRepId) -> Result<Client, ClientRegistrationError> {
+/// tries to register another client using the harness' ReqRepId
+fn try_register_client(harness: &TestHarness) -> Result<Client, ClientRegistrationError> {
     client::register_client(
-        ReqRepConfig::new(reqrep_id, timer_buckets()),
+        ReqRepConfig::new(harness.reqrep_id(), timer_buckets()),
         None,
-        client::DialerConfig::new(server_url(reqrep_id)),
+        client::DialerConfig::new(harness.url().clone()),
         global_executor(),
     )
 }
 
+/// the harness is torn down when the World is dropped, i.e., when the scenario is done
 #[derive(Default)]
 pub struct World {
-    reqrep_id: Option<ReqRepId>,
+    harness: Option<TestHarness>,
+}
+
+impl World {
+    fn reqrep_id(&self) -> Option<ReqRepId> {
+        self.harness.as_ref().map(TestHarness::reqrep_id)
+    }
 }
@@ -16,8 +16,7 @@
 
 use cucumber_rust::*;
 
-use crate::steps::reqrep::client::{register_basic_client, try_register_basic_client, World};
-use oysterpack_trust::concurrent::messaging::reqrep::ReqRepId;
+use crate::steps::reqrep::client::{spawn_harness, try_register_client, World};
 use oysterpack_trust_nng::reqrep::client as nng_client;
 use oysterpack_trust_nng::reqrep::client::ClientRegistrationError;
 
@@ -26,14 +25,13 @@ steps!(World => {
 
     // Scenario: [01D5J1HJMGKN7AF39DPP4TBYRE] Register a ReqRep service using a unique ReqRepId
     then regex "01D5J1HJMGKN7AF39DPP4TBYRE" | world, _matches, _step | {
-        let reqrep_id = ReqRepId::generate();
-        world.reqrep_id = Some(reqrep_id);
-        let client = register_basic_client(reqrep_id);
-        assert_eq!(client.id(), reqrep_id);
+        let harness = spawn_harness();
+        assert_eq!(harness.client().id(), harness.reqrep_id());
+        world.harness = Some(harness);
     };
 
     then regex "01D5J1HJMGKN7AF39DPP4TBYRE" | world, _matches, _step | {
-        world.reqrep_id.iter().cloned().for_each(|id| {
+        world.reqrep_id().iter().cloned().for_each(|id| {
             let client = nng_client::client(id).unwrap();
             assert_eq!(client.id(), id);
         })
@@ -41,17 +39,16 @@ steps!(World => {
 
     // Scenario: [01D5J244J52Y4A7WGZ67ZNP0RS] Try to register 2 ReqRep services using the same ReqRepId
     given regex "01D5J244J52Y4A7WGZ67ZNP0RS" | world, _matches, _step | {
-        let reqrep_id = ReqRepId::generate();
-        world.reqrep_id = Some(reqrep_id);
-        let client = register_basic_client(reqrep_id);
-        assert_eq!(client.id(), reqrep_id);
+        let harness = spawn_harness();
+        assert_eq!(harness.client().id(), harness.reqrep_id());
+        world.harness = Some(harness);
     };
 
     then regex "01D5J244J52Y4A7WGZ67ZNP0RS" | world, _matches, _step | {
-        world.reqrep_id.iter().cloned().for_each(|id| {
-            match try_register_basic_client(id) {
+        world.harness.iter().for_each(|harness| {
+            match try_register_client(harness) {
                 Ok(_) => panic!("Should have failed to register"),
-                Err(ClientRegistrationError::ClientAlreadyRegistered(reqrep_id)) => assert_eq!(reqrep_id, id),
+                Err(ClientRegistrationError::ClientAlreadyRegistered(reqrep_id)) => assert_eq!(reqrep_id, harness.reqrep_id()),
                 Err(err) => panic!(format!("Failed with unexpected error: {}", err))
             }
         })
@@ -0,0 +1,19 @@
+Feature: [01M57E7QMQCB9XS38YEV5XJ8NT] Gathered metrics can be captured as snapshots, which can be diffed
+
+  - MetricRegistry::snapshot() gathers all metrics into a timestamped snapshot
+  - snapshots are serializable
+  - diffing 2 snapshots computes counter and histogram deltas and per second rates
+    - gauges and summaries are not included in the diff
+    - if a counter or histogram was reset in between the snapshots, then the current value is used as the delta
+  - ProcessMetrics can be diffed in the same way
+
+  Scenario: [01M57JMVQTC8FQGR33WG3K9BWW] Diffing 2 snapshots
+    Given [01M57JMVQTC8FQGR33WG3K9BWW] a snapshot is taken
+    When [01M57JMVQTC8FQGR33WG3K9BWW] a counter and histogram are updated and another snapshot is taken
+    Then [01M57JMVQTC8FQGR33WG3K9BWW] the diff contains the counter and histogram deltas
+
+  Scenario: [01M57JMVQWKT8DCZRETV53MR3N] Computing per second rates from 2 snapshots
+    Then [01M57JMVQWKT8DCZRETV53MR3N] the rates are the deltas divided by the elapsed time between the snapshots
+
+  Scenario: [01M57JMVQYHE9X2TYZXK527C5C] Diffing ProcessMetrics
+    Then [01M57JMVQYHE9X2TYZXK527C5C] the CPU and gauge levels are diffed while max fds and start time are reported as is
@@ -0,0 +1,14 @@
+Feature: [01M57EQ9A41APFMJXPND57KAV1] On Linux, a runtime metrics collector is automatically registered
+
+  - reports `/proc` based thread metrics per thread group
+    - thread count, CPU seconds, context switches, I/O bytes, and page faults
+  - Executor threads are grouped by ExecutorId
+  - threads that do not belong to a registered thread group are grouped under "other"
+
+  Scenario: [01M57JMVR0A60F2QE3SV27WA0F] Executor threads are reported under the ExecutorId thread group
+    Given [01M57JMVR0A60F2QE3SV27WA0F] an Executor is registered
+    When [01M57JMVR0A60F2QE3SV27WA0F] the runtime metrics are gathered
+    Then [01M57JMVR0A60F2QE3SV27WA0F] the Executor threads are counted under the ExecutorId thread group
+
+  Scenario: [01M57JMVR22E1RQNPNA3MZXZPB] Threads that do not belong to a registered thread group
+    Then [01M57JMVR22E1RQNPNA3MZXZPB] the threads are counted under the other thread group
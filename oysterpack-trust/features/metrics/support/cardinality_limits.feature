@@ -0,0 +1,17 @@
+Feature: [01M57ENQ5YCPJ23P02FN3FXCE3] Metric vectors can be guarded by cardinality limits
+
+  - per metric limits are configured via the *VecBuilder types
+  - guarded metric vectors are registered via MetricRegistry::register_guarded()
+  - the global limit is configured per registry via MetricRegistry::set_cardinality_limit()
+  - label sets that exceed the limit are mapped to the overflow series, counted, and logged
+
+  Scenario: [01M57JMVRD7C5MQR4XYRVTCCXC] The per metric cardinality limit is reached
+    Given [01M57JMVRD7C5MQR4XYRVTCCXC] a guarded metric vector is registered with a cardinality limit of 2
+    When [01M57JMVRD7C5MQR4XYRVTCCXC] 3 label sets are used
+    Then [01M57JMVRD7C5MQR4XYRVTCCXC] the 3rd label set is mapped to the overflow series and counted as rejected
+
+  Scenario: [01M57JMVRFKEAYKGJDPRZM3KQG] A label set is removed from a guarded metric vector
+    Then [01M57JMVRFKEAYKGJDPRZM3KQG] capacity is freed up for a new label set
+
+  Scenario: [01M57JMVRJAQ6EPJ6JKNBRC8Z8] Guarded metric vector label sets count against the registry's global limit
+    Then [01M57JMVRJAQ6EPJ6JKNBRC8Z8] the registry cardinality reflects the guarded label sets
@@ -0,0 +1,13 @@
+Feature: [01M57EGQYJWRYGK5K2PW8NZVRQ] The registered metric descriptors can be exported as a catalog
+
+  - as JSON or Markdown
+  - as Prometheus recording rules that alias the ULID based metric names using names derived from the help text
+  - a catalog can be built from a Prometheus or OpenMetrics text exposition
+
+  Scenario: [01M57JMVR97GNPYJD782CAN73W] Exporting the metric registry catalog
+    Given [01M57JMVR97GNPYJD782CAN73W] a metric is registered
+    When [01M57JMVR97GNPYJD782CAN73W] the catalog is retrieved from the metric registry
+    Then [01M57JMVR97GNPYJD782CAN73W] the catalog contains the metric descriptor, which can be rendered as JSON, Markdown, and recording rules
+
+  Scenario: [01M57JMVRBC3QN6N4B9FH8P82R] Building the catalog from an OpenMetrics text exposition
+    Then [01M57JMVRBC3QN6N4B9FH8P82R] the counter samples are mapped back to their metric family
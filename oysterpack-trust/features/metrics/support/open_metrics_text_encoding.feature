@@ -0,0 +1,12 @@
+Feature: [01M57EDWN5W3H9JSYRB3ZX3TGC] Text encoding metrics in the OpenMetrics 1.0 format
+
+  - the text format is negotiated via the HTTP Accept header
+  - exemplars carrying a ULID trace id can be recorded for counters and histograms
+
+  Scenario: [01M57JMVR5BVYW2Y070520ZDHX] Encoding metrics without an Accept header
+    Then [01M57JMVR5BVYW2Y070520ZDHX] the metrics are encoded using the prometheus text format
+
+  Scenario: [01M57JMVR7CJGN7P0B5KRMATHT] Encoding metrics using the OpenMetrics Accept header
+    Given [01M57JMVR7CJGN7P0B5KRMATHT] an exemplar is recorded for a counter
+    When [01M57JMVR7CJGN7P0B5KRMATHT] metrics are encoded using the OpenMetrics Accept header
+    Then [01M57JMVR7CJGN7P0B5KRMATHT] the metrics are encoded using the OpenMetrics text format with the exemplar
@@ -209,8 +209,11 @@ impl ExecutorRegistry {
 impl Default for ExecutorRegistry {
     fn default() -> Self {
         fn default_executor() -> Executor {
-            let mut builder = ExecutorBuilder::new(Executor::GLOBAL_EXECUTOR_ID).builder();
-            Executor::new(Executor::GLOBAL_EXECUTOR_ID, &mut builder, None).unwrap()
+            let executor_builder = ExecutorBuilder::new(Executor::GLOBAL_EXECUTOR_ID);
+            let mut builder = executor_builder.builder();
+            let executor = Executor::new(Executor::GLOBAL_EXECUTOR_ID, &mut builder, None).unwrap();
+            executor_builder.register_thread_group();
+            executor
         }
 
         Self {
@@ -458,12 +461,13 @@ impl ExecutorBuilder {
     }
 
     fn builder(&self) -> ThreadPoolBuilder {
+        let thread_name_prefix = format!("{}-", self.id);
         let executor_thread_gauge_after_start =
             metrics::THREAD_POOL_SIZE_GAUGE.with_label_values(&[self.id.to_string().as_str()]);
         let executor_thread_gauge_before_stop = executor_thread_gauge_after_start.clone();
         let mut builder = ThreadPool::builder();
         builder
-            .name_prefix(format!("{}-", self.id))
+            .name_prefix(thread_name_prefix)
             .after_start(move |thread_index| {
                 executor_thread_gauge_after_start.inc();
                 debug!(
@@ -495,11 +499,21 @@ impl ExecutorBuilder {
     /// the life of the app.
     pub fn register(self) -> Result<Executor, ExecutorRegistryError> {
         let mut threadpool_builder = self.builder();
-        EXECUTOR_REGISTRY.register(
+        let executor = EXECUTOR_REGISTRY.register(
             self.id,
             &mut threadpool_builder,
             self.stack_size.as_ref().map(|size| size.get()),
-        )
+        )?;
+        self.register_thread_group();
+        Ok(executor)
+    }
+
+    /// The Executor threads are reported by the runtime metrics collector under the ExecutorId
+    fn register_thread_group(&self) {
+        crate::metrics::runtime::register_thread_group(
+            format!("{}-", self.id).as_str(),
+            self.id.to_string().as_str(),
+        );
     }
 }
 
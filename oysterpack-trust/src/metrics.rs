@@ -52,6 +52,13 @@
 //!   - [MetricRegistry::gather_for_metric_ids()](struct.MetricRegistry.html#method.gather_for_metric_ids)
 //!   - [MetricRegistry::gather_for_labels()](struct.MetricRegistry.html#method.gather_for_labels)
 //!   - [MetricRegistry::gather_process_metrics()](struct.MetricRegistry.html#method.gather_process_metrics)
+//! - *[01M57EQ9A41APFMJXPND57KAV1]* On Linux, a [runtime](runtime/index.html) metrics collector is automatically registered,
+//!   which reports `/proc` based thread metrics per thread group, e.g., per Executor
+//!   - thread count, CPU seconds, context switches, I/O bytes, and page faults
+//! - *[01M57E7QMQCB9XS38YEV5XJ8NT]* Gathered metrics can be captured as [snapshots](struct.MetricsSnapshot.html),
+//!   which can be diffed to compute counter and histogram deltas and per second rates
+//!   - [MetricRegistry::snapshot()](struct.MetricRegistry.html#method.snapshot)
+//!   - [ProcessMetrics](struct.ProcessMetrics.html) can be diffed in the same way
 //!
 //! ## Metric Collector Features
 //! - *[01D3JAHR4Z02XTJGTNE4D63VRT]* Any `prometheus::core::Collector` can be registered
@@ -89,6 +96,18 @@
 //!     - [HistogramBuilder](struct.HistogramBuilder.html)
 //!     - [HistogramVecBuilder](struct.HistogramVecBuilder.html)
 //! - *[01D3M9X86BSYWW3132JQHWA3AT]* Text encoding metrics in a prometheus compatible format
+//! - *[01M57EDWN5W3H9JSYRB3ZX3TGC]* Text encoding metrics in the [OpenMetrics](openmetrics/index.html) 1.0 format
+//!   - exemplars carrying a ULID trace id can be recorded for counters and histograms via the
+//!     registry's [ExemplarStore](struct.MetricRegistry.html#method.exemplars)
+//!   - the text format can be negotiated via the HTTP `Accept` header - see [MetricRegistry::encode_metrics()](struct.MetricRegistry.html#method.encode_metrics)
+//! - *[01M57ENQ5YCPJ23P02FN3FXCE3]* Metric vectors can be guarded by [cardinality](cardinality/index.html) limits
+//!   - per metric limits are configured via the `*VecBuilder` types, e.g., [IntCounterVecBuilder::with_cardinality_limit()](struct.IntCounterVecBuilder.html#method.with_cardinality_limit)
+//!   - guarded metric vectors are registered via [MetricRegistry::register_guarded()](struct.MetricRegistry.html#method.register_guarded)
+//!   - the global limit is configured per registry via [MetricRegistry::set_cardinality_limit()](struct.MetricRegistry.html#method.set_cardinality_limit)
+//!   - label sets that exceed the limit are mapped to the overflow series, counted, and logged
+//! - *[01M57EGQYJWRYGK5K2PW8NZVRQ]* The registered metric descriptors can be exported as a [catalog](catalog/index.html)
+//!   - as JSON or Markdown
+//!   - as Prometheus recording rules that alias the ULID based metric names using names derived from the help text
 //! - *[01D3XX3ZBB7VW0GGRA60PMFC1M]* Time conversion functions to report timings in seconds as f64
 //!   - in prometheus, it is a common practice to report timer metrics in secs
 //!     - [nanos_as_secs_f64](fn.nanos_as_secs_f64.html)
@@ -112,7 +131,7 @@ use parking_lot::RwLock;
 use prometheus::{core::Collector, Encoder};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap, HashSet},
     fmt,
     hash::{BuildHasher, BuildHasherDefault},
     io::Write,
@@ -121,9 +140,14 @@ use std::{
     num::NonZeroUsize,
     str::FromStr,
     sync::Arc,
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 
+pub mod cardinality;
+pub mod catalog;
+pub mod openmetrics;
+pub mod runtime;
+
 lazy_static! {
     /// Global metrics registry
     static ref METRIC_REGISTRY: MetricRegistry = MetricRegistry::default();
@@ -222,6 +246,8 @@ pub struct CounterVecBuilder {
     help: String,
     variable_label_ids: Vec<LabelId>,
     const_labels: Option<HashMap<LabelId, String>>,
+    #[serde(default)]
+    cardinality_limit: Option<usize>,
 }
 
 impl CounterVecBuilder {
@@ -236,6 +262,7 @@ impl CounterVecBuilder {
             help: help.as_ref().to_string(),
             variable_label_ids,
             const_labels: None,
+            cardinality_limit: None,
         }
     }
 
@@ -247,6 +274,13 @@ impl CounterVecBuilder {
         self
     }
 
+    /// Sets the max number of label sets that the guarded metric vector will track
+    /// - defaults to [DEFAULT_CARDINALITY_LIMIT](cardinality/constant.DEFAULT_CARDINALITY_LIMIT.html)
+    pub fn with_cardinality_limit(mut self, limit: usize) -> Self {
+        self.cardinality_limit = Some(limit);
+        self
+    }
+
     /// build the new Counter
     pub fn build(mut self) -> prometheus::Result<prometheus::CounterVec> {
         match self.const_labels.take() {
@@ -266,6 +300,19 @@ impl CounterVecBuilder {
     }
 }
 
+impl cardinality::GuardedMetricVecBuilder for CounterVecBuilder {
+    type Vec = prometheus::CounterVec;
+
+    fn cardinality_limit(&self) -> usize {
+        self.cardinality_limit
+            .unwrap_or(cardinality::DEFAULT_CARDINALITY_LIMIT)
+    }
+
+    fn build_metric_vec(self) -> prometheus::Result<prometheus::CounterVec> {
+        self.build()
+    }
+}
+
 /// IntCounterVec builder
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IntCounterVecBuilder {
@@ -273,6 +320,8 @@ pub struct IntCounterVecBuilder {
     help: String,
     variable_label_ids: Vec<LabelId>,
     const_labels: Option<HashMap<LabelId, String>>,
+    #[serde(default)]
+    cardinality_limit: Option<usize>,
 }
 
 impl IntCounterVecBuilder {
@@ -287,6 +336,7 @@ impl IntCounterVecBuilder {
             help: help.as_ref().to_string(),
             variable_label_ids,
             const_labels: None,
+            cardinality_limit: None,
         }
     }
 
@@ -298,6 +348,13 @@ impl IntCounterVecBuilder {
         self
     }
 
+    /// Sets the max number of label sets that the guarded metric vector will track
+    /// - defaults to [DEFAULT_CARDINALITY_LIMIT](cardinality/constant.DEFAULT_CARDINALITY_LIMIT.html)
+    pub fn with_cardinality_limit(mut self, limit: usize) -> Self {
+        self.cardinality_limit = Some(limit);
+        self
+    }
+
     /// build the new Counter
     pub fn build(mut self) -> prometheus::Result<prometheus::IntCounterVec> {
         match self.const_labels.take() {
@@ -317,6 +374,19 @@ impl IntCounterVecBuilder {
     }
 }
 
+impl cardinality::GuardedMetricVecBuilder for IntCounterVecBuilder {
+    type Vec = prometheus::IntCounterVec;
+
+    fn cardinality_limit(&self) -> usize {
+        self.cardinality_limit
+            .unwrap_or(cardinality::DEFAULT_CARDINALITY_LIMIT)
+    }
+
+    fn build_metric_vec(self) -> prometheus::Result<prometheus::IntCounterVec> {
+        self.build()
+    }
+}
+
 /// Gauge builder
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GaugeBuilder {
@@ -402,6 +472,8 @@ pub struct IntGaugeVecBuilder {
     help: String,
     variable_label_ids: Vec<LabelId>,
     const_labels: Option<HashMap<LabelId, String>>,
+    #[serde(default)]
+    cardinality_limit: Option<usize>,
 }
 
 impl IntGaugeVecBuilder {
@@ -416,6 +488,7 @@ impl IntGaugeVecBuilder {
             help: help.as_ref().to_string(),
             variable_label_ids,
             const_labels: None,
+            cardinality_limit: None,
         }
     }
 
@@ -427,6 +500,13 @@ impl IntGaugeVecBuilder {
         self
     }
 
+    /// Sets the max number of label sets that the guarded metric vector will track
+    /// - defaults to [DEFAULT_CARDINALITY_LIMIT](cardinality/constant.DEFAULT_CARDINALITY_LIMIT.html)
+    pub fn with_cardinality_limit(mut self, limit: usize) -> Self {
+        self.cardinality_limit = Some(limit);
+        self
+    }
+
     /// build the new Counter
     pub fn build(mut self) -> prometheus::Result<prometheus::IntGaugeVec> {
         match self.const_labels.take() {
@@ -446,6 +526,19 @@ impl IntGaugeVecBuilder {
     }
 }
 
+impl cardinality::GuardedMetricVecBuilder for IntGaugeVecBuilder {
+    type Vec = prometheus::IntGaugeVec;
+
+    fn cardinality_limit(&self) -> usize {
+        self.cardinality_limit
+            .unwrap_or(cardinality::DEFAULT_CARDINALITY_LIMIT)
+    }
+
+    fn build_metric_vec(self) -> prometheus::Result<prometheus::IntGaugeVec> {
+        self.build()
+    }
+}
+
 /// Counter builder
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GaugeVecBuilder {
@@ -453,6 +546,8 @@ pub struct GaugeVecBuilder {
     help: String,
     variable_label_ids: Vec<LabelId>,
     const_labels: Option<HashMap<LabelId, String>>,
+    #[serde(default)]
+    cardinality_limit: Option<usize>,
 }
 
 impl GaugeVecBuilder {
@@ -467,6 +562,7 @@ impl GaugeVecBuilder {
             help: help.as_ref().to_string(),
             variable_label_ids,
             const_labels: None,
+            cardinality_limit: None,
         }
     }
 
@@ -478,6 +574,13 @@ impl GaugeVecBuilder {
         self
     }
 
+    /// Sets the max number of label sets that the guarded metric vector will track
+    /// - defaults to [DEFAULT_CARDINALITY_LIMIT](cardinality/constant.DEFAULT_CARDINALITY_LIMIT.html)
+    pub fn with_cardinality_limit(mut self, limit: usize) -> Self {
+        self.cardinality_limit = Some(limit);
+        self
+    }
+
     /// build the new Counter
     pub fn build(mut self) -> prometheus::Result<prometheus::GaugeVec> {
         match self.const_labels.take() {
@@ -497,6 +600,19 @@ impl GaugeVecBuilder {
     }
 }
 
+impl cardinality::GuardedMetricVecBuilder for GaugeVecBuilder {
+    type Vec = prometheus::GaugeVec;
+
+    fn cardinality_limit(&self) -> usize {
+        self.cardinality_limit
+            .unwrap_or(cardinality::DEFAULT_CARDINALITY_LIMIT)
+    }
+
+    fn build_metric_vec(self) -> prometheus::Result<prometheus::GaugeVec> {
+        self.build()
+    }
+}
+
 /// Histogram builder
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HistogramBuilder {
@@ -552,6 +668,8 @@ pub struct HistogramVecBuilder {
     const_labels: Option<HashMap<LabelId, String>>,
     buckets: Vec<f64>,
     variable_label_ids: Vec<LabelId>,
+    #[serde(default)]
+    cardinality_limit: Option<usize>,
 }
 
 impl HistogramVecBuilder {
@@ -566,6 +684,7 @@ impl HistogramVecBuilder {
             metric_id,
             help: help.as_ref().to_string(),
             const_labels: None,
+            cardinality_limit: None,
             buckets,
             variable_label_ids,
         }
@@ -579,6 +698,13 @@ impl HistogramVecBuilder {
         self
     }
 
+    /// Sets the max number of label sets that the guarded metric vector will track
+    /// - defaults to [DEFAULT_CARDINALITY_LIMIT](cardinality/constant.DEFAULT_CARDINALITY_LIMIT.html)
+    pub fn with_cardinality_limit(mut self, limit: usize) -> Self {
+        self.cardinality_limit = Some(limit);
+        self
+    }
+
     /// build the new Counter
     pub fn build(mut self) -> prometheus::Result<prometheus::HistogramVec> {
         match self.const_labels.take() {
@@ -600,6 +726,19 @@ impl HistogramVecBuilder {
     }
 }
 
+impl cardinality::GuardedMetricVecBuilder for HistogramVecBuilder {
+    type Vec = prometheus::HistogramVec;
+
+    fn cardinality_limit(&self) -> usize {
+        self.cardinality_limit
+            .unwrap_or(cardinality::DEFAULT_CARDINALITY_LIMIT)
+    }
+
+    fn build_metric_vec(self) -> prometheus::Result<prometheus::HistogramVec> {
+        self.build()
+    }
+}
+
 /// IntCounter constructor using MetricId and LabelId
 fn new_int_counter<S: BuildHasher, Help: AsRef<str>>(
     metric_id: MetricId,
@@ -801,9 +940,12 @@ pub fn parse_desc_metric_id(desc: &prometheus::core::Desc) -> Option<MetricId> {
 }
 /// Metric Registry
 /// - process metrics collector is automatically added
+/// - on Linux, the [runtime](runtime/index.html) metrics collector is automatically added
 pub struct MetricRegistry {
     registry: prometheus::Registry,
     metric_collectors: RwLock<Vec<ArcCollector>>,
+    cardinality: Arc<cardinality::CardinalityBudget>,
+    exemplars: openmetrics::ExemplarStore,
 }
 
 impl MetricRegistry {
@@ -957,6 +1099,12 @@ impl MetricRegistry {
             .collect()
     }
 
+    /// Returns a catalog of the registered metric descriptors, which can be exported to help make
+    /// sense of the ULID based metric names
+    pub fn catalog(&self) -> catalog::MetricCatalog {
+        catalog::MetricCatalog::from_descs(&self.descs(), &self.gather())
+    }
+
     /// Returns descriptors for the specified MetricId(s)
     pub fn descs_for_metric_ids(&self, metric_ids: &[MetricId]) -> Vec<prometheus::core::Desc> {
         let metric_names = metric_ids
@@ -1310,6 +1458,65 @@ impl MetricRegistry {
         encoder.encode(&metric_families, writer)
     }
 
+    /// Encodes a snapshot of the current metrics using the [OpenMetrics](openmetrics/index.html) text format
+    /// - `_created` series are reported for metrics without variable labels using the time the
+    ///   metric collector was registered
+    /// - exemplars recorded via the registry's [exemplar store](#method.exemplars) are included for
+    ///   counters and histograms - exemplars for series that are no longer gathered are pruned
+    pub fn open_metrics_encode_metrics<W: Write>(&self, writer: &mut W) -> prometheus::Result<()> {
+        let metric_families = self.registry.gather();
+        self.exemplars.retain_series(&metric_families);
+        let encoder = openmetrics::OpenMetricsEncoder::new()
+            .with_created(self.series_created())
+            .with_exemplars(self.exemplars.clone());
+        encoder.encode(&metric_families, writer)
+    }
+
+    /// Returns the exemplar store that is used when encoding metrics in the OpenMetrics text format
+    /// - exemplars must be recorded for metrics that are registered with this registry
+    pub fn exemplars(&self) -> &openmetrics::ExemplarStore {
+        &self.exemplars
+    }
+
+    /// Encodes a snapshot of the current metrics using the text format negotiated via the HTTP
+    /// `Accept` header value.
+    /// - returns the text format that was used to encode the metrics, which the exporter should use
+    ///   to set the response `Content-Type`
+    /// - see [openmetrics::TextFormat::negotiate()](openmetrics/enum.TextFormat.html#method.negotiate)
+    pub fn encode_metrics<W: Write>(
+        &self,
+        accept: Option<&str>,
+        writer: &mut W,
+    ) -> prometheus::Result<openmetrics::TextFormat> {
+        let format = openmetrics::TextFormat::negotiate(accept);
+        match format {
+            openmetrics::TextFormat::Prometheus => self.text_encode_metrics(writer)?,
+            openmetrics::TextFormat::OpenMetrics => self.open_metrics_encode_metrics(writer)?,
+        }
+        Ok(format)
+    }
+
+    /// Returns when each series was created, for metrics without variable labels
+    /// - metric vector children are created on demand, i.e., their creation time is not known
+    fn series_created(&self) -> HashMap<openmetrics::SeriesKey, SystemTime> {
+        let metric_collectors = self.metric_collectors.read();
+        metric_collectors
+            .iter()
+            .fold(HashMap::new(), |mut created, collector| {
+                for desc in collector.desc() {
+                    if desc.variable_labels.is_empty() {
+                        created
+                            .entry(openmetrics::SeriesKey::new(
+                                &desc.fq_name,
+                                &desc.const_label_pairs,
+                            ))
+                            .or_insert_with(|| collector.registered_at());
+                    }
+                }
+                created
+            })
+    }
+
     /// gathers metrics from all registered metric collectors
     pub fn gather(&self) -> Vec<prometheus::proto::MetricFamily> {
         self.registry.gather()
@@ -1492,15 +1699,66 @@ impl MetricRegistry {
         ProcessMetrics::collect(&collectors[0])
     }
 
+    /// Builds a metric vector that is wrapped in a [CardinalityGuard](cardinality/struct.CardinalityGuard.html)
+    /// and registers it.
+    /// - the guard's label sets count against this registry's global cardinality limit
+    /// - rejected label sets are counted by a counter that is registered with this registry - see
+    ///   [CARDINALITY_REJECTED_COUNTER_METRIC_ID](cardinality/constant.CARDINALITY_REJECTED_COUNTER_METRIC_ID.html)
+    pub fn register_guarded<B: cardinality::GuardedMetricVecBuilder>(
+        &self,
+        builder: B,
+    ) -> prometheus::Result<cardinality::CardinalityGuard<B::Vec>> {
+        self.cardinality.register_rejected_counter(self)?;
+        let limit = builder.cardinality_limit();
+        let guard = cardinality::CardinalityGuard::new(
+            builder.build_metric_vec()?,
+            limit,
+            Arc::clone(&self.cardinality),
+        );
+        self.register(guard.clone())?;
+        Ok(guard)
+    }
+
+    /// Sets the global cardinality limit, i.e., the max number of label sets tracked across all
+    /// [guarded](cardinality/index.html) metric vectors that are registered with this registry
+    /// - lowering the limit does not evict label sets that are already being tracked
+    pub fn set_cardinality_limit(&self, limit: usize) {
+        self.cardinality.set_limit(limit);
+    }
+
+    /// Returns the global cardinality limit
+    pub fn cardinality_limit(&self) -> usize {
+        self.cardinality.limit()
+    }
+
+    /// Returns the number of label sets tracked across all [guarded](cardinality/index.html) metric
+    /// vectors that are registered with this registry
+    pub fn cardinality(&self) -> usize {
+        self.cardinality.count()
+    }
+
+    /// Gathers all metrics and returns them as a timestamped snapshot
+    /// - two snapshots can be diffed in order to compute deltas and rates - see [MetricsSnapshot](struct.MetricsSnapshot.html)
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot::new(self.gather())
+    }
+
     fn default() -> Self {
         let registry = Self {
             registry: prometheus::Registry::new(),
             metric_collectors: RwLock::new(Vec::new()),
+            cardinality: Arc::new(cardinality::CardinalityBudget::new(
+                cardinality::DEFAULT_GLOBAL_CARDINALITY_LIMIT,
+            )),
+            exemplars: openmetrics::ExemplarStore::default(),
         };
 
         registry
             .register(prometheus::process_collector::ProcessCollector::for_self())
             .unwrap();
+        if cfg!(target_os = "linux") {
+            registry.register(runtime::RuntimeCollector::new()).unwrap();
+        }
 
         registry
     }
@@ -1632,18 +1890,23 @@ pub fn nanos_as_secs_f64(nanos: u64) -> f64 {
 
 /// Returns the number of seconds contained by the `Duration` as `f64`.
 pub fn duration_as_secs_f64(duration: Duration) -> f64 {
-    (duration.as_secs() as f64) + (duration.as_nanos() as f64) / f64::from(NANOS_PER_SEC)
+    (duration.as_secs() as f64) + f64::from(duration.subsec_nanos()) / f64::from(NANOS_PER_SEC)
 }
 
 /// Arc wrapped metrics collector
 /// - metric collectors that are registered are stored within the MetricRegistry within an ArcCollector
 /// - this enables the collectors to be shared and used across threads
 #[derive(Clone)]
-pub struct ArcCollector(Arc<dyn prometheus::core::Collector + 'static>);
+pub struct ArcCollector(Arc<dyn prometheus::core::Collector + 'static>, SystemTime);
 
 impl ArcCollector {
     fn new(collector: impl prometheus::core::Collector + 'static) -> Self {
-        ArcCollector(Arc::new(collector))
+        ArcCollector(Arc::new(collector), SystemTime::now())
+    }
+
+    /// When the collector was registered
+    pub fn registered_at(&self) -> SystemTime {
+        self.1
     }
 }
 
@@ -1767,6 +2030,376 @@ impl ProcessMetrics {
     pub fn start_time_seconds(&self) -> f64 {
         self.start_time_seconds
     }
+
+    /// Returns the change since the `earlier` process metrics were collected.
+    /// - the CPU counter delta is computed using counter semantics, i.e., if the counter was reset then
+    ///   the current value is the delta
+    /// - the gauge deltas reflect the change in level, and thus may be negative
+    /// - `max_fds` and `start_time_seconds` are not levels that change over time, thus they are not
+    ///   diffed - the current values are reported as is
+    pub fn diff(&self, earlier: &ProcessMetrics) -> ProcessMetrics {
+        ProcessMetrics {
+            cpu_seconds_total: counter_delta(self.cpu_seconds_total, earlier.cpu_seconds_total),
+            open_fds: self.open_fds - earlier.open_fds,
+            max_fds: self.max_fds,
+            virtual_memory_bytes: self.virtual_memory_bytes - earlier.virtual_memory_bytes,
+            resident_memory_bytes: self.resident_memory_bytes - earlier.resident_memory_bytes,
+            start_time_seconds: self.start_time_seconds,
+        }
+    }
+
+    /// Returns the per second rate of change since the `earlier` process metrics were collected.
+    /// - `elapsed` is the time between the 2 collections
+    /// - if `elapsed` is zero, then all rates are zero
+    /// - `max_fds` and `start_time_seconds` are reported as is, i.e., they have no rate
+    pub fn rate(&self, earlier: &ProcessMetrics, elapsed: Duration) -> ProcessMetrics {
+        let diff = self.diff(earlier);
+        let secs = duration_as_secs_f64(elapsed);
+        let per_sec = |value: f64| if secs > 0.0 { value / secs } else { 0.0 };
+        ProcessMetrics {
+            cpu_seconds_total: per_sec(diff.cpu_seconds_total),
+            open_fds: per_sec(diff.open_fds),
+            max_fds: diff.max_fds,
+            virtual_memory_bytes: per_sec(diff.virtual_memory_bytes),
+            resident_memory_bytes: per_sec(diff.resident_memory_bytes),
+            start_time_seconds: diff.start_time_seconds,
+        }
+    }
+}
+
+/// computes a counter delta
+/// - if the counter went backwards, then it means the counter was reset, and the current value is the delta
+fn counter_delta(current: f64, earlier: f64) -> f64 {
+    if current < earlier {
+        current
+    } else {
+        current - earlier
+    }
+}
+
+/// A timestamped snapshot of gathered metrics.
+/// - [MetricRegistry::snapshot()](struct.MetricRegistry.html#method.snapshot) gathers all metrics into a snapshot
+/// - snapshots are serializable, which means they can be stored and diffed later
+///
+/// ## Diffing snapshots
+/// - [diff()](#method.diff) computes the deltas for counters and histograms
+/// - [rate()](#method.rate) computes the per second rates for counters and histograms
+/// - gauges and summaries are not included in the diff
+/// - if a counter or histogram was reset in between the snapshots, then the current value is used as the delta
+/// - if a metric does not exist in the earlier snapshot, then the metric's earlier value is assumed to be zero
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    timestamp: SystemTime,
+    samples: Vec<MetricSample>,
+}
+
+impl MetricsSnapshot {
+    /// constructor
+    /// - the snapshot is timestamped with the current time
+    pub fn new(metric_families: Vec<prometheus::proto::MetricFamily>) -> MetricsSnapshot {
+        let timestamp = SystemTime::now();
+        let samples = metric_families
+            .iter()
+            .flat_map(|mf| {
+                mf.get_metric()
+                    .iter()
+                    .map(move |metric| MetricSample::new(mf, metric))
+            })
+            .collect();
+        MetricsSnapshot { timestamp, samples }
+    }
+
+    /// When the snapshot was taken
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+
+    /// Returns the metric samples
+    pub fn samples(&self) -> &[MetricSample] {
+        &self.samples
+    }
+
+    /// Returns the sample that matches the specified metric name and labels
+    pub fn sample(&self, name: &str, labels: &BTreeMap<String, String>) -> Option<&MetricSample> {
+        self.samples
+            .iter()
+            .find(|sample| sample.name == name && sample.labels == *labels)
+    }
+
+    /// Returns the time elapsed between the `earlier` snapshot and this snapshot
+    /// - if the `earlier` snapshot is actually later, then zero is returned
+    pub fn elapsed_since(&self, earlier: &MetricsSnapshot) -> Duration {
+        self.timestamp
+            .duration_since(earlier.timestamp)
+            .unwrap_or_else(|_| Duration::from_secs(0))
+    }
+
+    /// Computes the counter and histogram deltas relative to the `earlier` snapshot
+    pub fn diff(&self, earlier: &MetricsSnapshot) -> MetricsDiff {
+        let earlier_samples: HashMap<(&str, &BTreeMap<String, String>), &MetricSample> = earlier
+            .samples
+            .iter()
+            .map(|sample| ((sample.name.as_str(), &sample.labels), sample))
+            .collect();
+
+        let samples = self
+            .samples
+            .iter()
+            .filter_map(|sample| {
+                let earlier_sample = earlier_samples
+                    .get(&(sample.name.as_str(), &sample.labels))
+                    .map(|sample| &sample.value);
+                sample
+                    .value
+                    .delta(earlier_sample)
+                    .map(|value| MetricSample {
+                        name: sample.name.clone(),
+                        labels: sample.labels.clone(),
+                        value,
+                    })
+            })
+            .collect();
+
+        MetricsDiff {
+            elapsed: self.elapsed_since(earlier),
+            samples,
+        }
+    }
+
+    /// Computes the per second rates for counters and histograms relative to the `earlier` snapshot
+    /// - if no time has elapsed between the snapshots, then all rates are zero
+    pub fn rate(&self, earlier: &MetricsSnapshot) -> MetricsDiff {
+        let diff = self.diff(earlier);
+        let secs = duration_as_secs_f64(diff.elapsed);
+        let per_sec = |value: f64| if secs > 0.0 { value / secs } else { 0.0 };
+        MetricsDiff {
+            elapsed: diff.elapsed,
+            samples: diff
+                .samples
+                .into_iter()
+                .map(|sample| MetricSample {
+                    value: sample.value.map(per_sec),
+                    ..sample
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<Vec<prometheus::proto::MetricFamily>> for MetricsSnapshot {
+    fn from(metric_families: Vec<prometheus::proto::MetricFamily>) -> Self {
+        MetricsSnapshot::new(metric_families)
+    }
+}
+
+/// The result of diffing 2 [MetricsSnapshot(s)](struct.MetricsSnapshot.html)
+/// - depending on how it was computed, the sample values are either deltas or per second rates
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricsDiff {
+    elapsed: Duration,
+    samples: Vec<MetricSample>,
+}
+
+impl MetricsDiff {
+    /// The time elapsed between the 2 snapshots
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns the diffed metric samples
+    pub fn samples(&self) -> &[MetricSample] {
+        &self.samples
+    }
+
+    /// Returns the sample that matches the specified metric name and labels
+    pub fn sample(&self, name: &str, labels: &BTreeMap<String, String>) -> Option<&MetricSample> {
+        self.samples
+            .iter()
+            .find(|sample| sample.name == name && sample.labels == *labels)
+    }
+}
+
+/// A single metric sample, i.e., a metric value identified by its metric name and label pairs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricSample {
+    name: String,
+    labels: BTreeMap<String, String>,
+    value: MetricValue,
+}
+
+impl MetricSample {
+    fn new(
+        metric_family: &prometheus::proto::MetricFamily,
+        metric: &prometheus::proto::Metric,
+    ) -> MetricSample {
+        use prometheus::proto::MetricType;
+
+        let labels = metric
+            .get_label()
+            .iter()
+            .map(|label_pair| {
+                (
+                    label_pair.get_name().to_string(),
+                    label_pair.get_value().to_string(),
+                )
+            })
+            .collect();
+
+        let value = match metric_family.get_field_type() {
+            MetricType::COUNTER => MetricValue::Counter(metric.get_counter().get_value()),
+            MetricType::GAUGE => MetricValue::Gauge(metric.get_gauge().get_value()),
+            MetricType::UNTYPED => MetricValue::Untyped(metric.get_untyped().get_value()),
+            MetricType::SUMMARY => {
+                let summary = metric.get_summary();
+                MetricValue::Summary {
+                    sample_count: summary.get_sample_count() as f64,
+                    sample_sum: summary.get_sample_sum(),
+                }
+            }
+            MetricType::HISTOGRAM => {
+                let histogram = metric.get_histogram();
+                MetricValue::Histogram {
+                    sample_count: histogram.get_sample_count() as f64,
+                    sample_sum: histogram.get_sample_sum(),
+                    buckets: histogram
+                        .get_bucket()
+                        .iter()
+                        .map(|bucket| {
+                            (
+                                bucket.get_upper_bound(),
+                                bucket.get_cumulative_count() as f64,
+                            )
+                        })
+                        .collect(),
+                }
+            }
+        };
+
+        MetricSample {
+            name: metric_family.get_name().to_string(),
+            labels,
+            value,
+        }
+    }
+
+    /// Metric name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Metric label pairs, i.e., constant and variable labels
+    pub fn labels(&self) -> &BTreeMap<String, String> {
+        &self.labels
+    }
+
+    /// Metric value
+    pub fn value(&self) -> &MetricValue {
+        &self.value
+    }
+}
+
+/// Metric sample value
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MetricValue {
+    /// Counter value
+    Counter(f64),
+    /// Gauge value
+    Gauge(f64),
+    /// Untyped value
+    Untyped(f64),
+    /// Summary sample count and sum
+    Summary {
+        /// number of observations
+        sample_count: f64,
+        /// sum of all observations
+        sample_sum: f64,
+    },
+    /// Histogram sample count, sum, and buckets
+    Histogram {
+        /// number of observations
+        sample_count: f64,
+        /// sum of all observations
+        sample_sum: f64,
+        /// (upper bound, cumulative count) pairs, ordered by upper bound
+        buckets: Vec<(f64, f64)>,
+    },
+}
+
+impl MetricValue {
+    /// Computes the delta for counters and histograms. None is returned for all other metric types.
+    fn delta(&self, earlier: Option<&MetricValue>) -> Option<MetricValue> {
+        match (self, earlier) {
+            (MetricValue::Counter(current), Some(MetricValue::Counter(earlier))) => {
+                Some(MetricValue::Counter(counter_delta(*current, *earlier)))
+            }
+            (MetricValue::Counter(current), _) => Some(MetricValue::Counter(*current)),
+            (
+                MetricValue::Histogram {
+                    sample_count,
+                    sample_sum,
+                    buckets,
+                },
+                Some(MetricValue::Histogram {
+                    sample_count: earlier_sample_count,
+                    sample_sum: earlier_sample_sum,
+                    buckets: earlier_buckets,
+                }),
+            ) => {
+                let same_buckets = buckets.len() == earlier_buckets.len()
+                    && buckets
+                        .iter()
+                        .zip(earlier_buckets.iter())
+                        .all(|((bound, _), (earlier_bound, _))| bound == earlier_bound);
+                if *sample_count < *earlier_sample_count || !same_buckets {
+                    // the histogram was reset
+                    Some(self.clone())
+                } else {
+                    Some(MetricValue::Histogram {
+                        sample_count: sample_count - earlier_sample_count,
+                        sample_sum: sample_sum - earlier_sample_sum,
+                        buckets: buckets
+                            .iter()
+                            .zip(earlier_buckets.iter())
+                            .map(|((bound, count), (_, earlier_count))| {
+                                (*bound, counter_delta(*count, *earlier_count))
+                            })
+                            .collect(),
+                    })
+                }
+            }
+            (MetricValue::Histogram { .. }, _) => Some(self.clone()),
+            _ => None,
+        }
+    }
+
+    /// applies the function to the values
+    /// - histogram bucket upper bounds are left untouched
+    fn map<F: Fn(f64) -> f64>(self, f: F) -> MetricValue {
+        match self {
+            MetricValue::Counter(value) => MetricValue::Counter(f(value)),
+            MetricValue::Gauge(value) => MetricValue::Gauge(f(value)),
+            MetricValue::Untyped(value) => MetricValue::Untyped(f(value)),
+            MetricValue::Summary {
+                sample_count,
+                sample_sum,
+            } => MetricValue::Summary {
+                sample_count: f(sample_count),
+                sample_sum: f(sample_sum),
+            },
+            MetricValue::Histogram {
+                sample_count,
+                sample_sum,
+                buckets,
+            } => MetricValue::Histogram {
+                sample_count: f(sample_count),
+                sample_sum: f(sample_sum),
+                buckets: buckets
+                    .into_iter()
+                    .map(|(bound, count)| (bound, f(count)))
+                    .collect(),
+            },
+        }
+    }
 }
 
 /// constructs new buckets that are meant to be used for a timer based histogram
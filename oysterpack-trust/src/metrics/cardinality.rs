@@ -0,0 +1,355 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Provides cardinality limits for metric vectors.
+//!
+//! Label values such as request or connection ids can explode the number of series, which will
+//! eventually take down the registry in long running servers. A [CardinalityGuard](struct.CardinalityGuard.html)
+//! wraps a metric vector and limits the number of label sets that it will track.
+//!
+//! ## Features
+//! - per metric limits - see [DEFAULT_CARDINALITY_LIMIT](constant.DEFAULT_CARDINALITY_LIMIT.html)
+//! - a global limit across all guarded metric vectors, which is managed by the [MetricRegistry](../struct.MetricRegistry.html#method.set_cardinality_limit)
+//!   - guarded metric vectors are bound to the registry that they are registered with via
+//!     [MetricRegistry::register_guarded()](../struct.MetricRegistry.html#method.register_guarded)
+//! - once the limit is reached, new label sets are mapped to the overflow series, i.e., all variable
+//!   label values are set to [OVERFLOW_LABEL_VALUE](constant.OVERFLOW_LABEL_VALUE.html)
+//! - rejected label sets are counted per metric - see [CARDINALITY_REJECTED_COUNTER_METRIC_ID](constant.CARDINALITY_REJECTED_COUNTER_METRIC_ID.html)
+//!   - the counter is registered with the same registry as the guarded metric vectors
+//! - a warning is logged the first time a metric's label set is rejected
+
+use super::{LabelId, MetricId, MetricRegistry};
+use oysterpack_log::*;
+use parking_lot::{Mutex, RwLock};
+use prometheus::core::{Collector, MetricVec, MetricVecBuilder};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+/// The default per metric cardinality limit, i.e., the max number of label sets that will be tracked
+pub const DEFAULT_CARDINALITY_LIMIT: usize = 1000;
+
+/// The default global cardinality limit across all guarded metric vectors
+pub const DEFAULT_GLOBAL_CARDINALITY_LIMIT: usize = 100_000;
+
+/// Label value that is used for the overflow series
+pub const OVERFLOW_LABEL_VALUE: &str = "__overflow__";
+
+/// MetricId for the counter that tracks the number of rejected label sets: `M01M57EKWEG8YPY0W21Q43EBGG3`
+pub const CARDINALITY_REJECTED_COUNTER_METRIC_ID: MetricId =
+    MetricId(2166788352957656207884455603027165699);
+
+/// The guarded metric name is used as the label value: `L01M57EKWEJV0BJYFNX7YGEX760`
+pub const METRIC_NAME_LABEL_ID: LabelId = LabelId(2166788352960756022883957827991149760);
+
+/// Guarded CounterVec
+pub type GuardedCounterVec = CardinalityGuard<prometheus::CounterVec>;
+/// Guarded IntCounterVec
+pub type GuardedIntCounterVec = CardinalityGuard<prometheus::IntCounterVec>;
+/// Guarded GaugeVec
+pub type GuardedGaugeVec = CardinalityGuard<prometheus::GaugeVec>;
+/// Guarded IntGaugeVec
+pub type GuardedIntGaugeVec = CardinalityGuard<prometheus::IntGaugeVec>;
+/// Guarded HistogramVec
+pub type GuardedHistogramVec = CardinalityGuard<prometheus::HistogramVec>;
+
+/// Builds metric vectors that can be guarded - see [MetricRegistry::register_guarded()](../struct.MetricRegistry.html#method.register_guarded)
+pub trait GuardedMetricVecBuilder {
+    /// metric vector type
+    type Vec: LabeledMetricVec;
+
+    /// The max number of label sets that the guarded metric vector will track
+    fn cardinality_limit(&self) -> usize;
+
+    /// builds the metric vector, which is not guarded
+    fn build_metric_vec(self) -> prometheus::Result<Self::Vec>;
+}
+
+/// Tracks the number of label sets across all guarded metric vectors that are registered with a
+/// [MetricRegistry](../struct.MetricRegistry.html)
+#[derive(Debug)]
+pub(crate) struct CardinalityBudget {
+    limit: AtomicUsize,
+    count: AtomicUsize,
+    /// Metric: Number of label sets that were rejected because a cardinality limit was reached
+    rejected: prometheus::IntCounterVec,
+    rejected_registered: Mutex<bool>,
+}
+
+impl CardinalityBudget {
+    pub(crate) fn new(limit: usize) -> CardinalityBudget {
+        CardinalityBudget {
+            limit: AtomicUsize::new(limit),
+            count: AtomicUsize::new(0),
+            rejected: super::new_int_counter_vec(
+                CARDINALITY_REJECTED_COUNTER_METRIC_ID,
+                "Rejected metric label set count",
+                &[METRIC_NAME_LABEL_ID],
+                None::<HashMap<LabelId, String>>,
+            )
+            .unwrap(),
+            rejected_registered: Mutex::new(false),
+        }
+    }
+
+    /// Registers the rejected label set counter with the registry that owns this budget, the first
+    /// time a guarded metric vector is registered
+    pub(crate) fn register_rejected_counter(
+        &self,
+        registry: &MetricRegistry,
+    ) -> prometheus::Result<()> {
+        let mut registered = self.rejected_registered.lock();
+        if !*registered {
+            registry.register(self.rejected.clone())?;
+            *registered = true;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn limit(&self) -> usize {
+        self.limit.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn set_limit(&self, limit: usize) {
+        self.limit.store(limit, Ordering::SeqCst);
+    }
+
+    pub(crate) fn count(&self) -> usize {
+        self.count.load(Ordering::SeqCst)
+    }
+
+    /// Tries to reserve capacity for a new label set - returns false if the limit has been reached
+    fn acquire(&self) -> bool {
+        let limit = self.limit();
+        let mut current = self.count.load(Ordering::SeqCst);
+        loop {
+            if current >= limit {
+                return false;
+            }
+            match self.count.compare_exchange_weak(
+                current,
+                current + 1,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => return true,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    fn release(&self, count: usize) {
+        if count > 0 {
+            self.count.fetch_sub(count, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Metric vector that can be guarded
+pub trait LabeledMetricVec: Collector + 'static {
+    /// metric type
+    type M;
+
+    /// Returns the metric for the specified label values, creating it if needed
+    fn with_label_values(&self, vals: &[&str]) -> Self::M;
+
+    /// Removes the metric for the specified label values
+    fn remove_label_values(&self, vals: &[&str]) -> prometheus::Result<()>;
+}
+
+impl<T: MetricVecBuilder + 'static> LabeledMetricVec for MetricVec<T> {
+    type M = T::M;
+
+    fn with_label_values(&self, vals: &[&str]) -> T::M {
+        MetricVec::with_label_values(self, vals)
+    }
+
+    fn remove_label_values(&self, vals: &[&str]) -> prometheus::Result<()> {
+        MetricVec::remove_label_values(self, vals)
+    }
+}
+
+/// Limits the number of label sets that a metric vector will track.
+/// - CardinalityGuard is a Collector, which is registered via [MetricRegistry::register_guarded()](../struct.MetricRegistry.html#method.register_guarded)
+/// - label sets count against the global cardinality limit of the registry that the guard is registered with
+/// - clones share the same underlying metric vector and label set tracking
+pub struct CardinalityGuard<V: LabeledMetricVec> {
+    inner: Arc<Inner<V>>,
+}
+
+struct Inner<V: LabeledMetricVec> {
+    metric_vec: V,
+    metric_name: String,
+    limit: usize,
+    budget: Arc<CardinalityBudget>,
+    label_sets: RwLock<HashSet<Vec<String>>>,
+    overflow_logged: AtomicBool,
+}
+
+impl<V: LabeledMetricVec> Drop for Inner<V> {
+    fn drop(&mut self) {
+        self.budget.release(self.label_sets.read().len());
+    }
+}
+
+impl<V: LabeledMetricVec> CardinalityGuard<V> {
+    pub(crate) fn new(
+        metric_vec: V,
+        limit: usize,
+        budget: Arc<CardinalityBudget>,
+    ) -> CardinalityGuard<V> {
+        let metric_name = metric_vec
+            .desc()
+            .first()
+            .map(|desc| desc.fq_name.clone())
+            .unwrap_or_default();
+        CardinalityGuard {
+            inner: Arc::new(Inner {
+                metric_vec,
+                metric_name,
+                limit,
+                budget,
+                label_sets: RwLock::new(HashSet::new()),
+                overflow_logged: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// Returns the metric for the specified label values.
+    /// - if the label set is new and either the per metric or global cardinality limit has been
+    ///   reached, then the overflow metric is returned
+    ///
+    /// ## Panics
+    /// If the number of label values does not match the number of variable labels
+    pub fn with_label_values(&self, vals: &[&str]) -> V::M {
+        if self.admit(vals) {
+            self.inner.metric_vec.with_label_values(vals)
+        } else {
+            let overflow = vec![OVERFLOW_LABEL_VALUE; vals.len()];
+            self.inner.metric_vec.with_label_values(&overflow)
+        }
+    }
+
+    /// Removes the metric for the specified label values, which frees up capacity for a new label set.
+    pub fn remove_label_values(&self, vals: &[&str]) -> prometheus::Result<()> {
+        self.inner.metric_vec.remove_label_values(vals)?;
+        let key: Vec<String> = vals.iter().map(|val| (*val).to_string()).collect();
+        if self.inner.label_sets.write().remove(&key) {
+            self.inner.budget.release(1);
+        }
+        Ok(())
+    }
+
+    /// Returns the number of label sets that are being tracked - the overflow series is not counted
+    pub fn cardinality(&self) -> usize {
+        self.inner.label_sets.read().len()
+    }
+
+    /// The max number of label sets that will be tracked
+    pub fn limit(&self) -> usize {
+        self.inner.limit
+    }
+
+    /// Returns the number of label sets that were rejected because a cardinality limit was reached
+    pub fn rejected_count(&self) -> u64 {
+        self.inner
+            .budget
+            .rejected
+            .with_label_values(&[self.inner.metric_name.as_str()])
+            .get() as u64
+    }
+
+    /// Returns the metric name
+    pub fn metric_name(&self) -> &str {
+        &self.inner.metric_name
+    }
+
+    /// Returns the underlying metric vector, which is not guarded
+    pub fn metric_vec(&self) -> &V {
+        &self.inner.metric_vec
+    }
+
+    fn admit(&self, vals: &[&str]) -> bool {
+        let key: Vec<String> = vals.iter().map(|val| (*val).to_string()).collect();
+        if self.inner.label_sets.read().contains(&key) {
+            return true;
+        }
+
+        let mut label_sets = self.inner.label_sets.write();
+        if label_sets.contains(&key) {
+            return true;
+        }
+        if label_sets.len() >= self.inner.limit {
+            self.reject(&key, "metric", self.inner.limit);
+            return false;
+        }
+        if !self.inner.budget.acquire() {
+            self.reject(&key, "global", self.inner.budget.limit());
+            return false;
+        }
+        label_sets.insert(key);
+        true
+    }
+
+    fn reject(&self, label_values: &[String], limit_type: &str, limit: usize) {
+        self.inner
+            .budget
+            .rejected
+            .with_label_values(&[self.inner.metric_name.as_str()])
+            .inc();
+        if !self.inner.overflow_logged.swap(true, Ordering::Relaxed) {
+            warn!(
+                "{} cardinality limit was reached for metric {} (limit = {}): label values {:?} were mapped to the overflow series",
+                limit_type, self.inner.metric_name, limit, label_values
+            );
+        }
+    }
+}
+
+impl<V: LabeledMetricVec> Clone for CardinalityGuard<V> {
+    fn clone(&self) -> Self {
+        CardinalityGuard {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<V: LabeledMetricVec> Collector for CardinalityGuard<V> {
+    fn desc(&self) -> Vec<&prometheus::core::Desc> {
+        self.inner.metric_vec.desc()
+    }
+
+    fn collect(&self) -> Vec<prometheus::proto::MetricFamily> {
+        self.inner.metric_vec.collect()
+    }
+}
+
+impl<V: LabeledMetricVec> fmt::Debug for CardinalityGuard<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "CardinalityGuard({}, cardinality = {}, limit = {})",
+            self.inner.metric_name,
+            self.cardinality(),
+            self.inner.limit
+        )
+    }
+}
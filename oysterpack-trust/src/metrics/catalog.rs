@@ -0,0 +1,534 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Provides a catalog of metric descriptors, which makes ULID based metric names understandable to
+//! operators.
+//!
+//! ## Features
+//! - the catalog is serializable, e.g., to JSON
+//! - the catalog can be rendered as a Markdown table - [MetricCatalog::to_markdown()](struct.MetricCatalog.html#method.to_markdown)
+//! - Prometheus recording rules can be generated that alias each metric with a name derived from
+//!   its help text - [MetricCatalog::to_recording_rules()](struct.MetricCatalog.html#method.to_recording_rules)
+//! - a catalog can be built from a Prometheus or OpenMetrics text exposition, e.g., a scrape of a
+//!   running process's metrics endpoint - [MetricCatalog::from_text_exposition()](struct.MetricCatalog.html#method.from_text_exposition)
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt::Write,
+};
+
+/// The max alias length - longer aliases are truncated on a word boundary
+pub const MAX_ALIAS_LEN: usize = 64;
+
+/// Metric descriptor catalog
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetricCatalog {
+    descriptors: Vec<MetricDescriptor>,
+}
+
+impl MetricCatalog {
+    /// constructor
+    /// - descriptors are sorted by name
+    pub fn new(mut descriptors: Vec<MetricDescriptor>) -> MetricCatalog {
+        descriptors.sort_by(|a, b| a.name.cmp(&b.name));
+        MetricCatalog { descriptors }
+    }
+
+    /// Builds the catalog from the specified descriptors.
+    /// - the metric families are used to lookup the metric types - descriptors for metrics that
+    ///   have not yet reported any samples will not have a type
+    pub fn from_descs(
+        descs: &[prometheus::core::Desc],
+        metric_families: &[prometheus::proto::MetricFamily],
+    ) -> MetricCatalog {
+        let metric_types = metric_families
+            .iter()
+            .map(|mf| (mf.get_name(), MetricType::from(mf.get_field_type())))
+            .collect::<BTreeMap<_, _>>();
+        let descriptors = descs
+            .iter()
+            .map(|desc| MetricDescriptor {
+                name: desc.fq_name.clone(),
+                help: desc.help.clone(),
+                metric_type: metric_types.get(desc.fq_name.as_str()).cloned(),
+                const_labels: desc
+                    .const_label_pairs
+                    .iter()
+                    .map(|label| (label.get_name().to_string(), label.get_value().to_string()))
+                    .collect(),
+                variable_labels: desc.variable_labels.clone(),
+            })
+            .collect();
+        MetricCatalog::new(descriptors)
+    }
+
+    /// Builds the catalog from a Prometheus or OpenMetrics text exposition.
+    /// - descriptors are built from the `# HELP` and `# TYPE` comments
+    /// - OpenMetrics `_total` and `_created` samples are mapped back to their metric family
+    /// - label names are collected from the samples - because the text exposition does not
+    ///   distinguish between const and variable labels, all label names are reported as variable labels
+    pub fn from_text_exposition(text: &str) -> MetricCatalog {
+        let mut descriptors: BTreeMap<String, MetricDescriptor> = BTreeMap::new();
+        fn descriptor<'a>(
+            descriptors: &'a mut BTreeMap<String, MetricDescriptor>,
+            name: &str,
+        ) -> &'a mut MetricDescriptor {
+            descriptors
+                .entry(name.to_string())
+                .or_insert_with(|| MetricDescriptor {
+                    name: name.to_string(),
+                    ..MetricDescriptor::default()
+                })
+        }
+
+        let mut label_names: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            if line.starts_with('#') {
+                let mut tokens = line[1..].trim_start().splitn(3, ' ');
+                match (tokens.next(), tokens.next(), tokens.next()) {
+                    (Some("HELP"), Some(name), help) => {
+                        descriptor(&mut descriptors, name).help =
+                            unescape_help(help.unwrap_or("").trim());
+                    }
+                    (Some("TYPE"), Some(name), Some(metric_type)) => {
+                        descriptor(&mut descriptors, name).metric_type =
+                            metric_type.trim().parse().ok();
+                    }
+                    _ => (),
+                }
+                continue;
+            }
+
+            let name_end = line
+                .find(|c: char| c == '{' || c.is_whitespace())
+                .unwrap_or_else(|| line.len());
+            let sample_name = &line[..name_end];
+            let name = family_name(&descriptors, sample_name).to_string();
+            let labels = label_names.entry(name).or_insert_with(BTreeSet::new);
+            if line[name_end..].starts_with('{') {
+                labels.extend(
+                    sample_label_names(&line[name_end + 1..])
+                        .filter(|label| *label != "le" && *label != "quantile")
+                        .map(str::to_string),
+                );
+            }
+        }
+
+        for (name, labels) in label_names {
+            descriptor(&mut descriptors, &name).variable_labels = labels.into_iter().collect();
+        }
+        MetricCatalog::new(descriptors.into_iter().map(|(_, desc)| desc).collect())
+    }
+
+    /// Returns the metric descriptors sorted by name
+    pub fn descriptors(&self) -> &[MetricDescriptor] {
+        &self.descriptors
+    }
+
+    /// Returns the descriptor for the specified metric name
+    pub fn descriptor(&self, name: &str) -> Option<&MetricDescriptor> {
+        self.descriptors.iter().find(|desc| desc.name == name)
+    }
+
+    /// Renders the catalog as a Markdown table
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        md.push_str("| Metric | Type | Help | Const Labels | Variable Labels |\n");
+        md.push_str("|--------|------|------|--------------|-----------------|\n");
+        for desc in &self.descriptors {
+            let const_labels = desc
+                .const_labels
+                .iter()
+                .map(|(name, value)| format!("`{}={}`", name, value))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let variable_labels = desc
+                .variable_labels
+                .iter()
+                .map(|name| format!("`{}`", name))
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(
+                md,
+                "| `{}` | {} | {} | {} | {} |",
+                desc.name,
+                desc.metric_type.map_or("", MetricType::as_str),
+                escape_markdown(&desc.help),
+                const_labels,
+                variable_labels
+            )
+            .unwrap();
+        }
+        md
+    }
+
+    /// Generates a Prometheus recording rules file (YAML), which records each metric under an alias
+    /// that is derived from its help text.
+    /// - metrics with no help text are skipped
+    /// - alias collisions are resolved by appending a numeric suffix
+    /// - histogram and summary metrics are recorded for each of their `_bucket`, `_sum`, and `_count`
+    ///   series
+    pub fn to_recording_rules(&self, group_name: &str) -> String {
+        let mut yaml = String::new();
+        writeln!(yaml, "groups:").unwrap();
+        writeln!(yaml, "  - name: {}", group_name).unwrap();
+        writeln!(yaml, "    rules:").unwrap();
+        let mut aliases = HashSet::new();
+        for desc in &self.descriptors {
+            let alias = match desc.alias() {
+                Some(alias) => unique_alias(&mut aliases, alias),
+                None => continue,
+            };
+            let suffixes: &[&str] = match desc.metric_type {
+                Some(MetricType::Histogram) => &["_bucket", "_sum", "_count"],
+                Some(MetricType::Summary) => &["", "_sum", "_count"],
+                _ => &[""],
+            };
+            for suffix in suffixes {
+                writeln!(yaml, "      - record: {}{}", alias, suffix).unwrap();
+                writeln!(yaml, "        expr: {}{}", desc.name, suffix).unwrap();
+            }
+        }
+        yaml
+    }
+}
+
+/// Metric descriptor
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetricDescriptor {
+    name: String,
+    help: String,
+    metric_type: Option<MetricType>,
+    const_labels: BTreeMap<String, String>,
+    variable_labels: Vec<String>,
+}
+
+impl MetricDescriptor {
+    /// Metric name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Metric help text
+    pub fn help(&self) -> &str {
+        &self.help
+    }
+
+    /// Metric type - None if the type is unknown
+    pub fn metric_type(&self) -> Option<MetricType> {
+        self.metric_type
+    }
+
+    /// Const labels
+    pub fn const_labels(&self) -> &BTreeMap<String, String> {
+        &self.const_labels
+    }
+
+    /// Variable label names
+    pub fn variable_labels(&self) -> &[String] {
+        &self.variable_labels
+    }
+
+    /// Derives a friendly metric name from the help text, which is a valid Prometheus metric name
+    /// - returns None if the help text contains no alphanumeric chars
+    pub fn alias(&self) -> Option<String> {
+        let words = self
+            .help
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase);
+        let mut alias = String::new();
+        for word in words {
+            if alias.is_empty() {
+                // metric names must not start with a digit
+                if word.starts_with(|c: char| c.is_ascii_digit()) {
+                    alias.push('_');
+                }
+                alias.push_str(&word);
+                continue;
+            }
+            if alias.len() + word.len() + 1 > MAX_ALIAS_LEN {
+                break;
+            }
+            alias.push('_');
+            alias.push_str(&word);
+        }
+        if alias.is_empty() {
+            return None;
+        }
+        // the first word may exceed the max length on its own
+        alias.truncate(MAX_ALIAS_LEN);
+        Some(alias)
+    }
+}
+
+/// Metric types
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MetricType {
+    /// Counter
+    Counter,
+    /// Gauge
+    Gauge,
+    /// Histogram
+    Histogram,
+    /// Summary
+    Summary,
+    /// Untyped
+    Untyped,
+}
+
+impl MetricType {
+    /// Returns the type name used in the Prometheus text exposition format
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MetricType::Counter => "counter",
+            MetricType::Gauge => "gauge",
+            MetricType::Histogram => "histogram",
+            MetricType::Summary => "summary",
+            MetricType::Untyped => "untyped",
+        }
+    }
+}
+
+impl From<prometheus::proto::MetricType> for MetricType {
+    fn from(metric_type: prometheus::proto::MetricType) -> MetricType {
+        match metric_type {
+            prometheus::proto::MetricType::COUNTER => MetricType::Counter,
+            prometheus::proto::MetricType::GAUGE => MetricType::Gauge,
+            prometheus::proto::MetricType::HISTOGRAM => MetricType::Histogram,
+            prometheus::proto::MetricType::SUMMARY => MetricType::Summary,
+            prometheus::proto::MetricType::UNTYPED => MetricType::Untyped,
+        }
+    }
+}
+
+impl std::str::FromStr for MetricType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<MetricType, String> {
+        match s {
+            "counter" => Ok(MetricType::Counter),
+            "gauge" => Ok(MetricType::Gauge),
+            "histogram" => Ok(MetricType::Histogram),
+            "summary" => Ok(MetricType::Summary),
+            "untyped" | "unknown" => Ok(MetricType::Untyped),
+            _ => Err(format!("unknown metric type: {}", s)),
+        }
+    }
+}
+
+/// maps sample names back to their metric family name
+/// - histogram and summary samples: `_bucket`, `_sum`, `_count`, and the OpenMetrics `_created`
+/// - OpenMetrics counter samples: `_total` and `_created`
+fn family_name<'a>(
+    descriptors: &BTreeMap<String, MetricDescriptor>,
+    sample_name: &'a str,
+) -> &'a str {
+    for suffix in &["_bucket", "_sum", "_count", "_total", "_created"] {
+        if sample_name.ends_with(suffix) {
+            let name = &sample_name[..sample_name.len() - suffix.len()];
+            let is_family = descriptors
+                .get(name)
+                .map_or(false, |desc| match desc.metric_type {
+                    Some(MetricType::Histogram) | Some(MetricType::Summary) => *suffix != "_total",
+                    Some(MetricType::Counter) => *suffix == "_total" || *suffix == "_created",
+                    _ => false,
+                });
+            if is_family {
+                return name;
+            }
+        }
+    }
+    sample_name
+}
+
+/// parses label names from the sample labels, i.e., the text following the opening `{`
+fn sample_label_names(labels: &str) -> impl Iterator<Item = &str> {
+    let mut names = Vec::new();
+    let mut rest = labels;
+    while let Some(eq) = rest.find('=') {
+        let name = rest[..eq].trim().trim_start_matches(',').trim();
+        if name.starts_with('}') {
+            break;
+        }
+        names.push(name);
+        // skip over the quoted label value
+        let value = &rest[eq + 1..];
+        let mut escaped = false;
+        let mut end = value.len();
+        for (i, c) in value.char_indices().skip(1) {
+            match c {
+                '\\' if !escaped => escaped = true,
+                '"' if !escaped => {
+                    end = i + 1;
+                    break;
+                }
+                _ => escaped = false,
+            }
+        }
+        rest = &value[end..];
+    }
+    names.into_iter()
+}
+
+fn unique_alias(aliases: &mut HashSet<String>, alias: String) -> String {
+    let mut unique = alias.clone();
+    let mut n = 2;
+    while aliases.contains(&unique) {
+        unique = format!("{}_{}", alias, n);
+        n += 1;
+    }
+    aliases.insert(unique.clone());
+    unique
+}
+
+fn unescape_help(help: &str) -> String {
+    help.replace("\\n", "\n").replace("\\\\", "\\")
+}
+
+fn escape_markdown(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+#[allow(warnings)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT_EXPOSITION: &str = r#"# HELP M01D4ZHRS7RV42RXN1R83Q8QDPA Executor spawned task count
+# TYPE M01D4ZHRS7RV42RXN1R83Q8QDPA counter
+M01D4ZHRS7RV42RXN1R83Q8QDPA{L01D4ZHRS7RV42RXN1R83Q8QDPB="01D4ZHRS7RV42RXN1R83Q8QDPC"} 10
+# HELP M01D4ZHRS7RV42RXN1R83Q8QDPD ReqRep request processing timer in seconds
+# TYPE M01D4ZHRS7RV42RXN1R83Q8QDPD histogram
+M01D4ZHRS7RV42RXN1R83Q8QDPD_bucket{L01D4ZHRS7RV42RXN1R83Q8QDPB="a,b",le="0.1"} 1
+M01D4ZHRS7RV42RXN1R83Q8QDPD_bucket{L01D4ZHRS7RV42RXN1R83Q8QDPB="a,b",le="+Inf"} 1
+M01D4ZHRS7RV42RXN1R83Q8QDPD_sum{L01D4ZHRS7RV42RXN1R83Q8QDPB="a,b"} 0.05
+M01D4ZHRS7RV42RXN1R83Q8QDPD_count{L01D4ZHRS7RV42RXN1R83Q8QDPB="a,b"} 1
+# HELP M01D4ZHRS7RV42RXN1R83Q8QDPE Executor spawned task count
+# TYPE M01D4ZHRS7RV42RXN1R83Q8QDPE counter
+M01D4ZHRS7RV42RXN1R83Q8QDPE 1
+"#;
+
+    #[test]
+    fn catalog_from_text_exposition() {
+        let catalog = MetricCatalog::from_text_exposition(TEXT_EXPOSITION);
+        assert_eq!(catalog.descriptors().len(), 3);
+        let desc = catalog.descriptor("M01D4ZHRS7RV42RXN1R83Q8QDPD").unwrap();
+        assert_eq!(desc.metric_type(), Some(MetricType::Histogram));
+        assert_eq!(desc.help(), "ReqRep request processing timer in seconds");
+        assert_eq!(
+            desc.variable_labels(),
+            &["L01D4ZHRS7RV42RXN1R83Q8QDPB".to_string()]
+        );
+        assert_eq!(
+            desc.alias().unwrap(),
+            "reqrep_request_processing_timer_in_seconds"
+        );
+
+        let json = serde_json::to_string_pretty(&catalog).unwrap();
+        println!("{}", json);
+        let catalog2: MetricCatalog = serde_json::from_str(&json).unwrap();
+        assert_eq!(catalog, catalog2);
+
+        let md = catalog.to_markdown();
+        println!("{}", md);
+        assert!(md.contains(
+            "| `M01D4ZHRS7RV42RXN1R83Q8QDPA` | counter | Executor spawned task count |  | `L01D4ZHRS7RV42RXN1R83Q8QDPB` |"
+        ));
+    }
+
+    #[test]
+    fn recording_rules() {
+        let catalog = MetricCatalog::from_text_exposition(TEXT_EXPOSITION);
+        let rules = catalog.to_recording_rules("oysterpack");
+        println!("{}", rules);
+        assert!(rules.starts_with("groups:\n  - name: oysterpack\n    rules:\n"));
+        assert!(rules.contains(
+            "      - record: executor_spawned_task_count\n        expr: M01D4ZHRS7RV42RXN1R83Q8QDPA\n"
+        ));
+        assert!(rules.contains(
+            "      - record: executor_spawned_task_count_2\n        expr: M01D4ZHRS7RV42RXN1R83Q8QDPE\n"
+        ));
+        assert!(rules.contains(
+            "      - record: reqrep_request_processing_timer_in_seconds_bucket\n        expr: M01D4ZHRS7RV42RXN1R83Q8QDPD_bucket\n"
+        ));
+    }
+
+    #[test]
+    fn alias_is_truncated_on_word_boundary() {
+        let desc = MetricDescriptor {
+            name: "M01D4ZHRS7RV42RXN1R83Q8QDPA".to_string(),
+            help: "1 very long help text that goes on and on and on and on and on and on and on forever".to_string(),
+            ..MetricDescriptor::default()
+        };
+        let alias = desc.alias().unwrap();
+        assert!(alias.len() <= MAX_ALIAS_LEN);
+        assert!(alias.starts_with("_1_very_long"));
+        assert!(!alias.ends_with('_'));
+
+        let desc = MetricDescriptor {
+            name: "M01D4ZHRS7RV42RXN1R83Q8QDPA".to_string(),
+            help: "1".repeat(MAX_ALIAS_LEN * 2),
+            ..MetricDescriptor::default()
+        };
+        let alias = desc.alias().unwrap();
+        assert_eq!(alias.len(), MAX_ALIAS_LEN);
+        assert!(alias.starts_with("_1"));
+    }
+
+    #[test]
+    fn catalog_from_open_metrics_text_exposition() {
+        const OPEN_METRICS_TEXT: &str = r#"# TYPE M01D4ZHRS7RV42RXN1R83Q8QDPA counter
+# HELP M01D4ZHRS7RV42RXN1R83Q8QDPA Executor spawned task count
+M01D4ZHRS7RV42RXN1R83Q8QDPA_total{L01D4ZHRS7RV42RXN1R83Q8QDPB="01D4ZHRS7RV42RXN1R83Q8QDPC"} 10.0
+M01D4ZHRS7RV42RXN1R83Q8QDPA_created{L01D4ZHRS7RV42RXN1R83Q8QDPB="01D4ZHRS7RV42RXN1R83Q8QDPC"} 1556668800.0
+# TYPE M01D4ZHRS7RV42RXN1R83Q8QDPD histogram
+# HELP M01D4ZHRS7RV42RXN1R83Q8QDPD ReqRep request processing timer in seconds
+M01D4ZHRS7RV42RXN1R83Q8QDPD_bucket{le="+Inf"} 1
+M01D4ZHRS7RV42RXN1R83Q8QDPD_sum 0.05
+M01D4ZHRS7RV42RXN1R83Q8QDPD_count 1
+M01D4ZHRS7RV42RXN1R83Q8QDPD_created 1556668800.0
+# TYPE M01D4ZHRS7RV42RXN1R83Q8QDPE gauge
+# HELP M01D4ZHRS7RV42RXN1R83Q8QDPE Queue size total
+M01D4ZHRS7RV42RXN1R83Q8QDPE_total 1
+# EOF
+"#;
+        let catalog = MetricCatalog::from_text_exposition(OPEN_METRICS_TEXT);
+        println!("{:#?}", catalog);
+        let desc = catalog.descriptor("M01D4ZHRS7RV42RXN1R83Q8QDPA").unwrap();
+        assert_eq!(desc.metric_type(), Some(MetricType::Counter));
+        assert_eq!(
+            desc.variable_labels(),
+            &["L01D4ZHRS7RV42RXN1R83Q8QDPB".to_string()]
+        );
+        assert!(catalog
+            .descriptor("M01D4ZHRS7RV42RXN1R83Q8QDPA_total")
+            .is_none());
+        assert!(catalog
+            .descriptor("M01D4ZHRS7RV42RXN1R83Q8QDPA_created")
+            .is_none());
+        assert!(catalog
+            .descriptor("M01D4ZHRS7RV42RXN1R83Q8QDPD_created")
+            .is_none());
+        // the `_total` suffix is only stripped for counters
+        assert!(catalog
+            .descriptor("M01D4ZHRS7RV42RXN1R83Q8QDPE_total")
+            .is_some());
+        assert_eq!(catalog.descriptors().len(), 4);
+    }
+}
@@ -0,0 +1,958 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Provides support for the [OpenMetrics](https://github.com/OpenObservability/OpenMetrics) 1.0 text format.
+//!
+//! ## Features
+//! - [OpenMetricsEncoder](struct.OpenMetricsEncoder.html) implements `prometheus::Encoder`
+//!   - the exposition is terminated with `# EOF`
+//!   - counters are reported as `{name}_total`
+//!   - counters, histograms and summaries report `{name}_created` for series whose creation time is
+//!     known, i.e., metrics without variable labels report the time they were registered - metric
+//!     vector children are created on demand, and thus do not report `_created`
+//!   - `# UNIT` metadata is reported when the metric name is suffixed with a base unit, e.g., `_seconds`
+//! - exemplars carrying a ULID trace id can be recorded for counters and histograms
+//!   - exemplars are stored in an [ExemplarStore](struct.ExemplarStore.html), which is scoped to a
+//!     [MetricRegistry](../struct.MetricRegistry.html#method.exemplars)
+//!   - exemplars are recorded via handles that are bound to a single series:
+//!     [ExemplarCounter](struct.ExemplarCounter.html), [ExemplarIntCounter](struct.ExemplarIntCounter.html)
+//!     and [ExemplarHistogram](struct.ExemplarHistogram.html)
+//!   - only the latest exemplar is retained per counter and per histogram bucket
+//!   - the store is bounded - once full, the least recently recorded exemplar is evicted
+//!   - exemplars for series that are no longer gathered, e.g., removed metric vector label sets,
+//!     are pruned when metrics are encoded
+//! - [TextFormat::negotiate()](enum.TextFormat.html#method.negotiate) selects the exposition text
+//!   format via the HTTP `Accept` header
+
+use oysterpack_uid::ULID;
+use parking_lot::RwLock;
+use prometheus::{
+    core::{Collector, Metric},
+    proto::{LabelPair, MetricFamily, MetricType},
+};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    io::Write,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// OpenMetrics text format content type
+pub const OPENMETRICS_TEXT_FORMAT: &str =
+    "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Base units that are recognized as metric name suffixes
+pub const UNITS: &[&str] = &[
+    "seconds", "bytes", "ratio", "meters", "grams", "celsius", "volts", "amperes", "joules",
+];
+
+/// Default max number of exemplars that are retained by an ExemplarStore
+pub const DEFAULT_MAX_EXEMPLARS: usize = 10_000;
+
+/// Identifies a time series, i.e., the metric name and its label pairs
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SeriesKey {
+    name: String,
+    labels: BTreeMap<String, String>,
+}
+
+impl SeriesKey {
+    /// constructor
+    pub fn new(name: &str, labels: &[LabelPair]) -> SeriesKey {
+        SeriesKey {
+            name: name.to_string(),
+            labels: labels
+                .iter()
+                .map(|label| (label.get_name().to_string(), label.get_value().to_string()))
+                .collect(),
+        }
+    }
+
+    /// Returns the series key for the metric, i.e., for a metric vector child the variable labels
+    /// are included
+    pub fn for_metric<M: Metric>(name: &str, metric: &M) -> SeriesKey {
+        SeriesKey::new(name, metric.metric().get_label())
+    }
+
+    /// metric name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// label pairs
+    pub fn labels(&self) -> &BTreeMap<String, String> {
+        &self.labels
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ExemplarKey {
+    series: SeriesKey,
+    /// histogram bucket upper bound bits - counters use a bucket of None
+    bucket: Option<u64>,
+}
+
+impl ExemplarKey {
+    fn new(series: SeriesKey, bucket: Option<f64>) -> ExemplarKey {
+        ExemplarKey {
+            series,
+            bucket: bucket.map(f64::to_bits),
+        }
+    }
+}
+
+/// An exemplar references data outside of the metric set, i.e., a trace
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Exemplar {
+    trace_id: ULID,
+    value: f64,
+    timestamp: SystemTime,
+}
+
+impl Exemplar {
+    /// constructor - timestamped with the current time
+    pub fn new(trace_id: ULID, value: f64) -> Exemplar {
+        Exemplar {
+            trace_id,
+            value,
+            timestamp: SystemTime::now(),
+        }
+    }
+
+    /// trace ID
+    pub fn trace_id(&self) -> ULID {
+        self.trace_id
+    }
+
+    /// the observed value
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// when the exemplar was recorded
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+}
+
+impl fmt::Display for Exemplar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "# {{trace_id=\"{}\"}} {} {}",
+            self.trace_id,
+            format_f64(self.value),
+            format_f64(unix_secs(self.timestamp))
+        )
+    }
+}
+
+/// Bounded exemplar store
+/// - cloning is cheap, i.e., clones share the same store
+#[derive(Clone)]
+pub struct ExemplarStore {
+    inner: Arc<ExemplarStoreInner>,
+}
+
+struct ExemplarStoreInner {
+    exemplars: RwLock<Exemplars>,
+    max_exemplars: AtomicUsize,
+}
+
+/// Exemplars are tracked in the order they were recorded, which makes evicting the least
+/// recently recorded exemplar O(log n)
+#[derive(Default)]
+struct Exemplars {
+    entries: HashMap<ExemplarKey, (u64, Exemplar)>,
+    /// record sequence -> key, i.e., the first entry is the least recently recorded
+    recorded: BTreeMap<u64, ExemplarKey>,
+    seq: u64,
+}
+
+impl Exemplars {
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn get(&self, key: &ExemplarKey) -> Option<&Exemplar> {
+        self.entries.get(key).map(|(_, exemplar)| exemplar)
+    }
+
+    fn insert(&mut self, key: ExemplarKey, exemplar: Exemplar) {
+        self.seq += 1;
+        if let Some((seq, _)) = self.entries.insert(key.clone(), (self.seq, exemplar)) {
+            self.recorded.remove(&seq);
+        }
+        self.recorded.insert(self.seq, key);
+    }
+
+    fn evict_oldest(&mut self) {
+        let oldest = self.recorded.keys().next().cloned();
+        if let Some(seq) = oldest {
+            if let Some(key) = self.recorded.remove(&seq) {
+                self.entries.remove(&key);
+            }
+        }
+    }
+
+    fn retain<F: Fn(&ExemplarKey) -> bool>(&mut self, f: F) {
+        let recorded = &mut self.recorded;
+        self.entries.retain(|key, entry| {
+            let retain = f(key);
+            if !retain {
+                recorded.remove(&entry.0);
+            }
+            retain
+        });
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.recorded.clear();
+    }
+}
+
+impl Default for ExemplarStore {
+    fn default() -> ExemplarStore {
+        ExemplarStore::new(DEFAULT_MAX_EXEMPLARS)
+    }
+}
+
+impl ExemplarStore {
+    /// constructor
+    pub fn new(max_exemplars: usize) -> ExemplarStore {
+        ExemplarStore {
+            inner: Arc::new(ExemplarStoreInner {
+                exemplars: RwLock::new(Exemplars::default()),
+                max_exemplars: AtomicUsize::new(max_exemplars),
+            }),
+        }
+    }
+
+    /// Max number of exemplars that are retained
+    pub fn max_exemplars(&self) -> usize {
+        self.inner.max_exemplars.load(Ordering::Relaxed)
+    }
+
+    /// Sets the max number of exemplars that are retained
+    /// - lowering the limit takes effect on the next exemplar that is recorded
+    pub fn set_max_exemplars(&self, max_exemplars: usize) {
+        self.inner
+            .max_exemplars
+            .store(max_exemplars, Ordering::Relaxed);
+    }
+
+    /// Number of exemplars that are retained
+    pub fn len(&self) -> usize {
+        self.inner.exemplars.read().len()
+    }
+
+    /// Returns true if no exemplars are retained
+    pub fn is_empty(&self) -> bool {
+        self.inner.exemplars.read().len() == 0
+    }
+
+    /// Returns the latest exemplar for the series
+    /// - for histograms, the bucket upper bound must be specified
+    pub fn exemplar(&self, series: &SeriesKey, bucket: Option<f64>) -> Option<Exemplar> {
+        self.inner
+            .exemplars
+            .read()
+            .get(&ExemplarKey::new(series.clone(), bucket))
+            .cloned()
+    }
+
+    /// Returns a handle that increments the counter and records exemplars for it
+    pub fn counter(&self, counter: prometheus::Counter) -> ExemplarCounter {
+        ExemplarCounter {
+            series: series_key(&counter),
+            counter,
+            store: self.clone(),
+        }
+    }
+
+    /// Returns a handle that increments the counter and records exemplars for it
+    pub fn int_counter(&self, counter: prometheus::IntCounter) -> ExemplarIntCounter {
+        ExemplarIntCounter {
+            series: series_key(&counter),
+            counter,
+            store: self.clone(),
+        }
+    }
+
+    /// Returns a handle that observes values and records exemplars for the histogram bucket that
+    /// the value falls into
+    pub fn histogram(&self, histogram: prometheus::Histogram) -> ExemplarHistogram {
+        let metric = histogram.metric();
+        let series = histogram
+            .desc()
+            .first()
+            .map(|desc| SeriesKey::new(&desc.fq_name, metric.get_label()))
+            .unwrap_or_else(|| SeriesKey::new("", &[]));
+        let upper_bounds = metric
+            .get_histogram()
+            .get_bucket()
+            .iter()
+            .map(|bucket| bucket.get_upper_bound())
+            .collect();
+        ExemplarHistogram {
+            series,
+            upper_bounds,
+            histogram,
+            store: self.clone(),
+        }
+    }
+
+    /// Records the exemplar - if the store is full, then the least recently recorded exemplar is
+    /// evicted
+    fn record(&self, series: &SeriesKey, bucket: Option<f64>, exemplar: Exemplar) {
+        let key = ExemplarKey::new(series.clone(), bucket);
+        let max_exemplars = self.max_exemplars();
+        let mut exemplars = self.inner.exemplars.write();
+        if exemplars.get(&key).is_none() {
+            if max_exemplars == 0 {
+                exemplars.clear();
+                return;
+            }
+            while exemplars.len() >= max_exemplars {
+                exemplars.evict_oldest();
+            }
+        }
+        exemplars.insert(key, exemplar);
+    }
+
+    /// Removes exemplars for series that are not contained by the metric families
+    pub fn retain_series(&self, metric_families: &[MetricFamily]) {
+        let series: HashSet<SeriesKey> = metric_families
+            .iter()
+            .flat_map(|mf| {
+                mf.get_metric()
+                    .iter()
+                    .map(move |metric| SeriesKey::new(mf.get_name(), metric.get_label()))
+            })
+            .collect();
+        self.inner
+            .exemplars
+            .write()
+            .retain(|key| series.contains(&key.series));
+    }
+
+    /// Removes all exemplars
+    pub fn clear(&self) {
+        self.inner.exemplars.write().clear();
+    }
+}
+
+impl fmt::Debug for ExemplarStore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ExemplarStore(len = {}, max_exemplars = {})",
+            self.len(),
+            self.max_exemplars()
+        )
+    }
+}
+
+fn series_key<M: Collector + Metric>(metric: &M) -> SeriesKey {
+    metric
+        .desc()
+        .first()
+        .map(|desc| SeriesKey::for_metric(&desc.fq_name, metric))
+        .unwrap_or_else(|| SeriesKey::new("", &[]))
+}
+
+/// Counter handle that records exemplars
+/// - the series key is computed once, i.e., the handle should be reused
+#[derive(Debug, Clone)]
+pub struct ExemplarCounter {
+    counter: prometheus::Counter,
+    series: SeriesKey,
+    store: ExemplarStore,
+}
+
+impl ExemplarCounter {
+    /// Increments the counter and records an exemplar for it
+    pub fn inc_by(&self, value: f64, trace_id: ULID) {
+        self.counter.inc_by(value);
+        self.store
+            .record(&self.series, None, Exemplar::new(trace_id, value));
+    }
+
+    /// the counter
+    pub fn counter(&self) -> &prometheus::Counter {
+        &self.counter
+    }
+}
+
+/// IntCounter handle that records exemplars
+/// - the series key is computed once, i.e., the handle should be reused
+#[derive(Debug, Clone)]
+pub struct ExemplarIntCounter {
+    counter: prometheus::IntCounter,
+    series: SeriesKey,
+    store: ExemplarStore,
+}
+
+impl ExemplarIntCounter {
+    /// Increments the counter and records an exemplar for it
+    pub fn inc_by(&self, value: i64, trace_id: ULID) {
+        self.counter.inc_by(value);
+        self.store
+            .record(&self.series, None, Exemplar::new(trace_id, value as f64));
+    }
+
+    /// the counter
+    pub fn counter(&self) -> &prometheus::IntCounter {
+        &self.counter
+    }
+}
+
+/// Histogram handle that records exemplars
+/// - the series key and bucket upper bounds are computed once, i.e., the handle should be reused
+#[derive(Debug, Clone)]
+pub struct ExemplarHistogram {
+    histogram: prometheus::Histogram,
+    series: SeriesKey,
+    upper_bounds: Vec<f64>,
+    store: ExemplarStore,
+}
+
+impl ExemplarHistogram {
+    /// Observes the value and records an exemplar for the histogram bucket that the value falls into
+    pub fn observe(&self, value: f64, trace_id: ULID) {
+        self.histogram.observe(value);
+        let bucket = self
+            .upper_bounds
+            .iter()
+            .cloned()
+            .find(|upper_bound| value <= *upper_bound)
+            .unwrap_or(std::f64::INFINITY);
+        self.store
+            .record(&self.series, Some(bucket), Exemplar::new(trace_id, value));
+    }
+
+    /// the histogram
+    pub fn histogram(&self) -> &prometheus::Histogram {
+        &self.histogram
+    }
+}
+
+/// Encodes metrics using the OpenMetrics text format
+#[derive(Debug, Clone, Default)]
+pub struct OpenMetricsEncoder {
+    created: HashMap<SeriesKey, SystemTime>,
+    exemplars: Option<ExemplarStore>,
+}
+
+impl OpenMetricsEncoder {
+    /// constructor
+    pub fn new() -> OpenMetricsEncoder {
+        OpenMetricsEncoder::default()
+    }
+
+    /// Series creation times, which are used to report `_created` series
+    pub fn with_created(self, created: HashMap<SeriesKey, SystemTime>) -> OpenMetricsEncoder {
+        OpenMetricsEncoder { created, ..self }
+    }
+
+    /// Exemplars that are reported for counters and histograms
+    pub fn with_exemplars(self, exemplars: ExemplarStore) -> OpenMetricsEncoder {
+        OpenMetricsEncoder {
+            exemplars: Some(exemplars),
+            ..self
+        }
+    }
+
+    fn exemplar(&self, series: &SeriesKey, bucket: Option<f64>) -> Option<Exemplar> {
+        self.exemplars
+            .as_ref()
+            .and_then(|exemplars| exemplars.exemplar(series, bucket))
+    }
+
+    fn encode_family<W: Write>(&self, mf: &MetricFamily, writer: &mut W) -> prometheus::Result<()> {
+        let metric_type = mf.get_field_type();
+        let name = match metric_type {
+            MetricType::COUNTER => mf.get_name().trim_end_matches("_total"),
+            _ => mf.get_name(),
+        };
+        let type_name = match metric_type {
+            MetricType::COUNTER => "counter",
+            MetricType::GAUGE => "gauge",
+            MetricType::SUMMARY => "summary",
+            MetricType::HISTOGRAM => "histogram",
+            MetricType::UNTYPED => "unknown",
+        };
+        writeln!(writer, "# TYPE {} {}", name, type_name)?;
+        if let Some(unit) = unit(name) {
+            writeln!(writer, "# UNIT {} {}", name, unit)?;
+        }
+        if !mf.get_help().is_empty() {
+            writeln!(writer, "# HELP {} {}", name, escape_help(mf.get_help()))?;
+        }
+
+        for metric in mf.get_metric() {
+            let labels = metric.get_label();
+            let series = SeriesKey::new(mf.get_name(), labels);
+            let created = self.created.get(&series).cloned().map(unix_secs);
+            match metric_type {
+                MetricType::COUNTER => {
+                    let sample_name = format!("{}_total", name);
+                    write_sample(
+                        writer,
+                        &sample_name,
+                        labels,
+                        None,
+                        metric.get_counter().get_value(),
+                    )?;
+                    write_exemplar(writer, self.exemplar(&series, None))?;
+                    write_created(writer, name, labels, created)?;
+                }
+                MetricType::GAUGE => {
+                    write_sample(writer, name, labels, None, metric.get_gauge().get_value())?;
+                    writeln!(writer)?;
+                }
+                MetricType::UNTYPED => {
+                    write_sample(writer, name, labels, None, metric.get_untyped().get_value())?;
+                    writeln!(writer)?;
+                }
+                MetricType::SUMMARY => {
+                    let summary = metric.get_summary();
+                    for quantile in summary.get_quantile() {
+                        write_sample(
+                            writer,
+                            name,
+                            labels,
+                            Some(("quantile", quantile.get_quantile())),
+                            quantile.get_value(),
+                        )?;
+                        writeln!(writer)?;
+                    }
+                    write_sample(
+                        writer,
+                        &format!("{}_sum", name),
+                        labels,
+                        None,
+                        summary.get_sample_sum(),
+                    )?;
+                    writeln!(writer)?;
+                    write_sample(
+                        writer,
+                        &format!("{}_count", name),
+                        labels,
+                        None,
+                        summary.get_sample_count() as f64,
+                    )?;
+                    writeln!(writer)?;
+                    write_created(writer, name, labels, created)?;
+                }
+                MetricType::HISTOGRAM => {
+                    let histogram = metric.get_histogram();
+                    let bucket_name = format!("{}_bucket", name);
+                    let mut inf_bucket_reported = false;
+                    for bucket in histogram.get_bucket() {
+                        let upper_bound = bucket.get_upper_bound();
+                        inf_bucket_reported |= upper_bound == std::f64::INFINITY;
+                        write_sample(
+                            writer,
+                            &bucket_name,
+                            labels,
+                            Some(("le", upper_bound)),
+                            bucket.get_cumulative_count() as f64,
+                        )?;
+                        write_exemplar(writer, self.exemplar(&series, Some(upper_bound)))?;
+                    }
+                    if !inf_bucket_reported {
+                        write_sample(
+                            writer,
+                            &bucket_name,
+                            labels,
+                            Some(("le", std::f64::INFINITY)),
+                            histogram.get_sample_count() as f64,
+                        )?;
+                        write_exemplar(writer, self.exemplar(&series, Some(std::f64::INFINITY)))?;
+                    }
+                    write_sample(
+                        writer,
+                        &format!("{}_sum", name),
+                        labels,
+                        None,
+                        histogram.get_sample_sum(),
+                    )?;
+                    writeln!(writer)?;
+                    write_sample(
+                        writer,
+                        &format!("{}_count", name),
+                        labels,
+                        None,
+                        histogram.get_sample_count() as f64,
+                    )?;
+                    writeln!(writer)?;
+                    write_created(writer, name, labels, created)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl prometheus::Encoder for OpenMetricsEncoder {
+    fn encode<W: Write>(
+        &self,
+        metric_families: &[MetricFamily],
+        writer: &mut W,
+    ) -> prometheus::Result<()> {
+        for mf in metric_families {
+            if mf.get_metric().is_empty() {
+                continue;
+            }
+            self.encode_family(mf, writer)?;
+        }
+        writeln!(writer, "# EOF")?;
+        Ok(())
+    }
+
+    fn format_type(&self) -> &str {
+        OPENMETRICS_TEXT_FORMAT
+    }
+}
+
+/// Metrics exposition text formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextFormat {
+    /// Prometheus text format 0.0.4
+    Prometheus,
+    /// OpenMetrics text format 1.0.0
+    OpenMetrics,
+}
+
+impl TextFormat {
+    /// Selects the text format based on the HTTP `Accept` header value.
+    /// - media ranges are weighted using their `q` parameter - on ties, the first listed wins
+    /// - defaults to the Prometheus text format if the header is not specified or no supported
+    ///   media range is found
+    pub fn negotiate(accept: Option<&str>) -> TextFormat {
+        let mut selected: Option<(TextFormat, f32)> = None;
+        for media_range in accept.unwrap_or("").split(',') {
+            let mut params = media_range.split(';').map(str::trim);
+            let format = match params.next().map(str::to_lowercase) {
+                Some(ref media_type) if media_type == "application/openmetrics-text" => {
+                    TextFormat::OpenMetrics
+                }
+                Some(ref media_type) if media_type == "text/plain" => TextFormat::Prometheus,
+                _ => continue,
+            };
+            let q = params
+                .filter_map(|param| {
+                    let mut kv = param.splitn(2, '=').map(str::trim);
+                    match (kv.next(), kv.next()) {
+                        (Some("q"), Some(q)) => q.parse::<f32>().ok(),
+                        _ => None,
+                    }
+                })
+                .next()
+                .unwrap_or(1.0);
+            if q <= 0.0 {
+                continue;
+            }
+            if selected.map_or(true, |(_, selected_q)| q > selected_q) {
+                selected = Some((format, q));
+            }
+        }
+        selected.map_or(TextFormat::Prometheus, |(format, _)| format)
+    }
+
+    /// Returns the HTTP content type for the format
+    pub fn content_type(self) -> &'static str {
+        match self {
+            TextFormat::Prometheus => prometheus::TEXT_FORMAT,
+            TextFormat::OpenMetrics => OPENMETRICS_TEXT_FORMAT,
+        }
+    }
+}
+
+impl Default for TextFormat {
+    fn default() -> TextFormat {
+        TextFormat::Prometheus
+    }
+}
+
+/// Returns the unit if the metric name is suffixed with a recognized base unit
+fn unit(name: &str) -> Option<&'static str> {
+    UNITS
+        .iter()
+        .find(|unit| name.ends_with(&format!("_{}", unit)))
+        .cloned()
+}
+
+/// writes the sample without a line terminator, which enables an exemplar to be appended
+fn write_sample<W: Write>(
+    writer: &mut W,
+    name: &str,
+    labels: &[LabelPair],
+    extra_label: Option<(&str, f64)>,
+    value: f64,
+) -> prometheus::Result<()> {
+    write!(writer, "{}", name)?;
+    if !labels.is_empty() || extra_label.is_some() {
+        let labels = labels
+            .iter()
+            .map(|label| {
+                format!(
+                    "{}=\"{}\"",
+                    label.get_name(),
+                    escape_label_value(label.get_value())
+                )
+            })
+            .chain(extra_label.map(|(name, value)| format!("{}=\"{}\"", name, format_f64(value))))
+            .collect::<Vec<_>>();
+        write!(writer, "{{{}}}", labels.join(","))?;
+    }
+    write!(writer, " {}", format_f64(value))?;
+    Ok(())
+}
+
+fn write_exemplar<W: Write>(writer: &mut W, exemplar: Option<Exemplar>) -> prometheus::Result<()> {
+    match exemplar {
+        Some(exemplar) => writeln!(writer, " {}", exemplar)?,
+        None => writeln!(writer)?,
+    }
+    Ok(())
+}
+
+fn write_created<W: Write>(
+    writer: &mut W,
+    name: &str,
+    labels: &[LabelPair],
+    created: Option<f64>,
+) -> prometheus::Result<()> {
+    if let Some(created) = created {
+        write_sample(writer, &format!("{}_created", name), labels, None, created)?;
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+fn format_f64(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value.is_sign_positive() {
+            "+Inf".to_string()
+        } else {
+            "-Inf".to_string()
+        }
+    } else {
+        format!("{:?}", value)
+    }
+}
+
+fn unix_secs(time: SystemTime) -> f64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(super::duration_as_secs_f64)
+        .unwrap_or(0.0)
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn escape_help(help: &str) -> String {
+    help.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[allow(warnings)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::Encoder;
+
+    #[test]
+    fn negotiate_text_format() {
+        assert_eq!(TextFormat::negotiate(None), TextFormat::Prometheus);
+        assert_eq!(TextFormat::negotiate(Some("*/*")), TextFormat::Prometheus);
+        assert_eq!(
+            TextFormat::negotiate(Some(
+                "application/openmetrics-text; version=1.0.0,text/plain;version=0.0.4;q=0.5,*/*;q=0.1"
+            )),
+            TextFormat::OpenMetrics
+        );
+        assert_eq!(
+            TextFormat::negotiate(Some(
+                "application/openmetrics-text;q=0.3,text/plain;version=0.0.4;q=0.5"
+            )),
+            TextFormat::Prometheus
+        );
+        assert_eq!(
+            TextFormat::negotiate(Some("application/openmetrics-text;q=0")),
+            TextFormat::Prometheus
+        );
+        assert_eq!(
+            TextFormat::OpenMetrics.content_type(),
+            OPENMETRICS_TEXT_FORMAT
+        );
+    }
+
+    #[test]
+    fn encode_counter_with_exemplar() {
+        let registry = prometheus::Registry::new();
+        let counter =
+            prometheus::IntCounter::new("openmetrics_counter_test", "counter help").unwrap();
+        registry.register(Box::new(counter.clone())).unwrap();
+        let exemplars = ExemplarStore::default();
+        let trace_id = ULID::generate();
+        exemplars.int_counter(counter.clone()).inc_by(5, trace_id);
+
+        let mut created = HashMap::new();
+        created.insert(SeriesKey::new("openmetrics_counter_test", &[]), UNIX_EPOCH);
+        let encoder = OpenMetricsEncoder::new()
+            .with_created(created)
+            .with_exemplars(exemplars);
+        let mut buf = Vec::new();
+        encoder.encode(&registry.gather(), &mut buf).unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        println!("{}", text);
+        assert!(text.contains("# TYPE openmetrics_counter_test counter\n"));
+        assert!(text.contains(&format!(
+            "openmetrics_counter_test_total 5.0 # {{trace_id=\"{}\"}} 5.0 ",
+            trace_id
+        )));
+        assert!(text.contains("openmetrics_counter_test_created 0.0\n"));
+        assert!(text.ends_with("# EOF\n"));
+    }
+
+    #[test]
+    fn encode_histogram_with_exemplar_and_unit() {
+        let registry = prometheus::Registry::new();
+        let histogram = prometheus::Histogram::with_opts(
+            prometheus::HistogramOpts::new("openmetrics_histogram_test_seconds", "histogram help")
+                .buckets(vec![0.1, 1.0]),
+        )
+        .unwrap();
+        registry.register(Box::new(histogram.clone())).unwrap();
+        let exemplars = ExemplarStore::default();
+        let trace_id = ULID::generate();
+        exemplars
+            .histogram(histogram.clone())
+            .observe(0.5, trace_id);
+        histogram.observe(5.0);
+
+        let mut buf = Vec::new();
+        OpenMetricsEncoder::new()
+            .with_exemplars(exemplars)
+            .encode(&registry.gather(), &mut buf)
+            .unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        println!("{}", text);
+        assert!(text.contains("# UNIT openmetrics_histogram_test_seconds seconds\n"));
+        assert!(text.contains("openmetrics_histogram_test_seconds_bucket{le=\"0.1\"} 0.0\n"));
+        assert!(text.contains(&format!(
+            "openmetrics_histogram_test_seconds_bucket{{le=\"1.0\"}} 1.0 # {{trace_id=\"{}\"}} 0.5 ",
+            trace_id
+        )));
+        assert!(text.contains("openmetrics_histogram_test_seconds_bucket{le=\"+Inf\"} 2.0\n"));
+        assert!(text.contains("openmetrics_histogram_test_seconds_count 2.0\n"));
+        assert!(!text.contains("_created"));
+        assert!(text.ends_with("# EOF\n"));
+    }
+
+    #[test]
+    fn exemplar_store_is_bounded() {
+        // GIVEN: a store that retains at most 2 exemplars
+        let exemplars = ExemplarStore::new(2);
+        let counter_vec = prometheus::IntCounterVec::new(
+            prometheus::Opts::new("exemplar_store_bounded_test", "help"),
+            &["id"],
+        )
+        .unwrap();
+        let counters: Vec<_> = (0..3)
+            .map(|i| {
+                exemplars.int_counter(counter_vec.with_label_values(&[i.to_string().as_str()]))
+            })
+            .collect();
+
+        // WHEN: exemplars are recorded for 3 series
+        for counter in counters.iter() {
+            counter.inc_by(1, ULID::generate());
+        }
+        // THEN: the least recently recorded exemplar is evicted
+        assert_eq!(exemplars.len(), 2);
+        let series = |i: usize| {
+            SeriesKey::for_metric(
+                "exemplar_store_bounded_test",
+                &counter_vec.with_label_values(&[i.to_string().as_str()]),
+            )
+        };
+        assert!(exemplars.exemplar(&series(0), None).is_none());
+        assert!(exemplars.exemplar(&series(1), None).is_some());
+        assert!(exemplars.exemplar(&series(2), None).is_some());
+
+        // WHEN: an exemplar is recorded for a series that is already tracked
+        counters[2].inc_by(1, ULID::generate());
+        // THEN: nothing is evicted
+        assert_eq!(exemplars.len(), 2);
+        assert!(exemplars.exemplar(&series(1), None).is_some());
+
+        // WHEN: series 1 is recorded again, and then series 0
+        counters[1].inc_by(1, ULID::generate());
+        counters[0].inc_by(1, ULID::generate());
+        // THEN: series 2 is evicted because it is now the least recently recorded
+        assert_eq!(exemplars.len(), 2);
+        assert!(exemplars.exemplar(&series(0), None).is_some());
+        assert!(exemplars.exemplar(&series(1), None).is_some());
+        assert!(exemplars.exemplar(&series(2), None).is_none());
+    }
+
+    #[test]
+    fn exemplar_store_prunes_series_that_are_not_gathered() {
+        // GIVEN: exemplars recorded for 2 label sets
+        let registry = prometheus::Registry::new();
+        let counter_vec = prometheus::IntCounterVec::new(
+            prometheus::Opts::new("exemplar_store_prune_test", "help"),
+            &["id"],
+        )
+        .unwrap();
+        registry.register(Box::new(counter_vec.clone())).unwrap();
+        let exemplars = ExemplarStore::default();
+        exemplars
+            .int_counter(counter_vec.with_label_values(&["a"]))
+            .inc_by(1, ULID::generate());
+        exemplars
+            .int_counter(counter_vec.with_label_values(&["b"]))
+            .inc_by(1, ULID::generate());
+        assert_eq!(exemplars.len(), 2);
+
+        // WHEN: a label set is removed
+        counter_vec.remove_label_values(&["a"]).unwrap();
+        exemplars.retain_series(&registry.gather());
+        // THEN: its exemplar is pruned
+        assert_eq!(exemplars.len(), 1);
+
+        // WHEN: the metric is no longer gathered
+        exemplars.retain_series(&[]);
+        // THEN: all of its exemplars are pruned
+        assert!(exemplars.is_empty());
+    }
+}
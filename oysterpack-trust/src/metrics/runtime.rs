@@ -0,0 +1,522 @@
+/*
+ * Copyright 2019 OysterPack Inc.
+ *
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Provides a Linux `/proc` based collector for process runtime metrics, which are reported per
+//! thread group.
+//!
+//! ## Features
+//! - the following metrics are collected from `/proc/self/task/{tid}/*` and summed per thread group
+//!   - thread count
+//!   - CPU seconds, split by user and system mode
+//!   - voluntary and nonvoluntary context switches
+//!   - I/O read and write bytes
+//!   - minor and major page faults
+//! - threads are grouped by thread name prefix - see [register_thread_group()](fn.register_thread_group.html)
+//!   - [Executor](../../concurrent/execution/struct.Executor.html) threads are grouped by ExecutorId,
+//!     i.e., the Executor thread name prefix `{ExecutorId}-` is registered once the Executor is registered
+//!   - threads that do not match any registered prefix are grouped under [OTHER_THREAD_GROUP](constant.OTHER_THREAD_GROUP.html)
+//! - the collector is registered automatically with the global [MetricRegistry](../struct.MetricRegistry.html)
+//!   on Linux
+//!   - on other platforms, no thread stats are collected
+//!
+//! ## Notes
+//! - Linux truncates thread names to 15 bytes, thus thread name prefixes are matched against the
+//!   first 15 bytes. Prefixes that share the same first 15 bytes cannot be distinguished. For
+//!   Executor threads, the first 15 bytes consist of the ULID's 10 char timestamp followed by 5
+//!   random chars, i.e., ExecutorIds would need to be generated within the same millisecond and
+//!   share 25 random bits in order to collide.
+//! - CPU times are converted to seconds using the kernel's clock ticks per second, i.e., `sysconf(_SC_CLK_TCK)`
+//! - I/O stats may not be readable depending on the process permissions - in which case, they are
+//!   reported as zero
+
+use super::{LabelId, MetricId};
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use prometheus::{
+    core::{Collector, Desc},
+    proto::{LabelPair, Metric, MetricFamily, MetricType},
+};
+use std::collections::{BTreeMap, HashMap};
+#[cfg(target_os = "linux")]
+use std::fs;
+
+/// Linux thread names are truncated to this length
+pub const THREAD_NAME_MAX_LEN: usize = 15;
+
+/// Clock ticks per second that is used if `sysconf(_SC_CLK_TCK)` fails or is not supported
+pub const DEFAULT_CLOCK_TICKS_PER_SEC: f64 = 100.0;
+
+/// Thread group label value for threads that do not belong to a registered thread group
+pub const OTHER_THREAD_GROUP: &str = "other";
+
+/// MetricId for thread count gauge: `M01M57EQ99KF4NJYX8BM0Y5MMJV`
+pub const THREAD_COUNT_METRIC_ID: MetricId = MetricId(2166788487703552843805976436686869083);
+/// MetricId for CPU seconds counter: `M01M57EQ99P9P4KPJDB7WRX0M19`
+pub const THREAD_CPU_SECONDS_METRIC_ID: MetricId = MetricId(2166788487706973571985998723071103017);
+/// MetricId for context switch counter: `M01M57EQ99RGCJ08WGR2D2ZXGDC`
+pub const THREAD_CONTEXT_SWITCHES_METRIC_ID: MetricId =
+    MetricId(2166788487709644564342362482707186092);
+/// MetricId for I/O bytes counter: `M01M57EQ99T6H47T85TKT9ZP88S`
+pub const THREAD_IO_BYTES_METRIC_ID: MetricId = MetricId(2166788487711690021808298342320120089);
+/// MetricId for page faults counter: `M01M57EQ99YX26E2MH3XF50BFJS`
+pub const THREAD_PAGE_FAULTS_METRIC_ID: MetricId = MetricId(2166788487717377012651937865398599257);
+/// The thread group will be used as the label value: `L01M57EQ9A0D7JNXHEM5EHSDBGW`
+pub const THREAD_GROUP_LABEL_ID: LabelId = LabelId(2166788487719196756101083830588714524);
+/// The mode will be used as the label value, e.g., `user` or `system` for CPU seconds: `L01M57EQ9A29CBX8697BAS4R2YT`
+pub const MODE_LABEL_ID: LabelId = LabelId(2166788487721469145170740202493971418);
+
+lazy_static! {
+    /// thread name prefix -> thread group
+    static ref THREAD_GROUPS: RwLock<BTreeMap<String, String>> = RwLock::new(BTreeMap::new());
+
+    /// Clock ticks per second, i.e., USER_HZ, which is used to report CPU times in `/proc`
+    static ref CLOCK_TICKS_PER_SEC: f64 = read_clock_ticks_per_sec();
+}
+
+#[cfg(target_os = "linux")]
+fn read_clock_ticks_per_sec() -> f64 {
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks > 0 {
+        ticks as f64
+    } else {
+        DEFAULT_CLOCK_TICKS_PER_SEC
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_clock_ticks_per_sec() -> f64 {
+    DEFAULT_CLOCK_TICKS_PER_SEC
+}
+
+/// Returns the clock ticks per second, i.e., USER_HZ, which is used to report CPU times in `/proc`
+pub fn clock_ticks_per_sec() -> f64 {
+    *CLOCK_TICKS_PER_SEC
+}
+
+/// Registers a thread group using the specified thread name prefix.
+/// - the prefix is truncated to [THREAD_NAME_MAX_LEN](constant.THREAD_NAME_MAX_LEN.html)
+/// - if more than 1 prefix matches a thread name, then the longest prefix wins
+pub fn register_thread_group(thread_name_prefix: &str, thread_group: &str) {
+    let prefix = truncate(thread_name_prefix, THREAD_NAME_MAX_LEN);
+    THREAD_GROUPS
+        .write()
+        .insert(prefix.to_string(), thread_group.to_string());
+}
+
+/// Unregisters the thread group for the specified thread name prefix
+pub fn unregister_thread_group(thread_name_prefix: &str) {
+    let prefix = truncate(thread_name_prefix, THREAD_NAME_MAX_LEN);
+    THREAD_GROUPS.write().remove(prefix);
+}
+
+/// Returns the thread group for the specified thread name
+pub fn thread_group(thread_name: &str) -> String {
+    let thread_name = truncate(thread_name, THREAD_NAME_MAX_LEN);
+    THREAD_GROUPS
+        .read()
+        .iter()
+        .filter(|(prefix, _)| thread_name.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map_or_else(
+            || OTHER_THREAD_GROUP.to_string(),
+            |(_, group)| group.clone(),
+        )
+}
+
+fn truncate(s: &str, max_len: usize) -> &str {
+    if s.len() <= max_len {
+        return s;
+    }
+    let mut end = max_len;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+/// Thread stats read from `/proc/self/task/{tid}`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ThreadStats {
+    /// number of threads
+    pub thread_count: u64,
+    /// CPU seconds spent in user mode
+    pub user_cpu_seconds: f64,
+    /// CPU seconds spent in system mode
+    pub system_cpu_seconds: f64,
+    /// voluntary context switches
+    pub voluntary_context_switches: u64,
+    /// nonvoluntary context switches
+    pub nonvoluntary_context_switches: u64,
+    /// bytes read from storage
+    pub read_bytes: u64,
+    /// bytes written to storage
+    pub write_bytes: u64,
+    /// minor page faults
+    pub minor_page_faults: u64,
+    /// major page faults
+    pub major_page_faults: u64,
+}
+
+impl ThreadStats {
+    #[cfg(target_os = "linux")]
+    fn add(&mut self, other: &ThreadStats) {
+        self.thread_count += other.thread_count;
+        self.user_cpu_seconds += other.user_cpu_seconds;
+        self.system_cpu_seconds += other.system_cpu_seconds;
+        self.voluntary_context_switches += other.voluntary_context_switches;
+        self.nonvoluntary_context_switches += other.nonvoluntary_context_switches;
+        self.read_bytes += other.read_bytes;
+        self.write_bytes += other.write_bytes;
+        self.minor_page_faults += other.minor_page_faults;
+        self.major_page_faults += other.major_page_faults;
+    }
+
+    /// Reads the stats for the specified thread
+    /// - returns None if the thread stats could not be read, e.g., the thread has exited
+    #[cfg(target_os = "linux")]
+    fn read(tid: &str) -> Option<(String, ThreadStats)> {
+        let task_dir = format!("/proc/self/task/{}", tid);
+        let name = fs::read_to_string(format!("{}/comm", task_dir))
+            .ok()?
+            .trim_end()
+            .to_string();
+
+        let mut stats = ThreadStats {
+            thread_count: 1,
+            ..ThreadStats::default()
+        };
+
+        // the thread name may contain spaces and parens, thus the fields are parsed after the last ')'
+        let stat = fs::read_to_string(format!("{}/stat", task_dir)).ok()?;
+        let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+        let field = |i: usize| fields.get(i).and_then(|field| field.parse::<u64>().ok());
+        // field indexes are offset by 3 relative to proc(5), i.e., `state` (field 3) is at index 0
+        stats.minor_page_faults = field(7)?;
+        stats.major_page_faults = field(9)?;
+        stats.user_cpu_seconds = field(11)? as f64 / clock_ticks_per_sec();
+        stats.system_cpu_seconds = field(12)? as f64 / clock_ticks_per_sec();
+
+        if let Ok(status) = fs::read_to_string(format!("{}/status", task_dir)) {
+            let values = parse_key_values(&status);
+            stats.voluntary_context_switches =
+                values.get("voluntary_ctxt_switches").cloned().unwrap_or(0);
+            stats.nonvoluntary_context_switches = values
+                .get("nonvoluntary_ctxt_switches")
+                .cloned()
+                .unwrap_or(0);
+        }
+
+        if let Ok(io) = fs::read_to_string(format!("{}/io", task_dir)) {
+            let values = parse_key_values(&io);
+            stats.read_bytes = values.get("read_bytes").cloned().unwrap_or(0);
+            stats.write_bytes = values.get("write_bytes").cloned().unwrap_or(0);
+        }
+
+        Some((name, stats))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn parse_key_values(text: &str) -> HashMap<&str, u64> {
+    text.lines()
+        .filter_map(|line| {
+            let mut kv = line.splitn(2, ':');
+            match (kv.next(), kv.next()) {
+                (Some(key), Some(value)) => value
+                    .trim()
+                    .parse::<u64>()
+                    .ok()
+                    .map(|value| (key.trim(), value)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Reads the thread stats for the current process and sums them per thread group
+#[cfg(target_os = "linux")]
+pub fn thread_group_stats() -> BTreeMap<String, ThreadStats> {
+    let mut groups = BTreeMap::new();
+    let tasks = match fs::read_dir("/proc/self/task") {
+        Ok(tasks) => tasks,
+        Err(_) => return groups,
+    };
+    for task in tasks.filter_map(Result::ok) {
+        let tid = task.file_name();
+        let tid = match tid.to_str() {
+            Some(tid) => tid,
+            None => continue,
+        };
+        if let Some((name, stats)) = ThreadStats::read(tid) {
+            groups
+                .entry(thread_group(&name))
+                .or_insert_with(ThreadStats::default)
+                .add(&stats);
+        }
+    }
+    groups
+}
+
+/// Thread stats are only collected on Linux - on other platforms, no thread groups are reported
+#[cfg(not(target_os = "linux"))]
+pub fn thread_group_stats() -> BTreeMap<String, ThreadStats> {
+    BTreeMap::new()
+}
+
+/// Collects runtime metrics from `/proc` per thread group
+#[derive(Debug)]
+pub struct RuntimeCollector {
+    descs: Vec<Desc>,
+}
+
+impl RuntimeCollector {
+    /// constructor
+    pub fn new() -> RuntimeCollector {
+        let thread_group_label = THREAD_GROUP_LABEL_ID.name();
+        let mode_label = MODE_LABEL_ID.name();
+        let desc = |metric_id: MetricId, help: &str, labels: Vec<String>| {
+            Desc::new(metric_id.name(), help.to_string(), labels, HashMap::new()).unwrap()
+        };
+        RuntimeCollector {
+            descs: vec![
+                desc(
+                    THREAD_COUNT_METRIC_ID,
+                    "Thread count",
+                    vec![thread_group_label.clone()],
+                ),
+                desc(
+                    THREAD_CPU_SECONDS_METRIC_ID,
+                    "Thread CPU seconds",
+                    vec![thread_group_label.clone(), mode_label.clone()],
+                ),
+                desc(
+                    THREAD_CONTEXT_SWITCHES_METRIC_ID,
+                    "Thread context switch count",
+                    vec![thread_group_label.clone(), mode_label.clone()],
+                ),
+                desc(
+                    THREAD_IO_BYTES_METRIC_ID,
+                    "Thread I/O bytes",
+                    vec![thread_group_label.clone(), mode_label.clone()],
+                ),
+                desc(
+                    THREAD_PAGE_FAULTS_METRIC_ID,
+                    "Thread page fault count",
+                    vec![thread_group_label, mode_label],
+                ),
+            ],
+        }
+    }
+}
+
+impl Default for RuntimeCollector {
+    fn default() -> RuntimeCollector {
+        RuntimeCollector::new()
+    }
+}
+
+impl Collector for RuntimeCollector {
+    fn desc(&self) -> Vec<&Desc> {
+        self.descs.iter().collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let groups = thread_group_stats();
+        let mut thread_count = metric_family(&self.descs[0], MetricType::GAUGE);
+        let mut cpu_seconds = metric_family(&self.descs[1], MetricType::COUNTER);
+        let mut context_switches = metric_family(&self.descs[2], MetricType::COUNTER);
+        let mut io_bytes = metric_family(&self.descs[3], MetricType::COUNTER);
+        let mut page_faults = metric_family(&self.descs[4], MetricType::COUNTER);
+        for (group, stats) in &groups {
+            push_gauge(&mut thread_count, group, None, stats.thread_count as f64);
+            push_counter(&mut cpu_seconds, group, "user", stats.user_cpu_seconds);
+            push_counter(&mut cpu_seconds, group, "system", stats.system_cpu_seconds);
+            push_counter(
+                &mut context_switches,
+                group,
+                "voluntary",
+                stats.voluntary_context_switches as f64,
+            );
+            push_counter(
+                &mut context_switches,
+                group,
+                "nonvoluntary",
+                stats.nonvoluntary_context_switches as f64,
+            );
+            push_counter(&mut io_bytes, group, "read", stats.read_bytes as f64);
+            push_counter(&mut io_bytes, group, "write", stats.write_bytes as f64);
+            push_counter(
+                &mut page_faults,
+                group,
+                "minor",
+                stats.minor_page_faults as f64,
+            );
+            push_counter(
+                &mut page_faults,
+                group,
+                "major",
+                stats.major_page_faults as f64,
+            );
+        }
+        vec![
+            thread_count,
+            cpu_seconds,
+            context_switches,
+            io_bytes,
+            page_faults,
+        ]
+    }
+}
+
+fn metric_family(desc: &Desc, metric_type: MetricType) -> MetricFamily {
+    let mut mf = MetricFamily::new();
+    mf.set_name(desc.fq_name.clone());
+    mf.set_help(desc.help.clone());
+    mf.set_field_type(metric_type);
+    mf
+}
+
+fn new_metric(group: &str, mode: Option<&str>) -> Metric {
+    let label_pair = |label_id: LabelId, value: &str| {
+        let mut label_pair = LabelPair::new();
+        label_pair.set_name(label_id.name());
+        label_pair.set_value(value.to_string());
+        label_pair
+    };
+    let mut metric = Metric::new();
+    metric
+        .mut_label()
+        .push(label_pair(THREAD_GROUP_LABEL_ID, group));
+    if let Some(mode) = mode {
+        metric.mut_label().push(label_pair(MODE_LABEL_ID, mode));
+    }
+    metric
+}
+
+fn push_gauge(mf: &mut MetricFamily, group: &str, mode: Option<&str>, value: f64) {
+    let mut metric = new_metric(group, mode);
+    metric.mut_gauge().set_value(value);
+    mf.mut_metric().push(metric);
+}
+
+fn push_counter(mf: &mut MetricFamily, group: &str, mode: &str, value: f64) {
+    let mut metric = new_metric(group, Some(mode));
+    metric.mut_counter().set_value(value);
+    mf.mut_metric().push(metric);
+}
+
+#[allow(warnings)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configure_logging;
+    use oysterpack_log::*;
+    use std::thread;
+
+    #[test]
+    fn thread_group_prefix_matching() {
+        register_thread_group("01M57EQ9A41APFMJXPND57KAV1-", "01M57EQ9A41APFMJXPND57KAV1");
+        assert_eq!(
+            thread_group("01M57EQ9A41APFMJXPND57KAV1-3"),
+            "01M57EQ9A41APFMJXPND57KAV1"
+        );
+        // Linux truncates thread names to 15 bytes
+        assert_eq!(
+            thread_group("01M57EQ9A41APFM"),
+            "01M57EQ9A41APFMJXPND57KAV1"
+        );
+        assert_eq!(thread_group("main"), OTHER_THREAD_GROUP);
+
+        // the longest matching prefix wins
+        register_thread_group("prefix-test-", "prefix-test");
+        register_thread_group("prefix-test-io-", "prefix-test-io");
+        assert_eq!(thread_group("prefix-test-io-1"), "prefix-test-io");
+        assert_eq!(thread_group("prefix-test-1"), "prefix-test");
+        unregister_thread_group("prefix-test-io-");
+        assert_eq!(thread_group("prefix-test-io-1"), "prefix-test");
+        unregister_thread_group("prefix-test-");
+        assert_eq!(thread_group("prefix-test-1"), OTHER_THREAD_GROUP);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn clock_ticks_per_sec_is_read_from_sysconf() {
+        assert_eq!(
+            clock_ticks_per_sec(),
+            unsafe { libc::sysconf(libc::_SC_CLK_TCK) } as f64
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn executor_threads_are_grouped_by_executor_id() {
+        configure_logging();
+
+        // GIVEN: an Executor is registered
+        let executor_id = crate::concurrent::execution::ExecutorId::generate();
+        crate::concurrent::execution::ExecutorBuilder::new(executor_id)
+            .set_pool_size(std::num::NonZeroUsize::new(2).unwrap())
+            .register()
+            .unwrap();
+
+        // WHEN: the thread stats are read once the Executor threads have started
+        let thread_group = executor_id.to_string();
+        let mut thread_count = 0;
+        for _ in 0..100 {
+            thread_count = thread_group_stats()
+                .get(&thread_group)
+                .map_or(0, |stats| stats.thread_count);
+            if thread_count == 2 {
+                break;
+            }
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+        // THEN: the Executor threads are grouped by their ExecutorId thread name prefix
+        assert_eq!(thread_count, 2);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn runtime_collector() {
+        configure_logging();
+
+        register_thread_group("runtime-test-", "runtime-test");
+        let (tx, rx) = std::sync::mpsc::channel::<()>();
+        let handle = thread::Builder::new()
+            .name("runtime-test-1".to_string())
+            .spawn(move || {
+                let _ = rx.recv();
+            })
+            .unwrap();
+
+        let groups = thread_group_stats();
+        info!("{:#?}", groups);
+        assert_eq!(groups.get("runtime-test").unwrap().thread_count, 1);
+
+        let collector = RuntimeCollector::new();
+        let mfs = collector.collect();
+        assert_eq!(mfs.len(), collector.desc().len());
+        let thread_count = &mfs[0];
+        assert_eq!(thread_count.get_name(), THREAD_COUNT_METRIC_ID.name());
+        assert!(thread_count.get_metric().iter().any(|metric| {
+            metric.get_label()[0].get_value() == "runtime-test"
+                && metric.get_gauge().get_value() == 1.0
+        }));
+
+        tx.send(()).unwrap();
+        handle.join().unwrap();
+    }
+}
@@ -20,7 +20,11 @@ use super::*;
 use crate::configure_logging;
 use maplit::*;
 use oysterpack_log::*;
-use std::{collections::HashSet, thread, time::Duration};
+use std::{
+    collections::{BTreeMap, HashSet},
+    thread,
+    time::Duration,
+};
 
 const METRIC_ID_1: MetricId = MetricId(1871943882688894749067493983019708136);
 
@@ -778,8 +782,12 @@ fn metric_registry_gather() {
     };
     register_histograms();
 
-    // adding 1 because the ProcessCollector is automatically registered
-    assert_eq!(metric_ids.len() + 1, registry.collector_count());
+    // the ProcessCollector and RuntimeCollector (on Linux) are automatically registered
+    let auto_registered_count = if cfg!(target_os = "linux") { 2 } else { 1 };
+    assert_eq!(
+        metric_ids.len() + auto_registered_count,
+        registry.collector_count()
+    );
 
     let metrics = registry.gather();
     info!("{:#?}", metrics);
@@ -802,6 +810,138 @@ fn registry_gather_process_metrics() {
     assert!(process_metrics.start_time_seconds() > 0.0);
 }
 
+#[test]
+fn process_metrics_diff() {
+    configure_logging();
+
+    let metric_registry = MetricRegistry::default();
+    let earlier = metric_registry.gather_process_metrics();
+    // burn some CPU
+    let mut n: u64 = 0;
+    for i in 0..1_000_000 {
+        n = n.wrapping_add(i);
+    }
+    info!("n = {}", n);
+    let later = metric_registry.gather_process_metrics();
+
+    let diff = later.diff(&earlier);
+    info!("{:#?}", diff);
+    assert!(diff.cpu_seconds_total() >= 0.0);
+    // max fds and start time are not diffed
+    assert_eq!(diff.start_time_seconds(), later.start_time_seconds());
+    assert_eq!(diff.max_fds(), later.max_fds());
+
+    // a zero elapsed time produces zero rates
+    let rate = later.rate(&earlier, Duration::from_secs(0));
+    assert_eq!(rate.cpu_seconds_total(), 0.0);
+    assert_eq!(rate.start_time_seconds(), later.start_time_seconds());
+    assert_eq!(rate.max_fds(), later.max_fds());
+}
+
+#[test]
+fn metrics_snapshot_diff_and_rate() {
+    configure_logging();
+
+    let metric_registry = MetricRegistry::default();
+    let counter_id = MetricId::generate();
+    let counter = metric_registry
+        .register_int_counter(counter_id, "counter", None)
+        .unwrap();
+    let histogram_id = MetricId::generate();
+    let histogram = metric_registry
+        .register_histogram(histogram_id, "histogram", vec![1.0, 2.0, 5.0], None)
+        .unwrap();
+    let gauge_id = MetricId::generate();
+    let gauge = metric_registry
+        .register_int_gauge(gauge_id, "gauge", None)
+        .unwrap();
+
+    counter.inc_by(10);
+    histogram.observe(0.5);
+    gauge.set(5);
+    let earlier = metric_registry.snapshot();
+
+    thread::sleep(Duration::from_millis(10));
+    counter.inc_by(5);
+    histogram.observe(0.5);
+    histogram.observe(3.0);
+    gauge.set(7);
+    let later = metric_registry.snapshot();
+
+    let no_labels = BTreeMap::new();
+    let diff = later.diff(&earlier);
+    info!("{:#?}", diff);
+    assert!(diff.elapsed() >= Duration::from_millis(10));
+    // counter delta is computed
+    match diff.sample(&counter_id.name(), &no_labels).unwrap().value() {
+        MetricValue::Counter(delta) => assert_eq!(*delta, 5.0),
+        value => panic!("unexpected value: {:?}", value),
+    }
+    // histogram bucket deltas are computed
+    match diff
+        .sample(&histogram_id.name(), &no_labels)
+        .unwrap()
+        .value()
+    {
+        MetricValue::Histogram {
+            sample_count,
+            sample_sum,
+            buckets,
+        } => {
+            assert_eq!(*sample_count, 2.0);
+            assert_eq!(*sample_sum, 3.5);
+            assert_eq!(*buckets, vec![(1.0, 1.0), (2.0, 1.0), (5.0, 2.0)]);
+        }
+        value => panic!("unexpected value: {:?}", value),
+    }
+    // gauges are not diffed
+    assert!(diff.sample(&gauge_id.name(), &no_labels).is_none());
+
+    // rates are computed per second
+    let elapsed_secs = duration_as_secs_f64(later.elapsed_since(&earlier));
+    let rate = later.rate(&earlier);
+    match rate.sample(&counter_id.name(), &no_labels).unwrap().value() {
+        MetricValue::Counter(rate) => assert_eq!(*rate, 5.0 / elapsed_secs),
+        value => panic!("unexpected value: {:?}", value),
+    }
+
+    // snapshots are serializable
+    let json = serde_json::to_string(&later).unwrap();
+    let later_deserialized: MetricsSnapshot = serde_json::from_str(&json).unwrap();
+    assert_eq!(later_deserialized, later);
+}
+
+#[test]
+fn metrics_snapshot_diff_counter_reset() {
+    configure_logging();
+
+    let metric_registry = MetricRegistry::default();
+    let counter_id = MetricId::generate();
+    let counter = metric_registry
+        .register_counter(counter_id, "counter", None)
+        .unwrap();
+    counter.inc_by(10.0);
+    let earlier = metric_registry.snapshot();
+
+    // simulate a counter reset, e.g., the process restarted
+    let metric_registry = MetricRegistry::default();
+    let counter = metric_registry
+        .register_counter(counter_id, "counter", None)
+        .unwrap();
+    counter.inc_by(3.0);
+    let later = metric_registry.snapshot();
+
+    let diff = later.diff(&earlier);
+    match diff
+        .sample(&counter_id.name(), &BTreeMap::new())
+        .unwrap()
+        .value()
+    {
+        MetricValue::Counter(delta) => assert_eq!(*delta, 3.0),
+        value => panic!("unexpected value: {:?}", value),
+    }
+}
+
 #[test]
 fn registry_gather_metrics() {
     configure_logging();
@@ -1031,6 +1171,12 @@ fn registry_gather_metrics_by_name() {
     assert_eq!(collectors.len(), 2);
 }
 
+#[test]
+fn duration_as_secs_f64_whole_secs() {
+    assert_eq!(duration_as_secs_f64(Duration::from_millis(2500)), 2.5);
+    assert_eq!(duration_as_secs_f64(Duration::from_secs(3)), 3.0);
+}
+
 #[test]
 fn exponential_timer_buckets() {
     let buckets = super::exponential_timer_buckets(
@@ -1139,3 +1285,226 @@ fn timer_buckets() {
     println!("timer_buckets(vec![])-> {:?}", result);
     assert!(result.is_err());
 }
+
+#[test]
+fn guarded_int_counter_vec_cardinality_limit() {
+    configure_logging();
+
+    let metric_id = MetricId::generate();
+    let registry = MetricRegistry::default();
+    let counter_vec = registry
+        .register_guarded(
+            IntCounterVecBuilder::new(metric_id, "Guarded counter", vec![LabelId::generate()])
+                .with_cardinality_limit(2),
+        )
+        .unwrap();
+    assert_eq!(counter_vec.limit(), 2);
+
+    counter_vec.with_label_values(&["a"]).inc();
+    counter_vec.with_label_values(&["b"]).inc();
+    counter_vec.with_label_values(&["a"]).inc();
+    assert_eq!(counter_vec.cardinality(), 2);
+    assert_eq!(counter_vec.rejected_count(), 0);
+
+    // the limit has been reached, thus new label sets are mapped to the overflow series
+    counter_vec.with_label_values(&["c"]).inc();
+    counter_vec.with_label_values(&["d"]).inc();
+    assert_eq!(counter_vec.cardinality(), 2);
+    assert_eq!(counter_vec.rejected_count(), 2);
+    // rejected label sets are counted in the registry that the guard is registered with
+    let rejected =
+        registry.gather_for_metric_ids(&[cardinality::CARDINALITY_REJECTED_COUNTER_METRIC_ID]);
+    assert_eq!(rejected[0].get_metric()[0].get_counter().get_value(), 2.0);
+    assert_eq!(
+        counter_vec
+            .metric_vec()
+            .with_label_values(&[cardinality::OVERFLOW_LABEL_VALUE])
+            .get(),
+        2
+    );
+    assert_eq!(counter_vec.metric_vec().with_label_values(&["a"]).get(), 2);
+
+    // removing a label set frees up capacity
+    counter_vec.remove_label_values(&["a"]).unwrap();
+    assert_eq!(counter_vec.cardinality(), 1);
+    counter_vec.with_label_values(&["c"]).inc();
+    assert_eq!(counter_vec.cardinality(), 2);
+    assert_eq!(counter_vec.metric_vec().with_label_values(&["c"]).get(), 1);
+
+    let mfs = registry.gather_for_metric_ids(&[metric_id]);
+    assert_eq!(mfs[0].get_metric().len(), 3);
+}
+
+#[test]
+fn vec_builder_cardinality_limit_is_serializable() {
+    let builder = HistogramVecBuilder::new(
+        MetricId::generate(),
+        "Guarded histogram",
+        vec![0.1, 1.0],
+        vec![LabelId::generate()],
+    )
+    .with_cardinality_limit(10);
+    let json = serde_json::to_string(&builder).unwrap();
+    let builder: HistogramVecBuilder = serde_json::from_str(&json).unwrap();
+    let registry = MetricRegistry::default();
+    assert_eq!(registry.register_guarded(builder).unwrap().limit(), 10);
+}
+
+#[test]
+fn guarded_metric_vecs_are_bound_to_their_registry() {
+    configure_logging();
+
+    // GIVEN: 2 registries with different global cardinality limits
+    let registry_1 = MetricRegistry::default();
+    registry_1.set_cardinality_limit(1);
+    let registry_2 = MetricRegistry::default();
+    registry_2.set_cardinality_limit(3);
+    let label_id = LabelId::generate();
+    let counter_vec_1 = registry_1
+        .register_guarded(IntCounterVecBuilder::new(
+            MetricId::generate(),
+            "Guarded counter",
+            vec![label_id],
+        ))
+        .unwrap();
+    let counter_vec_2 = registry_2
+        .register_guarded(IntCounterVecBuilder::new(
+            MetricId::generate(),
+            "Guarded counter",
+            vec![label_id],
+        ))
+        .unwrap();
+
+    // WHEN: label sets are added to each guarded metric vec
+    for value in &["a", "b", "c", "d"] {
+        counter_vec_1.with_label_values(&[value]).inc();
+        counter_vec_2.with_label_values(&[value]).inc();
+    }
+
+    // THEN: each guarded metric vec is limited by its own registry's global limit
+    assert_eq!(counter_vec_1.cardinality(), 1);
+    assert_eq!(registry_1.cardinality(), 1);
+    assert_eq!(counter_vec_2.cardinality(), 3);
+    assert_eq!(registry_2.cardinality(), 3);
+    // AND: rejected label sets are counted by each registry's own rejected counter
+    assert_eq!(counter_vec_1.rejected_count(), 3);
+    assert_eq!(counter_vec_2.rejected_count(), 1);
+    // AND: the rejected counter is only registered once per registry
+    registry_2
+        .register_guarded(IntCounterVecBuilder::new(
+            MetricId::generate(),
+            "Guarded counter",
+            vec![label_id],
+        ))
+        .unwrap();
+
+    // WHEN: a label set is removed
+    counter_vec_2.remove_label_values(&["a"]).unwrap();
+    // THEN: only the registry that the guarded metric vec is registered with releases capacity
+    assert_eq!(registry_1.cardinality(), 1);
+    assert_eq!(registry_2.cardinality(), 2);
+}
+
+#[test]
+fn metric_registry_encode_metrics() {
+    configure_logging();
+
+    // GIVEN: a counter and a counter vec that are registered
+    let registry = MetricRegistry::default();
+    let counter_id = MetricId::generate();
+    let counter = registry
+        .register_int_counter(counter_id, "Counter", None)
+        .unwrap();
+    let counter_vec_id = MetricId::generate();
+    let counter_vec = registry
+        .register_int_counter_vec(counter_vec_id, "Counter vec", &[LabelId::generate()], None)
+        .unwrap();
+    // AND: exemplars are recorded
+    let trace_id = ULID::generate();
+    registry
+        .exemplars()
+        .int_counter(counter.clone())
+        .inc_by(2, trace_id);
+    registry
+        .exemplars()
+        .int_counter(counter_vec.with_label_values(&["a"]))
+        .inc_by(1, ULID::generate());
+    assert_eq!(registry.exemplars().len(), 2);
+
+    // WHEN: metrics are encoded without an Accept header
+    let mut buf = Vec::new();
+    let format = registry.encode_metrics(None, &mut buf).unwrap();
+    let text = String::from_utf8(buf).unwrap();
+    // THEN: the prometheus text format is used
+    assert_eq!(format, openmetrics::TextFormat::Prometheus);
+    assert!(text.contains(&format!("{} 2\n", counter_id)));
+    assert!(!text.contains("# EOF"));
+
+    // WHEN: metrics are encoded using the OpenMetrics format
+    let mut buf = Vec::new();
+    let format = registry
+        .encode_metrics(Some(openmetrics::OPENMETRICS_TEXT_FORMAT), &mut buf)
+        .unwrap();
+    let text = String::from_utf8(buf).unwrap();
+    info!("{}", text);
+    assert_eq!(format, openmetrics::TextFormat::OpenMetrics);
+    // THEN: the exemplars are included
+    assert!(text.contains(&format!(
+        "{}_total 2.0 # {{trace_id=\"{}\"}} 2.0 ",
+        counter_id, trace_id
+    )));
+    // AND: `_created` is reported for the counter, but not for the counter vec children
+    assert!(text.contains(&format!("{}_created ", counter_id)));
+    assert!(!text.contains(&format!("{}_created", counter_vec_id)));
+    assert!(text.ends_with("# EOF\n"));
+
+    // WHEN: the counter vec label set is removed
+    counter_vec.remove_label_values(&["a"]).unwrap();
+    let mut buf = Vec::new();
+    registry.open_metrics_encode_metrics(&mut buf).unwrap();
+    // THEN: its exemplar is pruned
+    assert_eq!(registry.exemplars().len(), 1);
+}
+
+#[test]
+fn metric_registry_catalog() {
+    configure_logging();
+
+    // GIVEN: a counter with a const label and a counter vec that are registered
+    let registry = MetricRegistry::default();
+    let counter_id = MetricId::generate();
+    let const_label_id = LabelId::generate();
+    let counter = registry
+        .register_int_counter(
+            counter_id,
+            "Executor spawned task count",
+            Some(hashmap! {const_label_id => "A".to_string()}),
+        )
+        .unwrap();
+    counter.inc();
+    let counter_vec_id = MetricId::generate();
+    let label_id = LabelId::generate();
+    let _counter_vec = registry
+        .register_int_counter_vec(counter_vec_id, "Counter vec", &[label_id], None)
+        .unwrap();
+
+    // WHEN: the catalog is retrieved
+    let catalog = registry.catalog();
+    info!("{}", catalog.to_markdown());
+
+    // THEN: it contains a descriptor for each registered metric
+    assert_eq!(catalog.descriptors().len(), 2);
+    let desc = catalog.descriptor(&counter_id.name()).unwrap();
+    assert_eq!(desc.help(), "Executor spawned task count");
+    assert_eq!(desc.metric_type(), Some(catalog::MetricType::Counter));
+    assert_eq!(
+        desc.const_labels().get(&const_label_id.name()),
+        Some(&"A".to_string())
+    );
+    assert!(desc.variable_labels().is_empty());
+    assert_eq!(desc.alias().unwrap(), "executor_spawned_task_count");
+    // AND: the counter vec has no type because it has not reported any samples
+    let desc = catalog.descriptor(&counter_vec_id.name()).unwrap();
+    assert_eq!(desc.metric_type(), None);
+    assert_eq!(desc.variable_labels(), &[label_id.name()]);
+}
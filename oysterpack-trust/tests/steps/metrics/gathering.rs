@@ -25,8 +25,15 @@ use prometheus::{
     Counter, CounterVec, Gauge, GaugeVec, Histogram, HistogramVec, IntCounter, IntCounterVec,
     IntGauge, IntGaugeVec,
 };
+use oysterpack_trust::concurrent::execution::{ExecutorBuilder, ExecutorId};
 use std::collections::HashMap;
-use std::{collections::HashSet, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashSet},
+    num::NonZeroUsize,
+    sync::Arc,
+    thread,
+    time::Duration,
+};
 
 steps!(World => {
     // Feature: [01D43V3KAZ276MQZY1TZG793EQ] Gathering all metrics
@@ -389,6 +396,113 @@ steps!(World => {
         assert!(world.metric_families.is_empty());
     };
 
+    // Feature: [01M57E7QMQCB9XS38YEV5XJ8NT] Gathered metrics can be captured as snapshots, which can be diffed
+
+    // Scenario: [01M57JMVQTC8FQGR33WG3K9BWW] Diffing 2 snapshots
+    given regex "01M57JMVQTC8FQGR33WG3K9BWW" | world, _matches, _step | {
+        world.register_snapshot_metrics();
+        world.snapshot = Some(metrics::registry().snapshot());
+    };
+
+    when regex "01M57JMVQTC8FQGR33WG3K9BWW" | world, _matches, _step| {
+        world.update_snapshot_metrics();
+        let snapshot = metrics::registry().snapshot();
+        world.snapshot_diff = Some(snapshot.diff(world.snapshot.as_ref().unwrap()));
+    };
+
+    then regex "01M57JMVQTC8FQGR33WG3K9BWW" | world, _matches, _step| {
+        let diff = world.snapshot_diff.as_ref().unwrap();
+        let (counter_name, histogram_name) = world.snapshot_metric_names();
+        assert_eq!(
+            diff.sample(&counter_name, &BTreeMap::new()).unwrap().value(),
+            &metrics::MetricValue::Counter(3.0)
+        );
+        match diff.sample(&histogram_name, &BTreeMap::new()).unwrap().value() {
+            metrics::MetricValue::Histogram { sample_count, sample_sum, .. } => {
+                assert_eq!(*sample_count, 2.0);
+                assert_eq!(*sample_sum, 3.0);
+            }
+            value => panic!("expected histogram value: {:?}", value),
+        }
+    };
+
+    // Scenario: [01M57JMVQWKT8DCZRETV53MR3N] Computing per second rates from 2 snapshots
+    then regex "01M57JMVQWKT8DCZRETV53MR3N" | world, _matches, _step| {
+        world.register_snapshot_metrics();
+        let earlier = metrics::registry().snapshot();
+        world.update_snapshot_metrics();
+        thread::sleep(Duration::from_millis(10));
+        let later = metrics::registry().snapshot();
+
+        let secs = metrics::duration_as_secs_f64(later.elapsed_since(&earlier));
+        assert!(secs > 0.0);
+        let rate = later.rate(&earlier);
+        let (counter_name, _) = world.snapshot_metric_names();
+        assert_eq!(
+            rate.sample(&counter_name, &BTreeMap::new()).unwrap().value(),
+            &metrics::MetricValue::Counter(3.0 / secs)
+        );
+    };
+
+    // Scenario: [01M57JMVQYHE9X2TYZXK527C5C] Diffing ProcessMetrics
+    then regex "01M57JMVQYHE9X2TYZXK527C5C" | _world, _matches, _step| {
+        let earlier = metrics::registry().gather_process_metrics();
+        let later = metrics::registry().gather_process_metrics();
+        let diff = later.diff(&earlier);
+        println!("{:#?}", diff);
+        assert!(diff.cpu_seconds_total() >= 0.0);
+        assert_eq!(diff.open_fds(), later.open_fds() - earlier.open_fds());
+        assert_eq!(diff.max_fds(), later.max_fds());
+        assert_eq!(diff.start_time_seconds(), later.start_time_seconds());
+    };
+
+    // Feature: [01M57EQ9A41APFMJXPND57KAV1] On Linux, a runtime metrics collector is automatically registered
+
+    // Scenario: [01M57JMVR0A60F2QE3SV27WA0F] Executor threads are reported under the ExecutorId thread group
+    given regex "01M57JMVR0A60F2QE3SV27WA0F" | world, _matches, _step | {
+        let executor_id = ExecutorId::generate();
+        ExecutorBuilder::new(executor_id)
+            .set_pool_size(NonZeroUsize::new(2).unwrap())
+            .register()
+            .unwrap();
+        world.executor_id = Some(executor_id);
+    };
+
+    when regex "01M57JMVR0A60F2QE3SV27WA0F" | world, _matches, _step| {
+        let thread_group = world.executor_id.unwrap().to_string();
+        // the executor threads register themselves once they have started
+        for _ in 0..100 {
+            world.thread_groups = metrics::runtime::thread_group_stats();
+            if world.thread_groups.get(&thread_group).map_or(0, |stats| stats.thread_count) == 2 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    };
+
+    then regex "01M57JMVR0A60F2QE3SV27WA0F" | world, _matches, _step| {
+        println!("{:#?}", world.thread_groups);
+        let thread_group = world.executor_id.unwrap().to_string();
+        assert_eq!(world.thread_groups.get(&thread_group).unwrap().thread_count, 2);
+        let mfs = metrics::registry().gather_for_metric_ids(&[metrics::runtime::THREAD_COUNT_METRIC_ID]);
+        assert!(mfs[0].get_metric().iter().any(|metric| metric.get_label()[0].get_value() == thread_group));
+    };
+
+    // Scenario: [01M57JMVR22E1RQNPNA3MZXZPB] Threads that do not belong to a registered thread group
+    then regex "01M57JMVR22E1RQNPNA3MZXZPB" | _world, _matches, _step| {
+        let (tx, rx) = std::sync::mpsc::channel::<()>();
+        let handle = thread::Builder::new()
+            .name("01M57JMVR22E1".to_string())
+            .spawn(move || {
+                let _ = rx.recv();
+            })
+            .unwrap();
+        assert_eq!(metrics::runtime::thread_group("01M57JMVR22E1"), metrics::runtime::OTHER_THREAD_GROUP);
+        let thread_groups = metrics::runtime::thread_group_stats();
+        assert!(thread_groups.get(metrics::runtime::OTHER_THREAD_GROUP).unwrap().thread_count >= 1);
+        tx.send(()).unwrap();
+        handle.join().unwrap();
+    };
 
 });
 
@@ -426,9 +540,48 @@ pub struct World {
     desc_ids: Vec<metrics::DescId>,
     desc_names: Vec<String>,
     labels: HashMap<String, String>,
+
+    snapshot_counter: Option<IntCounter>,
+    snapshot_histogram: Option<Histogram>,
+    snapshot: Option<metrics::MetricsSnapshot>,
+    snapshot_diff: Option<metrics::MetricsDiff>,
+
+    executor_id: Option<ExecutorId>,
+    thread_groups: BTreeMap<String, metrics::runtime::ThreadStats>,
 }
 
 impl World {
+    fn register_snapshot_metrics(&mut self) {
+        let counter = metrics::registry()
+            .register_int_counter(metrics::MetricId::generate(), "snapshot counter", None)
+            .unwrap();
+        let histogram = metrics::registry()
+            .register_histogram(
+                metrics::MetricId::generate(),
+                "snapshot histogram",
+                vec![1.0, 2.0, 5.0],
+                None,
+            )
+            .unwrap();
+        self.snapshot_counter = Some(counter);
+        self.snapshot_histogram = Some(histogram);
+    }
+
+    fn update_snapshot_metrics(&self) {
+        self.snapshot_counter.as_ref().unwrap().inc_by(3);
+        let histogram = self.snapshot_histogram.as_ref().unwrap();
+        histogram.observe(1.0);
+        histogram.observe(2.0);
+    }
+
+    /// returns the (counter, histogram) metric names
+    fn snapshot_metric_names(&self) -> (String, String) {
+        (
+            self.snapshot_counter.as_ref().unwrap().desc()[0].fq_name.clone(),
+            self.snapshot_histogram.as_ref().unwrap().desc()[0].fq_name.clone(),
+        )
+    }
+
     fn metric_ids(&self) -> Vec<metrics::MetricId> {
         vec![
             self.int_counter.desc()[0].fq_name.as_str().parse().unwrap(),
@@ -625,6 +778,14 @@ impl Default for World {
             desc_ids: Vec::new(),
             desc_names: Vec::new(),
             labels: HashMap::new(),
+
+            snapshot_counter: None,
+            snapshot_histogram: None,
+            snapshot: None,
+            snapshot_diff: None,
+
+            executor_id: None,
+            thread_groups: BTreeMap::new(),
         }
     }
 }
@@ -16,7 +16,8 @@
 
 use cucumber_rust::*;
 
-use oysterpack_trust::metrics::{self, timer_buckets};
+use oysterpack_trust::metrics::{self, cardinality, catalog, openmetrics, timer_buckets};
+use oysterpack_uid::ULID;
 use prometheus::core::Collector;
 use std::{num::NonZeroUsize, thread, time::Duration};
 
@@ -379,9 +380,153 @@ steps!(World => {
         assert!(result.is_err());
     };
 
+    // Feature: [01M57EDWN5W3H9JSYRB3ZX3TGC] Text encoding metrics in the OpenMetrics 1.0 format
+
+    // Scenario: [01M57JMVR5BVYW2Y070520ZDHX] Encoding metrics without an Accept header
+    then regex "01M57JMVR5BVYW2Y070520ZDHX" | _world, _matches, _step| {
+        let mut buf = Vec::new();
+        let format = metrics::registry().encode_metrics(None, &mut buf).unwrap();
+        assert_eq!(format, openmetrics::TextFormat::Prometheus);
+        assert!(!String::from_utf8(buf).unwrap().contains("# EOF"));
+    };
+
+    // Scenario: [01M57JMVR7CJGN7P0B5KRMATHT] Encoding metrics using the OpenMetrics Accept header
+    given regex "01M57JMVR7CJGN7P0B5KRMATHT" | world, _matches, _step| {
+        let metric_id = metrics::MetricId::generate();
+        let counter = metrics::registry().register_int_counter(metric_id, "help", None).unwrap();
+        let trace_id = ULID::generate();
+        metrics::registry().exemplars().int_counter(counter).inc_by(2, trace_id);
+        world.exemplar = Some((metric_id, trace_id));
+    };
+
+    when regex "01M57JMVR7CJGN7P0B5KRMATHT" | world, _matches, _step| {
+        world.text_format = metrics::registry()
+            .encode_metrics(Some(openmetrics::OPENMETRICS_TEXT_FORMAT), &mut world.text_encoded_metrics)
+            .ok();
+    };
+
+    then regex "01M57JMVR7CJGN7P0B5KRMATHT" | world, _matches, _step| {
+        assert_eq!(world.text_format, Some(openmetrics::TextFormat::OpenMetrics));
+        let metrics_text = String::from_utf8_lossy(&world.text_encoded_metrics);
+        println!("{}", metrics_text);
+        let (metric_id, trace_id) = world.exemplar.unwrap();
+        assert!(metrics_text.contains(&format!("{}_total 2.0 # {{trace_id=\"{}\"}} 2.0 ", metric_id, trace_id)));
+        assert!(metrics_text.ends_with("# EOF\n"));
+    };
+
+    // Feature: [01M57EGQYJWRYGK5K2PW8NZVRQ] The registered metric descriptors can be exported as a catalog
+
+    // Scenario: [01M57JMVR97GNPYJD782CAN73W] Exporting the metric registry catalog
+    given regex "01M57JMVR97GNPYJD782CAN73W" | world, _matches, _step| {
+        let metric_id = metrics::MetricId::generate();
+        let counter = metrics::registry().register_int_counter(metric_id, "Catalog feature counter", None).unwrap();
+        counter.inc();
+        world.metric_id = Some(metric_id);
+    };
+
+    when regex "01M57JMVR97GNPYJD782CAN73W" | world, _matches, _step| {
+        world.catalog = Some(metrics::registry().catalog());
+    };
+
+    then regex "01M57JMVR97GNPYJD782CAN73W" | world, _matches, _step| {
+        let catalog = world.catalog.as_ref().unwrap();
+        let metric_name = world.metric_id.unwrap().name();
+        let desc = catalog.descriptor(&metric_name).unwrap();
+        assert_eq!(desc.help(), "Catalog feature counter");
+        assert_eq!(desc.metric_type(), Some(catalog::MetricType::Counter));
+
+        let json = serde_json::to_string(catalog).unwrap();
+        let catalog2: catalog::MetricCatalog = serde_json::from_str(&json).unwrap();
+        assert_eq!(*catalog, catalog2);
+        assert!(catalog.to_markdown().contains(&format!("| `{}` | counter | Catalog feature counter |", metric_name)));
+        assert!(catalog
+            .to_recording_rules("oysterpack")
+            .contains(&format!("        expr: {}\n", metric_name)));
+    };
+
+    // Scenario: [01M57JMVRBC3QN6N4B9FH8P82R] Building the catalog from an OpenMetrics text exposition
+    then regex "01M57JMVRBC3QN6N4B9FH8P82R" | _world, _matches, _step| {
+        let metric_id = metrics::MetricId::generate();
+        let counter = metrics::registry().register_int_counter(metric_id, "OpenMetrics counter", None).unwrap();
+        counter.inc();
+        let mut buf = Vec::new();
+        metrics::registry().open_metrics_encode_metrics(&mut buf).unwrap();
+
+        let catalog = catalog::MetricCatalog::from_text_exposition(&String::from_utf8(buf).unwrap());
+        let desc = catalog.descriptor(&metric_id.name()).unwrap();
+        assert_eq!(desc.metric_type(), Some(catalog::MetricType::Counter));
+        assert_eq!(desc.help(), "OpenMetrics counter");
+        assert!(catalog.descriptor(&format!("{}_total", metric_id)).is_none());
+        assert!(catalog.descriptor(&format!("{}_created", metric_id)).is_none());
+    };
+
+    // Feature: [01M57ENQ5YCPJ23P02FN3FXCE3] Metric vectors can be guarded by cardinality limits
+
+    // Scenario: [01M57JMVRD7C5MQR4XYRVTCCXC] The per metric cardinality limit is reached
+    given regex "01M57JMVRD7C5MQR4XYRVTCCXC" | world, _matches, _step| {
+        world.guarded_counter_vec = Some(register_guarded_counter_vec(2));
+    };
+
+    when regex "01M57JMVRD7C5MQR4XYRVTCCXC" | world, _matches, _step| {
+        let counter_vec = world.guarded_counter_vec.as_ref().unwrap();
+        for value in &["a", "b", "c"] {
+            counter_vec.with_label_values(&[value]).inc();
+        }
+    };
+
+    then regex "01M57JMVRD7C5MQR4XYRVTCCXC" | world, _matches, _step| {
+        let counter_vec = world.guarded_counter_vec.as_ref().unwrap();
+        assert_eq!(counter_vec.cardinality(), 2);
+        assert_eq!(counter_vec.rejected_count(), 1);
+        assert_eq!(counter_vec.metric_vec().with_label_values(&[cardinality::OVERFLOW_LABEL_VALUE]).get(), 1);
+    };
+
+    // Scenario: [01M57JMVRFKEAYKGJDPRZM3KQG] A label set is removed from a guarded metric vector
+    then regex "01M57JMVRFKEAYKGJDPRZM3KQG" | _world, _matches, _step| {
+        let counter_vec = register_guarded_counter_vec(1);
+        counter_vec.with_label_values(&["a"]).inc();
+        counter_vec.with_label_values(&["b"]).inc();
+        assert_eq!(counter_vec.metric_vec().with_label_values(&["b"]).get(), 0);
+
+        counter_vec.remove_label_values(&["a"]).unwrap();
+        assert_eq!(counter_vec.cardinality(), 0);
+        counter_vec.with_label_values(&["b"]).inc();
+        assert_eq!(counter_vec.cardinality(), 1);
+        assert_eq!(counter_vec.metric_vec().with_label_values(&["b"]).get(), 1);
+    };
+
+    // Scenario: [01M57JMVRJAQ6EPJ6JKNBRC8Z8] Guarded metric vector label sets count against the registry's global limit
+    then regex "01M57JMVRJAQ6EPJ6JKNBRC8Z8" | _world, _matches, _step| {
+        let counter_vec = register_guarded_counter_vec(10);
+        let cardinality = metrics::registry().cardinality();
+        counter_vec.with_label_values(&["a"]).inc();
+        counter_vec.with_label_values(&["b"]).inc();
+        assert_eq!(metrics::registry().cardinality(), cardinality + 2);
+        counter_vec.remove_label_values(&["a"]).unwrap();
+        assert_eq!(metrics::registry().cardinality(), cardinality + 1);
+    };
+
 });
 
+fn register_guarded_counter_vec(limit: usize) -> cardinality::GuardedIntCounterVec {
+    metrics::registry()
+        .register_guarded(
+            metrics::IntCounterVecBuilder::new(
+                metrics::MetricId::generate(),
+                "Guarded counter",
+                vec![metrics::LabelId::generate()],
+            )
+            .with_cardinality_limit(limit),
+        )
+        .unwrap()
+}
+
 #[derive(Clone, Default)]
 pub struct World {
     text_encoded_metrics: Vec<u8>,
+    text_format: Option<openmetrics::TextFormat>,
+    exemplar: Option<(metrics::MetricId, ULID)>,
+    metric_id: Option<metrics::MetricId>,
+    catalog: Option<catalog::MetricCatalog>,
+    guarded_counter_vec: Option<cardinality::GuardedIntCounterVec>,
 }